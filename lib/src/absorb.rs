@@ -0,0 +1,194 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Moving the hunks of a commit into the ancestors that last modified the
+//! lines around them ("absorb").
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use crate::backend::{BackendError, CommitId, TreeId, TreeValue};
+use crate::commit::Commit;
+use crate::commit_builder::CommitBuilder;
+use crate::diff::{find_line_ranges, Diff, DiffHunk};
+use crate::files;
+use crate::line_origins::{get_line_origins, read_file_content, LineOrigin};
+use crate::matchers::Matcher;
+use crate::repo::{MutableRepo, RepoRef};
+use crate::repo_path::RepoPath;
+use crate::settings::UserSettings;
+use crate::tree::Diff as TreeDiff;
+
+/// A replacement of a range of lines in a destination commit's version of a
+/// file.
+struct LineEdit {
+    lines: Range<usize>,
+    new_content: Vec<u8>,
+}
+
+/// Splits the changes in `source` by the commit among `destinations` that
+/// last modified the lines around each hunk. Hunks that don't have a single
+/// such commit are left out. Returns the new tree of each destination commit
+/// with its hunks applied. `source` must have a single parent, and
+/// `destinations` should only contain ancestors of it.
+pub fn split_hunks_to_trees(
+    repo: RepoRef,
+    source: &Commit,
+    destinations: &HashSet<CommitId>,
+    matcher: &dyn Matcher,
+) -> Result<HashMap<CommitId, TreeId>, BackendError> {
+    let store = repo.store();
+    let parent = &source.parents()[0];
+    let mut edits: HashMap<CommitId, HashMap<RepoPath, Vec<LineEdit>>> = HashMap::new();
+    for (path, diff) in parent.tree().diff(&source.tree(), matcher) {
+        if !matches!(
+            diff,
+            TreeDiff::Modified(TreeValue::Normal { .. }, TreeValue::Normal { .. })
+        ) {
+            continue;
+        }
+        let left_content = read_file_content(parent, &path)?.unwrap();
+        let right_content = read_file_content(source, &path)?.unwrap();
+        if files::is_binary(&left_content) || files::is_binary(&right_content) {
+            continue;
+        }
+        let origins = get_line_origins(repo, parent, &path, |id| destinations.contains(id))?;
+        let mut left_line = 0;
+        let diff = Diff::for_tokenizer(&[&left_content, &right_content], &find_line_ranges);
+        for hunk in diff.hunks() {
+            match hunk {
+                DiffHunk::Matching(content) => {
+                    left_line += count_lines(content);
+                }
+                DiffHunk::Different(contents) => {
+                    let lines = left_line..left_line + count_lines(contents[0]);
+                    left_line = lines.end;
+                    if let Some((commit_id, lines)) = find_destination(&origins, lines) {
+                        edits
+                            .entry(commit_id)
+                            .or_default()
+                            .entry(path.clone())
+                            .or_default()
+                            .push(LineEdit {
+                                lines,
+                                new_content: contents[1].to_vec(),
+                            });
+                    }
+                }
+            }
+        }
+    }
+
+    let mut selected_trees = HashMap::new();
+    for (commit_id, path_edits) in edits {
+        let commit = store.get_commit(&commit_id)?;
+        let mut tree_builder = store.tree_builder(commit.tree_id().clone());
+        for (path, mut edits) in path_edits {
+            let executable = match commit.tree().path_value(&path) {
+                Some(TreeValue::Normal { executable, .. }) => executable,
+                _ => false,
+            };
+            let content = read_file_content(&commit, &path)?.unwrap();
+            let mut lines = content.split_inclusive(|b| *b == b'\n').collect_vec();
+            // Apply the edits from the end so the line numbers of the remaining
+            // edits stay valid
+            edits.sort_by_key(|edit| (edit.lines.start, edit.lines.end));
+            for edit in edits.iter().rev() {
+                lines.splice(edit.lines.clone(), [edit.new_content.as_slice()]);
+            }
+            let new_content = lines.concat();
+            let id = store.write_file(&path, &mut new_content.as_slice())?;
+            tree_builder.set(path, TreeValue::Normal { id, executable });
+        }
+        selected_trees.insert(commit_id, tree_builder.write_tree());
+    }
+    Ok(selected_trees)
+}
+
+fn count_lines(content: &[u8]) -> usize {
+    content.split_inclusive(|b| *b == b'\n').count()
+}
+
+/// Finds the commit that a hunk replacing `lines` in the source's parent should
+/// be absorbed into, and the lines it replaces in that commit's version of the
+/// file. Modified lines must all have been introduced together by one commit.
+/// For pure additions, the lines around the hunk must have been.
+fn find_destination(
+    origins: &[Option<LineOrigin>],
+    lines: Range<usize>,
+) -> Option<(CommitId, Range<usize>)> {
+    let neighbors = if lines.is_empty() {
+        // Use the lines before and after the addition
+        let start = lines.start.saturating_sub(1);
+        let end = (lines.end + 1).min(origins.len());
+        &origins[start..end]
+    } else {
+        &origins[lines.clone()]
+    };
+    let neighbors: Vec<&LineOrigin> = neighbors
+        .iter()
+        .map(Option::as_ref)
+        .collect::<Option<_>>()?;
+    let first = neighbors.first()?;
+    let mut expected_line = first.line_number;
+    for origin in &neighbors {
+        if origin.commit_id != first.commit_id || origin.line_number != expected_line {
+            return None;
+        }
+        expected_line += 1;
+    }
+    let destination_lines = if lines.is_empty() {
+        // Insert before the line after the addition, or at the end
+        let insert_at = if lines.start < origins.len() {
+            neighbors.last().unwrap().line_number
+        } else {
+            expected_line
+        };
+        insert_at..insert_at
+    } else {
+        first.line_number..expected_line
+    };
+    Some((first.commit_id.clone(), destination_lines))
+}
+
+/// Rewrites the destination commits to have the trees returned by
+/// `split_hunks_to_trees()`. The caller is expected to rebase the descendants
+/// afterwards. Returns the rewritten commits, children first.
+pub fn absorb_hunks(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    source: &Commit,
+    selected_trees: HashMap<CommitId, TreeId>,
+) -> Result<Vec<Commit>, BackendError> {
+    let store = mut_repo.store().clone();
+    let index = mut_repo.index();
+    let destination_ids = selected_trees
+        .keys()
+        .sorted_by_key(|id| Reverse(index.commit_id_to_pos(id)))
+        .cloned()
+        .collect_vec();
+    let mut rewritten_commits = vec![];
+    for commit_id in destination_ids {
+        let commit = store.get_commit(&commit_id)?;
+        let new_commit = CommitBuilder::for_rewrite_from(settings, &commit)
+            .set_tree(selected_trees[&commit_id].clone())
+            .set_predecessors(vec![commit_id, source.id().clone()])
+            .write_to_repo(mut_repo)?;
+        rewritten_commits.push(new_commit);
+    }
+    Ok(rewritten_commits)
+}
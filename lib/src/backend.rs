@@ -21,6 +21,7 @@ use std::vec::Vec;
 use thiserror::Error;
 
 use crate::repo_path::{RepoPath, RepoPathComponent};
+use crate::signing::SignError;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub struct CommitId(Vec<u8>);
@@ -263,13 +264,30 @@ pub struct Commit {
     pub author: Signature,
     pub committer: Signature,
     pub is_open: bool,
+    pub secure_sig: Option<SecureSig>,
+    /// The commit this one was copied from, e.g. by `jj duplicate`. Unlike a
+    /// predecessor, the original is not replaced by the copy.
+    pub copied_from: Option<CommitId>,
 }
 
+/// A cryptographic signature of a commit, along with the exact bytes that were
+/// signed. What the signed data looks like depends on the backend.
 #[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SecureSig {
+    pub data: Vec<u8>,
+    pub sig: Vec<u8>,
+}
+
+/// Produces a signature of the given data. Passed to `Backend::write_commit()`
+/// when the commit should be signed.
+pub type SigningFn<'a> = dyn Fn(&[u8]) -> Result<Vec<u8>, SignError> + 'a;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct ConflictPart {
-    // TODO: Store e.g. CommitId here too? Labels (theirs/ours/base)? Would those still be
-    //       useful e.g. after rebasing this conflict?
     pub value: TreeValue,
+    /// Optional human-readable description of where this side came from, such
+    /// as the commit it was taken from. Shown next to the conflict markers.
+    pub label: Option<String>,
 }
 
 #[derive(Default, Debug, PartialEq, Eq, Clone)]
@@ -292,7 +310,7 @@ pub enum BackendError {
 
 pub type BackendResult<T> = Result<T, BackendError>;
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub enum TreeValue {
     Normal { id: FileId, executable: bool },
     Symlink(SymlinkId),
@@ -395,5 +413,11 @@ pub trait Backend: Send + Sync + Debug {
 
     fn read_commit(&self, id: &CommitId) -> BackendResult<Commit>;
 
-    fn write_commit(&self, contents: &Commit) -> BackendResult<CommitId>;
+    /// Writes the commit, signing it with `sign_with` if given. Returns the
+    /// commit as it was written, which includes the signature.
+    fn write_commit(
+        &self,
+        contents: Commit,
+        sign_with: Option<&SigningFn>,
+    ) -> BackendResult<(CommitId, Commit)>;
 }
@@ -0,0 +1,203 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finding the first bad commit in a range of commits by binary search.
+//!
+//! The range is the commits that are ancestors of the bad commit but not of any
+//! good commit. Each step picks a commit about halfway through the range to
+//! test. Marking it good removes it and its ancestors from the range, and
+//! marking it bad makes it the new end of the range.
+
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use tempfile::NamedTempFile;
+
+use crate::backend::CommitId;
+use crate::index::IndexRef;
+
+/// The revisions that have been marked during a bisection. Stored in a file
+/// in the repo between commands.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BisectState {
+    /// The latest revision marked bad
+    pub bad: Option<CommitId>,
+    pub good: Vec<CommitId>,
+    pub skipped: Vec<CommitId>,
+    /// The revision that was last picked for testing
+    pub current: Option<CommitId>,
+}
+
+/// What to do next in a bisection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BisectionStep {
+    /// No revision has been marked bad yet
+    NeedsBad,
+    /// No revision has been marked good yet
+    NeedsGood,
+    /// The bad revision is an ancestor of a good revision
+    BadIsAncestorOfGood,
+    /// A marked revision isn't in the index, e.g. because the operation that
+    /// added it was undone
+    UnknownRevision(CommitId),
+    /// This revision should be tested next. `num_untested` is the number of
+    /// candidates for the first bad revision that haven't been tested,
+    /// including this one.
+    Test {
+        commit_id: CommitId,
+        num_untested: usize,
+    },
+    /// The first bad revision was found
+    Found(CommitId),
+    /// Only skipped revisions are left to test, so the first bad revision
+    /// could be any of these
+    Indeterminate(Vec<CommitId>),
+}
+
+fn state_path(repo_path: &Path) -> PathBuf {
+    repo_path.join("bisect")
+}
+
+impl BisectState {
+    /// Loads the state of the bisection in progress in the repo at
+    /// `repo_path`, if any.
+    pub fn load(repo_path: &Path) -> std::io::Result<Option<BisectState>> {
+        let content = match fs::read_to_string(state_path(repo_path)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let malformed = || std::io::Error::new(ErrorKind::InvalidData, "malformed bisect state");
+        let mut state = BisectState::default();
+        for line in content.lines() {
+            let (kind, hex) = line.split_once(' ').ok_or_else(malformed)?;
+            let id = CommitId::new(hex::decode(hex).map_err(|_| malformed())?);
+            match kind {
+                "bad" => state.bad = Some(id),
+                "good" => state.good.push(id),
+                "skip" => state.skipped.push(id),
+                "current" => state.current = Some(id),
+                _ => return Err(malformed()),
+            }
+        }
+        Ok(Some(state))
+    }
+
+    pub fn save(&self, repo_path: &Path) -> std::io::Result<()> {
+        let mut temp_file = NamedTempFile::new_in(repo_path)?;
+        if let Some(id) = &self.bad {
+            writeln!(temp_file, "bad {}", id.hex())?;
+        }
+        for id in &self.good {
+            writeln!(temp_file, "good {}", id.hex())?;
+        }
+        for id in &self.skipped {
+            writeln!(temp_file, "skip {}", id.hex())?;
+        }
+        if let Some(id) = &self.current {
+            writeln!(temp_file, "current {}", id.hex())?;
+        }
+        temp_file
+            .persist(state_path(repo_path))
+            .map_err(|err| err.error)?;
+        Ok(())
+    }
+
+    /// Ends the bisection in progress in the repo at `repo_path`, if any.
+    pub fn remove(repo_path: &Path) -> std::io::Result<()> {
+        match fs::remove_file(state_path(repo_path)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn mark_bad(&mut self, id: CommitId) {
+        self.bad = Some(id);
+    }
+
+    pub fn mark_good(&mut self, id: CommitId) {
+        if !self.good.contains(&id) {
+            self.good.push(id);
+        }
+    }
+
+    pub fn mark_skipped(&mut self, id: CommitId) {
+        if !self.skipped.contains(&id) {
+            self.skipped.push(id);
+        }
+    }
+
+    /// Decides what to do next, given the revisions marked so far.
+    ///
+    /// The revision to test is the untested candidate whose generation number
+    /// is closest to halfway between the oldest candidate and the bad
+    /// revision. In a linear history, that splits the candidates in half.
+    pub fn next_step(&self, index: IndexRef) -> BisectionStep {
+        let bad = match &self.bad {
+            None => return BisectionStep::NeedsBad,
+            Some(bad) => bad,
+        };
+        if self.good.is_empty() {
+            return BisectionStep::NeedsGood;
+        }
+        let marked = std::iter::once(bad)
+            .chain(&self.good)
+            .chain(&self.skipped)
+            .chain(&self.current);
+        for id in marked {
+            if !index.has_id(id) {
+                return BisectionStep::UnknownRevision(id.clone());
+            }
+        }
+        let candidates = index
+            .walk_revs(std::slice::from_ref(bad), &self.good)
+            .map(|entry| (entry.commit_id(), entry.generation_number()))
+            .collect_vec();
+        if candidates.is_empty() {
+            return BisectionStep::BadIsAncestorOfGood;
+        }
+        let untested = candidates
+            .iter()
+            .filter(|(id, _)| id != bad && !self.skipped.contains(id))
+            .collect_vec();
+        if untested.is_empty() {
+            let mut skipped = candidates
+                .iter()
+                .filter(|(id, _)| self.skipped.contains(id))
+                .map(|(id, _)| id.clone())
+                .peekable();
+            if skipped.peek().is_none() {
+                return BisectionStep::Found(bad.clone());
+            }
+            return BisectionStep::Indeterminate(
+                std::iter::once(bad.clone()).chain(skipped).collect(),
+            );
+        }
+        let bad_generation = index.entry_by_id(bad).unwrap().generation_number();
+        let oldest_generation = candidates.iter().map(|(_, gen)| *gen).min().unwrap();
+        let middle = (oldest_generation + bad_generation) / 2;
+        // The walk is in reverse index order, so on ties this prefers the
+        // revision that was added to the repo later
+        let (commit_id, _) = untested
+            .iter()
+            .min_by_key(|(_, gen)| (middle as i64 - *gen as i64).abs())
+            .unwrap();
+        BisectionStep::Test {
+            commit_id: commit_id.clone(),
+            num_untested: untested.len(),
+        }
+    }
+}
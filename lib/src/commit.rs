@@ -20,6 +20,7 @@ use std::sync::Arc;
 use crate::backend;
 use crate::backend::{ChangeId, CommitId, Signature, TreeId};
 use crate::repo_path::RepoPath;
+use crate::signing::{SignResult, Verification};
 use crate::store::Store;
 use crate::tree::Tree;
 
@@ -145,4 +146,23 @@ impl Commit {
     pub fn committer(&self) -> &Signature {
         &self.data.committer
     }
+
+    /// The commit this one was copied from, if any
+    pub fn copied_from(&self) -> Option<&CommitId> {
+        self.data.copied_from.as_ref()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.data.secure_sig.is_some()
+    }
+
+    /// Verifies the commit's signature. Returns `None` if the commit is not
+    /// signed.
+    pub fn verification(&self) -> Option<SignResult<Verification>> {
+        self.data.secure_sig.as_ref().map(|secure_sig| {
+            self.store
+                .signer()
+                .verify(&secure_sig.data, &secure_sig.sig)
+        })
+    }
 }
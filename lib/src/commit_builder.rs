@@ -12,18 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use uuid::Uuid;
 
 use crate::backend;
-use crate::backend::{ChangeId, CommitId, Signature, TreeId};
+use crate::backend::{BackendResult, ChangeId, CommitId, Signature, TreeId};
 use crate::commit::Commit;
 use crate::repo::MutableRepo;
-use crate::settings::UserSettings;
+use crate::settings::{JJRng, UserSettings};
 
 #[derive(Debug)]
 pub struct CommitBuilder {
     commit: backend::Commit,
     rewrite_source: Option<Commit>,
+    rng: Arc<JJRng>,
 }
 
 pub fn new_change_id() -> ChangeId {
@@ -37,15 +40,18 @@ impl CommitBuilder {
             parents: vec![],
             predecessors: vec![],
             root_tree: tree_id,
-            change_id: new_change_id(),
+            change_id: settings.get_rng().new_change_id(),
             description: String::new(),
             author: signature.clone(),
             committer: signature,
             is_open: false,
+            secure_sig: None,
+            copied_from: None,
         };
         CommitBuilder {
             commit,
             rewrite_source: None,
+            rng: settings.get_rng(),
         }
     }
 
@@ -53,6 +59,8 @@ impl CommitBuilder {
         let mut commit = predecessor.store_commit().clone();
         commit.predecessors = vec![predecessor.id().clone()];
         commit.committer = settings.signature();
+        // The old signature doesn't cover the rewritten commit
+        commit.secure_sig = None;
         // If the user had not configured a name and email before but now they have,
         // update the the author fields with the new information.
         if commit.author.name == UserSettings::user_name_placeholder() {
@@ -64,6 +72,7 @@ impl CommitBuilder {
         CommitBuilder {
             commit,
             rewrite_source: Some(predecessor.clone()),
+            rng: settings.get_rng(),
         }
     }
 
@@ -77,15 +86,18 @@ impl CommitBuilder {
             parents: vec![parent_id],
             predecessors: vec![],
             root_tree: tree_id,
-            change_id: new_change_id(),
+            change_id: settings.get_rng().new_change_id(),
             description: String::new(),
             author: signature.clone(),
             committer: signature,
             is_open: true,
+            secure_sig: None,
+            copied_from: None,
         };
         CommitBuilder {
             commit,
             rewrite_source: None,
+            rng: settings.get_rng(),
         }
     }
 
@@ -110,7 +122,12 @@ impl CommitBuilder {
     }
 
     pub fn generate_new_change_id(mut self) -> Self {
-        self.commit.change_id = new_change_id();
+        self.commit.change_id = self.rng.new_change_id();
+        self
+    }
+
+    pub fn set_copied_from(mut self, copied_from: Option<CommitId>) -> Self {
+        self.commit.copied_from = copied_from;
         self
     }
 
@@ -134,7 +151,7 @@ impl CommitBuilder {
         self
     }
 
-    pub fn write_to_repo(mut self, repo: &mut MutableRepo) -> Commit {
+    pub fn write_to_repo(mut self, repo: &mut MutableRepo) -> BackendResult<Commit> {
         let parents = &mut self.commit.parents;
         if parents.contains(repo.store().root_commit_id()) {
             assert_eq!(parents.len(), 1);
@@ -146,10 +163,10 @@ impl CommitBuilder {
                 rewrite_source_id.replace(rewrite_source.id().clone());
             }
         }
-        let commit = repo.write_commit(self.commit);
+        let commit = repo.write_commit(self.commit)?;
         if let Some(rewrite_source_id) = rewrite_source_id {
             repo.record_rewritten_commit(rewrite_source_id, commit.id().clone())
         }
-        commit
+        Ok(commit)
     }
 }
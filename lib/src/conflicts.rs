@@ -20,15 +20,59 @@ use crate::backend::{BackendResult, Conflict, ConflictId, ConflictPart, TreeValu
 use crate::diff::{find_line_ranges, Diff, DiffHunk};
 use crate::files;
 use crate::files::{MergeHunk, MergeResult};
+use crate::gitattributes::GitAttributesFile;
 use crate::repo_path::RepoPath;
 use crate::store::Store;
+use crate::tree::Tree;
 
-const CONFLICT_START_LINE: &[u8] = b"<<<<<<<\n";
-const CONFLICT_END_LINE: &[u8] = b">>>>>>>\n";
-const CONFLICT_MINUS_LINE: &[u8] = b"-------\n";
-const CONFLICT_PLUS_LINE: &[u8] = b"+++++++\n";
+const CONFLICT_START_MARKER: &[u8] = b"<<<<<<<";
+const CONFLICT_END_MARKER: &[u8] = b">>>>>>>";
+const CONFLICT_MINUS_MARKER: &[u8] = b"-------";
+const CONFLICT_PLUS_MARKER: &[u8] = b"+++++++";
+const CONFLICT_GIT_BASE_MARKER: &[u8] = b"|||||||";
+const CONFLICT_GIT_SEPARATOR_MARKER: &[u8] = b"=======";
+
+/// How conflicts are written to files with conflict markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictMarkerStyle {
+    /// Shows the diff from a removed part to an added part, followed by the
+    /// remaining parts as snapshots.
+    Diff,
+    /// Shows every part as a snapshot, starting with an added part and then
+    /// alternating between removed and added parts.
+    Snapshot,
+    /// Uses Git's "diff3" style with `|||||||` and `=======` markers. Conflicts
+    /// with more than two sides are written in the `Snapshot` style instead.
+    Git,
+}
+
+impl Default for ConflictMarkerStyle {
+    fn default() -> Self {
+        ConflictMarkerStyle::Diff
+    }
+}
+
+impl ConflictMarkerStyle {
+    /// Parses the value of the `ui.conflict-marker-style` setting.
+    pub fn from_config_value(value: &str) -> Option<Self> {
+        match value {
+            "diff" => Some(ConflictMarkerStyle::Diff),
+            "snapshot" => Some(ConflictMarkerStyle::Snapshot),
+            "git" | "diff3" => Some(ConflictMarkerStyle::Git),
+            _ => None,
+        }
+    }
+}
 
 fn describe_conflict_part(part: &ConflictPart) -> String {
+    let description = describe_conflict_part_value(part);
+    match &part.label {
+        None => description,
+        Some(label) => format!("{} ({})", description, label),
+    }
+}
+
+fn describe_conflict_part_value(part: &ConflictPart) -> String {
     match &part.value {
         TreeValue::Normal {
             id,
@@ -57,7 +101,8 @@ fn describe_conflict_part(part: &ConflictPart) -> String {
     }
 }
 
-fn describe_conflict(conflict: &Conflict, file: &mut dyn Write) -> std::io::Result<()> {
+/// Writes a description of each part of the conflict.
+pub fn describe_conflict(conflict: &Conflict, file: &mut dyn Write) -> std::io::Result<()> {
     file.write_all(b"Conflict:\n")?;
     for part in &conflict.removes {
         file.write_all(format!("  Removing {}\n", describe_conflict_part(part)).as_bytes())?;
@@ -68,6 +113,24 @@ fn describe_conflict(conflict: &Conflict, file: &mut dyn Write) -> std::io::Resu
     Ok(())
 }
 
+fn describe_binary_conflict(conflict: &Conflict, file: &mut dyn Write) -> std::io::Result<()> {
+    file.write_all(b"Conflict in binary file:\n")?;
+    for part in &conflict.removes {
+        file.write_all(format!("  Removing {}\n", describe_conflict_part(part)).as_bytes())?;
+    }
+    for (i, part) in conflict.adds.iter().enumerate() {
+        file.write_all(
+            format!(
+                "  Adding {} (side #{})\n",
+                describe_conflict_part(part),
+                i + 1
+            )
+            .as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
 fn file_parts(parts: &[ConflictPart]) -> Vec<&ConflictPart> {
     parts
         .iter()
@@ -101,6 +164,25 @@ fn get_file_contents(store: &Store, path: &RepoPath, part: &ConflictPart) -> Vec
     }
 }
 
+/// Writes a conflict marker line, followed by the label, if any.
+fn write_marker(marker: &[u8], label: Option<&str>, output: &mut dyn Write) -> std::io::Result<()> {
+    output.write_all(marker)?;
+    if let Some(label) = label {
+        output.write_all(b" ")?;
+        output.write_all(label.as_bytes())?;
+    }
+    output.write_all(b"\n")
+}
+
+/// Returns true if the line is the given conflict marker, optionally followed
+/// by a label.
+fn is_marker_line(line: &[u8], marker: &[u8]) -> bool {
+    match line.strip_prefix(marker) {
+        Some(rest) => rest == b"\n" || rest.starts_with(b" "),
+        None => false,
+    }
+}
+
 fn write_diff_hunks(hunks: &[DiffHunk], file: &mut dyn Write) -> std::io::Result<()> {
     for hunk in hunks {
         match hunk {
@@ -125,10 +207,16 @@ fn write_diff_hunks(hunks: &[DiffHunk], file: &mut dyn Write) -> std::io::Result
     Ok(())
 }
 
+/// Writes the conflict in a form the user can edit, with conflict markers
+/// around the conflicting hunks. The `.gitattributes` files in
+/// `attributes_tree`, if given, decide whether the file is binary, in which
+/// case the conflict is described instead.
 pub fn materialize_conflict(
     store: &Store,
     path: &RepoPath,
     conflict: &Conflict,
+    attributes_tree: Option<&Tree>,
+    style: ConflictMarkerStyle,
     output: &mut dyn Write,
 ) -> std::io::Result<()> {
     let file_adds = file_parts(&conflict.adds);
@@ -150,6 +238,23 @@ pub fn materialize_conflict(
         .collect_vec();
     let removed_slices = removed_content.iter().map(Vec::as_slice).collect_vec();
     let added_slices = added_content.iter().map(Vec::as_slice).collect_vec();
+    let is_binary = attributes_tree
+        .and_then(|tree| {
+            GitAttributesFile::for_path_in_tree(tree, path)
+                .is_binary(&path.to_internal_file_string())
+        })
+        .unwrap_or_else(|| {
+            removed_slices
+                .iter()
+                .chain(added_slices.iter())
+                .any(|content| files::is_binary(content))
+        });
+    if is_binary {
+        // Conflict markers would corrupt binary files, so describe the conflict
+        // instead and let the user pick a side.
+        describe_binary_conflict(conflict, output)?;
+        return Ok(());
+    }
 
     let merge_result = files::merge(&removed_slices, &added_slices);
     match merge_result {
@@ -162,41 +267,22 @@ pub fn materialize_conflict(
                     MergeHunk::Resolved(content) => {
                         output.write_all(&content)?;
                     }
-                    MergeHunk::Conflict {
-                        mut removes,
-                        mut adds,
-                    } => {
-                        output.write_all(CONFLICT_START_LINE)?;
-                        while !removes.is_empty() && !adds.is_empty() {
-                            let left = &removes[0];
-                            let mut diffs = vec![];
-                            for right in &adds {
-                                diffs.push(
-                                    Diff::for_tokenizer(&[left, right], &find_line_ranges)
-                                        .hunks()
-                                        .collect_vec(),
-                                );
+                    MergeHunk::Conflict { removes, adds } => {
+                        // Keep track of which part each slice came from so we can
+                        // print its label.
+                        let removes = file_removes.iter().copied().zip(removes).collect_vec();
+                        let adds = file_adds.iter().copied().zip(adds).collect_vec();
+                        match style {
+                            ConflictMarkerStyle::Diff => {
+                                write_diff_style_hunk(removes, adds, output)?;
+                            }
+                            ConflictMarkerStyle::Git if removes.len() == 1 && adds.len() == 2 => {
+                                write_git_style_hunk(&removes[0], &adds[0], &adds[1], output)?;
+                            }
+                            ConflictMarkerStyle::Snapshot | ConflictMarkerStyle::Git => {
+                                write_snapshot_style_hunk(&removes, &adds, output)?;
                             }
-                            let min_diff_index = diffs
-                                .iter()
-                                .position_min_by_key(|diff| diff_size(diff))
-                                .unwrap();
-                            output.write_all(CONFLICT_MINUS_LINE)?;
-                            output.write_all(CONFLICT_PLUS_LINE)?;
-                            write_diff_hunks(&diffs[min_diff_index], output)?;
-                            removes.remove(0);
-                            adds.remove(min_diff_index);
-                        }
-
-                        for slice in removes {
-                            output.write_all(CONFLICT_MINUS_LINE)?;
-                            output.write_all(&slice)?;
-                        }
-                        for slice in adds {
-                            output.write_all(CONFLICT_PLUS_LINE)?;
-                            output.write_all(&slice)?;
                         }
-                        output.write_all(CONFLICT_END_LINE)?;
                     }
                 }
             }
@@ -205,6 +291,85 @@ pub fn materialize_conflict(
     Ok(())
 }
 
+fn write_diff_style_hunk(
+    mut removes: Vec<(&ConflictPart, Vec<u8>)>,
+    mut adds: Vec<(&ConflictPart, Vec<u8>)>,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    write_marker(CONFLICT_START_MARKER, None, output)?;
+    while !removes.is_empty() && !adds.is_empty() {
+        let (left_part, left) = &removes[0];
+        let mut diffs = vec![];
+        for (_, right) in &adds {
+            diffs.push(
+                Diff::for_tokenizer(&[left, right], &find_line_ranges)
+                    .hunks()
+                    .collect_vec(),
+            );
+        }
+        let min_diff_index = diffs
+            .iter()
+            .position_min_by_key(|diff| diff_size(diff))
+            .unwrap();
+        let (right_part, _) = &adds[min_diff_index];
+        write_marker(CONFLICT_MINUS_MARKER, left_part.label.as_deref(), output)?;
+        write_marker(CONFLICT_PLUS_MARKER, right_part.label.as_deref(), output)?;
+        write_diff_hunks(&diffs[min_diff_index], output)?;
+        removes.remove(0);
+        adds.remove(min_diff_index);
+    }
+
+    for (part, slice) in removes {
+        write_marker(CONFLICT_MINUS_MARKER, part.label.as_deref(), output)?;
+        output.write_all(&slice)?;
+    }
+    for (part, slice) in adds {
+        write_marker(CONFLICT_PLUS_MARKER, part.label.as_deref(), output)?;
+        output.write_all(&slice)?;
+    }
+    write_marker(CONFLICT_END_MARKER, None, output)
+}
+
+fn write_snapshot_style_hunk(
+    removes: &[(&ConflictPart, Vec<u8>)],
+    adds: &[(&ConflictPart, Vec<u8>)],
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    // Starting with an added part is what tells the parser that this is not the
+    // diff style.
+    write_marker(CONFLICT_START_MARKER, None, output)?;
+    for (i, (part, slice)) in adds.iter().enumerate() {
+        if i > 0 {
+            if let Some((remove_part, remove_slice)) = removes.get(i - 1) {
+                write_marker(CONFLICT_MINUS_MARKER, remove_part.label.as_deref(), output)?;
+                output.write_all(remove_slice)?;
+            }
+        }
+        write_marker(CONFLICT_PLUS_MARKER, part.label.as_deref(), output)?;
+        output.write_all(slice)?;
+    }
+    for (part, slice) in removes.iter().skip(adds.len().saturating_sub(1)) {
+        write_marker(CONFLICT_MINUS_MARKER, part.label.as_deref(), output)?;
+        output.write_all(slice)?;
+    }
+    write_marker(CONFLICT_END_MARKER, None, output)
+}
+
+fn write_git_style_hunk(
+    (base_part, base): &(&ConflictPart, Vec<u8>),
+    (left_part, left): &(&ConflictPart, Vec<u8>),
+    (right_part, right): &(&ConflictPart, Vec<u8>),
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    write_marker(CONFLICT_START_MARKER, left_part.label.as_deref(), output)?;
+    output.write_all(left)?;
+    write_marker(CONFLICT_GIT_BASE_MARKER, base_part.label.as_deref(), output)?;
+    output.write_all(base)?;
+    write_marker(CONFLICT_GIT_SEPARATOR_MARKER, None, output)?;
+    output.write_all(right)?;
+    write_marker(CONFLICT_END_MARKER, right_part.label.as_deref(), output)
+}
+
 fn diff_size(hunks: &[DiffHunk]) -> usize {
     hunks
         .iter()
@@ -219,9 +384,11 @@ pub fn conflict_to_materialized_value(
     store: &Store,
     path: &RepoPath,
     conflict: &Conflict,
+    attributes_tree: Option<&Tree>,
+    style: ConflictMarkerStyle,
 ) -> TreeValue {
     let mut buf = vec![];
-    materialize_conflict(store, path, conflict, &mut buf).unwrap();
+    materialize_conflict(store, path, conflict, attributes_tree, style, &mut buf).unwrap();
     let file_id = store.write_file(path, &mut Cursor::new(&buf)).unwrap();
     TreeValue::Normal {
         id: file_id,
@@ -232,7 +399,9 @@ pub fn conflict_to_materialized_value(
 /// Parses conflict markers from a slice. Returns None if there were no valid
 /// conflict markers. The caller has to provide the expected number of removed
 /// and added inputs to the conflicts. Conflict markers that are otherwise valid
-/// will be considered invalid if they don't have the expected arity.
+/// will be considered invalid if they don't have the expected arity. Each
+/// conflict can be in any `ConflictMarkerStyle`; the style is detected from
+/// the markers.
 // TODO: "parse" is not usually the opposite of "materialize", so maybe we
 // should rename them to "serialize" and "deserialize"?
 pub fn parse_conflict(input: &[u8], num_removes: usize, num_adds: usize) -> Option<Vec<MergeHunk>> {
@@ -243,11 +412,13 @@ pub fn parse_conflict(input: &[u8], num_removes: usize, num_adds: usize) -> Opti
     let mut pos = 0;
     let mut resolved_start = 0;
     let mut conflict_start = None;
+    let mut conflict_start_line = &input[..0];
     for line in input.split_inclusive(|b| *b == b'\n') {
-        if line == CONFLICT_START_LINE {
+        if is_marker_line(line, CONFLICT_START_MARKER) {
             conflict_start = Some(pos);
-        } else if conflict_start.is_some() && line == CONFLICT_END_LINE {
-            let conflict_body = &input[conflict_start.unwrap() + CONFLICT_START_LINE.len()..pos];
+            conflict_start_line = line;
+        } else if conflict_start.is_some() && is_marker_line(line, CONFLICT_END_MARKER) {
+            let conflict_body = &input[conflict_start.unwrap() + conflict_start_line.len()..pos];
             let hunk = parse_conflict_hunk(conflict_body);
             match &hunk {
                 MergeHunk::Conflict { removes, adds }
@@ -278,20 +449,90 @@ pub fn parse_conflict(input: &[u8], num_removes: usize, num_adds: usize) -> Opti
 }
 
 fn parse_conflict_hunk(input: &[u8]) -> MergeHunk {
+    let mut lines = input.split_inclusive(|b| *b == b'\n');
+    if lines
+        .clone()
+        .any(|line| is_marker_line(line, CONFLICT_GIT_BASE_MARKER))
+    {
+        parse_git_style_conflict_hunk(input)
+    } else if lines
+        .next()
+        .map_or(false, |line| is_marker_line(line, CONFLICT_PLUS_MARKER))
+    {
+        parse_snapshot_style_conflict_hunk(input)
+    } else {
+        parse_diff_style_conflict_hunk(input)
+    }
+}
+
+fn parse_snapshot_style_conflict_hunk(input: &[u8]) -> MergeHunk {
+    let mut removes = vec![];
+    let mut adds = vec![];
+    let mut current: Option<&mut Vec<u8>> = None;
+    for line in input.split_inclusive(|b| *b == b'\n') {
+        if is_marker_line(line, CONFLICT_MINUS_MARKER) {
+            removes.push(vec![]);
+            current = removes.last_mut();
+        } else if is_marker_line(line, CONFLICT_PLUS_MARKER) {
+            adds.push(vec![]);
+            current = adds.last_mut();
+        } else if let Some(buf) = &mut current {
+            buf.extend_from_slice(line);
+        } else {
+            // Doesn't look like a conflict
+            return MergeHunk::Resolved(vec![]);
+        }
+    }
+    MergeHunk::Conflict { removes, adds }
+}
+
+fn parse_git_style_conflict_hunk(input: &[u8]) -> MergeHunk {
+    let mut left = vec![];
+    let mut base = vec![];
+    let mut right = vec![];
+    let mut base_seen = false;
+    let mut separator_seen = false;
+    for line in input.split_inclusive(|b| *b == b'\n') {
+        if !base_seen && is_marker_line(line, CONFLICT_GIT_BASE_MARKER) {
+            base_seen = true;
+        } else if base_seen
+            && !separator_seen
+            && is_marker_line(line, CONFLICT_GIT_SEPARATOR_MARKER)
+        {
+            separator_seen = true;
+        } else if separator_seen {
+            right.extend_from_slice(line);
+        } else if base_seen {
+            base.extend_from_slice(line);
+        } else {
+            left.extend_from_slice(line);
+        }
+    }
+    if !separator_seen {
+        // Doesn't look like a conflict
+        return MergeHunk::Resolved(vec![]);
+    }
+    MergeHunk::Conflict {
+        removes: vec![base],
+        adds: vec![left, right],
+    }
+}
+
+fn parse_diff_style_conflict_hunk(input: &[u8]) -> MergeHunk {
     let mut minus_seen = false;
     let mut plus_seen = false;
     let mut body_seen = false;
     let mut removes = vec![];
     let mut adds = vec![];
     for line in input.split_inclusive(|b| *b == b'\n') {
-        if line == CONFLICT_MINUS_LINE {
+        if is_marker_line(line, CONFLICT_MINUS_MARKER) {
             minus_seen = true;
             if body_seen {
                 plus_seen = false;
                 body_seen = false;
             }
             removes.push(vec![]);
-        } else if line == CONFLICT_PLUS_LINE {
+        } else if is_marker_line(line, CONFLICT_PLUS_MARKER) {
             plus_seen = true;
             if body_seen {
                 minus_seen = false;
@@ -330,7 +571,9 @@ pub fn update_conflict_from_content(
     store: &Store,
     path: &RepoPath,
     conflict_id: &ConflictId,
+    attributes_tree: Option<&Tree>,
     content: &[u8],
+    style: ConflictMarkerStyle,
 ) -> BackendResult<Option<ConflictId>> {
     let mut conflict = store.read_conflict(path, conflict_id)?;
 
@@ -340,7 +583,15 @@ pub fn update_conflict_from_content(
     // conflicts (for example) are not converted to regular files in the working
     // copy.
     let mut old_content = Vec::with_capacity(content.len());
-    materialize_conflict(store, path, &conflict, &mut old_content).unwrap();
+    materialize_conflict(
+        store,
+        path,
+        &conflict,
+        attributes_tree,
+        style,
+        &mut old_content,
+    )
+    .unwrap();
     if content == old_content {
         return Ok(Some(conflict_id.clone()));
     }
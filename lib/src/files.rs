@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::VecDeque;
 use std::fmt::{Debug, Error, Formatter};
 use std::ops::Range;
 
@@ -44,6 +44,17 @@ impl DiffLine<'_> {
     }
 }
 
+/// The number of bytes at the start of a file that are checked for NUL bytes
+/// when guessing if the file is binary. This is the same limit as Git uses.
+const BINARY_DETECTION_LENGTH: usize = 8000;
+
+/// Guesses whether the content is binary (as opposed to text) by checking for
+/// NUL bytes near the start of it, like Git does.
+pub fn is_binary(content: &[u8]) -> bool {
+    let len = content.len().min(BINARY_DETECTION_LENGTH);
+    content[..len].contains(&0)
+}
+
 pub fn diff<'a>(left: &'a [u8], right: &'a [u8]) -> DiffLineIterator<'a> {
     let diff_hunks = diff::diff(left, right);
     DiffLineIterator::new(diff_hunks)
@@ -204,17 +215,83 @@ struct SyncRegion {
     right: Range<usize>,
 }
 
-// TODO: Should we require `add.len() == removes.len() + 1`? If that condition
-// is false, it effectively means that we should pretend that there are empty
-// strings in `removes` or `adds` to make it true. Maybe we should have to
-// caller make it explicitly that way.
+/// Returns the value that the merge of `adds` and `removes` resolves to, if
+/// any. Each value is counted once for every time it's added and negatively for
+/// every time it's removed. The merge resolves if exactly one value has a
+/// positive count, which means that all sides that changed the value agree on
+/// the result. The result doesn't depend on the order of `removes` and `adds`.
+pub fn trivial_merge<'a, T: PartialEq>(removes: &'a [T], adds: &'a [T]) -> Option<&'a T> {
+    match net_counts(removes, adds)
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .exactly_one()
+    {
+        Ok((value, _)) => Some(value),
+        Err(_) => None,
+    }
+}
+
+/// Counts how many more times each value is added than removed.
+fn net_counts<'a, T: PartialEq>(removes: &'a [T], adds: &'a [T]) -> Vec<(&'a T, i32)> {
+    let mut counts: Vec<(&T, i32)> = vec![];
+    let removes = removes.iter().map(|value| (value, -1));
+    let adds = adds.iter().map(|value| (value, 1));
+    for (value, delta) in removes.chain(adds) {
+        match counts.iter_mut().find(|(counted, _)| *counted == value) {
+            Some((_, count)) => *count += delta,
+            None => counts.push((value, delta)),
+        }
+    }
+    counts
+}
+
+/// Resolves a hunk where the inputs differ. See `merge()`.
+fn merge_hunk<'a>(removes: &[&'a [u8]], adds: &[&'a [u8]]) -> Option<&'a [u8]> {
+    if adds.len() == removes.len() + 1 {
+        trivial_merge(removes, adds).copied()
+    } else if net_counts(removes, adds)
+        .iter()
+        .all(|(_, count)| *count <= 0)
+    {
+        // Nothing is left once the removes are applied, so the hunk is empty
+        Some(b"")
+    } else {
+        None
+    }
+}
+
+/// Merges the inputs line by line. The result is what you get by starting from
+/// the adds and taking away the removes, so in a 3-way merge, there's one
+/// remove, which is the base, and two adds, which are the sides. In general,
+/// there should be one more add than removes. Each region of lines is resolved
+/// independently:
+///
+/// * A region where all inputs match is kept if there are more adds than
+///   removes.
+/// * In a region where the inputs differ, each distinct content is counted for
+///   every add it appears in, and negatively for every remove. The region
+///   resolves to the content with a positive count if there's exactly one such
+///   content. That includes the cases where only some sides changed the region
+///   and they all made the same change.
+///
+/// If there are not one more adds than removes (which happens for conflicts
+/// where some sides are absent), a region where the inputs differ only
+/// resolves if nothing is left once the removes are applied.
+///
+/// The result doesn't depend on the order of the removes or of the adds, except
+/// for the order of the parts in conflicting hunks, which always matches the
+/// order of the inputs so the caller knows which part came from which input.
 pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
     let num_removes = removes.len();
-    // TODO: Using the first remove as base (first in the inputs) is how it's
-    // usually done for 3-way conflicts. Are there better heuristics when there are
-    // more than 3 parts?
     let mut diff_inputs = removes.to_vec();
     diff_inputs.extend(adds);
+    // The other inputs are aligned to the first input, so pick the smallest
+    // remove as the first input to make the alignment independent of the order
+    // of the inputs.
+    let base_index = (0..diff_inputs.len())
+        .min_by_key(|&index| (index >= num_removes, diff_inputs[index]))
+        .unwrap_or(0);
+    diff_inputs.swap(0, base_index);
 
     let diff = Diff::for_tokenizer(&diff_inputs, &diff::find_line_ranges);
     let mut resolved_hunk: Vec<u8> = vec![];
@@ -226,40 +303,11 @@ pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
                     resolved_hunk.extend(content);
                 }
             }
-            DiffHunk::Different(parts) => {
-                let mut removed_parts = parts[..num_removes].to_vec();
-                let mut added_parts = parts[num_removes..].to_vec();
-                // Remove pairs of parts that match in the removes and adds.
-                let mut added_index = 0;
-                while added_index < added_parts.len() {
-                    let added_part = added_parts[added_index];
-                    added_index += 1;
-                    for (removed_index, removed_part) in removed_parts.iter().enumerate() {
-                        if *removed_part == added_part {
-                            added_index -= 1;
-                            added_parts.remove(added_index);
-                            removed_parts.remove(removed_index);
-                            break;
-                        }
-                    }
-                }
-                let distinct_removes: HashSet<&[u8]> = removed_parts.iter().copied().collect();
-                let distinct_adds: HashSet<&[u8]> = added_parts.iter().copied().collect();
-                if removed_parts.is_empty() && added_parts.is_empty() {
-                    // The same content was added and removed, so there's
-                    // nothing left.
-                } else if distinct_removes.is_empty() && distinct_adds.len() == 1 {
-                    // All sides added the same content
-                    resolved_hunk.extend(added_parts[0]);
-                } else if distinct_removes.len() == 1 && distinct_adds.is_empty() {
-                    // All sides removed the same content
-                } else if distinct_removes.len() == 1
-                    && distinct_adds.len() == 1
-                    && added_parts.len() == removed_parts.len() + 1
-                {
-                    // All sides made the same change, and there's a matching extra base to apply it
-                    // to
-                    resolved_hunk.extend(added_parts[0]);
+            DiffHunk::Different(mut parts) => {
+                parts.swap(0, base_index);
+                let (removed_parts, added_parts) = parts.split_at(num_removes);
+                if let Some(content) = merge_hunk(removed_parts, added_parts) {
+                    resolved_hunk.extend(content);
                 } else {
                     if !resolved_hunk.is_empty() {
                         merge_hunks.push(MergeHunk::Resolved(resolved_hunk));
@@ -268,14 +316,8 @@ pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
                     // Include the unfiltered lists of removed and added here, so the caller
                     // knows which part corresponds to which input.
                     merge_hunks.push(MergeHunk::Conflict {
-                        removes: parts[..num_removes]
-                            .iter()
-                            .map(|part| part.to_vec())
-                            .collect_vec(),
-                        adds: parts[num_removes..]
-                            .iter()
-                            .map(|part| part.to_vec())
-                            .collect_vec(),
+                        removes: removed_parts.iter().map(|part| part.to_vec()).collect_vec(),
+                        adds: added_parts.iter().map(|part| part.to_vec()).collect_vec(),
                     });
                 }
             }
@@ -292,10 +334,145 @@ pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
     }
 }
 
+/// Merges the inputs as opaque blobs instead of line by line. This is used for
+/// binary files, where a line-based merge would produce garbage. Returns `None`
+/// if the inputs don't resolve to a single value.
+pub fn merge_binary(removes: &[&[u8]], adds: &[&[u8]]) -> Option<Vec<u8>> {
+    trivial_merge(removes, adds).map(|content| content.to_vec())
+}
+
+/// Builds the merged content from the hunks of a merge with conflicts, where
+/// each conflicting hunk is replaced by the result of `resolve_conflict`.
+/// Returns `None` if any conflicting hunk couldn't be resolved.
+pub fn resolve_conflict_hunks(
+    hunks: &[MergeHunk],
+    mut resolve_conflict: impl FnMut(&[Vec<u8>], &[Vec<u8>]) -> Option<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    let mut content = vec![];
+    for hunk in hunks {
+        match hunk {
+            MergeHunk::Resolved(resolved) => content.extend_from_slice(resolved),
+            MergeHunk::Conflict { removes, adds } => {
+                content.extend(resolve_conflict(removes, adds)?);
+            }
+        }
+    }
+    Some(content)
+}
+
+/// An external program that merges files, configured in
+/// `[merge-drivers.<name>]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeDriver {
+    pub program: String,
+    /// Arguments to pass to the program. `$base`, `$left`, and `$right` are
+    /// replaced by paths to the inputs, `$output` by the path the result should
+    /// be written to, and `$path` by the path of the file in the repo.
+    pub args: Vec<String>,
+}
+
+impl MergeDriver {
+    /// Runs the driver on a 3-way merge. Returns `None` if the driver exited
+    /// with a non-zero status, which means that it couldn't merge the inputs.
+    pub fn run(
+        &self,
+        path: &str,
+        base: &[u8],
+        left: &[u8],
+        right: &[u8],
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut paths = vec![];
+        for (name, content) in [("base", base), ("left", left), ("right", right)] {
+            let input_path = temp_dir.path().join(name);
+            std::fs::write(&input_path, content)?;
+            paths.push((name, input_path));
+        }
+        let output_path = temp_dir.path().join("output");
+        std::fs::write(&output_path, b"")?;
+        paths.push(("output", output_path.clone()));
+        let args = self.args.iter().map(|arg| {
+            let mut arg = arg.replace("$path", path);
+            for (name, path) in &paths {
+                arg = arg.replace(&format!("${name}"), &path.to_string_lossy());
+            }
+            arg
+        });
+        let status = std::process::Command::new(&self.program)
+            .args(args)
+            .status()?;
+        if !status.success() {
+            return Ok(None);
+        }
+        std::fs::read(&output_path).map(Some)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::prelude::*;
+
     use super::*;
 
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"a\nb\n"));
+        assert!(!is_binary("\u{e5}\n".as_bytes()));
+        assert!(is_binary(b"a\0b"));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        // NUL bytes are only looked for near the start of the content
+        let mut content = vec![b'a'; BINARY_DETECTION_LENGTH];
+        content.push(0);
+        assert!(!is_binary(&content));
+    }
+
+    #[test]
+    fn test_merge_binary() {
+        // One side changed
+        assert_eq!(
+            merge_binary(&[b"a\0"], &[b"a\0", b"b\0"]),
+            Some(b"b\0".to_vec())
+        );
+        assert_eq!(
+            merge_binary(&[b"a\0"], &[b"b\0", b"a\0"]),
+            Some(b"b\0".to_vec())
+        );
+        // Both sides made the same change
+        assert_eq!(
+            merge_binary(&[b"a\0"], &[b"b\0", b"b\0"]),
+            Some(b"b\0".to_vec())
+        );
+        // Both sides made different changes, even if they don't overlap
+        // line-wise
+        assert_eq!(
+            merge_binary(&[b"a\n\0\nb\n"], &[b"c\n\0\nb\n", b"a\n\0\nd\n"]),
+            None
+        );
+        // Nested conflict where one side was undone
+        assert_eq!(
+            merge_binary(&[b"a\0", b"b\0"], &[b"b\0", b"a\0", b"c\0"]),
+            Some(b"c\0".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_resolve_conflict_hunks() {
+        let hunks = match merge(&[b"a\nb\nc\n"], &[b"a\nB1\nc\n", b"a\nB2\nc\n"]) {
+            MergeResult::Conflict(hunks) => hunks,
+            MergeResult::Resolved(_) => panic!("expected a conflict"),
+        };
+        assert_eq!(
+            resolve_conflict_hunks(&hunks, |_, adds| Some(adds.concat())),
+            Some(b"a\nB1\nB2\nc\n".to_vec())
+        );
+        assert_eq!(
+            resolve_conflict_hunks(&hunks, |removes, _| removes.first().cloned()),
+            Some(b"a\nb\nc\n".to_vec())
+        );
+        assert_eq!(resolve_conflict_hunks(&hunks, |_, _| None), None);
+    }
+
     #[test]
     fn test_merge() {
         // Unchanged and empty on all sides
@@ -440,5 +617,158 @@ mod tests {
                 adds: vec![b"c".to_vec(), b"d".to_vec(), b"e".to_vec()]
             }])
         );
+        // All sides of a 3-way merge made the same change from different bases
+        assert_eq!(
+            merge(&[b"a", b"b"], &[b"c", b"c", b"c"]),
+            MergeResult::Resolved(b"c".to_vec())
+        );
+        // Two sides of a 4-way merge made the same change, and the others are
+        // unchanged
+        assert_eq!(
+            merge(&[b"a", b"a", b"a"], &[b"b", b"a", b"b", b"a"]),
+            MergeResult::Resolved(b"b".to_vec())
+        );
+        // Sides of a 4-way merge agree in one region but not in another
+        assert_eq!(
+            merge(
+                &[b"a\nb\nc\n", b"a\nb\nc\n", b"a\nb\nc\n"],
+                &[b"x\nb\nc\n", b"a\nb\nc\n", b"x\nb\ny\n", b"a\nb\nz\n"]
+            ),
+            MergeResult::Conflict(vec![
+                MergeHunk::Resolved(b"x\nb\n".to_vec()),
+                MergeHunk::Conflict {
+                    removes: vec![b"c\n".to_vec(), b"c\n".to_vec(), b"c\n".to_vec()],
+                    adds: vec![
+                        b"c\n".to_vec(),
+                        b"c\n".to_vec(),
+                        b"y\n".to_vec(),
+                        b"z\n".to_vec()
+                    ]
+                }
+            ])
+        );
+    }
+
+    /// Generates a file with a few lines from a small alphabet, so different
+    /// files often share lines.
+    fn random_content(rng: &mut StdRng) -> Vec<u8> {
+        let num_lines = rng.gen_range(0..6);
+        (0..num_lines)
+            .flat_map(|_| [b"abcd"[rng.gen_range(0..4)], b'\n'])
+            .collect()
+    }
+
+    /// Generates a file by randomly removing, replacing, and inserting lines in
+    /// `base`.
+    fn random_edit(rng: &mut StdRng, base: &[u8]) -> Vec<u8> {
+        let mut content = vec![];
+        for line in base.split_inclusive(|b| *b == b'\n') {
+            match rng.gen_range(0..6) {
+                0 => {}
+                1 => content.extend(random_content(rng)),
+                2 => {
+                    content.extend(line);
+                    content.extend(random_content(rng));
+                }
+                _ => content.extend(line),
+            }
+        }
+        content
+    }
+
+    /// Puts the parts of the conflicting hunks in a canonical order so results
+    /// of merging the same inputs in different orders can be compared.
+    fn sort_conflict_parts(result: MergeResult) -> MergeResult {
+        match result {
+            MergeResult::Resolved(_) => result,
+            MergeResult::Conflict(hunks) => MergeResult::Conflict(
+                hunks
+                    .into_iter()
+                    .map(|hunk| match hunk {
+                        MergeHunk::Resolved(_) => hunk,
+                        MergeHunk::Conflict { removes, adds } => MergeHunk::Conflict {
+                            removes: removes.into_iter().sorted().collect(),
+                            adds: adds.into_iter().sorted().collect(),
+                        },
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_merge_is_symmetric() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let base = random_content(&mut rng);
+            let left = random_edit(&mut rng, &base);
+            let right = random_edit(&mut rng, &base);
+            let left_right = merge(&[&base], &[&left, &right]);
+            let right_left = merge(&[&base], &[&right, &left]);
+            assert_eq!(
+                sort_conflict_parts(left_right.clone()),
+                sort_conflict_parts(right_left.clone()),
+                "base: {base:?}, left: {left:?}, right: {right:?}"
+            );
+            // The conflicting hunks have the parts in the order of the inputs
+            if let (MergeResult::Conflict(hunks1), MergeResult::Conflict(hunks2)) =
+                (left_right, right_left)
+            {
+                for (hunk1, hunk2) in hunks1.into_iter().zip(hunks2) {
+                    if let (
+                        MergeHunk::Conflict { adds: adds1, .. },
+                        MergeHunk::Conflict { adds: adds2, .. },
+                    ) = (hunk1, hunk2)
+                    {
+                        assert_eq!(adds1, adds2.into_iter().rev().collect_vec());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_unchanged_side() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let base = random_content(&mut rng);
+            let side = random_edit(&mut rng, &base);
+            assert_eq!(
+                merge(&[&base], &[&base, &side]),
+                MergeResult::Resolved(side.clone())
+            );
+            assert_eq!(
+                merge(&[&base], &[&side, &side]),
+                MergeResult::Resolved(side.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_n_way_is_symmetric() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            // Merges of 3 and 4 sides, like the merges of 3 and 4 parents
+            let num_removes = rng.gen_range(2..4);
+            let base = random_content(&mut rng);
+            let removes = (0..num_removes)
+                .map(|_| random_edit(&mut rng, &base))
+                .collect_vec();
+            let adds = (0..=num_removes)
+                .map(|_| random_edit(&mut rng, &base))
+                .collect_vec();
+            let removes = removes.iter().map(Vec::as_slice).collect_vec();
+            let adds = adds.iter().map(Vec::as_slice).collect_vec();
+            let expected = sort_conflict_parts(merge(&removes, &adds));
+            let mut shuffled_removes = removes.clone();
+            let mut shuffled_adds = adds.clone();
+            shuffled_removes.shuffle(&mut rng);
+            shuffled_adds.shuffle(&mut rng);
+            assert_eq!(
+                sort_conflict_parts(merge(&shuffled_removes, &shuffled_adds)),
+                expected,
+                "removes: {removes:?}, adds: {adds:?}"
+            );
+        }
     }
 }
@@ -25,8 +25,8 @@ use uuid::Uuid;
 
 use crate::backend::{
     Backend, BackendError, BackendResult, ChangeId, Commit, CommitId, Conflict, ConflictId,
-    ConflictPart, FileId, MillisSinceEpoch, Signature, SymlinkId, Timestamp, Tree, TreeId,
-    TreeValue,
+    ConflictPart, FileId, MillisSinceEpoch, SecureSig, Signature, SigningFn, SymlinkId, Timestamp,
+    Tree, TreeId, TreeValue,
 };
 use crate::repo_path::{RepoPath, RepoPathComponent};
 use crate::stacked_table::{TableSegment, TableStore};
@@ -111,7 +111,7 @@ fn signature_from_git(signature: git2::Signature) -> Signature {
     }
 }
 
-fn signature_to_git(signature: &Signature) -> git2::Signature {
+fn signature_to_git(signature: &Signature) -> git2::Signature<'static> {
     let name = &signature.name;
     let email = &signature.email;
     let time = git2::Time::new(
@@ -128,6 +128,9 @@ fn serialize_extras(commit: &Commit) -> Vec<u8> {
     for predecessor in &commit.predecessors {
         proto.predecessors.push(predecessor.to_bytes());
     }
+    if let Some(copied_from) = &commit.copied_from {
+        proto.copied_from = copied_from.to_bytes();
+    }
     proto.write_to_bytes().unwrap()
 }
 
@@ -139,6 +142,9 @@ fn deserialize_extras(commit: &mut Commit, bytes: &[u8]) {
     for predecessor in &proto.predecessors {
         commit.predecessors.push(CommitId::from_bytes(predecessor));
     }
+    if !proto.copied_from.is_empty() {
+        commit.copied_from = Some(CommitId::from_bytes(&proto.copied_from));
+    }
 }
 
 /// Creates a random ref in refs/jj/. Used for preventing GC of commits we
@@ -374,7 +380,15 @@ impl Backend for GitBackend {
             author,
             committer,
             is_open: false,
+            secure_sig: None,
+            copied_from: None,
         };
+        if let Ok((sig, data)) = locked_repo.extract_signature(&git_commit_id, None) {
+            commit.secure_sig = Some(SecureSig {
+                data: data.to_vec(),
+                sig: sig.to_vec(),
+            });
+        }
 
         let table = self.extra_metadata_store.get_head().map_err(|err| {
             BackendError::Other(format!("Failed to read non-git metadata: {err}"))
@@ -387,7 +401,11 @@ impl Backend for GitBackend {
         Ok(commit)
     }
 
-    fn write_commit(&self, contents: &Commit) -> BackendResult<CommitId> {
+    fn write_commit(
+        &self,
+        mut contents: Commit,
+        sign_with: Option<&SigningFn>,
+    ) -> BackendResult<(CommitId, Commit)> {
         let locked_repo = self.repo.lock().unwrap();
         let git_tree = locked_repo.find_tree(Oid::from_bytes(contents.root_tree.as_bytes())?)?;
         let author = signature_to_git(&contents.author);
@@ -401,16 +419,42 @@ impl Backend for GitBackend {
             parents.push(parent_git_commit);
         }
         let parent_refs = parents.iter().collect_vec();
-        let git_id = locked_repo.commit(
-            Some(&create_no_gc_ref()),
-            &author,
-            &committer,
-            message,
-            &git_tree,
-            &parent_refs,
-        )?;
+        let git_id = if let Some(sign) = sign_with {
+            let data = locked_repo.commit_create_buffer(
+                &author,
+                &committer,
+                message,
+                &git_tree,
+                &parent_refs,
+            )?;
+            let sig = sign(&data)
+                .map_err(|err| BackendError::Other(format!("Failed to sign commit: {err}")))?;
+            let git_id = locked_repo.commit_signed(
+                std::str::from_utf8(&data).unwrap(),
+                std::str::from_utf8(&sig).map_err(|_| {
+                    BackendError::Other("Commit signature is not valid UTF-8".to_string())
+                })?,
+                None,
+            )?;
+            // Unlike `commit()`, `commit_signed()` doesn't update a ref
+            locked_repo.reference(&create_no_gc_ref(), git_id, true, "jj commit")?;
+            contents.secure_sig = Some(SecureSig {
+                data: data.to_vec(),
+                sig,
+            });
+            git_id
+        } else {
+            locked_repo.commit(
+                Some(&create_no_gc_ref()),
+                &author,
+                &committer,
+                message,
+                &git_tree,
+                &parent_refs,
+            )?
+        };
         let id = CommitId::from_bytes(git_id.as_bytes());
-        let extras = serialize_extras(contents);
+        let extras = serialize_extras(&contents);
         let mut mut_table = self
             .extra_metadata_store
             .get_head()
@@ -430,7 +474,7 @@ impl Backend for GitBackend {
             .map_err(|err| {
                 BackendError::Other(format!("Failed to write non-git metadata: {err}"))
             })?;
-        Ok(id)
+        Ok((id, contents))
     }
 }
 
@@ -447,15 +491,23 @@ fn conflict_part_list_from_json(json: &serde_json::Value) -> Vec<ConflictPart> {
 }
 
 fn conflict_part_to_json(part: &ConflictPart) -> serde_json::Value {
-    serde_json::json!({
+    let mut json = serde_json::json!({
         "value": tree_value_to_json(&part.value),
-    })
+    });
+    if let Some(label) = &part.label {
+        json["label"] = serde_json::Value::from(label.as_str());
+    }
+    json
 }
 
 fn conflict_part_from_json(json: &serde_json::Value) -> ConflictPart {
     let json_value = json.get("value").unwrap();
     ConflictPart {
         value: tree_value_from_json(json_value),
+        label: json
+            .get("label")
+            .and_then(|label| label.as_str())
+            .map(str::to_string),
     }
 }
 
@@ -643,8 +695,10 @@ mod tests {
             author: signature.clone(),
             committer: signature,
             is_open: false,
+            secure_sig: None,
+            copied_from: None,
         };
-        let commit_id = store.write_commit(&commit).unwrap();
+        let (commit_id, _) = store.write_commit(commit, None).unwrap();
         let git_refs = store
             .git_repo()
             .unwrap()
@@ -679,12 +733,14 @@ mod tests {
             author: signature.clone(),
             committer: signature,
             is_open: false,
+            secure_sig: None,
+            copied_from: None,
         };
-        let commit_id1 = store.write_commit(&commit1).unwrap();
+        let (commit_id1, _) = store.write_commit(commit1.clone(), None).unwrap();
         let mut commit2 = commit1;
         commit2.predecessors.push(commit_id1.clone());
         let expected_error_message = format!("Git commit '{}' already exists", commit_id1.hex());
-        match store.write_commit(&commit2) {
+        match store.write_commit(commit2, None) {
             Ok(_) => {
                 panic!("expectedly successfully wrote two commits with the same git commit object")
             }
@@ -692,4 +748,54 @@ mod tests {
             Err(err) => panic!("unexpected error: {:?}", err),
         };
     }
+
+    #[test]
+    fn write_signed_commit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = GitBackend::init_internal(temp_dir.path().to_path_buf());
+        let signature = Signature {
+            name: "Someone".to_string(),
+            email: "someone@example.com".to_string(),
+            timestamp: Timestamp {
+                timestamp: MillisSinceEpoch(0),
+                tz_offset: 0,
+            },
+        };
+        let commit = Commit {
+            parents: vec![],
+            predecessors: vec![],
+            root_tree: store.empty_tree_id().clone(),
+            change_id: ChangeId::new(vec![]),
+            description: "initial".to_string(),
+            author: signature.clone(),
+            committer: signature,
+            is_open: false,
+            secure_sig: None,
+            copied_from: None,
+        };
+        let sign = |data: &[u8]| Ok(format!("signed {} bytes", data.len()).into_bytes());
+        let (commit_id, written_commit) = store.write_commit(commit, Some(&sign)).unwrap();
+        let secure_sig = written_commit.secure_sig.unwrap();
+        assert_eq!(
+            secure_sig.sig,
+            format!("signed {} bytes", secure_sig.data.len()).into_bytes()
+        );
+
+        // The signature is stored in the Git commit and read back from there
+        let read_commit = store.read_commit(&commit_id).unwrap();
+        assert_eq!(read_commit.secure_sig, Some(secure_sig));
+
+        // The signed commit is protected from GC just like unsigned commits
+        let git_refs = store
+            .git_repo()
+            .unwrap()
+            .references_glob("refs/jj/keep/*")
+            .unwrap()
+            .map(|git_ref| git_ref.unwrap().target().unwrap())
+            .collect_vec();
+        assert_eq!(
+            git_refs,
+            vec![Oid::from_bytes(commit_id.as_bytes()).unwrap()]
+        );
+    }
 }
@@ -0,0 +1,323 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::backend::TreeValue;
+use crate::gitignore::GitIgnoreLine;
+use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
+use crate::tree::Tree;
+
+/// The state of an attribute for a path, as described in gitattributes(5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeState {
+    /// The attribute was listed without a value (e.g. `text`)
+    Set,
+    /// The attribute was listed with a `-` prefix (e.g. `-text`)
+    Unset,
+    /// The attribute was listed with a value (e.g. `merge=union`)
+    Value(String),
+    /// No line mentions the attribute, or the last one to do so used a `!`
+    /// prefix (e.g. `!text`)
+    Unspecified,
+}
+
+/// How conflicting changes to a file should be merged, as set by the `merge`
+/// attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// No strategy was set. Files are merged line by line unless they look
+    /// binary.
+    Default,
+    /// Always merge line by line (`merge` or `merge=text`)
+    Text,
+    /// Never merge line by line (`-merge` or `merge=binary`)
+    Binary,
+    /// Keep the lines from all sides of conflicting hunks (`merge=union`)
+    Union,
+    /// Resolve conflicting hunks by picking the first side (`merge=ours`)
+    Ours,
+    /// Resolve conflicting hunks by picking the last side (`merge=theirs`)
+    Theirs,
+    /// Merge with the custom merge driver of the given name
+    Driver(String),
+}
+
+#[derive(Debug)]
+struct GitAttributesLine {
+    pattern: GitIgnoreLine,
+    attributes: Vec<(String, AttributeState)>,
+}
+
+impl GitAttributesLine {
+    fn parse(prefix: &str, input: &str) -> Option<GitAttributesLine> {
+        let input = input.strip_suffix('\r').unwrap_or(input).trim_start();
+        // Macro definitions are not supported (other than the built-in "binary"
+        // macro). Negative patterns are forbidden by Git.
+        if input.starts_with('#') || input.starts_with("[attr]") || input.starts_with('!') {
+            return None;
+        }
+        let mut words = input.split_whitespace();
+        let pattern = GitIgnoreLine::parse(prefix, words.next()?)?;
+        let mut attributes = vec![];
+        for word in words {
+            if let Some(name) = word.strip_prefix('-') {
+                attributes.push((name.to_string(), AttributeState::Unset));
+            } else if let Some(name) = word.strip_prefix('!') {
+                attributes.push((name.to_string(), AttributeState::Unspecified));
+            } else if let Some((name, value)) = word.split_once('=') {
+                attributes.push((name.to_string(), AttributeState::Value(value.to_string())));
+            } else {
+                attributes.push((word.to_string(), AttributeState::Set));
+                if word == "binary" {
+                    // The built-in "binary" macro expands to "-diff -merge -text".
+                    for name in ["diff", "merge", "text"] {
+                        attributes.push((name.to_string(), AttributeState::Unset));
+                    }
+                }
+            }
+        }
+        Some(GitAttributesLine {
+            pattern,
+            attributes,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct GitAttributesFile {
+    parent: Option<Arc<GitAttributesFile>>,
+    lines: Vec<GitAttributesLine>,
+}
+
+impl GitAttributesFile {
+    pub fn empty() -> Arc<GitAttributesFile> {
+        Arc::new(GitAttributesFile {
+            parent: None,
+            lines: vec![],
+        })
+    }
+
+    pub fn chain(
+        self: &Arc<GitAttributesFile>,
+        prefix: &str,
+        input: &[u8],
+    ) -> Arc<GitAttributesFile> {
+        let mut lines = vec![];
+        for input_line in input.split(|b| *b == b'\n') {
+            // Skip non-utf8 lines
+            if let Ok(line_string) = String::from_utf8(input_line.to_vec()) {
+                if let Some(line) = GitAttributesLine::parse(prefix, &line_string) {
+                    lines.push(line);
+                }
+            }
+        }
+
+        Arc::new(GitAttributesFile {
+            parent: Some(self.clone()),
+            lines,
+        })
+    }
+
+    /// Reads the `.gitattributes` files from `tree` that apply to `path`, i.e.
+    /// the ones in the root directory and in each of the path's ancestor
+    /// directories. Files that can't be read are ignored.
+    pub fn for_path_in_tree(tree: &Tree, path: &RepoPath) -> Arc<GitAttributesFile> {
+        let mut file = GitAttributesFile::empty();
+        let file_name = RepoPathComponent::from(".gitattributes");
+        let mut dir = RepoPath::root();
+        let mut components = path.components().iter();
+        // The last component is the file itself
+        components.next_back();
+        loop {
+            let attributes_path = dir.join(&file_name);
+            if let Some(TreeValue::Normal { id, .. }) = tree.path_value(&attributes_path) {
+                let mut content = vec![];
+                if let Ok(mut reader) = tree.store().read_file(&attributes_path, &id) {
+                    if reader.read_to_end(&mut content).is_ok() {
+                        file = file.chain(&dir.to_internal_dir_string(), &content);
+                    }
+                }
+            }
+            match components.next() {
+                Some(component) => dir = dir.join(component),
+                None => break,
+            }
+        }
+        file
+    }
+
+    fn all_lines_reversed<'a>(&'a self) -> Box<dyn Iterator<Item = &'a GitAttributesLine> + 'a> {
+        if let Some(parent) = &self.parent {
+            Box::new(self.lines.iter().rev().chain(parent.all_lines_reversed()))
+        } else {
+            Box::new(self.lines.iter().rev())
+        }
+    }
+
+    /// Returns the state of the attribute called `name` for the file at
+    /// `path`.
+    pub fn get(&self, path: &str, name: &str) -> AttributeState {
+        // Later lines take precedence, so check them in reverse
+        for line in self.all_lines_reversed() {
+            if line.pattern.matches(path) {
+                if let Some((_, state)) = line
+                    .attributes
+                    .iter()
+                    .rev()
+                    .find(|(attribute_name, _)| attribute_name == name)
+                {
+                    return state.clone();
+                }
+            }
+        }
+        AttributeState::Unspecified
+    }
+
+    /// Returns whether the file at `path` should be treated as binary. That's
+    /// the case if it has the `binary` attribute, or if it has the `text` or
+    /// `diff` attribute unset. Returns `None` if the attributes don't say
+    /// either way.
+    pub fn is_binary(&self, path: &str) -> Option<bool> {
+        match self.get(path, "text") {
+            AttributeState::Unset => return Some(true),
+            AttributeState::Set => return Some(false),
+            _ => {}
+        }
+        match self.get(path, "diff") {
+            AttributeState::Unset => Some(true),
+            AttributeState::Set => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns how conflicting changes to the file at `path` should be merged.
+    pub fn merge_strategy(&self, path: &str) -> MergeStrategy {
+        match self.get(path, "merge") {
+            AttributeState::Set => MergeStrategy::Text,
+            AttributeState::Unset => MergeStrategy::Binary,
+            AttributeState::Unspecified => MergeStrategy::Default,
+            AttributeState::Value(value) => match value.as_str() {
+                "text" => MergeStrategy::Text,
+                "binary" => MergeStrategy::Binary,
+                "union" => MergeStrategy::Union,
+                "ours" => MergeStrategy::Ours,
+                "theirs" => MergeStrategy::Theirs,
+                _ => MergeStrategy::Driver(value),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(input: &[u8], path: &str, name: &str) -> AttributeState {
+        let file = GitAttributesFile::empty().chain("", input);
+        file.get(path, name)
+    }
+
+    #[test]
+    fn test_gitattributes_empty_file() {
+        let file = GitAttributesFile::empty();
+        assert_eq!(file.get("foo", "text"), AttributeState::Unspecified);
+        assert_eq!(file.is_binary("foo"), None);
+    }
+
+    #[test]
+    fn test_gitattributes_states() {
+        let input = b"*.txt text -diff !merge eol=lf\n";
+        assert_eq!(get(input, "foo.txt", "text"), AttributeState::Set);
+        assert_eq!(get(input, "foo.txt", "diff"), AttributeState::Unset);
+        assert_eq!(get(input, "foo.txt", "merge"), AttributeState::Unspecified);
+        assert_eq!(
+            get(input, "foo.txt", "eol"),
+            AttributeState::Value("lf".to_string())
+        );
+        assert_eq!(get(input, "foo.txt", "other"), AttributeState::Unspecified);
+        assert_eq!(get(input, "foo.rs", "text"), AttributeState::Unspecified);
+    }
+
+    #[test]
+    fn test_gitattributes_ignored_lines() {
+        assert_eq!(
+            get(b"# *.txt text\n", "foo.txt", "text"),
+            AttributeState::Unspecified
+        );
+        assert_eq!(
+            get(b"[attr]foo text\n", "foo", "text"),
+            AttributeState::Unspecified
+        );
+        assert_eq!(
+            get(b"!foo.txt text\n", "foo.txt", "text"),
+            AttributeState::Unspecified
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_later_line_takes_precedence() {
+        let input = b"* text\n*.png -text\n";
+        assert_eq!(get(input, "foo.txt", "text"), AttributeState::Set);
+        assert_eq!(get(input, "foo.png", "text"), AttributeState::Unset);
+        let input = b"*.png -text\n* text\n";
+        assert_eq!(get(input, "foo.png", "text"), AttributeState::Set);
+        // A later line that doesn't mention the attribute doesn't override it
+        let input = b"*.png -text\n* eol=lf\n";
+        assert_eq!(get(input, "foo.png", "text"), AttributeState::Unset);
+    }
+
+    #[test]
+    fn test_gitattributes_chained_files() {
+        let file = GitAttributesFile::empty()
+            .chain("", b"*.dat binary\n")
+            .chain("dir/", b"*.dat text\n");
+        assert_eq!(file.is_binary("foo.dat"), Some(true));
+        assert_eq!(file.is_binary("dir/foo.dat"), Some(false));
+        assert_eq!(file.is_binary("dir/foo.txt"), None);
+    }
+
+    #[test]
+    fn test_gitattributes_binary_macro() {
+        let input = b"*.png binary\n";
+        assert_eq!(get(input, "foo.png", "binary"), AttributeState::Set);
+        assert_eq!(get(input, "foo.png", "diff"), AttributeState::Unset);
+        assert_eq!(get(input, "foo.png", "merge"), AttributeState::Unset);
+        assert_eq!(get(input, "foo.png", "text"), AttributeState::Unset);
+        let file = GitAttributesFile::empty().chain("", input);
+        assert_eq!(file.is_binary("foo.png"), Some(true));
+        assert_eq!(file.is_binary("foo.txt"), None);
+    }
+
+    #[test]
+    fn test_gitattributes_merge_strategy() {
+        let file = GitAttributesFile::empty().chain(
+            "",
+            b"*.txt merge\n*.dat -merge\nCHANGELOG.md merge=union\n*.lock merge=ours\n\
+              *.gen merge=theirs\n*.json merge=json-driver\n*.png binary\n",
+        );
+        assert_eq!(file.merge_strategy("foo.rs"), MergeStrategy::Default);
+        assert_eq!(file.merge_strategy("foo.txt"), MergeStrategy::Text);
+        assert_eq!(file.merge_strategy("foo.dat"), MergeStrategy::Binary);
+        assert_eq!(file.merge_strategy("foo.png"), MergeStrategy::Binary);
+        assert_eq!(file.merge_strategy("CHANGELOG.md"), MergeStrategy::Union);
+        assert_eq!(file.merge_strategy("Cargo.lock"), MergeStrategy::Ours);
+        assert_eq!(file.merge_strategy("foo.gen"), MergeStrategy::Theirs);
+        assert_eq!(
+            file.merge_strategy("foo.json"),
+            MergeStrategy::Driver("json-driver".to_string())
+        );
+    }
+}
@@ -21,7 +21,7 @@ use itertools::Itertools;
 use regex::{escape as regex_escape, Regex};
 
 #[derive(Debug)]
-struct GitIgnoreLine {
+pub(crate) struct GitIgnoreLine {
     is_negative: bool,
     regex: Regex,
 }
@@ -56,7 +56,7 @@ impl GitIgnoreLine {
         input.split_at(trimmed_len).0
     }
 
-    fn parse(prefix: &str, input: &str) -> Option<GitIgnoreLine> {
+    pub(crate) fn parse(prefix: &str, input: &str) -> Option<GitIgnoreLine> {
         assert!(prefix.is_empty() || prefix.ends_with('/'));
         if input.starts_with('#') {
             return None;
@@ -150,7 +150,7 @@ impl GitIgnoreLine {
         Some(GitIgnoreLine { is_negative, regex })
     }
 
-    fn matches(&self, path: &str) -> bool {
+    pub(crate) fn matches(&self, path: &str) -> bool {
         self.regex.is_match(path)
     }
 }
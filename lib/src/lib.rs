@@ -22,7 +22,9 @@ extern crate pest_derive;
 #[macro_use]
 extern crate maplit;
 
+pub mod absorb;
 pub mod backend;
+pub mod bisect;
 pub mod commit;
 pub mod commit_builder;
 pub mod conflicts;
@@ -32,9 +34,11 @@ pub mod file_util;
 pub mod files;
 pub mod git;
 pub mod git_backend;
+pub mod gitattributes;
 pub mod gitignore;
 pub mod index;
 pub mod index_store;
+pub mod line_origins;
 pub mod local_backend;
 pub mod lock;
 pub mod matchers;
@@ -42,14 +46,17 @@ pub mod nightly_shims;
 pub mod op_heads_store;
 pub mod op_store;
 pub mod operation;
+pub mod patch;
 pub mod protos;
 pub mod refs;
 pub mod repo;
 pub mod repo_path;
+pub mod rerere;
 pub mod revset;
 pub mod revset_graph_iterator;
 pub mod rewrite;
 pub mod settings;
+pub mod signing;
 pub mod simple_op_store;
 pub mod stacked_table;
 pub mod store;
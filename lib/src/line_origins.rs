@@ -0,0 +1,135 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracing of the lines of a file back to the commits that introduced them.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::backend::{BackendError, CommitId, TreeValue};
+use crate::commit::Commit;
+use crate::files;
+use crate::repo::RepoRef;
+use crate::repo_path::RepoPath;
+
+/// The commit that introduced a line, and the line's number in that commit's
+/// version of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    pub commit_id: CommitId,
+    /// The 0-based line number in the file as of `commit_id`.
+    pub line_number: usize,
+}
+
+/// Returns the content of the file at `path` in `commit`, or `None` if it's
+/// not a regular file there.
+pub fn read_file_content(
+    commit: &Commit,
+    path: &RepoPath,
+) -> Result<Option<Vec<u8>>, BackendError> {
+    match commit.tree().path_value(path) {
+        Some(TreeValue::Normal { id, .. }) => {
+            let mut content = vec![];
+            commit
+                .store()
+                .read_file(path, &id)?
+                .read_to_end(&mut content)
+                .map_err(|err| BackendError::Other(err.to_string()))?;
+            Ok(Some(content))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Finds the origin of each line of the file at `path` in `starting_commit` by
+/// walking the history backwards and carrying the lines over to the parents
+/// that have them unchanged. Lines that aren't in any parent are attributed to
+/// the commit. The walk doesn't go past commits for which `in_domain` returns
+/// false; lines that reach such a commit get no origin.
+pub fn get_line_origins(
+    repo: RepoRef,
+    starting_commit: &Commit,
+    path: &RepoPath,
+    in_domain: impl Fn(&CommitId) -> bool,
+) -> Result<Vec<Option<LineOrigin>>, BackendError> {
+    let store = repo.store();
+    let content = read_file_content(starting_commit, path)?.unwrap_or_default();
+    let num_lines = content.split_inclusive(|b| *b == b'\n').count();
+    let mut origins = vec![None; num_lines];
+    // For each commit still to be visited, the lines we're looking for in it, as
+    // pairs of line number in the starting commit and line number in the commit.
+    let mut pending: HashMap<CommitId, Vec<(usize, usize)>> = HashMap::new();
+    pending.insert(
+        starting_commit.id().clone(),
+        (0..num_lines).map(|line| (line, line)).collect(),
+    );
+    // The index walk visits children before their parents, so all the lines of
+    // a commit have been collected by the time it's visited.
+    for entry in repo.index().walk_revs(&[starting_commit.id().clone()], &[]) {
+        if pending.is_empty() {
+            break;
+        }
+        let commit_id = entry.commit_id();
+        let lines = match pending.remove(&commit_id) {
+            Some(lines) => lines,
+            None => continue,
+        };
+        if !in_domain(&commit_id) {
+            continue;
+        }
+        let commit = store.get_commit(&commit_id)?;
+        let content = read_file_content(&commit, path)?.unwrap_or_default();
+        // Several lines of the starting commit can reach the same line here
+        // through different parents of a merge.
+        let mut unmatched: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (start_line, line) in lines {
+            unmatched.entry(line).or_default().push(start_line);
+        }
+        for parent in commit.parents() {
+            if unmatched.is_empty() {
+                break;
+            }
+            let parent_content = match read_file_content(&parent, path)? {
+                Some(parent_content) => parent_content,
+                None => continue,
+            };
+            for diff_line in files::diff(&parent_content, &content) {
+                if !diff_line.has_left_content
+                    || !diff_line.has_right_content
+                    || !diff_line.is_unmodified()
+                {
+                    continue;
+                }
+                let line = diff_line.right_line_number as usize - 1;
+                if let Some(start_lines) = unmatched.remove(&line) {
+                    let parent_line = diff_line.left_line_number as usize - 1;
+                    pending.entry(parent.id().clone()).or_default().extend(
+                        start_lines
+                            .into_iter()
+                            .map(|start_line| (start_line, parent_line)),
+                    );
+                }
+            }
+        }
+        for (line, start_lines) in unmatched {
+            for start_line in start_lines {
+                origins[start_line] = Some(LineOrigin {
+                    commit_id: commit_id.clone(),
+                    line_number: line,
+                });
+            }
+        }
+    }
+    Ok(origins)
+}
@@ -24,8 +24,8 @@ use tempfile::{NamedTempFile, PersistError};
 
 use crate::backend::{
     Backend, BackendError, BackendResult, ChangeId, Commit, CommitId, Conflict, ConflictId,
-    ConflictPart, FileId, MillisSinceEpoch, Signature, SymlinkId, Timestamp, Tree, TreeId,
-    TreeValue,
+    ConflictPart, FileId, MillisSinceEpoch, SecureSig, Signature, SigningFn, SymlinkId, Timestamp,
+    Tree, TreeId, TreeValue,
 };
 use crate::file_util::persist_content_addressed_temp_file;
 use crate::repo_path::{RepoPath, RepoPathComponent};
@@ -223,10 +223,22 @@ impl Backend for LocalBackend {
         Ok(commit_from_proto(&proto))
     }
 
-    fn write_commit(&self, commit: &Commit) -> BackendResult<CommitId> {
+    fn write_commit(
+        &self,
+        mut commit: Commit,
+        sign_with: Option<&SigningFn>,
+    ) -> BackendResult<(CommitId, Commit)> {
         let temp_file = NamedTempFile::new_in(&self.path)?;
 
-        let proto = commit_to_proto(commit);
+        let mut proto = commit_to_proto(&commit);
+        if let Some(sign) = sign_with {
+            // The signature covers the serialized commit without the signature
+            let data = proto.write_to_bytes()?;
+            let sig = sign(&data)
+                .map_err(|err| BackendError::Other(format!("Failed to sign commit: {err}")))?;
+            proto.secure_sig = sig.clone();
+            commit.secure_sig = Some(SecureSig { data, sig });
+        }
         let mut proto_bytes: Vec<u8> = Vec::new();
         proto.write_to_writer(&mut proto_bytes)?;
 
@@ -235,7 +247,7 @@ impl Backend for LocalBackend {
         let id = CommitId::new(Blake2b512::digest(&proto_bytes).to_vec());
 
         persist_content_addressed_temp_file(temp_file, self.commit_path(&id))?;
-        Ok(id)
+        Ok((id, commit))
     }
 }
 
@@ -253,6 +265,12 @@ pub fn commit_to_proto(commit: &Commit) -> crate::protos::store::Commit {
     proto.author = MessageField::some(signature_to_proto(&commit.author));
     proto.committer = MessageField::some(signature_to_proto(&commit.committer));
     proto.is_open = commit.is_open;
+    if let Some(secure_sig) = &commit.secure_sig {
+        proto.secure_sig = secure_sig.sig.clone();
+    }
+    if let Some(copied_from) = &commit.copied_from {
+        proto.copied_from = copied_from.to_bytes();
+    }
     proto
 }
 
@@ -266,6 +284,14 @@ fn commit_from_proto(proto: &crate::protos::store::Commit) -> Commit {
         .collect();
     let root_tree = TreeId::new(proto.root_tree.to_vec());
     let change_id = ChangeId::new(proto.change_id.to_vec());
+    let secure_sig = (!proto.secure_sig.is_empty()).then(|| {
+        let mut unsigned_proto = proto.clone();
+        unsigned_proto.secure_sig.clear();
+        SecureSig {
+            data: unsigned_proto.write_to_bytes().unwrap(),
+            sig: proto.secure_sig.clone(),
+        }
+    });
     Commit {
         parents,
         predecessors,
@@ -275,6 +301,9 @@ fn commit_from_proto(proto: &crate::protos::store::Commit) -> Commit {
         author: signature_from_proto(&proto.author),
         committer: signature_from_proto(&proto.committer),
         is_open: proto.is_open,
+        secure_sig,
+        copied_from: (!proto.copied_from.is_empty())
+            .then(|| CommitId::new(proto.copied_from.clone())),
     }
 }
 
@@ -391,11 +420,13 @@ fn conflict_from_proto(proto: &crate::protos::store::Conflict) -> Conflict {
 fn conflict_part_from_proto(proto: &crate::protos::store::conflict::Part) -> ConflictPart {
     ConflictPart {
         value: tree_value_from_proto(proto.content.as_ref().unwrap()),
+        label: (!proto.label.is_empty()).then(|| proto.label.clone()),
     }
 }
 
 fn conflict_part_to_proto(part: &ConflictPart) -> crate::protos::store::conflict::Part {
     let mut proto = crate::protos::store::conflict::Part::new();
     proto.content = MessageField::some(tree_value_to_proto(&part.value));
+    proto.label = part.label.clone().unwrap_or_default();
     proto
 }
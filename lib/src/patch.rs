@@ -0,0 +1,831 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing and applying patches in the format produced by `git format-patch`
+//! (and `jj export-patch`).
+
+use std::io::Read;
+
+use chrono::DateTime;
+use thiserror::Error;
+
+use crate::backend::{
+    BackendError, FileId, MillisSinceEpoch, Signature, Timestamp, TreeId, TreeValue,
+};
+use crate::repo_path::RepoPath;
+use crate::tree::Tree;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PatchParseError {
+    #[error("Invalid hunk header: {0}")]
+    InvalidHunkHeader(String),
+    #[error("Unexpected end of hunk in diff of {0}")]
+    TruncatedHunk(String),
+}
+
+#[derive(Debug, Error)]
+pub enum PatchApplyError {
+    #[error("Hunk #{hunk} does not apply to {path}")]
+    HunkFailed { path: String, hunk: usize },
+    #[error("{0} does not exist")]
+    MissingFile(String),
+    #[error("{0} already exists")]
+    FileExists(String),
+    #[error("{0} is not a regular file")]
+    NotAFile(String),
+    #[error("Cannot apply binary patch to {0}")]
+    BinaryPatch(String),
+    #[error("Failed to read file with ID {}", .file_id.hex())]
+    ReadError {
+        source: std::io::Error,
+        file_id: FileId,
+    },
+    #[error("Unexpected error from backend: {0}")]
+    BackendError(#[from] BackendError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(Vec<u8>),
+    Removed(Vec<u8>),
+    Added(Vec<u8>),
+}
+
+impl HunkLine {
+    fn content_mut(&mut self) -> &mut Vec<u8> {
+        match self {
+            HunkLine::Context(content) | HunkLine::Removed(content) | HunkLine::Added(content) => {
+                content
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The 1-based line number where the hunk starts in the old file, as given
+    /// in the hunk header.
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&[u8]> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(content) | HunkLine::Removed(content) => Some(content.as_slice()),
+                HunkLine::Added(_) => None,
+            })
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&[u8]> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(content) | HunkLine::Added(content) => Some(content.as_slice()),
+                HunkLine::Removed(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns a copy of the hunk without up to `fuzz` context lines at each
+    /// end.
+    fn trimmed(&self, fuzz: usize) -> Hunk {
+        let leading = self
+            .lines
+            .iter()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count()
+            .min(fuzz);
+        let trailing = self.lines[leading..]
+            .iter()
+            .rev()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count()
+            .min(fuzz);
+        Hunk {
+            old_start: self.old_start + leading,
+            lines: self.lines[leading..self.lines.len() - trailing].to_vec(),
+        }
+    }
+}
+
+/// The changes to a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    /// The path before the change, or `None` if the file was added.
+    pub old_path: Option<RepoPath>,
+    /// The path after the change, or `None` if the file was removed.
+    pub new_path: Option<RepoPath>,
+    /// Whether the file is executable after the change, if the patch says so.
+    pub executable: Option<bool>,
+    /// Whether the patch says that the (binary) file changed without including
+    /// the changes.
+    pub binary: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    fn display_path(&self) -> String {
+        self.new_path
+            .as_ref()
+            .or(self.old_path.as_ref())
+            .unwrap()
+            .to_internal_file_string()
+    }
+}
+
+/// A single patch, typically one message in an mbox file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    /// The id of the commit the patch was created from, as given on the
+    /// leading "From <id> <date>" line.
+    pub source_commit_hex: Option<String>,
+    pub author: Option<Signature>,
+    pub description: String,
+    pub files: Vec<FilePatch>,
+}
+
+fn parse_signature(from: &str, date: Option<&str>) -> Option<Signature> {
+    let (name, email) = match from.rsplit_once('<') {
+        Some((name, email)) => (name.trim(), email.trim_end().strip_suffix('>')?),
+        None => ("", from.trim()),
+    };
+    let name = name.trim_matches('"');
+    let date = DateTime::parse_from_rfc2822(date?.trim()).ok()?;
+    Some(Signature {
+        name: name.to_string(),
+        email: email.to_string(),
+        timestamp: Timestamp {
+            timestamp: MillisSinceEpoch(date.timestamp_millis().try_into().ok()?),
+            tz_offset: date.offset().local_minus_utc() / 60,
+        },
+    })
+}
+
+fn strip_subject_prefix(subject: &str) -> &str {
+    match subject.strip_prefix('[') {
+        Some(rest) if subject.starts_with("[PATCH") => match rest.split_once(']') {
+            Some((_, subject)) => subject.trim_start(),
+            None => subject,
+        },
+        _ => subject,
+    }
+}
+
+fn parse_diff_git_paths(line: &str) -> Option<(String, String)> {
+    let paths = line.strip_prefix("diff --git a/")?;
+    let (old, new) = paths.split_once(" b/")?;
+    Some((old.to_string(), new.to_string()))
+}
+
+fn parse_path(path: &str, prefix: &str) -> Option<RepoPath> {
+    let path = path.split('\t').next().unwrap();
+    if path == "/dev/null" {
+        None
+    } else {
+        let path = path.strip_prefix(prefix).unwrap_or(path);
+        Some(RepoPath::from_internal_string(path))
+    }
+}
+
+fn parse_mode_executable(mode: &str) -> Option<bool> {
+    match mode.trim() {
+        "100755" => Some(true),
+        "100644" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), PatchParseError> {
+    let invalid = || PatchParseError::InvalidHunkHeader(line.to_string());
+    let ranges = line
+        .strip_prefix("@@ -")
+        .and_then(|rest| rest.split_once(" @@"))
+        .ok_or_else(invalid)?
+        .0;
+    let (old_range, new_range) = ranges.split_once(" +").ok_or_else(invalid)?;
+    let parse_range = |range: &str| -> Result<(usize, usize), PatchParseError> {
+        let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+        Ok((
+            start.parse().map_err(|_| invalid())?,
+            len.parse().map_err(|_| invalid())?,
+        ))
+    };
+    let (old_start, old_len) = parse_range(old_range)?;
+    let (_, new_len) = parse_range(new_range)?;
+    Ok((old_start, old_len, new_len))
+}
+
+/// Parses the diff part of a patch, i.e. the "diff --git" sections.
+fn parse_file_patches(lines: &[&[u8]]) -> Result<Vec<FilePatch>, PatchParseError> {
+    let mut files: Vec<FilePatch> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = String::from_utf8_lossy(lines[i]);
+        let line = line.trim_end_matches('\n');
+        i += 1;
+        if let Some((old, new)) = parse_diff_git_paths(line) {
+            files.push(FilePatch {
+                old_path: Some(RepoPath::from_internal_string(&old)),
+                new_path: Some(RepoPath::from_internal_string(&new)),
+                executable: None,
+                binary: false,
+                hunks: vec![],
+            });
+            continue;
+        }
+        let file = match files.last_mut() {
+            Some(file) => file,
+            None => continue,
+        };
+        if let Some(mode) = line.strip_prefix("new file mode ") {
+            file.old_path = None;
+            file.executable = parse_mode_executable(mode);
+        } else if line.starts_with("deleted file mode ") {
+            file.new_path = None;
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            file.executable = parse_mode_executable(mode);
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            file.old_path = Some(RepoPath::from_internal_string(path));
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            file.new_path = Some(RepoPath::from_internal_string(path));
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            file.old_path = parse_path(path, "a/");
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            file.new_path = parse_path(path, "b/");
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        } else if line.starts_with("@@ ") {
+            let (old_start, mut old_remaining, mut new_remaining) = parse_hunk_header(line)?;
+            let mut hunk = Hunk {
+                old_start,
+                lines: vec![],
+            };
+            while old_remaining > 0 || new_remaining > 0 {
+                let hunk_line = lines
+                    .get(i)
+                    .ok_or_else(|| PatchParseError::TruncatedHunk(file.display_path()))?;
+                i += 1;
+                let (kind, content) = match hunk_line.split_first() {
+                    Some((kind, content)) => (*kind, content.to_vec()),
+                    // Some tools strip the trailing space of empty context lines
+                    None => (b' ', vec![]),
+                };
+                match kind {
+                    b' ' | b'\n' => {
+                        let content = if kind == b'\n' { vec![b'\n'] } else { content };
+                        hunk.lines.push(HunkLine::Context(content));
+                        old_remaining = old_remaining.saturating_sub(1);
+                        new_remaining = new_remaining.saturating_sub(1);
+                    }
+                    b'-' => {
+                        hunk.lines.push(HunkLine::Removed(content));
+                        old_remaining = old_remaining.saturating_sub(1);
+                    }
+                    b'+' => {
+                        hunk.lines.push(HunkLine::Added(content));
+                        new_remaining = new_remaining.saturating_sub(1);
+                    }
+                    b'\\' => {}
+                    _ => return Err(PatchParseError::TruncatedHunk(file.display_path())),
+                }
+            }
+            // A "\ No newline at end of file" marker applies to the line before it
+            while let Some(marker) = lines.get(i) {
+                if !marker.starts_with(b"\\") {
+                    break;
+                }
+                i += 1;
+                if let Some(last) = hunk.lines.last_mut() {
+                    let content = last.content_mut();
+                    if content.ends_with(b"\n") {
+                        content.pop();
+                    }
+                }
+            }
+            file.hunks.push(hunk);
+        } else if line == "-- " {
+            // Start of the signature
+            break;
+        }
+    }
+    Ok(files)
+}
+
+fn parse_patch(message: &[&[u8]]) -> Result<Patch, PatchParseError> {
+    let mut source_commit_hex = None;
+    let mut from = None;
+    let mut date = None;
+    let mut subject: Option<String> = None;
+    let mut i = 0;
+    if let Some(first) = message.first() {
+        if first.starts_with(b"From ") {
+            source_commit_hex = mbox_separator_commit_hex(first);
+            i += 1;
+        }
+    }
+    // Parse the mail headers
+    let mut last_header: Option<String> = None;
+    while i < message.len() {
+        let line = String::from_utf8_lossy(message[i]);
+        let line = line.trim_end_matches('\n').trim_end_matches('\r');
+        if line.is_empty() || line.starts_with("diff --git ") {
+            break;
+        }
+        i += 1;
+        if line.starts_with(' ') || line.starts_with('\t') {
+            // Continuation of a folded header
+            if last_header.as_deref() == Some("subject") {
+                if let Some(subject) = &mut subject {
+                    subject.push(' ');
+                    subject.push_str(line.trim());
+                }
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.to_ascii_lowercase();
+            let value = value.trim().to_string();
+            match name.as_str() {
+                "from" => from = Some(value),
+                "date" => date = Some(value),
+                "subject" => subject = Some(value),
+                _ => {}
+            }
+            last_header = Some(name);
+        }
+    }
+    // The commit message body lasts until the "---" separator or the start of the
+    // diff
+    let mut body = vec![];
+    while i < message.len() {
+        let line = message[i];
+        if line == b"---\n" || line == b"---" || line.starts_with(b"diff --git ") {
+            break;
+        }
+        body.extend_from_slice(line);
+        i += 1;
+    }
+    let body = String::from_utf8_lossy(&body);
+    let body = body.trim();
+    let mut description = String::new();
+    if let Some(subject) = &subject {
+        description.push_str(strip_subject_prefix(subject));
+        description.push('\n');
+    }
+    if !body.is_empty() {
+        if !description.is_empty() {
+            description.push('\n');
+        }
+        description.push_str(body);
+        description.push('\n');
+    }
+    let author = from.and_then(|from| parse_signature(&from, date.as_deref()));
+    let files = parse_file_patches(&message[i..])?;
+    Ok(Patch {
+        source_commit_hex,
+        author,
+        description,
+        files,
+    })
+}
+
+/// Returns the commit id from a "From <commit id> <date>" line, as written by
+/// `git format-patch` and `jj export-patch`.
+fn mbox_separator_commit_hex(line: &[u8]) -> Option<String> {
+    let rest = line.strip_prefix(b"From ")?;
+    let id = rest.split(|b| *b == b' ').next().unwrap();
+    // Git commit ids have 40 (SHA-1) or 64 (SHA-256) hex digits, and commit ids
+    // in the local backend have 128
+    if matches!(id.len(), 40 | 64 | 128) && id.iter().all(u8::is_ascii_hexdigit) {
+        Some(String::from_utf8(id.to_vec()).unwrap())
+    } else {
+        None
+    }
+}
+
+fn is_mbox_separator(line: &[u8]) -> bool {
+    mbox_separator_commit_hex(line).is_some()
+}
+
+/// Parses the patches in an mbox file (or a single patch, possibly without any
+/// mail headers).
+pub fn parse_mbox(input: &[u8]) -> Result<Vec<Patch>, PatchParseError> {
+    let lines: Vec<&[u8]> = input.split_inclusive(|b| *b == b'\n').collect();
+    let mut message_starts: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| is_mbox_separator(line))
+        .map(|(i, _)| i)
+        .collect();
+    if message_starts.first() != Some(&0) {
+        message_starts.insert(0, 0);
+    }
+    let mut patches = vec![];
+    for (i, start) in message_starts.iter().enumerate() {
+        let end = message_starts.get(i + 1).copied().unwrap_or(lines.len());
+        let message = &lines[*start..end];
+        if message
+            .iter()
+            .all(|line| line.iter().all(u8::is_ascii_whitespace))
+        {
+            continue;
+        }
+        patches.push(parse_patch(message)?);
+    }
+    Ok(patches)
+}
+
+fn find_hunk_position(
+    lines: &[&[u8]],
+    old_lines: &[&[u8]],
+    expected: usize,
+    min: usize,
+) -> Option<usize> {
+    let matches_at = |pos: usize| {
+        pos >= min && pos + old_lines.len() <= lines.len() && lines[pos..].starts_with(old_lines)
+    };
+    // Search outwards from the expected position
+    let max_distance = expected.max(lines.len()) + 1;
+    for distance in 0..max_distance {
+        if matches_at(expected + distance) {
+            return Some(expected + distance);
+        }
+        if distance > 0 && distance <= expected && matches_at(expected - distance) {
+            return Some(expected - distance);
+        }
+    }
+    None
+}
+
+/// Applies the hunks to the content. Each hunk is first looked for near the
+/// position given in its header, then anywhere after the previous hunk. If it
+/// can't be found, up to `max_fuzz` context lines at each end of the hunk are
+/// ignored. On failure, returns the 0-based index of the hunk that didn't apply.
+pub fn apply_hunks(content: &[u8], hunks: &[Hunk], max_fuzz: usize) -> Result<Vec<u8>, usize> {
+    let lines: Vec<&[u8]> = content.split_inclusive(|b| *b == b'\n').collect();
+    let mut result = vec![];
+    // Index into `lines` up to which the content has been copied to `result`
+    let mut copied = 0;
+    // Difference between actual and expected positions of previous hunks
+    let mut offset: isize = 0;
+    for (hunk_index, hunk) in hunks.iter().enumerate() {
+        let mut applied = false;
+        for fuzz in 0..=max_fuzz {
+            let hunk = hunk.trimmed(fuzz);
+            let old_lines = hunk.old_lines();
+            // A hunk that doesn't remove any lines is inserted after the given line,
+            // other hunks start at the given line.
+            let header_position = if old_lines.is_empty() {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            };
+            let expected = (header_position as isize + offset).max(0) as usize;
+            let expected = expected.clamp(copied, lines.len());
+            if let Some(position) = find_hunk_position(&lines, &old_lines, expected, copied) {
+                for line in &lines[copied..position] {
+                    result.extend_from_slice(line);
+                }
+                for line in hunk.new_lines() {
+                    result.extend_from_slice(line);
+                }
+                copied = position + old_lines.len();
+                offset = position as isize - header_position as isize;
+                applied = true;
+                break;
+            }
+            if hunk
+                .lines
+                .iter()
+                .all(|line| !matches!(line, HunkLine::Context(_)))
+            {
+                // Nothing more to trim
+                break;
+            }
+        }
+        if !applied {
+            return Err(hunk_index);
+        }
+    }
+    for line in &lines[copied..] {
+        result.extend_from_slice(line);
+    }
+    Ok(result)
+}
+
+/// Applies the file patches to the tree and returns the id of the resulting
+/// tree.
+pub fn apply_patch(tree: &Tree, patch: &Patch, max_fuzz: usize) -> Result<TreeId, PatchApplyError> {
+    let store = tree.store();
+    let mut tree_builder = store.tree_builder(tree.id().clone());
+    for file in &patch.files {
+        let mut old_content = vec![];
+        let mut old_executable = false;
+        if let Some(old_path) = &file.old_path {
+            match tree.path_value(old_path) {
+                Some(TreeValue::Normal { id, executable }) => {
+                    store
+                        .read_file(old_path, &id)?
+                        .read_to_end(&mut old_content)
+                        .map_err(|err| PatchApplyError::ReadError {
+                            source: err,
+                            file_id: id.clone(),
+                        })?;
+                    old_executable = executable;
+                }
+                Some(_) => {
+                    return Err(PatchApplyError::NotAFile(
+                        old_path.to_internal_file_string(),
+                    ))
+                }
+                None => {
+                    return Err(PatchApplyError::MissingFile(
+                        old_path.to_internal_file_string(),
+                    ))
+                }
+            }
+        } else if let Some(new_path) = &file.new_path {
+            if tree.path_value(new_path).is_some() {
+                return Err(PatchApplyError::FileExists(
+                    new_path.to_internal_file_string(),
+                ));
+            }
+        }
+        if let Some(old_path) = &file.old_path {
+            if file.new_path.as_ref() != Some(old_path) {
+                tree_builder.remove(old_path.clone());
+            }
+        }
+        let new_path = match &file.new_path {
+            Some(new_path) => new_path,
+            None => continue,
+        };
+        if file.binary {
+            return Err(PatchApplyError::BinaryPatch(
+                new_path.to_internal_file_string(),
+            ));
+        }
+        let new_content = apply_hunks(&old_content, &file.hunks, max_fuzz).map_err(|hunk| {
+            PatchApplyError::HunkFailed {
+                path: new_path.to_internal_file_string(),
+                hunk: hunk + 1,
+            }
+        })?;
+        let id = store.write_file(new_path, &mut new_content.as_slice())?;
+        let executable = file.executable.unwrap_or(old_executable);
+        tree_builder.set(new_path.clone(), TreeValue::Normal { id, executable });
+    }
+    Ok(tree_builder.write_tree())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old_start: usize, lines: &[&str]) -> Hunk {
+        Hunk {
+            old_start,
+            lines: lines
+                .iter()
+                .map(|line| {
+                    let (kind, content) = line.split_at(1);
+                    let content = content.as_bytes().to_vec();
+                    match kind {
+                        " " => HunkLine::Context(content),
+                        "-" => HunkLine::Removed(content),
+                        "+" => HunkLine::Added(content),
+                        _ => panic!("unexpected line {line}"),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_mbox() {
+        let input = b"\
+From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001
+From: Some One <some.one@example.com>
+Date: Sat, 3 Feb 2001 04:05:07 +0700
+Subject: [PATCH 1/2] Add a file with a
+ long subject
+
+More details.
+---
+diff --git a/file b/file
+new file mode 100755
+index 0000000000..257cc5642c
+--- /dev/null
++++ b/file
+@@ -0,0 +1,2 @@
++foo
++bar
+\\ No newline at end of file
+--
+2.37.0
+
+From fedcba9876543210fedcba9876543210fedcba98 Mon Sep 17 00:00:00 2001
+From: Some One <some.one@example.com>
+Date: Sat, 3 Feb 2001 04:05:07 +0700
+Subject: [PATCH 2/2] Delete and modify
+
+---
+diff --git a/file b/file
+index 257cc5642c..3bd1f0e297 100644
+--- a/file
++++ b/file
+@@ -1,2 +1,2 @@
+ foo
+-bar
++baz
+diff --git a/other b/other
+deleted file mode 100644
+index 257cc5642c..0000000000
+--- a/other
++++ /dev/null
+@@ -1,1 +0,0 @@
+-other
+";
+        let patches = parse_mbox(input).unwrap();
+        assert_eq!(patches.len(), 2);
+
+        assert_eq!(
+            patches[0].source_commit_hex.as_deref(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+        let author = patches[0].author.as_ref().unwrap();
+        assert_eq!(author.name, "Some One");
+        assert_eq!(author.email, "some.one@example.com");
+        assert_eq!(author.timestamp.timestamp, MillisSinceEpoch(981147907000));
+        assert_eq!(author.timestamp.tz_offset, 420);
+        assert_eq!(
+            patches[0].description,
+            "Add a file with a long subject\n\nMore details.\n"
+        );
+        assert_eq!(
+            patches[0].files,
+            vec![FilePatch {
+                old_path: None,
+                new_path: Some(RepoPath::from_internal_string("file")),
+                executable: Some(true),
+                binary: false,
+                hunks: vec![hunk(0, &["+foo\n", "+bar"])],
+            }]
+        );
+
+        assert_eq!(patches[1].description, "Delete and modify\n");
+        assert_eq!(
+            patches[1].files,
+            vec![
+                FilePatch {
+                    old_path: Some(RepoPath::from_internal_string("file")),
+                    new_path: Some(RepoPath::from_internal_string("file")),
+                    executable: None,
+                    binary: false,
+                    hunks: vec![hunk(1, &[" foo\n", "-bar\n", "+baz\n"])],
+                },
+                FilePatch {
+                    old_path: Some(RepoPath::from_internal_string("other")),
+                    new_path: None,
+                    executable: None,
+                    binary: false,
+                    hunks: vec![hunk(1, &["-other\n"])],
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_mbox_separator_without_commit_id() {
+        let input = b"\
+From John Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Change
+
+diff --git a/file b/file
+--- a/file
++++ b/file
+@@ -1 +1 @@
+-foo
++bar
+";
+        let patches = parse_mbox(input).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].source_commit_hex, None);
+        assert_eq!(patches[0].description, "Change\n");
+
+        // Short or non-hex ids are not commit ids either
+        assert!(!is_mbox_separator(
+            b"From 0123abcd Mon Sep 17 00:00:00 2001\n"
+        ));
+        assert!(!is_mbox_separator(
+            b"From 0123456789abcdef0123456789abcdef0123456z Mon Sep 17 00:00:00 2001\n"
+        ));
+    }
+
+    #[test]
+    fn test_parse_plain_diff() {
+        let input = b"\
+diff --git a/file b/file
+index 257cc5642c..3bd1f0e297 100644
+--- a/file
++++ b/file
+@@ -1 +1 @@
+-foo
++bar
+";
+        let patches = parse_mbox(input).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].source_commit_hex, None);
+        assert_eq!(patches[0].author, None);
+        assert_eq!(patches[0].description, "");
+        assert_eq!(
+            patches[0].files[0].hunks,
+            vec![hunk(1, &["-foo\n", "+bar\n"])]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_hunk() {
+        let input = b"\
+diff --git a/file b/file
+--- a/file
++++ b/file
+@@ -1,2 +1,2 @@
+-foo
+";
+        assert_eq!(
+            parse_mbox(input),
+            Err(PatchParseError::TruncatedHunk("file".to_string()))
+        );
+        let input = b"\
+diff --git a/file b/file
+@@ -x +1 @@
+";
+        assert_eq!(
+            parse_mbox(input),
+            Err(PatchParseError::InvalidHunkHeader(
+                "@@ -x +1 @@".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_exact() {
+        let content = b"a\nb\nc\nd\ne\n";
+        let hunks = vec![hunk(2, &[" b\n", "-c\n", "+C\n", " d\n"])];
+        assert_eq!(
+            apply_hunks(content, &hunks, 0),
+            Ok(b"a\nb\nC\nd\ne\n".to_vec())
+        );
+        // Insertion into an empty file
+        let hunks = vec![hunk(0, &["+a\n"])];
+        assert_eq!(apply_hunks(b"", &hunks, 0), Ok(b"a\n".to_vec()));
+        // Same but with jj's hunk header style
+        let hunks = vec![hunk(1, &["+a\n"])];
+        assert_eq!(apply_hunks(b"", &hunks, 0), Ok(b"a\n".to_vec()));
+    }
+
+    #[test]
+    fn test_apply_hunks_offset() {
+        // The lines have moved down by two lines since the patch was created
+        let content = b"x\ny\na\nb\nc\nd\ne\nb\nc\n";
+        let hunks = vec![
+            hunk(1, &[" a\n", " b\n", "-c\n", "+C\n"]),
+            hunk(6, &[" b\n", "-c\n", "+C\n"]),
+        ];
+        assert_eq!(
+            apply_hunks(content, &hunks, 0),
+            Ok(b"x\ny\na\nb\nC\nd\ne\nb\nC\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_apply_hunks_fuzz() {
+        // The first context line has changed
+        let content = b"A\nb\nc\nd\n";
+        let hunks = vec![hunk(1, &[" a\n", " b\n", "-c\n", "+C\n", " d\n"])];
+        assert_eq!(apply_hunks(content, &hunks, 0), Err(0));
+        assert_eq!(
+            apply_hunks(content, &hunks, 1),
+            Ok(b"A\nb\nC\nd\n".to_vec())
+        );
+        // Fuzz doesn't allow removed lines to differ
+        let content = b"a\nb\nX\nd\n";
+        assert_eq!(apply_hunks(content, &hunks, 2), Err(0));
+    }
+}
@@ -23,7 +23,7 @@ use std::sync::{Arc, Mutex};
 use itertools::Itertools;
 use thiserror::Error;
 
-use crate::backend::{Backend, BackendError, ChangeId, CommitId};
+use crate::backend::{Backend, BackendError, BackendResult, ChangeId, CommitId};
 use crate::commit::Commit;
 use crate::commit_builder::CommitBuilder;
 use crate::dag_walk::topo_order_reverse;
@@ -34,10 +34,12 @@ use crate::local_backend::LocalBackend;
 use crate::op_heads_store::{LockedOpHeads, OpHeads, OpHeadsStore};
 use crate::op_store::{BranchTarget, OpStore, OperationId, RefTarget, WorkspaceId};
 use crate::operation::Operation;
-use crate::rewrite::DescendantRebaser;
+use crate::repo_path::RepoPath;
+use crate::rerere::ResolutionCache;
+use crate::rewrite::{DescendantRebaser, EmptyBehavior};
 use crate::settings::{RepoSettings, UserSettings};
 use crate::simple_op_store::SimpleOpStore;
-use crate::store::Store;
+use crate::store::{MergeSettings, Store};
 use crate::transaction::Transaction;
 use crate::view::{RefName, View};
 use crate::{backend, op_store};
@@ -166,8 +168,12 @@ impl ReadonlyRepo {
     ) -> Arc<ReadonlyRepo> {
         let repo_path = repo_path.canonicalize().unwrap();
         ReadonlyRepo::init_repo_dir(&repo_path);
-        let store = Store::new(backend_factory(repo_path.join("store")));
         let repo_settings = user_settings.with_repo(&repo_path).unwrap();
+        let store = Store::new_with_settings(
+            backend_factory(repo_path.join("store")),
+            merge_settings(user_settings, &repo_path),
+            repo_settings.signer(),
+        );
         let op_store: Arc<dyn OpStore> = Arc::new(SimpleOpStore::init(repo_path.join("op_store")));
         let mut root_view = op_store::View::default();
         root_view.head_ids.insert(store.root_commit_id().clone());
@@ -328,6 +334,17 @@ impl UnresolvedHeadRepo {
     }
 }
 
+fn merge_settings(user_settings: &UserSettings, repo_path: &Path) -> MergeSettings {
+    // Only use a cache of recorded conflict resolutions if the user enabled it
+    let resolution_cache = user_settings
+        .rerere_enabled()
+        .then(|| ResolutionCache::new(repo_path.join("rerere")));
+    MergeSettings {
+        resolution_cache,
+        merge_drivers: user_settings.merge_drivers(),
+    }
+}
+
 #[derive(Clone)]
 pub struct RepoLoader {
     repo_path: PathBuf,
@@ -347,8 +364,12 @@ impl RepoLoader {
         } else {
             Box::new(LocalBackend::load(store_path))
         };
-        let store = Store::new(backend);
         let repo_settings = user_settings.with_repo(&repo_path).unwrap();
+        let store = Store::new_with_settings(
+            backend,
+            merge_settings(user_settings, &repo_path),
+            repo_settings.signer(),
+        );
         let op_store: Arc<dyn OpStore> = Arc::new(SimpleOpStore::load(repo_path.join("op_store")));
         let op_heads_store = Arc::new(OpHeadsStore::load(repo_path.join("op_heads")));
         let index_store = Arc::new(IndexStore::load(repo_path.join("index")));
@@ -448,6 +469,9 @@ pub struct MutableRepo {
     view_dirty: bool,
     rewritten_commits: HashMap<CommitId, HashSet<CommitId>>,
     abandoned_commits: HashSet<CommitId>,
+    abandoned_commit_parents: HashMap<CommitId, Vec<CommitId>>,
+    empty_behavior: EmptyBehavior,
+    reused_resolutions: Vec<RepoPath>,
 }
 
 impl MutableRepo {
@@ -465,6 +489,9 @@ impl MutableRepo {
             view_dirty: false,
             rewritten_commits: Default::default(),
             abandoned_commits: Default::default(),
+            abandoned_commit_parents: Default::default(),
+            empty_behavior: EmptyBehavior::Keep,
+            reused_resolutions: vec![],
         }
     }
 
@@ -509,10 +536,10 @@ impl MutableRepo {
         (self.index, self.view.into_inner())
     }
 
-    pub fn write_commit(&mut self, commit: backend::Commit) -> Commit {
-        let commit = self.store().write_commit(commit);
+    pub fn write_commit(&mut self, commit: backend::Commit) -> BackendResult<Commit> {
+        let commit = self.store().write_commit(commit)?;
         self.add_head(&commit);
-        commit
+        Ok(commit)
     }
 
     /// Record a commit as having been rewritten in this transaction. This
@@ -542,8 +569,39 @@ impl MutableRepo {
         self.abandoned_commits.insert(old_id);
     }
 
+    /// Record a commit as having been abandoned in this transaction, with
+    /// descendants to be rebased onto `new_parent_ids` instead of onto the
+    /// commit's parents. This is used for commits that were rebased and became
+    /// empty.
+    pub fn record_abandoned_commit_with_parents(
+        &mut self,
+        old_id: CommitId,
+        new_parent_ids: Vec<CommitId>,
+    ) {
+        self.abandoned_commits.insert(old_id.clone());
+        self.abandoned_commit_parents.insert(old_id, new_parent_ids);
+    }
+
     pub fn clear_abandoned_commits(&mut self) {
         self.abandoned_commits.clear();
+        self.abandoned_commit_parents.clear();
+    }
+
+    /// Sets what `rebase_descendants()` does with descendants that become
+    /// empty.
+    pub fn set_empty_behavior(&mut self, empty: EmptyBehavior) {
+        self.empty_behavior = empty;
+    }
+
+    /// Record that merges in this transaction resolved the conflicts in the
+    /// files at `paths` with recorded resolutions, so they can be reported.
+    pub fn record_reused_resolutions(&mut self, paths: Vec<RepoPath>) {
+        self.reused_resolutions.extend(paths);
+    }
+
+    /// The paths recorded by `record_reused_resolutions()`.
+    pub fn reused_resolutions(&self) -> &[RepoPath] {
+        &self.reused_resolutions
     }
 
     pub fn has_rewrites(&self) -> bool {
@@ -556,12 +614,19 @@ impl MutableRepo {
         &'repo mut self,
         settings: &'settings UserSettings,
     ) -> DescendantRebaser<'settings, 'repo> {
-        DescendantRebaser::new(
+        let empty = self.empty_behavior;
+        let abandoned_commit_parents = self.abandoned_commit_parents.clone();
+        let mut rebaser = DescendantRebaser::new(
             settings,
             self,
             self.rewritten_commits.clone(),
             self.abandoned_commits.clone(),
-        )
+        );
+        rebaser.set_empty_behavior(empty);
+        for (old_id, new_parent_ids) in abandoned_commit_parents {
+            rebaser.set_abandoned_parents(old_id, new_parent_ids);
+        }
+        rebaser
     }
 
     pub fn rebase_descendants(&mut self, settings: &UserSettings) -> Result<usize, BackendError> {
@@ -587,13 +652,13 @@ impl MutableRepo {
         workspace_id: WorkspaceId,
         settings: &UserSettings,
         commit: &Commit,
-    ) -> Commit {
+    ) -> BackendResult<Commit> {
         self.leave_commit(&workspace_id);
         let open_commit =
             CommitBuilder::for_open_commit(settings, commit.id().clone(), commit.tree_id().clone())
-                .write_to_repo(self);
+                .write_to_repo(self)?;
         self.set_checkout(workspace_id, open_commit.id().clone());
-        open_commit
+        Ok(open_commit)
     }
 
     pub fn edit(&mut self, workspace_id: WorkspaceId, commit: &Commit) {
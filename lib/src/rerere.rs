@@ -0,0 +1,274 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reuse of recorded conflict resolutions ("rerere").
+//!
+//! When a conflicted file is resolved, the resolution of each conflicting
+//! hunk is recorded, keyed by a hash of the hunk's contents. When a merge later
+//! produces the same conflicting hunks, the recorded resolutions are applied
+//! instead of leaving a conflict.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use blake2::{Blake2b512, Digest};
+use itertools::Itertools;
+use tempfile::NamedTempFile;
+
+use crate::backend::{BackendError, ConflictPart, TreeValue};
+use crate::commit::Commit;
+use crate::files;
+use crate::files::{MergeHunk, MergeResult};
+use crate::matchers::EverythingMatcher;
+use crate::repo_path::RepoPath;
+use crate::store::Store;
+use crate::tree::{Diff, Tree};
+
+/// Stores the recorded resolutions of conflicting hunks on disk.
+#[derive(Debug)]
+pub struct ResolutionCache {
+    dir: PathBuf,
+}
+
+impl ResolutionCache {
+    /// Creates a cache that stores its resolutions in `dir`. The directory is
+    /// created when the first resolution is recorded.
+    pub fn new(dir: PathBuf) -> Self {
+        ResolutionCache { dir }
+    }
+
+    fn hunk_key(removes: &[Vec<u8>], adds: &[Vec<u8>]) -> String {
+        // Like Git, sort the parts so the same conflict gets the same key
+        // regardless of which side of the merge each part came from.
+        let mut hasher = Blake2b512::new();
+        for (prefix, contents) in [(b"-", removes), (b"+", adds)] {
+            for content in contents.iter().sorted() {
+                hasher.update(prefix);
+                hasher.update((content.len() as u64).to_le_bytes());
+                hasher.update(content);
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Records `resolution` as the resolution of the conflicting hunk with the
+    /// given removes and adds.
+    pub fn record_hunk(
+        &self,
+        removes: &[Vec<u8>],
+        adds: &[Vec<u8>],
+        resolution: &[u8],
+    ) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut temp_file = NamedTempFile::new_in(&self.dir)?;
+        temp_file.write_all(resolution)?;
+        // A newer resolution of the same hunk replaces the old one.
+        let path = self.dir.join(Self::hunk_key(removes, adds));
+        temp_file.persist(&path).map_err(|err| err.error)?;
+        Ok(())
+    }
+
+    /// Returns the recorded resolution of the conflicting hunk with the given
+    /// removes and adds, if any.
+    pub fn resolution_for_hunk(&self, removes: &[Vec<u8>], adds: &[Vec<u8>]) -> Option<Vec<u8>> {
+        let mut file = fs::File::open(self.dir.join(Self::hunk_key(removes, adds))).ok()?;
+        let mut resolution = vec![];
+        file.read_to_end(&mut resolution).ok()?;
+        Some(resolution)
+    }
+
+    /// Resolves the conflicting hunks of a merge with recorded resolutions.
+    /// Returns the resolved content if all the conflicting hunks had a recorded
+    /// resolution.
+    pub fn resolve_hunks(&self, hunks: &[MergeHunk]) -> Option<Vec<u8>> {
+        files::resolve_conflict_hunks(hunks, |removes, adds| {
+            self.resolution_for_hunk(removes, adds)
+        })
+    }
+
+    /// Records the resolution of each conflicting hunk in `hunks` given the
+    /// resolved content of the file. The resolved hunks are used to find the
+    /// parts of the content that correspond to the conflicting hunks. Returns
+    /// the number of recorded hunks, which is 0 if the resolved hunks were
+    /// changed so the content can't be matched up with the hunks.
+    pub fn record_resolution(
+        &self,
+        hunks: &[MergeHunk],
+        resolved_content: &[u8],
+    ) -> std::io::Result<usize> {
+        let resolutions = match split_resolution(hunks, resolved_content) {
+            Some(resolutions) => resolutions,
+            None => return Ok(0),
+        };
+        for (removes, adds, resolution) in &resolutions {
+            self.record_hunk(removes, adds, resolution)?;
+        }
+        Ok(resolutions.len())
+    }
+}
+
+/// The removes and adds of a conflicting hunk, and its resolution.
+type HunkResolution<'a> = (&'a [Vec<u8>], &'a [Vec<u8>], &'a [u8]);
+
+/// Finds the part of `content` that corresponds to each conflicting hunk. The
+/// resolved hunks must appear unchanged and in order in `content`, each
+/// starting at the beginning of a line.
+fn split_resolution<'a>(
+    hunks: &'a [MergeHunk],
+    content: &'a [u8],
+) -> Option<Vec<HunkResolution<'a>>> {
+    let mut resolutions = vec![];
+    let mut pending_conflict = None;
+    let mut pos = 0;
+    for (i, hunk) in hunks.iter().enumerate() {
+        match hunk {
+            MergeHunk::Resolved(resolved) if i == hunks.len() - 1 => {
+                // The last resolved hunk must be at the end of the content
+                if !content[pos..].ends_with(resolved) {
+                    return None;
+                }
+                let start = content.len() - resolved.len();
+                if let Some((removes, adds)) = pending_conflict.take() {
+                    resolutions.push((removes, adds, &content[pos..start]));
+                } else if start != pos {
+                    return None;
+                }
+                pos = content.len();
+            }
+            MergeHunk::Resolved(resolved) => {
+                let start = match pending_conflict.take() {
+                    None => {
+                        if !content[pos..].starts_with(resolved) {
+                            return None;
+                        }
+                        pos
+                    }
+                    Some((removes, adds)) => {
+                        let start = find_at_line_start(content, pos, resolved)?;
+                        resolutions.push((removes, adds, &content[pos..start]));
+                        start
+                    }
+                };
+                pos = start + resolved.len();
+            }
+            MergeHunk::Conflict { removes, adds } => {
+                if pending_conflict.is_some() {
+                    return None;
+                }
+                pending_conflict = Some((removes.as_slice(), adds.as_slice()));
+            }
+        }
+    }
+    if let Some((removes, adds)) = pending_conflict {
+        resolutions.push((removes, adds, &content[pos..]));
+    }
+    Some(resolutions)
+}
+
+fn find_at_line_start(content: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    (from..=content.len().checked_sub(needle.len())?).find(|&start| {
+        (start == 0 || content[start - 1] == b'\n')
+            && content[start..start + needle.len()] == *needle
+    })
+}
+
+/// Returns the merge hunks of the conflict at `path` if the conflict is between
+/// regular text files only.
+fn conflict_hunks(
+    store: &Store,
+    path: &RepoPath,
+    value: &TreeValue,
+) -> Result<Option<Vec<MergeHunk>>, BackendError> {
+    let conflict = match value {
+        TreeValue::Conflict(id) => store.read_conflict(path, id)?,
+        _ => return Ok(None),
+    };
+    let read_parts = |parts: &[ConflictPart]| -> Result<Option<Vec<Vec<u8>>>, BackendError> {
+        let mut contents = vec![];
+        for part in parts {
+            match &part.value {
+                TreeValue::Normal { id, .. } => {
+                    let mut content = vec![];
+                    store
+                        .read_file(path, id)?
+                        .read_to_end(&mut content)
+                        .map_err(|err| BackendError::Other(err.to_string()))?;
+                    contents.push(content);
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(contents))
+    };
+    let (removes, adds) = match (read_parts(&conflict.removes)?, read_parts(&conflict.adds)?) {
+        (Some(removes), Some(adds)) => (removes, adds),
+        _ => return Ok(None),
+    };
+    if adds.len() != removes.len() + 1
+        || removes
+            .iter()
+            .chain(adds.iter())
+            .any(|content| files::is_binary(content))
+    {
+        return Ok(None);
+    }
+    let removes = removes.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let adds = adds.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    match files::merge(&removes, &adds) {
+        MergeResult::Resolved(_) => Ok(None),
+        MergeResult::Conflict(hunks) => Ok(Some(hunks)),
+    }
+}
+
+/// Records the resolutions of the conflicts in `old_tree` that are resolved to
+/// regular files in `new_tree`. Returns the number of recorded hunks.
+pub fn record_resolutions(
+    cache: &ResolutionCache,
+    old_tree: &Tree,
+    new_tree: &Tree,
+) -> Result<usize, BackendError> {
+    let store = new_tree.store();
+    let mut num_recorded = 0;
+    for (path, diff) in old_tree.diff(new_tree, &EverythingMatcher) {
+        let (old_value, new_id) = match &diff {
+            Diff::Modified(old_value, TreeValue::Normal { id, .. }) => (old_value, id),
+            _ => continue,
+        };
+        let hunks = match conflict_hunks(store, &path, old_value)? {
+            Some(hunks) => hunks,
+            None => continue,
+        };
+        let mut resolved_content = vec![];
+        store
+            .read_file(&path, new_id)?
+            .read_to_end(&mut resolved_content)
+            .map_err(|err| BackendError::Other(err.to_string()))?;
+        num_recorded += cache
+            .record_resolution(&hunks, &resolved_content)
+            .map_err(|err| BackendError::Other(err.to_string()))?;
+    }
+    Ok(num_recorded)
+}
+
+/// Records how `new_commit` resolved the conflicts in `old_commit`, if the
+/// store has a resolution cache. Commands that rewrite the content of a commit
+/// call this with the commit before and after the rewrite.
+pub fn record_resolutions_in_rewrite(old_commit: &Commit, new_commit: &Commit) {
+    if let Some(cache) = new_commit.store().resolution_cache() {
+        // The cache is best-effort, so failing to record a resolution is not an
+        // error
+        record_resolutions(cache, &old_commit.tree(), &new_commit.tree()).ok();
+    }
+}
@@ -13,10 +13,11 @@
 // limitations under the License.
 
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
 use itertools::{process_results, Itertools};
 
-use crate::backend::{BackendError, CommitId};
+use crate::backend::{BackendError, BackendResult, CommitId};
 use crate::commit::Commit;
 use crate::commit_builder::CommitBuilder;
 use crate::dag_walk;
@@ -25,9 +26,55 @@ use crate::repo::{MutableRepo, RepoRef};
 use crate::repo_path::RepoPath;
 use crate::revset::RevsetExpression;
 use crate::settings::UserSettings;
-use crate::tree::{merge_trees, Tree};
+use crate::tree::{
+    merge_trees_reporting_resolutions, merge_trees_with_labels, ConflictLabels, Tree,
+};
 use crate::view::RefName;
 
+/// Returns a label describing the commit, for use in conflicts. The label
+/// contains the short change id, which still finds the commit after it's
+/// rewritten, the short commit id, and the first line of the description if
+/// there is one.
+pub fn conflict_label_for_commit(commit: &Commit) -> String {
+    let mut label = format!(
+        "{} (commit {})",
+        &commit.change_id().hex()[..12],
+        &commit.id().hex()[..12]
+    );
+    if let Some(first_line) = commit.description().lines().next() {
+        if !first_line.is_empty() {
+            label.push_str(&format!(" \"{first_line}\""));
+        }
+    }
+    label
+}
+
+/// Returns a label describing the merge of the given commits, or `None` if
+/// there are no commits (i.e. the root tree).
+pub fn conflict_label_for_commits(commits: &[Commit]) -> Option<String> {
+    match commits {
+        [] => None,
+        [commit] => Some(conflict_label_for_commit(commit)),
+        _ => Some(format!(
+            "merge of {}",
+            commits
+                .iter()
+                .map(|commit| commit.change_id().hex()[..12].to_string())
+                .join(", ")
+        )),
+    }
+}
+
+/// Returns the labels to use for conflicts created when rebasing `old_commit`
+/// onto `new_parents`.
+pub fn conflict_labels_for_rebase(old_commit: &Commit, new_parents: &[Commit]) -> ConflictLabels {
+    ConflictLabels {
+        side1: conflict_label_for_commits(new_parents),
+        base: conflict_label_for_commits(&old_commit.parents()),
+        side2: Some(conflict_label_for_commit(old_commit)),
+    }
+}
+
 pub fn merge_commit_trees(repo: RepoRef, commits: &[Commit]) -> Tree {
     let store = repo.store();
     if commits.is_empty() {
@@ -48,19 +95,79 @@ pub fn merge_commit_trees(repo: RepoRef, commits: &[Commit]) -> Tree {
                 .map(|id| store.get_commit(id).unwrap())
                 .collect_vec();
             let ancestor_tree = merge_commit_trees(repo, &ancestors);
-            let new_tree_id = merge_trees(&new_tree, &ancestor_tree, &other_commit.tree()).unwrap();
+            let labels = ConflictLabels {
+                side1: conflict_label_for_commits(&commits[0..i]),
+                base: conflict_label_for_commits(&ancestors),
+                side2: Some(conflict_label_for_commit(other_commit)),
+            };
+            let new_tree_id =
+                merge_trees_with_labels(&new_tree, &ancestor_tree, &other_commit.tree(), &labels)
+                    .unwrap();
             new_tree = store.get_tree(&RepoPath::root(), &new_tree_id).unwrap();
         }
         new_tree
     }
 }
 
+/// What to do with commits that are empty after being rebased. Only commits
+/// with a single parent can be empty, and commits that are checked out in a
+/// workspace are always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyBehavior {
+    /// Keep all commits.
+    Keep,
+    /// Abandon commits that were not empty before the rebase but are now, for
+    /// example because the same changes were already made in the destination.
+    AbandonNewlyEmpty,
+    /// Abandon all commits that are empty after the rebase.
+    AbandonAllEmpty,
+}
+
+impl Default for EmptyBehavior {
+    fn default() -> Self {
+        EmptyBehavior::Keep
+    }
+}
+
+impl FromStr for EmptyBehavior {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(EmptyBehavior::Keep),
+            "abandon-newly-empty" => Ok(EmptyBehavior::AbandonNewlyEmpty),
+            "abandon-all-empty" => Ok(EmptyBehavior::AbandonAllEmpty),
+            _ => Err("must be one of keep, abandon-newly-empty, or abandon-all-empty"),
+        }
+    }
+}
+
 pub fn rebase_commit(
     settings: &UserSettings,
     mut_repo: &mut MutableRepo,
     old_commit: &Commit,
     new_parents: &[Commit],
-) -> Commit {
+) -> BackendResult<Commit> {
+    let new_commit = rebase_commit_with_empty_behavior(
+        settings,
+        mut_repo,
+        old_commit,
+        new_parents,
+        EmptyBehavior::Keep,
+    )?;
+    Ok(new_commit.unwrap())
+}
+
+/// Rebases `old_commit` onto `new_parents`. Returns `None` without writing a
+/// new commit if the rebased commit would be empty and `empty` says to abandon
+/// it. The caller is then responsible for recording the commit as abandoned.
+pub fn rebase_commit_with_empty_behavior(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    old_commit: &Commit,
+    new_parents: &[Commit],
+    empty: EmptyBehavior,
+) -> BackendResult<Option<Commit>> {
     let old_parents = old_commit.parents();
     let old_parent_trees = old_parents
         .iter()
@@ -76,17 +183,37 @@ pub fn rebase_commit(
     } else {
         let old_base_tree = merge_commit_trees(mut_repo.as_repo_ref(), &old_parents);
         let new_base_tree = merge_commit_trees(mut_repo.as_repo_ref(), new_parents);
-        // TODO: pass in labels for the merge parts
-        merge_trees(&new_base_tree, &old_base_tree, &old_commit.tree()).unwrap()
+        let labels = conflict_labels_for_rebase(old_commit, new_parents);
+        let (tree_id, reused_resolutions) = merge_trees_reporting_resolutions(
+            &new_base_tree,
+            &old_base_tree,
+            &old_commit.tree(),
+            &labels,
+        )?;
+        mut_repo.record_reused_resolutions(reused_resolutions);
+        tree_id
+    };
+    let abandon = match empty {
+        EmptyBehavior::Keep => false,
+        EmptyBehavior::AbandonNewlyEmpty => !old_commit.is_empty(),
+        EmptyBehavior::AbandonAllEmpty => true,
     };
+    if abandon
+        && new_parents.len() == 1
+        && *new_parents[0].tree_id() == new_tree_id
+        && !mut_repo.view().is_checkout(old_commit.id())
+    {
+        return Ok(None);
+    }
     let new_parent_ids = new_parents
         .iter()
         .map(|commit| commit.id().clone())
         .collect();
-    CommitBuilder::for_rewrite_from(settings, old_commit)
+    let new_commit = CommitBuilder::for_rewrite_from(settings, old_commit)
         .set_parents(new_parent_ids)
         .set_tree(new_tree_id)
-        .write_to_repo(mut_repo)
+        .write_to_repo(mut_repo)?;
+    Ok(Some(new_commit))
 }
 
 pub fn back_out_commit(
@@ -94,11 +221,22 @@ pub fn back_out_commit(
     mut_repo: &mut MutableRepo,
     old_commit: &Commit,
     new_parents: &[Commit],
-) -> Commit {
-    let old_base_tree = merge_commit_trees(mut_repo.as_repo_ref(), &old_commit.parents());
+) -> BackendResult<Commit> {
+    let old_parents = old_commit.parents();
+    let old_base_tree = merge_commit_trees(mut_repo.as_repo_ref(), &old_parents);
     let new_base_tree = merge_commit_trees(mut_repo.as_repo_ref(), new_parents);
-    // TODO: pass in labels for the merge parts
-    let new_tree_id = merge_trees(&new_base_tree, &old_commit.tree(), &old_base_tree).unwrap();
+    let labels = ConflictLabels {
+        side1: conflict_label_for_commits(new_parents),
+        base: Some(conflict_label_for_commit(old_commit)),
+        side2: conflict_label_for_commits(&old_parents),
+    };
+    let (new_tree_id, reused_resolutions) = merge_trees_reporting_resolutions(
+        &new_base_tree,
+        &old_commit.tree(),
+        &old_base_tree,
+        &labels,
+    )?;
+    mut_repo.record_reused_resolutions(reused_resolutions);
     let new_parent_ids = new_parents
         .iter()
         .map(|commit| commit.id().clone())
@@ -111,9 +249,6 @@ pub fn back_out_commit(
 }
 
 /// Rebases descendants of a commit onto a new commit (or several).
-// TODO: Should there be an option to drop empty commits (and/or an option to
-// drop empty commits only if they weren't already empty)? Or maybe that
-// shouldn't be this type's job.
 pub struct DescendantRebaser<'settings, 'repo> {
     settings: &'settings UserSettings,
     mut_repo: &'repo mut MutableRepo,
@@ -129,6 +264,11 @@ pub struct DescendantRebaser<'settings, 'repo> {
     // want to rebase them. Instead, we record them in `replacements` when we visit them. That way,
     // their descendants will be rebased correctly.
     abandoned: HashSet<CommitId>,
+    // New parents of abandoned commits that should not be replaced by their
+    // own parents, typically because they were rebased and then abandoned.
+    abandoned_parents: HashMap<CommitId, Vec<CommitId>>,
+    // What to do with descendants that become empty.
+    empty: EmptyBehavior,
     new_commits: HashSet<CommitId>,
     rebased: HashMap<CommitId, CommitId>,
     // Names of branches where local target includes the commit id in the key.
@@ -179,11 +319,18 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
             Box::new(|entry| entry.commit_id()),
             Box::new(|entry| {
                 visited.insert(entry.commit_id());
+                // Commits that were already rewritten are not rebased, so they don't need
+                // to wait for their rewritten parents. Waiting could even create a cycle
+                // if a parent was rewritten onto the commit.
+                let is_rewritten = rewritten.contains_key(&entry.commit_id());
                 let mut dependents = vec![];
                 for parent in entry.parents() {
                     if let Some(targets) = rewritten.get(&parent.commit_id()) {
                         for target in targets {
-                            if to_visit_set.contains(target) && !visited.contains(target) {
+                            if !is_rewritten
+                                && to_visit_set.contains(target)
+                                && !visited.contains(target)
+                            {
                                 dependents.push(index.entry_by_id(target).unwrap());
                             }
                         }
@@ -238,6 +385,8 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
             divergent,
             to_visit,
             abandoned,
+            abandoned_parents: Default::default(),
+            empty: EmptyBehavior::Keep,
             new_commits,
             rebased: Default::default(),
             branches,
@@ -246,6 +395,18 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
         }
     }
 
+    /// Sets what to do with descendants that are empty after being rebased.
+    pub fn set_empty_behavior(&mut self, empty: EmptyBehavior) {
+        self.empty = empty;
+    }
+
+    /// Sets the commits that an abandoned commit should be replaced by.
+    /// Descendants of the abandoned commit are rebased onto them instead of
+    /// onto the abandoned commit's parents.
+    pub fn set_abandoned_parents(&mut self, old_id: CommitId, new_parent_ids: Vec<CommitId>) {
+        self.abandoned_parents.insert(old_id, new_parent_ids);
+    }
+
     /// Returns a map from `CommitId` of old commit to new commit. Includes the
     /// commits rebase so far. Does not include the inputs passed to
     /// `rebase_descendants`.
@@ -325,7 +486,10 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
         }
 
         self.heads_to_add.remove(&old_commit_id);
-        if !self.new_commits.contains(&old_commit_id) || self.rebased.contains_key(&old_commit_id) {
+        if !self.new_commits.contains(&old_commit_id)
+            || self.rebased.contains_key(&old_commit_id)
+            || self.abandoned.contains(&old_commit_id)
+        {
             self.heads_to_remove.push(old_commit_id);
         }
         Ok(())
@@ -351,7 +515,7 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
                 new_commit.id().clone(),
                 new_commit.tree_id().clone(),
             )
-            .write_to_repo(self.mut_repo)
+            .write_to_repo(self.mut_repo)?
         };
         for workspace_id in workspaces_to_update.into_iter() {
             self.mut_repo.edit(workspace_id, &new_checkout_commit);
@@ -379,7 +543,10 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
             }
             let old_commit = self.mut_repo.store().get_commit(&old_commit_id)?;
             let old_parent_ids = old_commit.parent_ids();
-            let new_parent_ids = self.new_parents(&old_parent_ids);
+            let new_parent_ids = match self.abandoned_parents.get(&old_commit_id) {
+                Some(abandoned_parent_ids) => self.new_parents(abandoned_parent_ids),
+                None => self.new_parents(&old_parent_ids),
+            };
             if self.abandoned.contains(&old_commit_id) {
                 // Update the `new_parents` map so descendants are rebased correctly.
                 self.new_parents
@@ -406,7 +573,28 @@ impl<'settings, 'repo> DescendantRebaser<'settings, 'repo> {
                     .map(|new_parent_id| self.mut_repo.store().get_commit(new_parent_id)),
                 |iter| iter.collect_vec(),
             )?;
-            let new_commit = rebase_commit(self.settings, self.mut_repo, &old_commit, &new_parents);
+            let new_commit = match rebase_commit_with_empty_behavior(
+                self.settings,
+                self.mut_repo,
+                &old_commit,
+                &new_parents,
+                self.empty,
+            )? {
+                Some(new_commit) => new_commit,
+                None => {
+                    // The commit became empty, so abandon it and rebase its descendants
+                    // onto its new parent instead.
+                    let new_parent_ids = new_parents
+                        .iter()
+                        .map(|commit| commit.id().clone())
+                        .collect_vec();
+                    self.abandoned.insert(old_commit_id.clone());
+                    self.new_parents
+                        .insert(old_commit_id.clone(), new_parent_ids.clone());
+                    self.update_references(old_commit_id, new_parent_ids, false)?;
+                    continue;
+                }
+            };
             self.rebased
                 .insert(old_commit_id.clone(), new_commit.id().clone());
             self.update_references(old_commit_id, vec![new_commit.id().clone()], true)?;
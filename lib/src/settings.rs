@@ -12,21 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use chrono::DateTime;
+use itertools::Itertools;
+use rand::prelude::*;
 
-use crate::backend::{Signature, Timestamp};
+use crate::backend::{ChangeId, Signature, Timestamp};
+use crate::conflicts::ConflictMarkerStyle;
+use crate::files::MergeDriver;
+use crate::signing::Signer;
 
 #[derive(Debug, Clone, Default)]
 pub struct UserSettings {
     config: config::Config,
     timestamp: Option<Timestamp>,
+    rng: Arc<JJRng>,
 }
 
 #[derive(Debug, Clone)]
 pub struct RepoSettings {
-    _config: config::Config,
+    config: config::Config,
 }
 
 impl UserSettings {
@@ -38,7 +46,12 @@ impl UserSettings {
             },
             Err(_) => None,
         };
-        UserSettings { config, timestamp }
+        let rng_seed = config.get_int("debug.randomness-seed").ok();
+        UserSettings {
+            config,
+            timestamp,
+            rng: Arc::new(JJRng::new(rng_seed.map(|seed| seed as u64))),
+        }
     }
 
     pub fn with_repo(&self, repo_path: &Path) -> Result<RepoSettings, config::ConfigError> {
@@ -50,7 +63,7 @@ impl UserSettings {
                     .format(config::FileFormat::Toml),
             )
             .build()?;
-        Ok(RepoSettings { _config: config })
+        Ok(RepoSettings { config })
     }
 
     pub fn user_name(&self) -> String {
@@ -88,13 +101,94 @@ impl UserSettings {
         }
     }
 
+    pub fn get_rng(&self) -> Arc<JJRng> {
+        self.rng.clone()
+    }
+
     pub fn enable_open_commits(&self) -> bool {
         self.config
             .get_bool("ui.enable-open-commits")
             .unwrap_or(false)
     }
 
+    pub fn rerere_enabled(&self) -> bool {
+        self.config.get_bool("rerere.enabled").unwrap_or(false)
+    }
+
+    /// Returns the custom merge drivers configured in `[merge-drivers.<name>]`.
+    /// Drivers without a `program` are skipped.
+    pub fn merge_drivers(&self) -> HashMap<String, MergeDriver> {
+        let names = self
+            .config
+            .get_table("merge-drivers")
+            .map(|table| table.into_keys().collect_vec())
+            .unwrap_or_default();
+        let mut drivers = HashMap::new();
+        for name in names {
+            let program = match self
+                .config
+                .get_string(&format!("merge-drivers.{name}.program"))
+            {
+                Ok(program) => program,
+                Err(_) => continue,
+            };
+            let args = self
+                .config
+                .get_array(&format!("merge-drivers.{name}.args"))
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|arg| arg.into_string().ok())
+                .collect();
+            drivers.insert(name, MergeDriver { program, args });
+        }
+        drivers
+    }
+
+    pub fn conflict_marker_style(&self) -> ConflictMarkerStyle {
+        self.config
+            .get_string("ui.conflict-marker-style")
+            .ok()
+            .and_then(|value| ConflictMarkerStyle::from_config_value(&value))
+            .unwrap_or_default()
+    }
+
     pub fn config(&self) -> &config::Config {
         &self.config
     }
 }
+
+/// The source of randomness for new change ids. It's seeded from
+/// `debug.randomness-seed` if that's set, which makes the ids reproducible in
+/// tests.
+#[derive(Debug)]
+pub struct JJRng(Mutex<StdRng>);
+
+impl JJRng {
+    fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        JJRng(Mutex::new(rng))
+    }
+
+    pub fn new_change_id(&self) -> ChangeId {
+        let mut bytes = vec![0; 16];
+        self.0.lock().unwrap().fill_bytes(&mut bytes);
+        ChangeId::new(bytes)
+    }
+}
+
+impl Default for JJRng {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl RepoSettings {
+    /// Returns a signer configured by the `signing.*` settings, which may be
+    /// set per repo.
+    pub fn signer(&self) -> Signer {
+        Signer::from_config(&self.config)
+    }
+}
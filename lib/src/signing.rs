@@ -0,0 +1,527 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing commits and verifying their signatures.
+//!
+//! The actual cryptography is done by external programs. GPG and SSH are
+//! supported out of the box, and any other program that speaks a simple
+//! protocol can be plugged in as the "command" backend (see
+//! `CommandBackend`).
+
+use std::fmt::Debug;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+/// Whether a signature could be verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigStatus {
+    /// The signature is valid and made by a trusted key
+    Good,
+    /// The signature doesn't match the signed data
+    Bad,
+    /// The signature couldn't be checked, e.g. because the key is unknown
+    Unknown,
+}
+
+/// The result of verifying a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub status: SigStatus,
+    /// The key that made the signature, if known
+    pub key: Option<String>,
+    /// Human-readable description of who made the signature, if known
+    pub display: Option<String>,
+}
+
+impl Verification {
+    pub fn unknown() -> Self {
+        Verification {
+            status: SigStatus::Unknown,
+            key: None,
+            display: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SignError {
+    #[error("No signing backend is configured (set `signing.backend`)")]
+    NoBackend,
+    #[error("Unknown signing backend '{0}'")]
+    UnknownBackend(String),
+    #[error("The {0} signing backend requires `signing.key` to be set")]
+    MissingKey(String),
+    #[error("Failed to run {program}: {err}")]
+    Io {
+        program: String,
+        err: std::io::Error,
+    },
+    #[error("{program} failed: {stderr}")]
+    Failed { program: String, stderr: String },
+}
+
+pub type SignResult<T> = Result<T, SignError>;
+
+/// A way of signing data and verifying signatures, such as GPG.
+pub trait SigningBackend: Debug + Send + Sync {
+    /// The name used to select this backend in `signing.backend`
+    fn name(&self) -> &str;
+
+    /// Whether this backend can verify the given signature
+    fn can_read(&self, sig: &[u8]) -> bool;
+
+    /// Signs `data` with the given key, or with the backend's default key
+    fn sign(&self, data: &[u8], key: Option<&str>) -> SignResult<Vec<u8>>;
+
+    fn verify(&self, data: &[u8], sig: &[u8]) -> SignResult<Verification>;
+}
+
+/// Runs `program` with the given arguments and `input` on stdin. Doesn't check
+/// the exit status.
+fn run_program(program: &str, args: &[&str], input: &[u8]) -> SignResult<Output> {
+    let io_error = |err| SignError::Io {
+        program: program.to_string(),
+        err,
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_error)?;
+    // If the program exits without reading all of its input, the write fails,
+    // but the exit status tells us more about what went wrong
+    let _ = child.stdin.take().unwrap().write_all(input);
+    child.wait_with_output().map_err(io_error)
+}
+
+fn check_success(program: &str, output: Output) -> SignResult<Vec<u8>> {
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(SignError::Failed {
+            program: program.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+/// Writes `content` to a temporary file, for programs that want some of their
+/// input as a file.
+fn write_temp_file(program: &str, content: &[u8]) -> SignResult<NamedTempFile> {
+    let io_error = |err| SignError::Io {
+        program: program.to_string(),
+        err,
+    };
+    let mut file = NamedTempFile::new().map_err(io_error)?;
+    file.write_all(content).map_err(io_error)?;
+    Ok(file)
+}
+
+fn path_str(file: &NamedTempFile) -> &str {
+    file.path().to_str().unwrap()
+}
+
+/// Signs with `gpg --detach-sign`.
+#[derive(Debug)]
+pub struct GpgBackend {
+    program: String,
+}
+
+impl GpgBackend {
+    pub fn new(program: String) -> Self {
+        GpgBackend { program }
+    }
+}
+
+impl Default for GpgBackend {
+    fn default() -> Self {
+        GpgBackend::new("gpg".to_string())
+    }
+}
+
+impl SigningBackend for GpgBackend {
+    fn name(&self) -> &str {
+        "gpg"
+    }
+
+    fn can_read(&self, sig: &[u8]) -> bool {
+        sig.starts_with(b"-----BEGIN PGP SIGNATURE-----")
+    }
+
+    fn sign(&self, data: &[u8], key: Option<&str>) -> SignResult<Vec<u8>> {
+        let mut args = vec!["--detach-sign", "--armor"];
+        if let Some(key) = key {
+            args.extend(["--local-user", key]);
+        }
+        let output = run_program(&self.program, &args, data)?;
+        check_success(&self.program, output)
+    }
+
+    fn verify(&self, data: &[u8], sig: &[u8]) -> SignResult<Verification> {
+        let sig_file = write_temp_file(&self.program, sig)?;
+        let args = [
+            "--keyid-format=long",
+            "--status-fd=1",
+            "--verify",
+            path_str(&sig_file),
+            "-",
+        ];
+        // gpg exits with an error for bad signatures too, so we only look at the
+        // status lines
+        let output = run_program(&self.program, &args, data)?;
+        Ok(parse_gpg_status(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+fn parse_gpg_status(status: &str) -> Verification {
+    for line in status.lines() {
+        let mut words = line.splitn(4, ' ');
+        if words.next() != Some("[GNUPG:]") {
+            continue;
+        }
+        // For ERRSIG, the rest of the line after the key is not a user id
+        let (status, has_user_id) = match words.next() {
+            Some("GOODSIG") => (SigStatus::Good, true),
+            Some("BADSIG") => (SigStatus::Bad, true),
+            Some("EXPSIG" | "EXPKEYSIG" | "REVKEYSIG") => (SigStatus::Unknown, true),
+            Some("ERRSIG") => (SigStatus::Unknown, false),
+            _ => continue,
+        };
+        let key = words.next().map(str::to_string);
+        let display = if has_user_id {
+            words.next().map(str::to_string)
+        } else {
+            None
+        };
+        return Verification {
+            status,
+            key,
+            display,
+        };
+    }
+    Verification::unknown()
+}
+
+/// Signs with `ssh-keygen -Y sign`. The key is the path to a private key file,
+/// or a public key whose private key is in the SSH agent.
+///
+/// Verification needs an "allowed signers" file (see `ssh-keygen(1)`) to tell
+/// which keys are trusted. Without one, valid signatures are reported as
+/// unknown.
+#[derive(Debug)]
+pub struct SshBackend {
+    program: String,
+    allowed_signers: Option<String>,
+}
+
+impl SshBackend {
+    pub fn new(program: String, allowed_signers: Option<String>) -> Self {
+        SshBackend {
+            program,
+            allowed_signers,
+        }
+    }
+}
+
+impl Default for SshBackend {
+    fn default() -> Self {
+        SshBackend::new("ssh-keygen".to_string(), None)
+    }
+}
+
+impl SigningBackend for SshBackend {
+    fn name(&self) -> &str {
+        "ssh"
+    }
+
+    fn can_read(&self, sig: &[u8]) -> bool {
+        sig.starts_with(b"-----BEGIN SSH SIGNATURE-----")
+    }
+
+    fn sign(&self, data: &[u8], key: Option<&str>) -> SignResult<Vec<u8>> {
+        let key = key.ok_or_else(|| SignError::MissingKey(self.name().to_string()))?;
+        // A literal public key has to be passed as a file, and the private key is
+        // then looked up in the agent
+        let public_key_file;
+        let mut args = vec!["-Y", "sign", "-n", "git"];
+        if key.starts_with("ssh-") {
+            public_key_file = write_temp_file(&self.program, key.as_bytes())?;
+            args.extend(["-U", "-f", path_str(&public_key_file)]);
+        } else {
+            args.extend(["-f", key]);
+        }
+        let output = run_program(&self.program, &args, data)?;
+        check_success(&self.program, output)
+    }
+
+    fn verify(&self, data: &[u8], sig: &[u8]) -> SignResult<Verification> {
+        let sig_file = write_temp_file(&self.program, sig)?;
+        let sig_path = path_str(&sig_file);
+        if let Some(allowed_signers) = &self.allowed_signers {
+            let output = run_program(
+                &self.program,
+                &[
+                    "-Y",
+                    "find-principals",
+                    "-f",
+                    allowed_signers,
+                    "-s",
+                    sig_path,
+                ],
+                &[],
+            )?;
+            let principals = String::from_utf8_lossy(&output.stdout);
+            if let Some(principal) = principals
+                .lines()
+                .next()
+                .filter(|_| output.status.success())
+            {
+                let output = run_program(
+                    &self.program,
+                    &[
+                        "-Y",
+                        "verify",
+                        "-f",
+                        allowed_signers,
+                        "-I",
+                        principal,
+                        "-n",
+                        "git",
+                        "-s",
+                        sig_path,
+                    ],
+                    data,
+                )?;
+                let status = if output.status.success() {
+                    SigStatus::Good
+                } else {
+                    SigStatus::Bad
+                };
+                return Ok(Verification {
+                    status,
+                    key: None,
+                    display: Some(principal.to_string()),
+                });
+            }
+        }
+        // The key isn't trusted, but we can still check that the signature matches
+        let output = run_program(
+            &self.program,
+            &["-Y", "check-novalidate", "-n", "git", "-s", sig_path],
+            data,
+        )?;
+        let status = if output.status.success() {
+            SigStatus::Unknown
+        } else {
+            SigStatus::Bad
+        };
+        Ok(Verification {
+            status,
+            key: None,
+            display: None,
+        })
+    }
+}
+
+/// Signs with an arbitrary program. The program is run as
+/// `<program> sign [<key>]` with the data to sign on stdin and should print the
+/// signature to stdout. To verify, it's run as `<program> verify <sig-file>`
+/// with the signed data on stdin. It should exit with status 0 for a good
+/// signature, 1 for a bad one, and anything else if it can't tell. The first
+/// line it prints is shown as the signer.
+///
+/// Since there's no way to tell which signatures the program understands, it's
+/// used for all signatures that the built-in backends don't recognize.
+#[derive(Debug)]
+pub struct CommandBackend {
+    program: String,
+}
+
+impl CommandBackend {
+    pub fn new(program: String) -> Self {
+        CommandBackend { program }
+    }
+}
+
+impl SigningBackend for CommandBackend {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn can_read(&self, _sig: &[u8]) -> bool {
+        true
+    }
+
+    fn sign(&self, data: &[u8], key: Option<&str>) -> SignResult<Vec<u8>> {
+        let mut args = vec!["sign"];
+        args.extend(key);
+        let output = run_program(&self.program, &args, data)?;
+        check_success(&self.program, output)
+    }
+
+    fn verify(&self, data: &[u8], sig: &[u8]) -> SignResult<Verification> {
+        let sig_file = write_temp_file(&self.program, sig)?;
+        let output = run_program(&self.program, &["verify", path_str(&sig_file)], data)?;
+        let status = match output.status.code() {
+            Some(0) => SigStatus::Good,
+            Some(1) => SigStatus::Bad,
+            _ => SigStatus::Unknown,
+        };
+        let display = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string);
+        Ok(Verification {
+            status,
+            key: None,
+            display,
+        })
+    }
+}
+
+/// Signs commits with the configured backend and verifies signatures with
+/// whichever backend recognizes them.
+#[derive(Debug)]
+pub struct Signer {
+    /// Name of the backend to sign with
+    main_backend: Option<String>,
+    key: Option<String>,
+    sign_all: bool,
+    /// Backends in the order in which they're asked whether they can read a
+    /// signature
+    backends: Vec<Box<dyn SigningBackend>>,
+}
+
+impl Default for Signer {
+    fn default() -> Self {
+        Signer::new(
+            None,
+            None,
+            false,
+            vec![
+                Box::new(GpgBackend::default()),
+                Box::new(SshBackend::default()),
+            ],
+        )
+    }
+}
+
+impl Signer {
+    pub fn new(
+        main_backend: Option<String>,
+        key: Option<String>,
+        sign_all: bool,
+        backends: Vec<Box<dyn SigningBackend>>,
+    ) -> Self {
+        Signer {
+            main_backend,
+            key,
+            sign_all,
+            backends,
+        }
+    }
+
+    /// Creates a signer from the `signing.*` config.
+    pub fn from_config(config: &config::Config) -> Self {
+        let program = |name: &str, default: &str| {
+            config
+                .get_string(&format!("signing.backends.{name}.program"))
+                .unwrap_or_else(|_| default.to_string())
+        };
+        let mut backends: Vec<Box<dyn SigningBackend>> = vec![
+            Box::new(GpgBackend::new(program("gpg", "gpg"))),
+            Box::new(SshBackend::new(
+                program("ssh", "ssh-keygen"),
+                config
+                    .get_string("signing.backends.ssh.allowed-signers")
+                    .ok(),
+            )),
+        ];
+        if let Ok(program) = config.get_string("signing.backends.command.program") {
+            backends.push(Box::new(CommandBackend::new(program)));
+        }
+        Signer::new(
+            config.get_string("signing.backend").ok(),
+            config.get_string("signing.key").ok(),
+            config.get_bool("signing.sign-all").unwrap_or(false),
+            backends,
+        )
+    }
+
+    /// Whether all new commits should be signed
+    pub fn sign_all(&self) -> bool {
+        self.sign_all
+    }
+
+    pub fn sign(&self, data: &[u8]) -> SignResult<Vec<u8>> {
+        let name = self.main_backend.as_ref().ok_or(SignError::NoBackend)?;
+        let backend = self
+            .backends
+            .iter()
+            .find(|backend| backend.name() == name)
+            .ok_or_else(|| SignError::UnknownBackend(name.clone()))?;
+        backend.sign(data, self.key.as_deref())
+    }
+
+    /// Verifies `sig` with the first backend that recognizes it. Signatures
+    /// that no backend recognizes have unknown status.
+    pub fn verify(&self, data: &[u8], sig: &[u8]) -> SignResult<Verification> {
+        match self.backends.iter().find(|backend| backend.can_read(sig)) {
+            Some(backend) => backend.verify(data, sig),
+            None => Ok(Verification::unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpg_status() {
+        assert_eq!(
+            parse_gpg_status(
+                "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 0123456789ABCDEF Some One <some@one.org>\n"
+            ),
+            Verification {
+                status: SigStatus::Good,
+                key: Some("0123456789ABCDEF".to_string()),
+                display: Some("Some One <some@one.org>".to_string()),
+            }
+        );
+        assert_eq!(
+            parse_gpg_status("[GNUPG:] BADSIG 0123456789ABCDEF Some One <some@one.org>\n"),
+            Verification {
+                status: SigStatus::Bad,
+                key: Some("0123456789ABCDEF".to_string()),
+                display: Some("Some One <some@one.org>".to_string()),
+            }
+        );
+        assert_eq!(
+            parse_gpg_status("[GNUPG:] ERRSIG 0123456789ABCDEF 1 8 00 1234567890 9 -\n"),
+            Verification {
+                status: SigStatus::Unknown,
+                key: Some("0123456789ABCDEF".to_string()),
+                display: None,
+            }
+        );
+        assert_eq!(parse_gpg_status(""), Verification::unknown());
+    }
+}
@@ -19,13 +19,25 @@ use std::sync::{Arc, RwLock};
 use crate::backend;
 use crate::backend::{
     Backend, BackendResult, ChangeId, CommitId, Conflict, ConflictId, FileId, MillisSinceEpoch,
-    Signature, SymlinkId, Timestamp, TreeId,
+    Signature, SigningFn, SymlinkId, Timestamp, TreeId,
 };
 use crate::commit::Commit;
+use crate::files::MergeDriver;
 use crate::repo_path::RepoPath;
+use crate::rerere::ResolutionCache;
+use crate::signing::Signer;
 use crate::tree::Tree;
 use crate::tree_builder::TreeBuilder;
 
+/// Settings for how trees are merged.
+#[derive(Debug, Default)]
+pub struct MergeSettings {
+    /// Cache of recorded conflict resolutions to use and record.
+    pub resolution_cache: Option<ResolutionCache>,
+    /// Custom merge drivers by name, for use with `merge=<name>` attributes.
+    pub merge_drivers: HashMap<String, MergeDriver>,
+}
+
 /// Wraps the low-level backend and makes it return more convenient types. Also
 /// adds the root commit and adds caching.
 #[derive(Debug)]
@@ -34,16 +46,30 @@ pub struct Store {
     root_commit_id: CommitId,
     commit_cache: RwLock<HashMap<CommitId, Arc<backend::Commit>>>,
     tree_cache: RwLock<HashMap<(RepoPath, TreeId), Arc<backend::Tree>>>,
+    merge_settings: MergeSettings,
+    signer: Signer,
 }
 
 impl Store {
     pub fn new(backend: Box<dyn Backend>) -> Arc<Self> {
+        Self::new_with_settings(backend, MergeSettings::default(), Signer::default())
+    }
+
+    /// Like `new()`, but with custom settings for how trees are merged and how
+    /// commits are signed.
+    pub fn new_with_settings(
+        backend: Box<dyn Backend>,
+        merge_settings: MergeSettings,
+        signer: Signer,
+    ) -> Arc<Self> {
         let root_commit_id = CommitId::new(vec![0; backend.hash_length()]);
         Arc::new(Store {
             backend,
             root_commit_id,
             commit_cache: Default::default(),
             tree_cache: Default::default(),
+            merge_settings,
+            signer,
         })
     }
 
@@ -59,6 +85,18 @@ impl Store {
         self.backend.empty_tree_id()
     }
 
+    pub fn resolution_cache(&self) -> Option<&ResolutionCache> {
+        self.merge_settings.resolution_cache.as_ref()
+    }
+
+    pub fn merge_driver(&self, name: &str) -> Option<&MergeDriver> {
+        self.merge_settings.merge_drivers.get(name)
+    }
+
+    pub fn signer(&self) -> &Signer {
+        &self.signer
+    }
+
     pub fn root_commit_id(&self) -> &CommitId {
         &self.root_commit_id
     }
@@ -92,6 +130,8 @@ impl Store {
             author: signature.clone(),
             committer: signature,
             is_open: false,
+            secure_sig: None,
+            copied_from: None,
         }
     }
 
@@ -113,15 +153,19 @@ impl Store {
         Ok(data)
     }
 
-    pub fn write_commit(self: &Arc<Self>, commit: backend::Commit) -> Commit {
-        let commit_id = self.backend.write_commit(&commit).unwrap();
+    /// Writes the commit, signing it if `signing.sign-all` is enabled. Fails if
+    /// signing fails.
+    pub fn write_commit(self: &Arc<Self>, commit: backend::Commit) -> BackendResult<Commit> {
+        let sign = |data: &[u8]| self.signer.sign(data);
+        let sign_with: Option<&SigningFn> = self.signer.sign_all().then(|| &sign as &SigningFn);
+        let (commit_id, commit) = self.backend.write_commit(commit, sign_with)?;
         let data = Arc::new(commit);
         {
             let mut write_locked_cache = self.commit_cache.write().unwrap();
             write_locked_cache.insert(commit_id.clone(), data.clone());
         }
 
-        Commit::new(self.clone(), commit_id, data)
+        Ok(Commit::new(self.clone(), commit_id, data))
     }
 
     pub fn get_tree(self: &Arc<Self>, dir: &RepoPath, id: &TreeId) -> BackendResult<Tree> {
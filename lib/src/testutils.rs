@@ -61,7 +61,10 @@ pub struct TestRepo {
 
 impl TestRepo {
     pub fn init(use_git: bool) -> Self {
-        let settings = user_settings();
+        Self::init_with_settings(&user_settings(), use_git)
+    }
+
+    pub fn init_with_settings(settings: &UserSettings, use_git: bool) -> Self {
         let temp_dir = tempfile::tempdir().unwrap();
 
         let repo_dir = temp_dir.path().join("repo");
@@ -70,11 +73,11 @@ impl TestRepo {
         let repo = if use_git {
             let git_path = temp_dir.path().join("git-repo");
             git2::Repository::init(&git_path).unwrap();
-            ReadonlyRepo::init(&settings, repo_dir, |store_path| {
+            ReadonlyRepo::init(settings, repo_dir, |store_path| {
                 Box::new(GitBackend::init_external(store_path, git_path.clone()))
             })
         } else {
-            ReadonlyRepo::init(&settings, repo_dir, |store_path| {
+            ReadonlyRepo::init(settings, repo_dir, |store_path| {
                 Box::new(LocalBackend::init(store_path))
             })
         };
@@ -212,6 +215,7 @@ impl<'settings, 'repo> CommitGraphBuilder<'settings, 'repo> {
     pub fn initial_commit(&mut self) -> Commit {
         create_random_commit(self.settings, self.mut_repo.base_repo().as_ref())
             .write_to_repo(self.mut_repo)
+            .unwrap()
     }
 
     pub fn commit_with_parents(&mut self, parents: &[&Commit]) -> Commit {
@@ -222,6 +226,7 @@ impl<'settings, 'repo> CommitGraphBuilder<'settings, 'repo> {
         create_random_commit(self.settings, self.mut_repo.base_repo().as_ref())
             .set_parents(parent_ids)
             .write_to_repo(self.mut_repo)
+            .unwrap()
     }
 }
 
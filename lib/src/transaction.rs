@@ -22,6 +22,7 @@ use crate::op_store;
 use crate::op_store::OperationMetadata;
 use crate::operation::Operation;
 use crate::repo::{MutableRepo, ReadonlyRepo, RepoLoader};
+use crate::rewrite::EmptyBehavior;
 use crate::view::View;
 
 pub struct Transaction {
@@ -60,6 +61,12 @@ impl Transaction {
         self.repo.as_mut().unwrap()
     }
 
+    /// Sets what happens to descendants that become empty when they're rebased
+    /// after commits are rewritten in this transaction.
+    pub fn set_empty_behavior(&mut self, empty: EmptyBehavior) {
+        self.mut_repo().set_empty_behavior(empty);
+    }
+
     pub fn merge_operation(&mut self, other_op: Operation) {
         let ancestor_op = closest_common_node(
             self.parent_ops.clone(),
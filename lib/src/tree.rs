@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fmt::{Debug, Error, Formatter};
 use std::io::Read;
@@ -26,7 +27,8 @@ use crate::backend::{
     BackendError, Conflict, ConflictId, ConflictPart, FileId, TreeEntriesNonRecursiveIterator,
     TreeEntry, TreeId, TreeValue,
 };
-use crate::files::MergeResult;
+use crate::files::{trivial_merge, MergeResult};
+use crate::gitattributes::{GitAttributesFile, MergeStrategy};
 use crate::matchers::{EverythingMatcher, Matcher};
 use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
 use crate::store::Store;
@@ -43,6 +45,15 @@ pub enum TreeMergeError {
     BackendError(#[from] BackendError),
 }
 
+impl From<TreeMergeError> for BackendError {
+    fn from(err: TreeMergeError) -> Self {
+        match err {
+            TreeMergeError::BackendError(err) => err,
+            err @ TreeMergeError::ReadError { .. } => BackendError::Other(err.to_string()),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Tree {
     store: Arc<Store>,
@@ -510,10 +521,72 @@ impl Iterator for TreeDiffIterator<'_> {
     }
 }
 
+/// Labels to record on the sides of any conflicts created by a merge.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ConflictLabels {
+    pub side1: Option<String>,
+    pub base: Option<String>,
+    pub side2: Option<String>,
+}
+
 pub fn merge_trees(
     side1_tree: &Tree,
     base_tree: &Tree,
     side2_tree: &Tree,
+) -> Result<TreeId, TreeMergeError> {
+    merge_trees_with_labels(
+        side1_tree,
+        base_tree,
+        side2_tree,
+        &ConflictLabels::default(),
+    )
+}
+
+/// Like `merge_trees()`, but attaches the given labels to the parts of any
+/// conflicts that are created.
+pub fn merge_trees_with_labels(
+    side1_tree: &Tree,
+    base_tree: &Tree,
+    side2_tree: &Tree,
+    labels: &ConflictLabels,
+) -> Result<TreeId, TreeMergeError> {
+    let (tree_id, _) =
+        merge_trees_reporting_resolutions(side1_tree, base_tree, side2_tree, labels)?;
+    Ok(tree_id)
+}
+
+/// Like `merge_trees_with_labels()`, but also returns the paths of the files
+/// whose conflicts were resolved with recorded resolutions.
+pub fn merge_trees_reporting_resolutions(
+    side1_tree: &Tree,
+    base_tree: &Tree,
+    side2_tree: &Tree,
+    labels: &ConflictLabels,
+) -> Result<(TreeId, Vec<RepoPath>), TreeMergeError> {
+    // The `.gitattributes` files on side 1 decide how files are merged
+    let context = MergeContext {
+        labels,
+        attributes_tree: side1_tree.dir().is_root().then(|| side1_tree),
+        reused_resolutions: RefCell::new(vec![]),
+    };
+    let tree_id = merge_trees_in_context(side1_tree, base_tree, side2_tree, &context)?;
+    Ok((tree_id, context.reused_resolutions.into_inner()))
+}
+
+/// State that's shared by the recursive merges of subtrees.
+struct MergeContext<'a> {
+    labels: &'a ConflictLabels,
+    /// The root tree to read `.gitattributes` files from
+    attributes_tree: Option<&'a Tree>,
+    /// The paths of the files that were resolved with recorded resolutions
+    reused_resolutions: RefCell<Vec<RepoPath>>,
+}
+
+fn merge_trees_in_context(
+    side1_tree: &Tree,
+    base_tree: &Tree,
+    side2_tree: &Tree,
+    context: &MergeContext,
 ) -> Result<TreeId, TreeMergeError> {
     let store = base_tree.store();
     let dir = base_tree.dir();
@@ -545,8 +618,15 @@ pub fn merge_trees(
             // value
         } else {
             // The two sides changed in different ways
-            let new_value =
-                merge_tree_value(store, dir, &basename, maybe_base, maybe_side1, maybe_side2)?;
+            let new_value = merge_tree_value(
+                store,
+                dir,
+                &basename,
+                maybe_base,
+                maybe_side1,
+                maybe_side2,
+                context,
+            )?;
             match new_value {
                 None => new_tree.remove(&basename),
                 Some(value) => new_tree.set(basename, value),
@@ -576,6 +656,7 @@ fn merge_tree_value(
     maybe_base: Option<&TreeValue>,
     maybe_side1: Option<&TreeValue>,
     maybe_side2: Option<&TreeValue>,
+    context: &MergeContext,
 ) -> Result<Option<TreeValue>, TreeMergeError> {
     // Resolve non-trivial conflicts:
     //   * resolve tree conflicts by recursing
@@ -593,7 +674,8 @@ fn merge_tree_value(
             let base_tree = store.get_tree(&subdir, base_id)?;
             let side1_tree = store.get_tree(&subdir, side1_id)?;
             let side2_tree = store.get_tree(&subdir, side2_id)?;
-            let merged_tree_id = merge_trees(&side1_tree, &base_tree, &side2_tree)?;
+            let merged_tree_id =
+                merge_trees_in_context(&side1_tree, &base_tree, &side2_tree, context)?;
             if merged_tree_id == *empty_tree_id {
                 None
             } else {
@@ -607,126 +689,248 @@ fn merge_tree_value(
             if let Some(base) = maybe_base {
                 conflict.removes.push(ConflictPart {
                     value: base.clone(),
+                    label: context.labels.base.clone(),
                 });
             }
             if let Some(side1) = maybe_side1 {
                 conflict.adds.push(ConflictPart {
                     value: side1.clone(),
+                    label: context.labels.side1.clone(),
                 });
             }
             if let Some(side2) = maybe_side2 {
                 conflict.adds.push(ConflictPart {
                     value: side2.clone(),
+                    label: context.labels.side2.clone(),
                 });
             }
             let filename = dir.join(basename);
             let conflict = simplify_conflict(store, &filename, &conflict)?;
-            if conflict.adds.is_empty() {
-                // If there are no values to add, then the path doesn't exist
-                return Ok(None);
-            }
-            if conflict.removes.is_empty() && conflict.adds.len() == 1 {
-                // A single add means that the current state is that state.
-                return Ok(Some(conflict.adds[0].value.clone()));
+            // If all sides ended up with the same value (including the absence of a
+            // value), then that's the result.
+            let (removed_values, added_values) = conflict_values(&conflict);
+            if let Some(value) = trivial_merge(&removed_values, &added_values) {
+                return Ok(value.cloned());
             }
-            if let Some((merged_content, executable)) =
-                try_resolve_file_conflict(store, &filename, &conflict)?
-            {
-                let id = store.write_file(&filename, &mut merged_content.as_slice())?;
-                Some(TreeValue::Normal { id, executable })
-            } else {
-                let conflict_id = store.write_conflict(&filename, &conflict)?;
-                Some(TreeValue::Conflict(conflict_id))
+            match try_resolve_file_conflict(store, &filename, &conflict, context)? {
+                Some(value) => value,
+                None => {
+                    let conflict = canonicalize_conflict(conflict);
+                    let conflict_id = store.write_conflict(&filename, &conflict)?;
+                    Some(TreeValue::Conflict(conflict_id))
+                }
             }
         }
     })
 }
 
+/// Sorts the parts of the conflict so that merging the same sides in any
+/// order results in the same conflict. The pairs of parts that cancel out have
+/// already been removed by `simplify_conflict()`.
+fn canonicalize_conflict(mut conflict: Conflict) -> Conflict {
+    conflict.removes.sort();
+    conflict.adds.sort();
+    conflict
+}
+
+/// Returns the values of the removes and the adds in the conflict. The lists
+/// are padded with `None` for the sides where the path is absent, so there's
+/// always one more add than removes.
+fn conflict_values(conflict: &Conflict) -> (Vec<Option<&TreeValue>>, Vec<Option<&TreeValue>>) {
+    let num_sides = std::cmp::max(conflict.adds.len(), conflict.removes.len() + 1);
+    let mut removed_values = conflict
+        .removes
+        .iter()
+        .map(|part| Some(&part.value))
+        .collect_vec();
+    removed_values.resize(num_sides - 1, None);
+    let mut added_values = conflict
+        .adds
+        .iter()
+        .map(|part| Some(&part.value))
+        .collect_vec();
+    added_values.resize(num_sides, None);
+    (removed_values, added_values)
+}
+
+/// Tries to resolve a conflict between files by merging the file contents and
+/// the executable bits separately. Returns `None` if the conflict can't be
+/// resolved, and `Some(None)` if it resolves to the file being deleted.
 fn try_resolve_file_conflict(
     store: &Store,
     filename: &RepoPath,
     conflict: &Conflict,
-) -> Result<Option<(Vec<u8>, bool)>, TreeMergeError> {
+    context: &MergeContext,
+) -> Result<Option<Option<TreeValue>>, TreeMergeError> {
     // If there are any non-file parts in the conflict, we can't merge it. We check
     // early so we don't waste time reading file contents if we can't merge them
-    // anyway. At the same time we determine whether the resulting file should
-    // be executable.
-    let mut exec_delta = 0;
-    let mut regular_delta = 0;
-    let mut removed_file_ids = vec![];
-    let mut added_file_ids = vec![];
-    for part in &conflict.removes {
-        match &part.value {
-            TreeValue::Normal { id, executable } => {
-                if *executable {
-                    exec_delta -= 1;
-                } else {
-                    regular_delta -= 1;
-                }
-                removed_file_ids.push(id.clone());
-            }
-            _ => {
-                return Ok(None);
-            }
+    // anyway.
+    let (removed_values, added_values) = conflict_values(conflict);
+    fn to_file<'a>(value: &Option<&'a TreeValue>) -> Option<Option<(&'a FileId, bool)>> {
+        match value {
+            Some(TreeValue::Normal { id, executable }) => Some(Some((id, *executable))),
+            Some(_) => None,
+            None => Some(None),
         }
     }
-    for part in &conflict.adds {
-        match &part.value {
-            TreeValue::Normal { id, executable } => {
-                if *executable {
-                    exec_delta += 1;
-                } else {
-                    regular_delta += 1;
-                }
-                added_file_ids.push(id.clone());
-            }
-            _ => {
-                return Ok(None);
-            }
-        }
+    let removed_files: Option<Vec<_>> = removed_values.iter().map(to_file).collect();
+    let added_files: Option<Vec<_>> = added_values.iter().map(to_file).collect();
+    let (removed_files, added_files) = match (removed_files, added_files) {
+        (Some(removed_files), Some(added_files)) => (removed_files, added_files),
+        _ => return Ok(None),
+    };
+
+    // The content and the executable bit are merged separately, so e.g. a change
+    // to the executable bit on one side doesn't conflict with a change to the
+    // content on the other side.
+    let removed_ids = removed_files
+        .iter()
+        .map(|file| file.map(|(id, _)| id))
+        .collect_vec();
+    let added_ids = added_files
+        .iter()
+        .map(|file| file.map(|(id, _)| id))
+        .collect_vec();
+    let merged_id = trivial_merge(&removed_ids, &added_ids);
+    if merged_id == Some(&None) {
+        // All sides that changed the file deleted it (e.g. one side deleted it and
+        // the other side only changed the executable bit)
+        return Ok(Some(None));
     }
-    let executable = if exec_delta > 0 && regular_delta <= 0 {
-        true
-    } else if regular_delta > 0 && exec_delta <= 0 {
-        false
-    } else {
+    let removed_executable = removed_files
+        .iter()
+        .map(|file| file.map(|(_, executable)| executable))
+        .collect_vec();
+    let added_executable = added_files
+        .iter()
+        .map(|file| file.map(|(_, executable)| executable))
+        .collect_vec();
+    let executable = match trivial_merge(&removed_executable, &added_executable) {
+        Some(Some(executable)) => *executable,
         // We're unable to determine whether the result should be executable
-        return Ok(None);
+        _ => return Ok(None),
+    };
+    if let Some(Some(id)) = merged_id {
+        return Ok(Some(Some(TreeValue::Normal {
+            id: (*id).clone(),
+            executable,
+        })));
+    }
+
+    // The content can only be merged line by line if the file is present on all
+    // sides.
+    let removed_file_ids: Option<Vec<_>> = removed_ids.into_iter().collect();
+    let added_file_ids: Option<Vec<_>> = added_ids.into_iter().collect();
+    let (removed_file_ids, added_file_ids) = match (removed_file_ids, added_file_ids) {
+        (Some(removed_file_ids), Some(added_file_ids)) => (removed_file_ids, added_file_ids),
+        _ => return Ok(None),
     };
     let mut removed_contents = vec![];
     let mut added_contents = vec![];
     for file_id in removed_file_ids {
-        let mut content = vec![];
-        store
-            .read_file(filename, &file_id)?
-            .read_to_end(&mut content)
-            .map_err(|err| TreeMergeError::ReadError {
-                source: err,
-                file_id,
-            })?;
-        removed_contents.push(content);
+        removed_contents.push(read_file_content(store, filename, file_id)?);
     }
     for file_id in added_file_ids {
-        let mut content = vec![];
-        store
-            .read_file(filename, &file_id)?
-            .read_to_end(&mut content)
-            .map_err(|err| TreeMergeError::ReadError {
-                source: err,
-                file_id,
-            })?;
-        added_contents.push(content);
-    }
-    let merge_result = files::merge(
-        &removed_contents.iter().map(Vec::as_slice).collect_vec(),
-        &added_contents.iter().map(Vec::as_slice).collect_vec(),
+        added_contents.push(read_file_content(store, filename, file_id)?);
+    }
+    let removed_slices = removed_contents.iter().map(Vec::as_slice).collect_vec();
+    let added_slices = added_contents.iter().map(Vec::as_slice).collect_vec();
+    let strategy = match context.attributes_tree {
+        Some(tree) => GitAttributesFile::for_path_in_tree(tree, filename)
+            .merge_strategy(&filename.to_internal_file_string()),
+        None => MergeStrategy::Default,
+    };
+    let merged_content = merge_file_contents(
+        store,
+        filename,
+        &strategy,
+        &removed_slices,
+        &added_slices,
+        context,
     );
-    match merge_result {
-        MergeResult::Resolved(merged_content) => Ok(Some((merged_content, executable))),
-        MergeResult::Conflict(_) => Ok(None),
+    match merged_content {
+        Some(merged_content) => {
+            let id = store.write_file(filename, &mut merged_content.as_slice())?;
+            Ok(Some(Some(TreeValue::Normal { id, executable })))
+        }
+        None => Ok(None),
     }
 }
 
+/// Merges the contents of a file according to its merge strategy. Returns
+/// `None` if there are conflicts left.
+fn merge_file_contents(
+    store: &Store,
+    filename: &RepoPath,
+    strategy: &MergeStrategy,
+    removes: &[&[u8]],
+    adds: &[&[u8]],
+    context: &MergeContext,
+) -> Option<Vec<u8>> {
+    let is_binary = match strategy {
+        MergeStrategy::Default => removes
+            .iter()
+            .chain(adds.iter())
+            .any(|content| files::is_binary(content)),
+        MergeStrategy::Binary => true,
+        _ => false,
+    };
+    if is_binary {
+        return files::merge_binary(removes, adds);
+    }
+    if let MergeStrategy::Driver(name) = strategy {
+        // Like Git, fall back to merging line by line if the driver is not
+        // configured. Drivers only support 3-way merges.
+        if let (Some(driver), [base], [left, right]) = (store.merge_driver(name), removes, adds) {
+            // If the driver fails to run, the file is left conflicted
+            return driver
+                .run(&filename.to_internal_file_string(), base, left, right)
+                .ok()
+                .flatten();
+        }
+    }
+    let hunks = match files::merge(removes, adds) {
+        MergeResult::Resolved(merged_content) => return Some(merged_content),
+        MergeResult::Conflict(hunks) => hunks,
+    };
+    match strategy {
+        MergeStrategy::Union => {
+            files::resolve_conflict_hunks(&hunks, |_, adds| Some(adds.concat()))
+        }
+        MergeStrategy::Ours => {
+            files::resolve_conflict_hunks(&hunks, |_, adds| adds.first().cloned())
+        }
+        MergeStrategy::Theirs => {
+            files::resolve_conflict_hunks(&hunks, |_, adds| adds.last().cloned())
+        }
+        // Use recorded resolutions of the conflicting hunks if there are any
+        _ => {
+            let merged_content = store.resolution_cache()?.resolve_hunks(&hunks)?;
+            context
+                .reused_resolutions
+                .borrow_mut()
+                .push(filename.clone());
+            Some(merged_content)
+        }
+    }
+}
+
+fn read_file_content(
+    store: &Store,
+    filename: &RepoPath,
+    file_id: &FileId,
+) -> Result<Vec<u8>, TreeMergeError> {
+    let mut content = vec![];
+    store
+        .read_file(filename, file_id)?
+        .read_to_end(&mut content)
+        .map_err(|err| TreeMergeError::ReadError {
+            source: err,
+            file_id: file_id.clone(),
+        })?;
+    Ok(content)
+}
+
 fn conflict_part_to_conflict(
     store: &Store,
     path: &RepoPath,
@@ -741,6 +945,7 @@ fn conflict_part_to_conflict(
             removes: vec![],
             adds: vec![ConflictPart {
                 value: other.clone(),
+                label: part.label.clone(),
             }],
         }),
     }
@@ -780,7 +985,13 @@ fn simplify_conflict(
     // {+D+B-A}, which is the desired conflict.
     //
     // Case 3 above:
-    // TODO: describe this case
+    // After rebasing B to C, the conflict in B' is {+B-A+C}. Rebasing D to B' gives
+    // D' with {+D-B+{+B-A+C}}, which is simplified to {+D-A+C} as in case 2. The
+    // user then resolves the conflict in B', producing B''. Rebasing D' onto B''
+    // gives the unsimplified conflict {+B''-{+B-A+C}+{+D-A+C}}. The negative
+    // inner conflict is moved into the outer conflict with its adds and removes
+    // swapped. We thus get {+B''-B+A-C+D-A+C}. After removing the add/remove
+    // pairs that cancel out, that's {+B''-B+D}, which is the desired conflict.
 
     // First expand any diffs with nested conflicts.
     let mut new_removes = vec![];
@@ -825,9 +1036,12 @@ fn simplify_conflict(
         }
     }
 
-    // TODO: We should probably remove duplicate entries here too. So if we have
-    // {+A+A}, that would become just {+A}. Similarly {+B-A+B} would be just
-    // {+B-A}.
+    // Duplicate entries are deliberately kept. Removing one of the adds in
+    // {+B-A+B} would leave {+B-A}, which breaks the invariant that a
+    // conflict has one more add than removes, and there's no remove that
+    // could go with it without changing what the conflict means. Conflicts
+    // like this one, where all sides end up with the same value, are
+    // instead resolved by `trivial_merge()`.
 
     Ok(Conflict {
         adds: new_adds,
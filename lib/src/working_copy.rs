@@ -34,7 +34,7 @@ use thiserror::Error;
 use crate::backend::{
     BackendError, ConflictId, FileId, MillisSinceEpoch, SymlinkId, TreeId, TreeValue,
 };
-use crate::conflicts::{materialize_conflict, update_conflict_from_content};
+use crate::conflicts::{materialize_conflict, update_conflict_from_content, ConflictMarkerStyle};
 use crate::gitignore::GitIgnoreFile;
 use crate::lock::FileLock;
 use crate::matchers::{DifferenceMatcher, Matcher, PrefixMatcher};
@@ -114,6 +114,7 @@ pub struct TreeState {
     // Currently only path prefixes
     sparse_patterns: Vec<RepoPath>,
     own_mtime: MillisSinceEpoch,
+    conflict_marker_style: ConflictMarkerStyle,
 }
 
 fn file_state_from_proto(proto: &crate::protos::working_copy::FileState) -> FileState {
@@ -340,6 +341,12 @@ impl TreeState {
         &self.sparse_patterns
     }
 
+    /// Sets the style of conflict markers to write when checking out conflicts.
+    /// Conflict markers in any style are recognized when snapshotting.
+    pub fn set_conflict_marker_style(&mut self, style: ConflictMarkerStyle) {
+        self.conflict_marker_style = style;
+    }
+
     fn sparse_matcher(&self) -> Box<dyn Matcher> {
         Box::new(PrefixMatcher::new(&self.sparse_patterns))
     }
@@ -362,6 +369,7 @@ impl TreeState {
             file_states: BTreeMap::new(),
             sparse_patterns: vec![RepoPath::root()],
             own_mtime: MillisSinceEpoch(0),
+            conflict_marker_style: ConflictMarkerStyle::default(),
         }
     }
 
@@ -467,6 +475,7 @@ impl TreeState {
             self.working_copy_path.clone(),
             base_ignores,
         )];
+        let current_tree = self.store.get_tree(&RepoPath::root(), &self.tree_id)?;
         let mut tree_builder = self.store.tree_builder(self.tree_id.clone());
         let mut deleted_files: HashSet<_> = self.file_states.keys().cloned().collect();
         while let Some((dir, disk_dir, git_ignore)) = work.pop() {
@@ -504,6 +513,7 @@ impl TreeState {
                             sub_path,
                             &entry,
                             git_ignore.as_ref(),
+                            &current_tree,
                             &mut tree_builder,
                         )?;
                     }
@@ -543,6 +553,7 @@ impl TreeState {
         repo_path: RepoPath,
         dir_entry: &DirEntry,
         git_ignore: &GitIgnoreFile,
+        current_tree: &Tree,
         tree_builder: &mut TreeBuilder,
     ) -> Result<(), SnapshotError> {
         let maybe_current_file_state = self.file_states.get_mut(&repo_path);
@@ -618,7 +629,9 @@ impl TreeState {
                                 self.store.as_ref(),
                                 &repo_path,
                                 id,
+                                Some(current_tree),
                                 &content,
+                                self.conflict_marker_style,
                             )
                             .unwrap()
                             {
@@ -731,6 +744,7 @@ impl TreeState {
         disk_path: &Path,
         path: &RepoPath,
         id: &ConflictId,
+        tree: &Tree,
     ) -> Result<FileState, CheckoutError> {
         create_parent_dirs(&self.working_copy_path, path)?;
         let conflict = self.store.read_conflict(path, id)?;
@@ -743,8 +757,15 @@ impl TreeState {
                 err,
             })?;
         let mut conflict_data = vec![];
-        materialize_conflict(self.store.as_ref(), path, &conflict, &mut conflict_data)
-            .expect("Failed to materialize conflict to in-memory buffer");
+        materialize_conflict(
+            self.store.as_ref(),
+            path,
+            &conflict,
+            Some(tree),
+            self.conflict_marker_style,
+            &mut conflict_data,
+        )
+        .expect("Failed to materialize conflict to in-memory buffer");
         file.write_all(&conflict_data)
             .map_err(|err| CheckoutError::IoError {
                 message: format!("Failed to write conflict to file {}", disk_path.display()),
@@ -853,7 +874,9 @@ impl TreeState {
                             self.write_file(&disk_path, &path, &id, executable)?
                         }
                         TreeValue::Symlink(id) => self.write_symlink(&disk_path, &path, &id)?,
-                        TreeValue::Conflict(id) => self.write_conflict(&disk_path, &path, &id)?,
+                        TreeValue::Conflict(id) => {
+                            self.write_conflict(&disk_path, &path, &id, new_tree)?
+                        }
                         TreeValue::GitSubmodule(_id) => {
                             println!("ignoring git submodule at {:?}", path);
                             return Ok(());
@@ -889,7 +912,7 @@ impl TreeState {
                             self.write_symlink(&disk_path, &path, &id)?
                         }
                         (_, TreeValue::Conflict(id)) => {
-                            self.write_conflict(&disk_path, &path, &id)?
+                            self.write_conflict(&disk_path, &path, &id, new_tree)?
                         }
                         (_, TreeValue::GitSubmodule(_id)) => {
                             println!("ignoring git submodule at {:?}", path);
@@ -962,6 +985,7 @@ pub struct WorkingCopy {
     operation_id: RefCell<Option<OperationId>>,
     workspace_id: RefCell<Option<WorkspaceId>>,
     tree_state: RefCell<Option<TreeState>>,
+    conflict_marker_style: ConflictMarkerStyle,
 }
 
 impl WorkingCopy {
@@ -991,6 +1015,7 @@ impl WorkingCopy {
             operation_id: RefCell::new(Some(operation_id)),
             workspace_id: RefCell::new(Some(workspace_id)),
             tree_state: RefCell::new(None),
+            conflict_marker_style: ConflictMarkerStyle::default(),
         }
     }
 
@@ -1002,6 +1027,7 @@ impl WorkingCopy {
             operation_id: RefCell::new(None),
             workspace_id: RefCell::new(None),
             tree_state: RefCell::new(None),
+            conflict_marker_style: ConflictMarkerStyle::default(),
         }
     }
 
@@ -1009,6 +1035,14 @@ impl WorkingCopy {
         &self.working_copy_path
     }
 
+    /// Sets the style of conflict markers to write when checking out conflicts.
+    pub fn set_conflict_marker_style(&mut self, style: ConflictMarkerStyle) {
+        self.conflict_marker_style = style;
+        if let Some(tree_state) = self.tree_state.get_mut() {
+            tree_state.set_conflict_marker_style(style);
+        }
+    }
+
     pub fn state_path(&self) -> &Path {
         &self.state_path
     }
@@ -1055,11 +1089,13 @@ impl WorkingCopy {
 
     fn tree_state(&self) -> RefMut<Option<TreeState>> {
         if self.tree_state.borrow().is_none() {
-            self.tree_state.replace(Some(TreeState::load(
+            let mut tree_state = TreeState::load(
                 self.store.clone(),
                 self.working_copy_path.clone(),
                 self.state_path.clone(),
-            )));
+            );
+            tree_state.set_conflict_marker_style(self.conflict_marker_style);
+            self.tree_state.replace(Some(tree_state));
         }
         self.tree_state.borrow_mut()
     }
@@ -19,7 +19,7 @@ use std::sync::Arc;
 
 use thiserror::Error;
 
-use crate::backend::Backend;
+use crate::backend::{Backend, BackendError};
 use crate::git_backend::GitBackend;
 use crate::local_backend::LocalBackend;
 use crate::op_store::WorkspaceId;
@@ -31,6 +31,8 @@ use crate::working_copy::WorkingCopy;
 pub enum WorkspaceInitError {
     #[error("The destination repo ({0}) already exists")]
     DestinationExists(PathBuf),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
 }
 
 #[derive(Error, Debug, PartialEq, Eq)]
@@ -67,7 +69,7 @@ fn init_working_copy(
     workspace_root: &Path,
     jj_dir: &Path,
     workspace_id: WorkspaceId,
-) -> (WorkingCopy, Arc<ReadonlyRepo>) {
+) -> Result<(WorkingCopy, Arc<ReadonlyRepo>), WorkspaceInitError> {
     let working_copy_state_path = jj_dir.join("working_copy");
     std::fs::create_dir(&working_copy_state_path).unwrap();
 
@@ -76,17 +78,18 @@ fn init_working_copy(
         workspace_id.clone(),
         user_settings,
         &repo.store().root_commit(),
-    );
+    )?;
     let repo = tx.commit();
 
-    let working_copy = WorkingCopy::init(
+    let mut working_copy = WorkingCopy::init(
         repo.store().clone(),
         workspace_root.to_path_buf(),
         working_copy_state_path,
         repo.op_id().clone(),
         workspace_id,
     );
-    (working_copy, repo)
+    working_copy.set_conflict_marker_style(user_settings.conflict_marker_style());
+    Ok((working_copy, repo))
 }
 
 impl Workspace {
@@ -146,7 +149,7 @@ impl Workspace {
             &workspace_root,
             &jj_dir,
             WorkspaceId::default(),
-        );
+        )?;
         let repo_loader = repo.loader();
         let workspace = Workspace::new(workspace_root, working_copy, repo_loader);
         Ok((workspace, repo))
@@ -168,7 +171,7 @@ impl Workspace {
 
         let repo_loader = RepoLoader::init(user_settings, repo_dir);
         let (working_copy, repo) =
-            init_working_copy(user_settings, repo, &workspace_root, &jj_dir, workspace_id);
+            init_working_copy(user_settings, repo, &workspace_root, &jj_dir, workspace_id)?;
         let workspace = Workspace::new(workspace_root, working_copy, repo_loader);
         Ok((workspace, repo))
     }
@@ -195,11 +198,12 @@ impl Workspace {
         }
         let repo_loader = RepoLoader::init(user_settings, repo_dir);
         let working_copy_state_path = jj_dir.join("working_copy");
-        let working_copy = WorkingCopy::load(
+        let mut working_copy = WorkingCopy::load(
             repo_loader.store().clone(),
             workspace_root.clone(),
             working_copy_state_path,
         );
+        working_copy.set_conflict_marker_style(user_settings.conflict_marker_style());
         Ok(Workspace::new(workspace_root, working_copy, repo_loader))
     }
 
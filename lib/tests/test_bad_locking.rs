@@ -103,7 +103,8 @@ fn test_bad_locking_children(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let initial = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![repo.store().root_commit_id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.commit();
 
     // Simulate a write of a commit that happens on one machine
@@ -118,7 +119,8 @@ fn test_bad_locking_children(use_git: bool) {
     let mut machine1_tx = machine1_repo.start_transaction("test");
     let child1 = testutils::create_random_commit(&settings, &machine1_repo)
         .set_parents(vec![initial.id().clone()])
-        .write_to_repo(machine1_tx.mut_repo());
+        .write_to_repo(machine1_tx.mut_repo())
+        .unwrap();
     machine1_tx.commit();
 
     // Simulate a write of a commit that happens on another machine
@@ -133,7 +135,8 @@ fn test_bad_locking_children(use_git: bool) {
     let mut machine2_tx = machine2_repo.start_transaction("test");
     let child2 = testutils::create_random_commit(&settings, &machine2_repo)
         .set_parents(vec![initial.id().clone()])
-        .write_to_repo(machine2_tx.mut_repo());
+        .write_to_repo(machine2_tx.mut_repo())
+        .unwrap();
     machine2_tx.commit();
 
     // Simulate that the distributed file system now has received the changes from
@@ -166,7 +169,8 @@ fn test_bad_locking_interrupted(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let initial = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![repo.store().root_commit_id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     // Simulate a crash that resulted in the old op-head left in place. We simulate
@@ -179,7 +183,8 @@ fn test_bad_locking_interrupted(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     testutils::create_random_commit(&settings, &repo)
         .set_parents(vec![initial.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let op_id = tx.commit().operation().id().clone();
 
     copy_directory(&backup_path, &op_heads_dir);
@@ -0,0 +1,241 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jujutsu_lib::backend::CommitId;
+use jujutsu_lib::bisect::{BisectState, BisectionStep};
+use jujutsu_lib::commit::Commit;
+use jujutsu_lib::repo::MutableRepo;
+use jujutsu_lib::settings::UserSettings;
+use jujutsu_lib::testutils;
+use jujutsu_lib::testutils::{create_random_commit, TestRepo};
+use tempfile::TempDir;
+use test_case::test_case;
+
+fn write_commit(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    parents: &[&Commit],
+) -> Commit {
+    create_random_commit(settings, mut_repo.base_repo())
+        .set_parents(parents.iter().map(|commit| commit.id().clone()).collect())
+        .write_to_repo(mut_repo)
+        .unwrap()
+}
+
+fn test_step(commit: &Commit, num_untested: usize) -> BisectionStep {
+    BisectionStep::Test {
+        commit_id: commit.id().clone(),
+        num_untested,
+    }
+}
+
+fn ids(commits: &[&Commit]) -> Vec<CommitId> {
+    commits.iter().map(|commit| commit.id().clone()).collect()
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_bisect_linear(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let root_commit = mut_repo.store().root_commit();
+    let mut commits = vec![root_commit];
+    for _ in 0..8 {
+        let commit = write_commit(&settings, mut_repo, &[commits.last().unwrap()]);
+        commits.push(commit);
+    }
+
+    let mut state = BisectState::default();
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::NeedsBad
+    );
+    state.mark_bad(commits[8].id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::NeedsGood
+    );
+    state.mark_good(commits[0].id().clone());
+    // The first bad commit is commits[3]
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        test_step(&commits[4], 7)
+    );
+    state.mark_bad(commits[4].id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        test_step(&commits[2], 3)
+    );
+    state.mark_good(commits[2].id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        test_step(&commits[3], 1)
+    );
+    state.mark_bad(commits[3].id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::Found(commits[3].id().clone())
+    );
+
+    // A bad commit that's an ancestor of a good commit is an error
+    state.mark_good(commits[5].id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::BadIsAncestorOfGood
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_bisect_skipped(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let root_commit = mut_repo.store().root_commit();
+    let commit1 = write_commit(&settings, mut_repo, &[&root_commit]);
+    let commit2 = write_commit(&settings, mut_repo, &[&commit1]);
+    let commit3 = write_commit(&settings, mut_repo, &[&commit2]);
+    let commit4 = write_commit(&settings, mut_repo, &[&commit3]);
+
+    let mut state = BisectState::default();
+    state.mark_good(commit1.id().clone());
+    state.mark_bad(commit4.id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        test_step(&commit3, 2)
+    );
+    // Another commit is picked when the best one is skipped
+    state.mark_skipped(commit3.id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        test_step(&commit2, 1)
+    );
+    // When only skipped commits are left, the result is ambiguous
+    state.mark_skipped(commit2.id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::Indeterminate(ids(&[&commit4, &commit3, &commit2]))
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_bisect_merge(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    // D
+    // |\
+    // B C
+    // |/
+    // A
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let root_commit = mut_repo.store().root_commit();
+    let commit_a = write_commit(&settings, mut_repo, &[&root_commit]);
+    let commit_b = write_commit(&settings, mut_repo, &[&commit_a]);
+    let commit_c = write_commit(&settings, mut_repo, &[&commit_a]);
+    let commit_d = write_commit(&settings, mut_repo, &[&commit_b, &commit_c]);
+
+    // Marking a merge's parent good doesn't make the other parent good
+    let mut state = BisectState::default();
+    state.mark_good(commit_a.id().clone());
+    state.mark_bad(commit_d.id().clone());
+    state.mark_good(commit_b.id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        test_step(&commit_c, 1)
+    );
+    state.mark_good(commit_c.id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::Found(commit_d.id().clone())
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_bisect_unknown_revision(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let root_commit = mut_repo.store().root_commit();
+    let commit = write_commit(&settings, mut_repo, &[&root_commit]);
+    // A well-formed id of a commit that doesn't exist, e.g. because the
+    // operation that added it was undone
+    let unknown_id = CommitId::new(vec![0xab; commit.id().as_bytes().len()]);
+
+    let mut state = BisectState::default();
+    state.mark_bad(commit.id().clone());
+    state.mark_good(unknown_id.clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::UnknownRevision(unknown_id.clone())
+    );
+
+    let mut state = BisectState::default();
+    state.mark_bad(unknown_id.clone());
+    state.mark_good(root_commit.id().clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::UnknownRevision(unknown_id.clone())
+    );
+
+    let mut state = BisectState::default();
+    state.mark_bad(commit.id().clone());
+    state.mark_good(root_commit.id().clone());
+    state.mark_skipped(unknown_id.clone());
+    assert_eq!(
+        state.next_step(mut_repo.index().as_index_ref()),
+        BisectionStep::UnknownRevision(unknown_id)
+    );
+}
+
+#[test]
+fn test_bisect_state_save_load() {
+    let temp_dir = TempDir::new().unwrap();
+    assert_eq!(BisectState::load(temp_dir.path()).unwrap(), None);
+
+    let mut state = BisectState::default();
+    state.mark_bad(CommitId::from_hex("aa"));
+    state.mark_good(CommitId::from_hex("bb"));
+    state.mark_good(CommitId::from_hex("cc"));
+    state.mark_skipped(CommitId::from_hex("dd"));
+    state.current = Some(CommitId::from_hex("ee"));
+    state.save(temp_dir.path()).unwrap();
+    assert_eq!(BisectState::load(temp_dir.path()).unwrap(), Some(state));
+
+    BisectState::remove(temp_dir.path()).unwrap();
+    assert_eq!(BisectState::load(temp_dir.path()).unwrap(), None);
+
+    // A corrupted state file is an error
+    for content in ["bad\n", "bad zz\n", "unknown aa\n"] {
+        std::fs::write(temp_dir.path().join("bisect"), content).unwrap();
+        assert_eq!(
+            BisectState::load(temp_dir.path()).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -42,7 +42,8 @@ fn test_initial(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let commit = CommitBuilder::for_new_commit(&settings, tree.id().clone())
         .set_parents(vec![store.root_commit_id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.commit();
 
     assert_eq!(commit.parents(), vec![store.root_commit()]);
@@ -87,7 +88,8 @@ fn test_rewrite(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let initial_commit = CommitBuilder::for_new_commit(&settings, initial_tree.id().clone())
         .set_parents(vec![store.root_commit_id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let rewritten_tree = testutils::create_tree(
@@ -109,7 +111,8 @@ fn test_rewrite(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let rewritten_commit = CommitBuilder::for_rewrite_from(&rewrite_settings, &initial_commit)
         .set_tree(rewritten_tree.id().clone())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     tx.commit();
     assert_eq!(rewritten_commit.parents(), vec![store.root_commit()]);
@@ -164,7 +167,8 @@ fn test_rewrite_update_missing_user(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let initial_commit =
         CommitBuilder::for_new_commit(&missing_user_settings, repo.store().empty_tree_id().clone())
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())
+            .unwrap();
     assert_eq!(initial_commit.author().name, "(no name configured)");
     assert_eq!(initial_commit.author().email, "(no email configured)");
     assert_eq!(initial_commit.committer().name, "(no name configured)");
@@ -178,8 +182,9 @@ fn test_rewrite_update_missing_user(use_git: bool) {
         .build()
         .unwrap();
     let settings = UserSettings::from_config(config);
-    let rewritten_commit =
-        CommitBuilder::for_rewrite_from(&settings, &initial_commit).write_to_repo(tx.mut_repo());
+    let rewritten_commit = CommitBuilder::for_rewrite_from(&settings, &initial_commit)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
 
     assert_eq!(rewritten_commit.author().name, "Configured User");
     assert_eq!(
@@ -211,7 +216,8 @@ fn test_commit_builder_descendants(use_git: bool) {
     // Test with for_new_commit()
     let mut tx = repo.start_transaction("test");
     CommitBuilder::for_new_commit(&settings, store.empty_tree_id().clone())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
     assert!(rebaser.rebase_next().unwrap().is_none());
 
@@ -222,13 +228,16 @@ fn test_commit_builder_descendants(use_git: bool) {
         commit2.id().clone(),
         store.empty_tree_id().clone(),
     )
-    .write_to_repo(tx.mut_repo());
+    .write_to_repo(tx.mut_repo())
+    .unwrap();
     let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
     assert!(rebaser.rebase_next().unwrap().is_none());
 
     // Test with for_rewrite_from()
     let mut tx = repo.start_transaction("test");
-    let commit4 = CommitBuilder::for_rewrite_from(&settings, &commit2).write_to_repo(tx.mut_repo());
+    let commit4 = CommitBuilder::for_rewrite_from(&settings, &commit2)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
     assert_rebased(rebaser.rebase_next().unwrap(), &commit3, &[&commit4]);
     assert!(rebaser.rebase_next().unwrap().is_none());
@@ -237,7 +246,36 @@ fn test_commit_builder_descendants(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     CommitBuilder::for_rewrite_from(&settings, &commit2)
         .generate_new_change_id()
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
     assert!(rebaser.rebase_next().unwrap().is_none());
 }
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_copied_from(use_git: bool) {
+    // The commit a commit was copied from is stored in the backend
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let mut tx = repo.start_transaction("test");
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let original = graph_builder.initial_commit();
+    let copy = CommitBuilder::for_rewrite_from(&settings, &original)
+        .generate_new_change_id()
+        .set_predecessors(vec![])
+        .set_copied_from(Some(original.id().clone()))
+        // Make sure the Git commit is different from the original
+        .set_description("copy".to_string())
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    tx.commit();
+
+    let copy = store.get_commit(copy.id()).unwrap();
+    assert_eq!(copy.copied_from(), Some(original.id()));
+    assert_eq!(copy.predecessor_ids(), vec![]);
+    assert_eq!(original.copied_from(), None);
+}
@@ -54,7 +54,9 @@ fn test_commit_parallel(use_git: bool) {
         let repo = repo.clone();
         let handle = thread::spawn(move || {
             let mut tx = repo.start_transaction("test");
-            testutils::create_random_commit(&settings, &repo).write_to_repo(tx.mut_repo());
+            testutils::create_random_commit(&settings, &repo)
+                .write_to_repo(tx.mut_repo())
+                .unwrap();
             tx.commit();
         });
         threads.push(handle);
@@ -88,7 +90,9 @@ fn test_commit_parallel_instances(use_git: bool) {
         let repo = ReadonlyRepo::load_at_head(&settings, repo.repo_path().clone()).unwrap();
         let handle = thread::spawn(move || {
             let mut tx = repo.start_transaction("test");
-            testutils::create_random_commit(&settings, &repo).write_to_repo(tx.mut_repo());
+            testutils::create_random_commit(&settings, &repo)
+                .write_to_repo(tx.mut_repo())
+                .unwrap();
             tx.commit();
         });
         threads.push(handle);
@@ -12,13 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jujutsu_lib::backend::{Conflict, ConflictPart, TreeValue};
-use jujutsu_lib::conflicts::{materialize_conflict, parse_conflict, update_conflict_from_content};
+use jujutsu_lib::backend::{Conflict, ConflictPart, FileId, TreeValue};
+use jujutsu_lib::conflicts::{
+    materialize_conflict, parse_conflict, update_conflict_from_content, ConflictMarkerStyle,
+};
 use jujutsu_lib::files::MergeHunk;
 use jujutsu_lib::repo_path::RepoPath;
 use jujutsu_lib::store::Store;
 use jujutsu_lib::testutils;
 use jujutsu_lib::testutils::TestRepo;
+use test_case::test_case;
 
 #[test]
 fn test_materialize_conflict_basic() {
@@ -65,6 +68,7 @@ line 5
                 id: base_id,
                 executable: false,
             },
+            label: None,
         }],
         adds: vec![
             ConflictPart {
@@ -72,12 +76,14 @@ line 5
                     id: left_id,
                     executable: false,
                 },
+                label: None,
             },
             ConflictPart {
                 value: TreeValue::Normal {
                     id: right_id,
                     executable: false,
                 },
+                label: None,
             },
         ],
     };
@@ -123,6 +129,184 @@ line 5
     );
 }
 
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_materialize_conflict_with_labels(use_git: bool) {
+    let test_repo = TestRepo::init(use_git);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, &path, "line 1\nline 2\nline 3\n");
+    let left_id = testutils::write_file(store, &path, "line 1\nleft 2\nline 3\n");
+    let right_id = testutils::write_file(store, &path, "line 1\nright 2\nline 3\n");
+    let conflict = Conflict {
+        removes: vec![ConflictPart {
+            value: TreeValue::Normal {
+                id: base_id,
+                executable: false,
+            },
+            label: Some("base".to_string()),
+        }],
+        adds: vec![
+            ConflictPart {
+                value: TreeValue::Normal {
+                    id: left_id,
+                    executable: false,
+                },
+                label: Some("left".to_string()),
+            },
+            ConflictPart {
+                value: TreeValue::Normal {
+                    id: right_id,
+                    executable: false,
+                },
+                label: None,
+            },
+        ],
+    };
+    // The labels survive a round-trip through the backend
+    let conflict_id = store.write_conflict(&path, &conflict).unwrap();
+    assert_eq!(store.read_conflict(&path, &conflict_id).unwrap(), conflict);
+
+    let materialized = materialize_conflict_string(store, &path, &conflict);
+    assert_eq!(
+        materialized,
+        "line 1
+<<<<<<<
+------- base
++++++++ left
+-line 2
++left 2
++++++++
+right 2
+>>>>>>>
+line 3
+"
+    );
+
+    // The labeled markers can be parsed back, and the labels are kept when
+    // the conflict is updated
+    let edited = materialized.replace("+left 2", "+edited 2");
+    let new_conflict_id = update_conflict_from_content(
+        store,
+        &path,
+        &conflict_id,
+        None,
+        edited.as_bytes(),
+        ConflictMarkerStyle::Diff,
+    )
+    .unwrap()
+    .unwrap();
+    let new_conflict = store.read_conflict(&path, &new_conflict_id).unwrap();
+    assert_eq!(
+        new_conflict
+            .removes
+            .iter()
+            .chain(new_conflict.adds.iter())
+            .map(|part| part.label.as_deref())
+            .collect::<Vec<_>>(),
+        vec![Some("base"), Some("left"), None]
+    );
+    let expected_left_id = testutils::write_file(store, &path, "line 1\nedited 2\nline 3\n");
+    assert_eq!(
+        new_conflict.adds[0].value,
+        TreeValue::Normal {
+            id: expected_left_id,
+            executable: false
+        }
+    );
+}
+
+#[test]
+fn test_materialize_conflict_styles() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, &path, "line 1\nline 2\nline 3\n");
+    let left_id = testutils::write_file(store, &path, "line 1\nleft 2\nline 3\n");
+    let right_id = testutils::write_file(store, &path, "line 1\nright 2\nline 3\n");
+    let conflict = Conflict {
+        removes: vec![ConflictPart {
+            value: TreeValue::Normal {
+                id: base_id,
+                executable: false,
+            },
+            label: Some("base".to_string()),
+        }],
+        adds: vec![
+            ConflictPart {
+                value: TreeValue::Normal {
+                    id: left_id,
+                    executable: false,
+                },
+                label: Some("left".to_string()),
+            },
+            ConflictPart {
+                value: TreeValue::Normal {
+                    id: right_id,
+                    executable: false,
+                },
+                label: Some("right".to_string()),
+            },
+        ],
+    };
+    insta::assert_snapshot!(
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Snapshot),
+        @r###"
+    line 1
+    <<<<<<<
+    +++++++ left
+    left 2
+    ------- base
+    line 2
+    +++++++ right
+    right 2
+    >>>>>>>
+    line 3
+    "###
+    );
+    insta::assert_snapshot!(
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git),
+        @r###"
+    line 1
+    <<<<<<< left
+    left 2
+    ||||||| base
+    line 2
+    =======
+    right 2
+    >>>>>>> right
+    line 3
+    "###
+    );
+
+    // Conflicts with more than two sides can't be represented in Git's style, so
+    // they're written in the snapshot style
+    let mut conflict = conflict;
+    conflict.removes.push(conflict.removes[0].clone());
+    conflict.adds.push(conflict.adds[0].clone());
+    insta::assert_snapshot!(
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git),
+        @r###"
+    line 1
+    <<<<<<<
+    +++++++ left
+    left 2
+    ------- base
+    line 2
+    +++++++ right
+    right 2
+    ------- base
+    line 2
+    +++++++ left
+    left 2
+    >>>>>>>
+    line 3
+    "###
+    );
+}
+
 #[test]
 fn test_materialize_conflict_modify_delete() {
     let test_repo = TestRepo::init(false);
@@ -165,6 +349,7 @@ line 5
                 id: base_id,
                 executable: false,
             },
+            label: None,
         }],
         adds: vec![
             ConflictPart {
@@ -172,12 +357,14 @@ line 5
                     id: left_id,
                     executable: false,
                 },
+                label: None,
             },
             ConflictPart {
                 value: TreeValue::Normal {
                     id: right_id,
                     executable: false,
                 },
+                label: None,
             },
         ],
     };
@@ -239,6 +426,7 @@ line 5
                 id: base_id,
                 executable: false,
             },
+            label: None,
         }],
         adds: vec![
             ConflictPart {
@@ -246,12 +434,14 @@ line 5
                     id: left_id,
                     executable: false,
                 },
+                label: None,
             },
             ConflictPart {
                 value: TreeValue::Normal {
                     id: right_id,
                     executable: false,
                 },
+                label: None,
             },
         ],
     };
@@ -274,6 +464,139 @@ line 5
     );
 }
 
+#[test]
+fn test_materialize_conflict_binary() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, &path, "line 1\0\nline 2\n");
+    let left_id = testutils::write_file(store, &path, "left 1\0\nline 2\n");
+    let right_id = testutils::write_file(store, &path, "line 1\0\nright 2\n");
+
+    let conflict = Conflict {
+        removes: vec![ConflictPart {
+            value: TreeValue::Normal {
+                id: base_id.clone(),
+                executable: false,
+            },
+            label: None,
+        }],
+        adds: vec![
+            ConflictPart {
+                value: TreeValue::Normal {
+                    id: left_id.clone(),
+                    executable: false,
+                },
+                label: None,
+            },
+            ConflictPart {
+                value: TreeValue::Normal {
+                    id: right_id.clone(),
+                    executable: false,
+                },
+                label: None,
+            },
+        ],
+    };
+
+    // No conflict markers are written into binary files. The conflict is
+    // described instead.
+    assert_eq!(
+        materialize_conflict_string(store, &path, &conflict),
+        format!(
+            "Conflict in binary file:
+  Removing file with id {}
+  Adding file with id {} (side #1)
+  Adding file with id {} (side #2)
+",
+            base_id.hex(),
+            left_id.hex(),
+            right_id.hex()
+        )
+    );
+}
+
+#[test]
+fn test_materialize_conflict_binary_attribute() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file.dat");
+    let base_id = testutils::write_file(store, &path, "line 1\nline 2\n");
+    let left_id = testutils::write_file(store, &path, "left 1\nline 2\n");
+    let right_id = testutils::write_file(store, &path, "line 1\nright 2\n");
+    let part = |id: &FileId| ConflictPart {
+        value: TreeValue::Normal {
+            id: id.clone(),
+            executable: false,
+        },
+        label: None,
+    };
+    let conflict = Conflict {
+        removes: vec![part(&base_id)],
+        adds: vec![part(&left_id), part(&right_id)],
+    };
+
+    // Text content is treated as binary if the attributes say so
+    let attributes_path = RepoPath::from_internal_string(".gitattributes");
+    let tree = testutils::create_tree(&test_repo.repo, &[(&attributes_path, "*.dat binary\n")]);
+    let mut materialized = vec![];
+    materialize_conflict(
+        store,
+        &path,
+        &conflict,
+        Some(&tree),
+        ConflictMarkerStyle::Diff,
+        &mut materialized,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(materialized).unwrap(),
+        format!(
+            "Conflict in binary file:
+  Removing file with id {}
+  Adding file with id {} (side #1)
+  Adding file with id {} (side #2)
+",
+            base_id.hex(),
+            left_id.hex(),
+            right_id.hex()
+        )
+    );
+
+    // Same thing with the "text" attribute unset
+    let tree = testutils::create_tree(&test_repo.repo, &[(&attributes_path, "*.dat -text\n")]);
+    let mut materialized = vec![];
+    materialize_conflict(
+        store,
+        &path,
+        &conflict,
+        Some(&tree),
+        ConflictMarkerStyle::Diff,
+        &mut materialized,
+    )
+    .unwrap();
+    assert!(String::from_utf8(materialized)
+        .unwrap()
+        .starts_with("Conflict in binary file:"));
+
+    // Conflict markers are used if the attributes say the file is text
+    let tree = testutils::create_tree(&test_repo.repo, &[(&attributes_path, "*.dat text\n")]);
+    let materialized_text = materialize_conflict_string(store, &path, &conflict);
+    let mut materialized = vec![];
+    materialize_conflict(
+        store,
+        &path,
+        &conflict,
+        Some(&tree),
+        ConflictMarkerStyle::Diff,
+        &mut materialized,
+    )
+    .unwrap();
+    assert_eq!(String::from_utf8(materialized).unwrap(), materialized_text);
+}
+
 #[test]
 fn test_parse_conflict_resolved() {
     assert_eq!(
@@ -322,6 +645,105 @@ line 5
     )
 }
 
+#[test]
+fn test_parse_conflict_with_labels() {
+    assert_eq!(
+        parse_conflict(
+            b"line 1
+<<<<<<<
+------- 012345abcdef \"base\"
++++++++ 6789abcdef01 \"left\"
+-line 2
++left
++++++++ right
+right
+>>>>>>>
+line 3
+",
+            1,
+            2
+        ),
+        Some(vec![
+            MergeHunk::Resolved(b"line 1\n".to_vec()),
+            MergeHunk::Conflict {
+                removes: vec![b"line 2\n".to_vec()],
+                adds: vec![b"left\n".to_vec(), b"right\n".to_vec()]
+            },
+            MergeHunk::Resolved(b"line 3\n".to_vec())
+        ])
+    )
+}
+
+#[test]
+fn test_parse_conflict_snapshot_style() {
+    assert_eq!(
+        parse_conflict(
+            b"line 1
+<<<<<<<
++++++++ left
+left
+------- base
++++++++ right
+right
+>>>>>>>
+line 3
+",
+            1,
+            2
+        ),
+        Some(vec![
+            MergeHunk::Resolved(b"line 1\n".to_vec()),
+            MergeHunk::Conflict {
+                removes: vec![b"".to_vec()],
+                adds: vec![b"left\n".to_vec(), b"right\n".to_vec()]
+            },
+            MergeHunk::Resolved(b"line 3\n".to_vec())
+        ])
+    )
+}
+
+#[test]
+fn test_parse_conflict_git_style() {
+    assert_eq!(
+        parse_conflict(
+            b"line 1
+<<<<<<< left
+left
+||||||| base
+line 2
+=======
+right
+>>>>>>> right
+line 3
+",
+            1,
+            2
+        ),
+        Some(vec![
+            MergeHunk::Resolved(b"line 1\n".to_vec()),
+            MergeHunk::Conflict {
+                removes: vec![b"line 2\n".to_vec()],
+                adds: vec![b"left\n".to_vec(), b"right\n".to_vec()]
+            },
+            MergeHunk::Resolved(b"line 3\n".to_vec())
+        ])
+    );
+    // Git's "merge" style without the base is not supported
+    assert_eq!(
+        parse_conflict(
+            b"<<<<<<< left
+left
+=======
+right
+>>>>>>> right
+",
+            1,
+            2
+        ),
+        None
+    );
+}
+
 #[test]
 fn test_parse_conflict_multi_way() {
     assert_eq!(
@@ -454,6 +876,7 @@ fn test_update_conflict_from_content() {
                 id: base_file_id,
                 executable: false,
             },
+            label: None,
         }],
         adds: vec![
             ConflictPart {
@@ -461,12 +884,14 @@ fn test_update_conflict_from_content() {
                     id: left_file_id,
                     executable: false,
                 },
+                label: None,
             },
             ConflictPart {
                 value: TreeValue::Normal {
                     id: right_file_id,
                     executable: false,
                 },
+                label: None,
             },
         ],
     };
@@ -475,8 +900,24 @@ fn test_update_conflict_from_content() {
     // If the content is unchanged compared to the materialized value, we get the
     // old conflict id back.
     let mut materialized = vec![];
-    materialize_conflict(store, &path, &conflict, &mut materialized).unwrap();
-    let result = update_conflict_from_content(store, &path, &conflict_id, &materialized).unwrap();
+    materialize_conflict(
+        store,
+        &path,
+        &conflict,
+        None,
+        ConflictMarkerStyle::Diff,
+        &mut materialized,
+    )
+    .unwrap();
+    let result = update_conflict_from_content(
+        store,
+        &path,
+        &conflict_id,
+        None,
+        &materialized,
+        ConflictMarkerStyle::Diff,
+    )
+    .unwrap();
     assert_eq!(result, Some(conflict_id.clone()));
 
     // If the conflict is resolved, we None back to indicate that.
@@ -484,13 +925,15 @@ fn test_update_conflict_from_content() {
         store,
         &path,
         &conflict_id,
+        None,
         b"resolved 1\nline 2\nresolved 3\n",
+        ConflictMarkerStyle::Diff,
     )
     .unwrap();
     assert_eq!(result, None);
 
     // If the conflict is partially resolved, we get a new conflict back.
-    let result = update_conflict_from_content(store, &path, &conflict_id, b"resolved 1\nline 2\n<<<<<<<\n-------\n+++++++\n-line 3\n+left 3\n+++++++\nright 3\n>>>>>>>\n").unwrap();
+    let result = update_conflict_from_content(store, &path, &conflict_id, None, b"resolved 1\nline 2\n<<<<<<<\n-------\n+++++++\n-line 3\n+left 3\n+++++++\nright 3\n>>>>>>>\n", ConflictMarkerStyle::Diff).unwrap();
     assert_ne!(result, None);
     assert_ne!(result, Some(conflict_id));
     let new_conflict = store.read_conflict(&path, &result.unwrap()).unwrap();
@@ -505,28 +948,109 @@ fn test_update_conflict_from_content() {
                 value: TreeValue::Normal {
                     id: new_base_file_id,
                     executable: false
-                }
+                },
+                label: None
             }],
             adds: vec![
                 ConflictPart {
                     value: TreeValue::Normal {
                         id: new_left_file_id,
                         executable: false
-                    }
+                    },
+                    label: None
                 },
                 ConflictPart {
                     value: TreeValue::Normal {
                         id: new_right_file_id,
                         executable: false
-                    }
+                    },
+                    label: None
                 }
             ]
         }
     )
 }
 
+#[test_case(ConflictMarkerStyle::Diff ; "diff")]
+#[test_case(ConflictMarkerStyle::Snapshot ; "snapshot")]
+#[test_case(ConflictMarkerStyle::Git ; "git")]
+fn test_update_conflict_from_content_styles(style: ConflictMarkerStyle) {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_file_id = testutils::write_file(store, &path, "line 1\nline 2\nline 3\n");
+    let left_file_id = testutils::write_file(store, &path, "left 1\nline 2\nleft 3\n");
+    let right_file_id = testutils::write_file(store, &path, "right 1\nline 2\nright 3\n");
+    let part = |id| ConflictPart {
+        value: TreeValue::Normal {
+            id,
+            executable: false,
+        },
+        label: None,
+    };
+    let conflict = Conflict {
+        removes: vec![part(base_file_id)],
+        adds: vec![part(left_file_id), part(right_file_id)],
+    };
+    let conflict_id = store.write_conflict(&path, &conflict).unwrap();
+
+    // Unchanged content gives the same conflict back
+    let materialized = materialize_conflict_string_with_style(store, &path, &conflict, style);
+    let result = update_conflict_from_content(
+        store,
+        &path,
+        &conflict_id,
+        None,
+        materialized.as_bytes(),
+        style,
+    )
+    .unwrap();
+    assert_eq!(result, Some(conflict_id.clone()));
+
+    // Resolving the first hunk in the materialized content gives a conflict with
+    // that hunk resolved in all parts
+    let (first_hunk, rest) = materialized.split_once("line 2\n").unwrap();
+    assert!(first_hunk.starts_with("<<<<<<<"));
+    let edited = format!("resolved 1\nline 2\n{}", rest);
+    let new_conflict_id =
+        update_conflict_from_content(store, &path, &conflict_id, None, edited.as_bytes(), style)
+            .unwrap()
+            .unwrap();
+    let new_conflict = store.read_conflict(&path, &new_conflict_id).unwrap();
+    let expected_conflict = Conflict {
+        removes: vec![part(testutils::write_file(
+            store,
+            &path,
+            "resolved 1\nline 2\nline 3\n",
+        ))],
+        adds: vec![
+            part(testutils::write_file(
+                store,
+                &path,
+                "resolved 1\nline 2\nleft 3\n",
+            )),
+            part(testutils::write_file(
+                store,
+                &path,
+                "resolved 1\nline 2\nright 3\n",
+            )),
+        ],
+    };
+    assert_eq!(new_conflict, expected_conflict);
+}
+
 fn materialize_conflict_string(store: &Store, path: &RepoPath, conflict: &Conflict) -> String {
+    materialize_conflict_string_with_style(store, path, conflict, ConflictMarkerStyle::Diff)
+}
+
+fn materialize_conflict_string_with_style(
+    store: &Store,
+    path: &RepoPath,
+    conflict: &Conflict,
+    style: ConflictMarkerStyle,
+) -> String {
     let mut result: Vec<u8> = vec![];
-    materialize_conflict(store, path, conflict, &mut result).unwrap();
+    materialize_conflict(store, path, conflict, None, style, &mut result).unwrap();
     String::from_utf8(result).unwrap()
 }
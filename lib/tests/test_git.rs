@@ -172,7 +172,8 @@ fn test_import_refs_reimport() {
     let mut tx = repo.start_transaction("test");
     let commit6 = create_random_commit(&settings, &repo)
         .set_parents(vec![commit_id(&commit2)])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().set_local_branch(
         "feature2".to_string(),
         RefTarget::Normal(commit6.id().clone()),
@@ -415,7 +416,8 @@ fn test_export_refs_branch_changed() {
     let mut tx = test_data.repo.start_transaction("test");
     let new_commit = testutils::create_random_commit(&test_data.settings, &test_data.repo)
         .set_parents(vec![CommitId::from_bytes(commit.id().as_bytes())])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().set_local_branch(
         "main".to_string(),
         RefTarget::Normal(new_commit.id().clone()),
@@ -453,7 +455,8 @@ fn test_export_refs_current_branch_changed() {
     let mut tx = test_data.repo.start_transaction("test");
     let new_commit = testutils::create_random_commit(&test_data.settings, &test_data.repo)
         .set_parents(vec![CommitId::from_bytes(commit1.id().as_bytes())])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().set_local_branch(
         "main".to_string(),
         RefTarget::Normal(new_commit.id().clone()),
@@ -488,7 +491,8 @@ fn test_export_refs_unborn_git_branch() {
     assert_eq!(git::export_refs(&test_data.repo, &git_repo), Ok(()));
     let mut tx = test_data.repo.start_transaction("test");
     let new_commit = testutils::create_random_commit(&test_data.settings, &test_data.repo)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().set_local_branch(
         "main".to_string(),
         RefTarget::Normal(new_commit.id().clone()),
@@ -686,7 +690,8 @@ fn set_up_push_repos(settings: &UserSettings, temp_dir: &TempDir) -> PushTestSet
     let mut tx = jj_repo.start_transaction("test");
     let new_commit = testutils::create_random_commit(settings, &jj_repo)
         .set_parents(vec![initial_commit_id])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let jj_repo = tx.commit();
     PushTestSetup {
         source_repo_dir,
@@ -807,8 +812,9 @@ fn test_push_updates_not_fast_forward() {
     let temp_dir = tempfile::tempdir().unwrap();
     let mut setup = set_up_push_repos(&settings, &temp_dir);
     let mut tx = setup.jj_repo.start_transaction("test");
-    let new_commit =
-        testutils::create_random_commit(&settings, &setup.jj_repo).write_to_repo(tx.mut_repo());
+    let new_commit = testutils::create_random_commit(&settings, &setup.jj_repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     setup.jj_repo = tx.commit();
     let result = git::push_updates(
         &setup.jj_repo.store().git_repo().unwrap(),
@@ -828,8 +834,9 @@ fn test_push_updates_not_fast_forward_with_force() {
     let temp_dir = tempfile::tempdir().unwrap();
     let mut setup = set_up_push_repos(&settings, &temp_dir);
     let mut tx = setup.jj_repo.start_transaction("test");
-    let new_commit =
-        testutils::create_random_commit(&settings, &setup.jj_repo).write_to_repo(tx.mut_repo());
+    let new_commit = testutils::create_random_commit(&settings, &setup.jj_repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     setup.jj_repo = tx.commit();
     let result = git::push_updates(
         &setup.jj_repo.store().git_repo().unwrap(),
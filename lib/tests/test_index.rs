@@ -289,7 +289,9 @@ fn test_index_commits_incremental(use_git: bool) {
 
     let root_commit = repo.store().root_commit();
     let mut tx = repo.start_transaction("test");
-    let commit_a = child_commit(&settings, repo, &root_commit).write_to_repo(tx.mut_repo());
+    let commit_a = child_commit(&settings, repo, &root_commit)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let index = repo.index();
@@ -297,8 +299,12 @@ fn test_index_commits_incremental(use_git: bool) {
     assert_eq!(index.num_commits(), 1 + 1);
 
     let mut tx = repo.start_transaction("test");
-    let commit_b = child_commit(&settings, &repo, &commit_a).write_to_repo(tx.mut_repo());
-    let commit_c = child_commit(&settings, &repo, &commit_b).write_to_repo(tx.mut_repo());
+    let commit_b = child_commit(&settings, &repo, &commit_a)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    let commit_c = child_commit(&settings, &repo, &commit_b)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.commit();
 
     let repo = ReadonlyRepo::load_at_head(&settings, repo.repo_path().clone()).unwrap();
@@ -335,7 +341,9 @@ fn test_index_commits_incremental_empty_transaction(use_git: bool) {
 
     let root_commit = repo.store().root_commit();
     let mut tx = repo.start_transaction("test");
-    let commit_a = child_commit(&settings, repo, &root_commit).write_to_repo(tx.mut_repo());
+    let commit_a = child_commit(&settings, repo, &root_commit)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let index = repo.index();
@@ -376,7 +384,9 @@ fn test_index_commits_incremental_already_indexed(use_git: bool) {
 
     let root_commit = repo.store().root_commit();
     let mut tx = repo.start_transaction("test");
-    let commit_a = child_commit(&settings, repo, &root_commit).write_to_repo(tx.mut_repo());
+    let commit_a = child_commit(&settings, repo, &root_commit)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     assert!(repo.index().has_id(commit_a.id()));
@@ -395,7 +405,9 @@ fn create_n_commits(
 ) -> Arc<ReadonlyRepo> {
     let mut tx = repo.start_transaction("test");
     for _ in 0..num_commits {
-        create_random_commit(settings, repo).write_to_repo(tx.mut_repo());
+        create_random_commit(settings, repo)
+            .write_to_repo(tx.mut_repo())
+            .unwrap();
     }
     tx.commit()
 }
@@ -39,7 +39,9 @@ fn test_init_local() {
 
     // Just test that we can write a commit to the store
     let mut tx = repo.start_transaction("test");
-    testutils::create_random_commit(&settings, &repo).write_to_repo(tx.mut_repo());
+    testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
 }
 
 #[test]
@@ -54,7 +56,9 @@ fn test_init_internal_git() {
 
     // Just test that we ca write a commit to the store
     let mut tx = repo.start_transaction("test");
-    testutils::create_random_commit(&settings, &repo).write_to_repo(tx.mut_repo());
+    testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
 }
 
 #[test]
@@ -76,7 +80,9 @@ fn test_init_external_git() {
 
     // Just test that we can write a commit to the store
     let mut tx = repo.start_transaction("test");
-    testutils::create_random_commit(&settings, &repo).write_to_repo(tx.mut_repo());
+    testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
 }
 
 #[test_case(false ; "local backend")]
@@ -0,0 +1,132 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jujutsu_lib::backend::CommitId;
+use jujutsu_lib::commit::Commit;
+use jujutsu_lib::commit_builder::CommitBuilder;
+use jujutsu_lib::line_origins::{get_line_origins, LineOrigin};
+use jujutsu_lib::repo::MutableRepo;
+use jujutsu_lib::repo_path::RepoPath;
+use jujutsu_lib::settings::UserSettings;
+use jujutsu_lib::testutils;
+use jujutsu_lib::testutils::TestRepo;
+use test_case::test_case;
+
+fn write_commit(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    parents: &[&Commit],
+    contents: &str,
+) -> Commit {
+    let path = RepoPath::from_internal_string("file");
+    let mut tree_builder = mut_repo
+        .store()
+        .tree_builder(mut_repo.store().empty_tree_id().clone());
+    testutils::write_normal_file(&mut tree_builder, &path, contents);
+    let tree_id = tree_builder.write_tree();
+    CommitBuilder::for_new_commit(settings, tree_id)
+        .set_parents(parents.iter().map(|commit| commit.id().clone()).collect())
+        .write_to_repo(mut_repo)
+        .unwrap()
+}
+
+fn origin(commit: &Commit, line_number: usize) -> Option<LineOrigin> {
+    Some(LineOrigin {
+        commit_id: commit.id().clone(),
+        line_number,
+    })
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_line_origins_through_merge(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let path = RepoPath::from_internal_string("file");
+
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let root_commit = mut_repo.store().root_commit();
+    let base = write_commit(&settings, mut_repo, &[&root_commit], "1\n2\n3\n");
+    let left = write_commit(&settings, mut_repo, &[&base], "0\n1\n2\n3\n");
+    let right = write_commit(&settings, mut_repo, &[&base], "1\n2\n3 right\n");
+    let merge = write_commit(
+        &settings,
+        mut_repo,
+        &[&left, &right],
+        "0\n1\n2\n3 right\n4\n",
+    );
+
+    let origins = get_line_origins(mut_repo.as_repo_ref(), &merge, &path, |_| true).unwrap();
+    assert_eq!(
+        origins,
+        vec![
+            origin(&left, 0),
+            origin(&base, 0),
+            origin(&base, 1),
+            origin(&right, 2),
+            origin(&merge, 4),
+        ]
+    );
+
+    // Lines that would be attributed to commits outside the domain get no origin
+    let in_domain = |id: &CommitId| id != base.id();
+    let origins = get_line_origins(mut_repo.as_repo_ref(), &merge, &path, in_domain).unwrap();
+    assert_eq!(
+        origins,
+        vec![
+            origin(&left, 0),
+            None,
+            None,
+            origin(&right, 2),
+            origin(&merge, 4),
+        ]
+    );
+
+    // A commit where the file doesn't exist has no lines
+    let origins = get_line_origins(mut_repo.as_repo_ref(), &root_commit, &path, |_| true).unwrap();
+    assert_eq!(origins, vec![]);
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_line_origins_same_line_through_both_parents(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let path = RepoPath::from_internal_string("file");
+
+    // Both copies of "x" in the merge come from the same line in the base, one
+    // through each parent
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let root_commit = mut_repo.store().root_commit();
+    let base = write_commit(&settings, mut_repo, &[&root_commit], "x\n");
+    let left = write_commit(&settings, mut_repo, &[&base], "x\np\n");
+    let right = write_commit(&settings, mut_repo, &[&base], "q\nq2\nx\n");
+    let merge = write_commit(&settings, mut_repo, &[&left, &right], "x\np\nq\nq2\nx\n");
+
+    let origins = get_line_origins(mut_repo.as_repo_ref(), &merge, &path, |_| true).unwrap();
+    assert_eq!(
+        origins,
+        vec![
+            origin(&base, 0),
+            origin(&left, 1),
+            origin(&right, 0),
+            origin(&right, 1),
+            origin(&base, 0),
+        ]
+    );
+}
@@ -25,7 +25,9 @@ fn test_load_at_operation(use_git: bool) {
     let repo = &test_repo.repo;
 
     let mut tx = repo.start_transaction("add commit");
-    let commit = testutils::create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let commit = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("remove commit");
@@ -16,6 +16,7 @@ use assert_matches::assert_matches;
 use itertools::Itertools;
 use jujutsu_lib::backend::{ConflictPart, TreeValue};
 use jujutsu_lib::commit_builder::CommitBuilder;
+use jujutsu_lib::conflicts::{materialize_conflict, ConflictMarkerStyle};
 use jujutsu_lib::repo_path::{RepoPath, RepoPathComponent};
 use jujutsu_lib::rewrite::rebase_commit;
 use jujutsu_lib::testutils::TestRepo;
@@ -123,17 +124,19 @@ fn test_same_type(use_git: bool) {
             let conflict = store
                 .read_conflict(&RepoPath::from_internal_string("_ab"), id)
                 .unwrap();
-            assert_eq!(
-                conflict.adds,
-                vec![
-                    ConflictPart {
-                        value: side1_tree.value(&component).cloned().unwrap()
-                    },
-                    ConflictPart {
-                        value: side2_tree.value(&component).cloned().unwrap()
-                    }
-                ]
-            );
+            let mut expected_adds = vec![
+                ConflictPart {
+                    value: side1_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+                ConflictPart {
+                    value: side2_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+            ];
+            // The parts are stored in sorted order
+            expected_adds.sort();
+            assert_eq!(conflict.adds, expected_adds);
             assert!(conflict.removes.is_empty());
         }
         _ => panic!("unexpected value"),
@@ -147,13 +150,15 @@ fn test_same_type(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
             assert_eq!(
                 conflict.adds,
                 vec![ConflictPart {
-                    value: side2_tree.value(&component).cloned().unwrap()
+                    value: side2_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
         }
@@ -168,13 +173,15 @@ fn test_same_type(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
             assert_eq!(
                 conflict.adds,
                 vec![ConflictPart {
-                    value: side1_tree.value(&component).cloned().unwrap()
+                    value: side1_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
         }
@@ -189,20 +196,23 @@ fn test_same_type(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
-            assert_eq!(
-                conflict.adds,
-                vec![
-                    ConflictPart {
-                        value: side1_tree.value(&component).cloned().unwrap()
-                    },
-                    ConflictPart {
-                        value: side2_tree.value(&component).cloned().unwrap()
-                    }
-                ]
-            );
+            let mut expected_adds = vec![
+                ConflictPart {
+                    value: side1_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+                ConflictPart {
+                    value: side2_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+            ];
+            // The parts are stored in sorted order
+            expected_adds.sort();
+            assert_eq!(conflict.adds, expected_adds);
         }
         _ => panic!("unexpected value"),
     };
@@ -407,17 +417,20 @@ fn test_types(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
             assert_eq!(
                 conflict.adds,
                 vec![
                     ConflictPart {
-                        value: side1_tree.value(&component).cloned().unwrap()
+                        value: side1_tree.value(&component).cloned().unwrap(),
+                        label: None
                     },
                     ConflictPart {
-                        value: side2_tree.value(&component).cloned().unwrap()
+                        value: side2_tree.value(&component).cloned().unwrap(),
+                        label: None
                     },
                 ]
             );
@@ -433,17 +446,20 @@ fn test_types(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
             assert_eq!(
                 conflict.adds,
                 vec![
                     ConflictPart {
-                        value: side1_tree.value(&component).cloned().unwrap()
+                        value: side1_tree.value(&component).cloned().unwrap(),
+                        label: None
                     },
                     ConflictPart {
-                        value: side2_tree.value(&component).cloned().unwrap()
+                        value: side2_tree.value(&component).cloned().unwrap(),
+                        label: None
                     },
                 ]
             );
@@ -508,20 +524,23 @@ fn test_simplify_conflict(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
-            assert_eq!(
-                conflict.adds,
-                vec![
-                    ConflictPart {
-                        value: branch_tree.value(&component).cloned().unwrap()
-                    },
-                    ConflictPart {
-                        value: upstream2_tree.value(&component).cloned().unwrap()
-                    },
-                ]
-            );
+            let mut expected_adds = vec![
+                ConflictPart {
+                    value: branch_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+                ConflictPart {
+                    value: upstream2_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+            ];
+            // The parts are stored in sorted order
+            expected_adds.sort();
+            assert_eq!(conflict.adds, expected_adds);
         }
         _ => panic!("unexpected value"),
     };
@@ -532,20 +551,23 @@ fn test_simplify_conflict(use_git: bool) {
             assert_eq!(
                 conflict.removes,
                 vec![ConflictPart {
-                    value: base_tree.value(&component).cloned().unwrap()
+                    value: base_tree.value(&component).cloned().unwrap(),
+                    label: None
                 }]
             );
-            assert_eq!(
-                conflict.adds,
-                vec![
-                    ConflictPart {
-                        value: upstream2_tree.value(&component).cloned().unwrap()
-                    },
-                    ConflictPart {
-                        value: branch_tree.value(&component).cloned().unwrap()
-                    },
-                ]
-            );
+            let mut expected_adds = vec![
+                ConflictPart {
+                    value: upstream2_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+                ConflictPart {
+                    value: branch_tree.value(&component).cloned().unwrap(),
+                    label: None,
+                },
+            ];
+            // The parts are stored in sorted order
+            expected_adds.sort();
+            assert_eq!(conflict.adds, expected_adds);
         }
         _ => panic!("unexpected value"),
     };
@@ -573,23 +595,33 @@ fn test_simplify_conflict_after_resolving_parent(use_git: bool) {
     let path = RepoPath::from_internal_string("dir/file");
     let mut tx = repo.start_transaction("test");
     let tree_a = testutils::create_tree(repo, &[(&path, "abc\ndef\nghi\n")]);
-    let commit_a =
-        CommitBuilder::for_new_commit(&settings, tree_a.id().clone()).write_to_repo(tx.mut_repo());
+    let commit_a = CommitBuilder::for_new_commit(&settings, tree_a.id().clone())
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let tree_b = testutils::create_tree(repo, &[(&path, "Abc\ndef\nghi\n")]);
     let commit_b = CommitBuilder::for_new_commit(&settings, tree_b.id().clone())
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let tree_c = testutils::create_tree(repo, &[(&path, "Abc\ndef\nGhi\n")]);
     let commit_c = CommitBuilder::for_new_commit(&settings, tree_c.id().clone())
         .set_parents(vec![commit_b.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let tree_d = testutils::create_tree(repo, &[(&path, "abC\ndef\nghi\n")]);
     let commit_d = CommitBuilder::for_new_commit(&settings, tree_d.id().clone())
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
-
-    let commit_b2 = rebase_commit(&settings, tx.mut_repo(), &commit_b, &[commit_d]);
-    let commit_c2 = rebase_commit(&settings, tx.mut_repo(), &commit_c, &[commit_b2.clone()]);
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+
+    let commit_b2 = rebase_commit(&settings, tx.mut_repo(), &commit_b, &[commit_d]).unwrap();
+    let commit_c2 = rebase_commit(
+        &settings,
+        tx.mut_repo(),
+        &commit_c,
+        std::slice::from_ref(&commit_b2),
+    )
+    .unwrap();
 
     // Test the setup: Both B and C should have conflicts.
     assert_matches!(
@@ -605,8 +637,9 @@ fn test_simplify_conflict_after_resolving_parent(use_git: bool) {
     let tree_b3 = testutils::create_tree(repo, &[(&path, "AbC\ndef\nghi\n")]);
     let commit_b3 = CommitBuilder::for_rewrite_from(&settings, &commit_b2)
         .set_tree(tree_b3.id().clone())
-        .write_to_repo(tx.mut_repo());
-    let commit_c3 = rebase_commit(&settings, tx.mut_repo(), &commit_c2, &[commit_b3]);
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    let commit_c3 = rebase_commit(&settings, tx.mut_repo(), &commit_c2, &[commit_b3]).unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     let repo = tx.commit();
 
@@ -628,5 +661,309 @@ fn test_simplify_conflict_after_resolving_parent(use_git: bool) {
     }
 }
 
-// TODO: Add tests for simplification of multi-way conflicts. Both the content
-// and the executable bit need testing.
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_executable_bit(use_git: bool) {
+    // Tests that the executable bit is merged separately from the content.
+
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let write_tree = |contents: Option<&str>, executable: bool| -> Tree {
+        let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+        match contents {
+            None => {}
+            Some(contents) if executable => {
+                testutils::write_executable_file(&mut tree_builder, &path, contents)
+            }
+            Some(contents) => testutils::write_normal_file(&mut tree_builder, &path, contents),
+        }
+        let tree_id = tree_builder.write_tree();
+        store.get_tree(&RepoPath::root(), &tree_id).unwrap()
+    };
+    let merge_trees = |base: &Tree, side1: &Tree, side2: &Tree| -> Option<TreeValue> {
+        let tree_id = tree::merge_trees(side1, base, side2).unwrap();
+        let tree = store.get_tree(&RepoPath::root(), &tree_id).unwrap();
+        tree.path_value(&path)
+    };
+
+    let base_tree = write_tree(Some("1\n2\n3\n"), false);
+    let exec_tree = write_tree(Some("1\n2\n3\n"), true);
+    let exec_modified_tree = write_tree(Some("ONE\n2\n3\n"), true);
+    let modified_tree = write_tree(Some("1\n2\nTHREE\n"), false);
+    let deleted_tree = write_tree(None, false);
+
+    // A change to the executable bit on one side and to the content on the other
+    // side is resolved
+    let modified_id = match modified_tree.path_value(&path) {
+        Some(TreeValue::Normal { id, .. }) => id,
+        other => panic!("unexpected value: {:#?}", other),
+    };
+    assert_eq!(
+        merge_trees(&base_tree, &exec_tree, &modified_tree),
+        Some(TreeValue::Normal {
+            id: modified_id,
+            executable: true
+        })
+    );
+
+    // The content is merged line by line while the executable bit is changed
+    match merge_trees(&base_tree, &exec_modified_tree, &modified_tree) {
+        Some(TreeValue::Normal {
+            id,
+            executable: true,
+        }) => {
+            assert_eq!(testutils::read_file(store, &path, &id), b"ONE\n2\nTHREE\n");
+        }
+        other => panic!("unexpected value: {:#?}", other),
+    }
+
+    // If one side only changed the executable bit, the deletion on the other
+    // side wins
+    assert_eq!(merge_trees(&base_tree, &exec_tree, &deleted_tree), None);
+    assert_eq!(merge_trees(&base_tree, &deleted_tree, &exec_tree), None);
+
+    // A change to the content conflicts with a deletion
+    assert_matches!(
+        merge_trees(&base_tree, &exec_modified_tree, &deleted_tree),
+        Some(TreeValue::Conflict(_))
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_simplify_multi_way_conflict(use_git: bool) {
+    // Tests that conflicts where all sides ended up with the same value after
+    // simplification are resolved, also for non-file values.
+
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let component = RepoPathComponent::from("file");
+    let path = RepoPath::from_internal_string("file");
+    let write_tree = |symlink_target: Option<&str>, contents: Option<&str>| -> Tree {
+        let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+        if let Some(target) = symlink_target {
+            testutils::write_symlink(&mut tree_builder, &path, target);
+        }
+        if let Some(contents) = contents {
+            testutils::write_normal_file(&mut tree_builder, &path, contents);
+        }
+        let tree_id = tree_builder.write_tree();
+        store.get_tree(&RepoPath::root(), &tree_id).unwrap()
+    };
+    let merge_trees = |base: &Tree, side1: &Tree, side2: &Tree| -> Tree {
+        let tree_id = tree::merge_trees(side1, base, side2).unwrap();
+        store.get_tree(&RepoPath::root(), &tree_id).unwrap()
+    };
+
+    let base_tree = write_tree(None, Some("base"));
+    let symlink_tree = write_tree(Some("target"), None);
+    let modified_tree = write_tree(None, Some("modified"));
+    let other_base_tree = write_tree(None, Some("other base"));
+    let deleted_tree = write_tree(None, None);
+
+    // Replacing the file by a symlink conflicts with modifying it
+    let conflict_tree = merge_trees(&base_tree, &symlink_tree, &modified_tree);
+    assert_matches!(
+        conflict_tree.value(&component),
+        Some(TreeValue::Conflict(_))
+    );
+
+    // If the modification is then replaced by the same symlink, both sides agree
+    // on the symlink, so the conflict {+symlink+symlink-base} is resolved.
+    let merged_tree = merge_trees(&modified_tree, &conflict_tree, &symlink_tree);
+    assert_eq!(
+        merged_tree.value(&component),
+        symlink_tree.value(&component)
+    );
+    let merged_tree = merge_trees(&modified_tree, &symlink_tree, &conflict_tree);
+    assert_eq!(
+        merged_tree.value(&component),
+        symlink_tree.value(&component)
+    );
+
+    // A conflict between a deletion and a modification, where the modification is
+    // later deleted from a different base
+    let conflict_tree = merge_trees(&base_tree, &deleted_tree, &modified_tree);
+    assert_matches!(
+        conflict_tree.value(&component),
+        Some(TreeValue::Conflict(_))
+    );
+    let merged_tree = merge_trees(&modified_tree, &conflict_tree, &deleted_tree);
+    assert_eq!(merged_tree.value(&component), None);
+
+    // Deleting it relative to an unrelated base leaves the conflict in place
+    let merged_tree = merge_trees(&other_base_tree, &conflict_tree, &deleted_tree);
+    assert_matches!(merged_tree.value(&component), Some(TreeValue::Conflict(_)));
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_octopus_merge_is_order_independent(use_git: bool) {
+    // Tests that merging three sides in any order results in the same conflict,
+    // without any nested conflicts, and that regions where the sides partially
+    // agree are resolved when the conflict is materialized.
+
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let component = RepoPathComponent::from("file");
+    let path = RepoPath::from_internal_string("file");
+    let base_tree = testutils::create_tree(repo, &[(&path, "a\nb\nc\n")]);
+    let side1_tree = testutils::create_tree(repo, &[(&path, "x\nb\nq\n")]);
+    let side2_tree = testutils::create_tree(repo, &[(&path, "w\nb\nc\n")]);
+    let side3_tree = testutils::create_tree(repo, &[(&path, "v\nb\nq\n")]);
+    let merge_trees = |side1: &Tree, side2: &Tree| -> Tree {
+        let tree_id = tree::merge_trees(side1, &base_tree, side2).unwrap();
+        store.get_tree(&RepoPath::root(), &tree_id).unwrap()
+    };
+
+    let sides = [&side1_tree, &side2_tree, &side3_tree];
+    let mut conflict_ids = vec![];
+    for order in (0..3).permutations(3) {
+        let merged_tree = merge_trees(sides[order[0]], sides[order[1]]);
+        let merged_tree = merge_trees(&merged_tree, sides[order[2]]);
+        match merged_tree.value(&component).unwrap() {
+            TreeValue::Conflict(id) => {
+                let conflict = store.read_conflict(&path, id).unwrap();
+                let mut content = vec![];
+                materialize_conflict(
+                    store,
+                    &path,
+                    &conflict,
+                    None,
+                    ConflictMarkerStyle::Diff,
+                    &mut content,
+                )
+                .unwrap();
+                assert!(content.ends_with(b">>>>>>>\nb\nq\n"));
+                conflict_ids.push(id.clone());
+            }
+            value => panic!("unexpected value {value:?}"),
+        }
+    }
+    assert!(conflict_ids.iter().all_equal());
+    let conflict = store.read_conflict(&path, &conflict_ids[0]).unwrap();
+    let base_value = base_tree.value(&component).unwrap();
+    assert_eq!(
+        conflict
+            .removes
+            .iter()
+            .map(|part| &part.value)
+            .collect_vec(),
+        vec![base_value; 2]
+    );
+    assert_eq!(
+        conflict.adds.iter().map(|part| &part.value).collect_vec(),
+        sides
+            .iter()
+            .map(|tree| tree.value(&component).unwrap())
+            .sorted()
+            .collect_vec()
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_binary_files(use_git: bool) {
+    // Tests that binary files are not merged line by line.
+
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_tree = testutils::create_tree(repo, &[(&path, "line 1\0\nline 2\n")]);
+    let side1_tree = testutils::create_tree(repo, &[(&path, "left 1\0\nline 2\n")]);
+    let side2_tree = testutils::create_tree(repo, &[(&path, "line 1\0\nright 2\n")]);
+    let side3_tree = testutils::create_tree(repo, &[(&path, "left 1\0\nline 2\n")]);
+
+    // Changes to different lines of a binary file result in a conflict
+    let merged_tree_id = tree::merge_trees(&side1_tree, &base_tree, &side2_tree).unwrap();
+    let merged_tree = store.get_tree(&RepoPath::root(), &merged_tree_id).unwrap();
+    assert_matches!(merged_tree.path_value(&path), Some(TreeValue::Conflict(_)));
+
+    // Changes on one side only are still resolved
+    let merged_tree_id = tree::merge_trees(&side1_tree, &base_tree, &base_tree).unwrap();
+    assert_eq!(&merged_tree_id, side1_tree.id());
+
+    // Identical changes on both sides are still resolved
+    let merged_tree_id = tree::merge_trees(&side1_tree, &base_tree, &side3_tree).unwrap();
+    assert_eq!(&merged_tree_id, side1_tree.id());
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_merge_attributes(use_git: bool) {
+    // Tests that the `merge` attribute from `.gitattributes` decides how
+    // conflicting changes are merged.
+
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let attributes_path = RepoPath::from_internal_string(".gitattributes");
+    let attributes = "CHANGELOG.md merge=union\n*.lock merge=ours\n*.gen merge=theirs\n\
+                      *.dat merge=binary\n";
+    let file_names = [
+        "CHANGELOG.md",
+        "Cargo.lock",
+        "file.gen",
+        "file.dat",
+        "file.txt",
+    ];
+    let write_tree = |contents: &str| -> Tree {
+        let paths = file_names
+            .iter()
+            .map(|name| RepoPath::from_internal_string(name))
+            .collect_vec();
+        let mut path_contents = vec![(&attributes_path, attributes)];
+        path_contents.extend(paths.iter().map(|path| (path, contents)));
+        testutils::create_tree(repo, &path_contents)
+    };
+    let base_tree = write_tree("a\nb\nc\n");
+    let side1_tree = write_tree("a\nleft\nc\n");
+    let side2_tree = write_tree("a\nright\nc\nd\n");
+
+    let merged_tree_id = tree::merge_trees(&side1_tree, &base_tree, &side2_tree).unwrap();
+    let merged_tree = store.get_tree(&RepoPath::root(), &merged_tree_id).unwrap();
+    let merged_content = |name: &str| -> Option<Vec<u8>> {
+        let path = RepoPath::from_internal_string(name);
+        match merged_tree.path_value(&path).unwrap() {
+            TreeValue::Normal { id, .. } => Some(testutils::read_file(store, &path, &id)),
+            TreeValue::Conflict(_) => None,
+            other => panic!("unexpected value: {:#?}", other),
+        }
+    };
+    assert_eq!(
+        merged_content("CHANGELOG.md"),
+        Some(b"a\nleft\nright\nc\nd\n".to_vec())
+    );
+    assert_eq!(
+        merged_content("Cargo.lock"),
+        Some(b"a\nleft\nc\nd\n".to_vec())
+    );
+    assert_eq!(
+        merged_content("file.gen"),
+        Some(b"a\nright\nc\nd\n".to_vec())
+    );
+    // Without attributes, the conflicting change is left as a conflict
+    assert_eq!(merged_content("file.txt"), None);
+
+    // Changes to different lines are not merged for binary files
+    let side3_tree = write_tree("a\nb\nc\nd\n");
+    let merged_tree_id = tree::merge_trees(&side1_tree, &base_tree, &side3_tree).unwrap();
+    let merged_tree = store.get_tree(&RepoPath::root(), &merged_tree_id).unwrap();
+    let path = RepoPath::from_internal_string("file.dat");
+    assert_matches!(merged_tree.path_value(&path), Some(TreeValue::Conflict(_)));
+    let path = RepoPath::from_internal_string("file.txt");
+    assert_matches!(
+        merged_tree.path_value(&path),
+        Some(TreeValue::Normal { .. })
+    );
+}
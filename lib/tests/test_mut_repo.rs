@@ -33,7 +33,8 @@ fn test_edit(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let checkout = testutils::create_random_commit(&settings, repo)
         .set_open(true)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
@@ -55,14 +56,16 @@ fn test_checkout_closed(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let requested_checkout = testutils::create_random_commit(&settings, repo)
         .set_open(false)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
     let ws_id = WorkspaceId::default();
     let actual_checkout = tx
         .mut_repo()
-        .check_out(ws_id.clone(), &settings, &requested_checkout);
+        .check_out(ws_id.clone(), &settings, &requested_checkout)
+        .unwrap();
     assert_eq!(actual_checkout.tree_id(), requested_checkout.tree_id());
     assert_eq!(actual_checkout.parents().len(), 1);
     assert_eq!(actual_checkout.parents()[0].id(), requested_checkout.id());
@@ -83,7 +86,8 @@ fn test_checkout_previous_not_empty(use_git: bool) {
     let mut_repo = tx.mut_repo();
     let old_checkout = testutils::create_random_commit(&settings, repo)
         .set_open(true)
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_checkout);
     let repo = tx.commit();
@@ -92,7 +96,8 @@ fn test_checkout_previous_not_empty(use_git: bool) {
     let mut_repo = tx.mut_repo();
     let new_checkout = testutils::create_random_commit(&settings, &repo)
         .set_open(true)
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.edit(ws_id, &new_checkout);
     mut_repo.rebase_descendants(&settings).unwrap();
     assert!(mut_repo.view().heads().contains(old_checkout.id()));
@@ -114,7 +119,8 @@ fn test_checkout_previous_empty(use_git: bool) {
         repo.store().root_commit_id().clone(),
         repo.store().empty_tree_id().clone(),
     )
-    .write_to_repo(mut_repo);
+    .write_to_repo(mut_repo)
+    .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_checkout);
     let repo = tx.commit();
@@ -123,7 +129,8 @@ fn test_checkout_previous_empty(use_git: bool) {
     let mut_repo = tx.mut_repo();
     let new_checkout = testutils::create_random_commit(&settings, &repo)
         .set_open(true)
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.edit(ws_id, &new_checkout);
     mut_repo.rebase_descendants(&settings).unwrap();
     assert!(!mut_repo.view().heads().contains(old_checkout.id()));
@@ -146,7 +153,8 @@ fn test_checkout_previous_empty_with_description(use_git: bool) {
         repo.store().empty_tree_id().clone(),
     )
     .set_description("not empty".to_string())
-    .write_to_repo(mut_repo);
+    .write_to_repo(mut_repo)
+    .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_checkout);
     let repo = tx.commit();
@@ -155,7 +163,8 @@ fn test_checkout_previous_empty_with_description(use_git: bool) {
     let mut_repo = tx.mut_repo();
     let new_checkout = testutils::create_random_commit(&settings, &repo)
         .set_open(true)
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.edit(ws_id, &new_checkout);
     mut_repo.rebase_descendants(&settings).unwrap();
     assert!(mut_repo.view().heads().contains(old_checkout.id()));
@@ -177,13 +186,15 @@ fn test_checkout_previous_empty_non_head(use_git: bool) {
         repo.store().root_commit_id().clone(),
         repo.store().empty_tree_id().clone(),
     )
-    .write_to_repo(mut_repo);
+    .write_to_repo(mut_repo)
+    .unwrap();
     let old_child = CommitBuilder::for_open_commit(
         &settings,
         old_checkout.id().clone(),
         old_checkout.tree_id().clone(),
     )
-    .write_to_repo(mut_repo);
+    .write_to_repo(mut_repo)
+    .unwrap();
     let ws_id = WorkspaceId::default();
     mut_repo.edit(ws_id.clone(), &old_checkout);
     let repo = tx.commit();
@@ -192,7 +203,8 @@ fn test_checkout_previous_empty_non_head(use_git: bool) {
     let mut_repo = tx.mut_repo();
     let new_checkout = testutils::create_random_commit(&settings, &repo)
         .set_open(true)
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.edit(ws_id, &new_checkout);
     mut_repo.rebase_descendants(&settings).unwrap();
     assert_eq!(
@@ -213,7 +225,8 @@ fn test_edit_initial(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let checkout = testutils::create_random_commit(&settings, repo)
         .set_open(true)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
@@ -235,7 +248,9 @@ fn test_add_head_success(use_git: bool) {
     // Create a commit outside of the repo by using a temporary transaction. Then
     // add that as a head.
     let mut tx = repo.start_transaction("test");
-    let new_commit = testutils::create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let new_commit = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     drop(tx);
 
     let index_stats = repo.index().stats();
@@ -298,7 +313,9 @@ fn test_add_head_not_immediate_child(use_git: bool) {
     let repo = &test_repo.repo;
 
     let mut tx = repo.start_transaction("test");
-    let initial = testutils::create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let initial = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     // Create some commit outside of the repo by using a temporary transaction. Then
@@ -307,10 +324,12 @@ fn test_add_head_not_immediate_child(use_git: bool) {
     let rewritten = testutils::create_random_commit(&settings, &repo)
         .set_change_id(initial.change_id().clone())
         .set_predecessors(vec![initial.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let child = testutils::create_random_commit(&settings, &repo)
         .set_parents(vec![rewritten.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     drop(tx);
 
     let index_stats = repo.index().stats();
@@ -384,7 +403,9 @@ fn test_add_public_head(use_git: bool) {
     let repo = &test_repo.repo;
 
     let mut tx = repo.start_transaction("test");
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
@@ -432,7 +453,9 @@ fn test_remove_public_head(use_git: bool) {
 
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.add_public_head(&commit1);
     let repo = tx.commit();
 
@@ -457,8 +480,12 @@ fn test_has_changed(use_git: bool) {
 
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.remove_head(commit2.id());
     mut_repo.add_public_head(&commit1);
     let ws_id = WorkspaceId::default();
@@ -41,7 +41,9 @@ fn test_unpublished_operation(use_git: bool) {
     assert_eq!(list_dir(&op_heads_dir), vec![repo.op_id().hex()]);
 
     let mut tx1 = repo.start_transaction("transaction 1");
-    testutils::create_random_commit(&settings, repo).write_to_repo(tx1.mut_repo());
+    testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     let unpublished_op = tx1.write();
     let op_id1 = unpublished_op.operation().id().clone();
     assert_ne!(op_id1, op_id0);
@@ -64,14 +66,18 @@ fn test_consecutive_operations(use_git: bool) {
     assert_eq!(list_dir(&op_heads_dir), vec![repo.op_id().hex()]);
 
     let mut tx1 = repo.start_transaction("transaction 1");
-    testutils::create_random_commit(&settings, repo).write_to_repo(tx1.mut_repo());
+    testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     let op_id1 = tx1.commit().operation().id().clone();
     assert_ne!(op_id1, op_id0);
     assert_eq!(list_dir(&op_heads_dir), vec![op_id1.hex()]);
 
     let repo = repo.reload_at_head(&settings).unwrap();
     let mut tx2 = repo.start_transaction("transaction 2");
-    testutils::create_random_commit(&settings, &repo).write_to_repo(tx2.mut_repo());
+    testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     let op_id2 = tx2.commit().operation().id().clone();
     assert_ne!(op_id2, op_id0);
     assert_ne!(op_id2, op_id1);
@@ -97,7 +103,9 @@ fn test_concurrent_operations(use_git: bool) {
     assert_eq!(list_dir(&op_heads_dir), vec![repo.op_id().hex()]);
 
     let mut tx1 = repo.start_transaction("transaction 1");
-    testutils::create_random_commit(&settings, repo).write_to_repo(tx1.mut_repo());
+    testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     let op_id1 = tx1.commit().operation().id().clone();
     assert_ne!(op_id1, op_id0);
     assert_eq!(list_dir(&op_heads_dir), vec![op_id1.hex()]);
@@ -105,7 +113,9 @@ fn test_concurrent_operations(use_git: bool) {
     // After both transactions have committed, we should have two op-heads on disk,
     // since they were run in parallel.
     let mut tx2 = repo.start_transaction("transaction 2");
-    testutils::create_random_commit(&settings, repo).write_to_repo(tx2.mut_repo());
+    testutils::create_random_commit(&settings, repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     let op_id2 = tx2.commit().operation().id().clone();
     assert_ne!(op_id2, op_id0);
     assert_ne!(op_id2, op_id1);
@@ -140,7 +150,8 @@ fn test_isolation(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let initial = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![repo.store().root_commit_id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("transaction 1");
@@ -154,11 +165,13 @@ fn test_isolation(use_git: bool) {
 
     let rewrite1 = CommitBuilder::for_rewrite_from(&settings, &initial)
         .set_description("rewrite1".to_string())
-        .write_to_repo(mut_repo1);
+        .write_to_repo(mut_repo1)
+        .unwrap();
     mut_repo1.rebase_descendants(&settings).unwrap();
     let rewrite2 = CommitBuilder::for_rewrite_from(&settings, &initial)
         .set_description("rewrite2".to_string())
-        .write_to_repo(mut_repo2);
+        .write_to_repo(mut_repo2)
+        .unwrap();
     mut_repo2.rebase_descendants(&settings).unwrap();
 
     // Neither transaction has committed yet, so each transaction sees its own
@@ -0,0 +1,179 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches::assert_matches;
+use jujutsu_lib::backend::TreeValue;
+use jujutsu_lib::commit::Commit;
+use jujutsu_lib::commit_builder::CommitBuilder;
+use jujutsu_lib::files::{self, MergeResult};
+use jujutsu_lib::repo_path::RepoPath;
+use jujutsu_lib::rerere;
+use jujutsu_lib::rerere::ResolutionCache;
+use jujutsu_lib::rewrite::rebase_commit;
+use jujutsu_lib::settings::UserSettings;
+use jujutsu_lib::testutils;
+use jujutsu_lib::testutils::TestRepo;
+use test_case::test_case;
+
+fn rerere_settings() -> UserSettings {
+    let config = config::Config::builder()
+        .add_source(testutils::user_settings().config().clone())
+        .set_override("rerere.enabled", true)
+        .unwrap()
+        .build()
+        .unwrap();
+    UserSettings::from_config(config)
+}
+
+fn file_content(commit: &Commit, path: &RepoPath) -> Vec<u8> {
+    match commit.tree().path_value(path) {
+        Some(TreeValue::Normal { id, .. }) => testutils::read_file(commit.store(), path, &id),
+        other => panic!("unexpected value: {:#?}", other),
+    }
+}
+
+#[test]
+fn test_record_resolution_of_hunks() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cache = ResolutionCache::new(temp_dir.path().join("rerere"));
+
+    let hunks = match files::merge(
+        &[b"a\nb\nc\nd\ne\n"],
+        &[b"a\nB1\nc\nd\nE1\n", b"a\nB2\nc\nd\nE2\n"],
+    ) {
+        MergeResult::Conflict(hunks) => hunks,
+        MergeResult::Resolved(_) => panic!("expected a conflict"),
+    };
+    assert_eq!(cache.resolve_hunks(&hunks), None);
+
+    // Each conflicting hunk's resolution is recorded separately
+    assert_eq!(
+        cache
+            .record_resolution(&hunks, b"a\nB\nB\nc\nd\nE\n")
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        cache.resolve_hunks(&hunks),
+        Some(b"a\nB\nB\nc\nd\nE\n".to_vec())
+    );
+
+    // The same conflicting hunk in a different file gets the same resolution
+    let hunks = match files::merge(&[b"x\nb\ny\n"], &[b"x\nB1\ny\n", b"x\nB2\ny\n"]) {
+        MergeResult::Conflict(hunks) => hunks,
+        MergeResult::Resolved(_) => panic!("expected a conflict"),
+    };
+    assert_eq!(cache.resolve_hunks(&hunks), Some(b"x\nB\nB\ny\n".to_vec()));
+
+    // The same conflicting hunk with the sides swapped gets the same resolution
+    let hunks = match files::merge(&[b"x\nb\ny\n"], &[b"x\nB2\ny\n", b"x\nB1\ny\n"]) {
+        MergeResult::Conflict(hunks) => hunks,
+        MergeResult::Resolved(_) => panic!("expected a conflict"),
+    };
+    assert_eq!(cache.resolve_hunks(&hunks), Some(b"x\nB\nB\ny\n".to_vec()));
+
+    // Nothing is recorded if the resolved parts of the file were changed
+    let hunks = match files::merge(&[b"a\nb\nc\n"], &[b"a\nB3\nc\n", b"a\nB4\nc\n"]) {
+        MergeResult::Conflict(hunks) => hunks,
+        MergeResult::Resolved(_) => panic!("expected a conflict"),
+    };
+    assert_eq!(cache.record_resolution(&hunks, b"A\nB\nc\n").unwrap(), 0);
+    assert_eq!(cache.resolve_hunks(&hunks), None);
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_rebase_reuses_recorded_resolution(use_git: bool) {
+    let settings = rerere_settings();
+    let test_repo = TestRepo::init_with_settings(&settings, use_git);
+    let repo = &test_repo.repo;
+    let path = RepoPath::from_internal_string("file");
+
+    // Set up a repo like this:
+    // D
+    // C
+    // | B2
+    // | | B1
+    // |/_/
+    // A
+    //
+    // B1 and B2 make the same change, which conflicts with C.
+    let mut tx = repo.start_transaction("test");
+    let write_commit = |tx: &mut jujutsu_lib::transaction::Transaction,
+                        parent: Option<&Commit>,
+                        description: &str,
+                        contents: &str| {
+        let tree = testutils::create_tree(repo, &[(&path, contents)]);
+        let mut builder = CommitBuilder::for_new_commit(&settings, tree.id().clone())
+            .set_description(description.to_string());
+        if let Some(parent) = parent {
+            builder = builder.set_parents(vec![parent.id().clone()]);
+        }
+        builder.write_to_repo(tx.mut_repo()).unwrap()
+    };
+    let commit_a = write_commit(&mut tx, None, "A", "a\nb\nc\n");
+    let commit_b1 = write_commit(&mut tx, Some(&commit_a), "B1", "a\nB\nc\n");
+    let commit_b2 = write_commit(&mut tx, Some(&commit_a), "B2", "a\nB\nc\n");
+    let commit_c = write_commit(&mut tx, Some(&commit_a), "C", "a\nC\nc\n");
+    let commit_d = write_commit(&mut tx, Some(&commit_c), "D", "a\nC\nc\nd\n");
+
+    // Rebasing B1 onto C creates a conflict
+    let rebased_b1 = rebase_commit(
+        &settings,
+        tx.mut_repo(),
+        &commit_b1,
+        std::slice::from_ref(&commit_c),
+    )
+    .unwrap();
+    assert_matches!(
+        rebased_b1.tree().path_value(&path),
+        Some(TreeValue::Conflict(_))
+    );
+    assert_eq!(tx.mut_repo().reused_resolutions(), &[]);
+
+    // Writing a commit that resolves the conflict doesn't record the resolution
+    // by itself
+    let resolved_tree = testutils::create_tree(repo, &[(&path, "a\nBC\nc\n")]);
+    let resolved_b1 = CommitBuilder::for_rewrite_from(&settings, &rebased_b1)
+        .set_tree(resolved_tree.id().clone())
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    let rebased_b2 = rebase_commit(
+        &settings,
+        tx.mut_repo(),
+        &commit_b2,
+        std::slice::from_ref(&commit_d),
+    )
+    .unwrap();
+    assert_matches!(
+        rebased_b2.tree().path_value(&path),
+        Some(TreeValue::Conflict(_))
+    );
+    assert_eq!(tx.mut_repo().reused_resolutions(), &[]);
+
+    // Rebasing B2 onto D reuses the resolution once it's recorded
+    rerere::record_resolutions_in_rewrite(&rebased_b1, &resolved_b1);
+    let rebased_b2 = rebase_commit(&settings, tx.mut_repo(), &commit_b2, &[commit_d]).unwrap();
+    assert_eq!(file_content(&rebased_b2, &path), b"a\nBC\nc\nd\n");
+    assert_eq!(
+        tx.mut_repo().reused_resolutions(),
+        std::slice::from_ref(&path)
+    );
+}
+
+#[test]
+fn test_rerere_disabled_by_default() {
+    let test_repo = TestRepo::init(false);
+    assert!(test_repo.repo.store().resolution_cache().is_none());
+}
@@ -57,7 +57,8 @@ fn test_resolve_symbol_commit_id() {
             .set_description(format!("test {}", i))
             .set_author(signature.clone())
             .set_committer(signature.clone())
-            .write_to_repo(mut_repo);
+            .write_to_repo(mut_repo)
+            .unwrap();
         commits.push(commit);
     }
     let repo = tx.commit();
@@ -239,8 +240,12 @@ fn test_resolve_symbol_checkout(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
 
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     let ws1 = WorkspaceId::new("ws1".to_string());
     let ws2 = WorkspaceId::new("ws2".to_string());
@@ -289,11 +294,21 @@ fn test_resolve_symbol_git_refs() {
     let mut_repo = tx.mut_repo();
 
     // Create some commits and refs to work with and so the repo is not empty
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit3 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit4 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit5 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit3 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit4 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit5 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_git_ref(
         "refs/heads/branch1".to_string(),
         RefTarget::Normal(commit1.id().clone()),
@@ -438,7 +453,9 @@ fn test_evaluate_expression_root_and_checkout(use_git: bool) {
     let mut_repo = tx.mut_repo();
 
     let root_commit = repo.store().root_commit();
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can find the root commit
     assert_eq!(
@@ -649,19 +666,25 @@ fn test_evaluate_expression_children(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
 
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit2 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit3 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit2.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit4 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit5 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit3.id().clone(), commit4.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can find children of the root commit
     assert_eq!(
@@ -953,19 +976,25 @@ fn test_evaluate_expression_descendants(use_git: bool) {
     let mut_repo = tx.mut_repo();
 
     let root_commit_id = repo.store().root_commit_id().clone();
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit2 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit3 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit2.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit4 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit5 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit3.id().clone(), commit4.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // The descendants of the root commit are all the commits in the repo
     assert_eq!(
@@ -1092,10 +1121,18 @@ fn test_evaluate_expression_git_refs(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
 
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit3 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit4 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit3 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit4 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can get git refs when there are none
     assert_eq!(
@@ -1161,7 +1198,9 @@ fn test_evaluate_expression_git_head(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
 
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can get git head when it's not set
     assert_eq!(
@@ -1185,10 +1224,18 @@ fn test_evaluate_expression_branches(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
 
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit3 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit4 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit3 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit4 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can get branches when there are none
     assert_eq!(
@@ -1254,10 +1301,18 @@ fn test_evaluate_expression_remote_branches(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
 
-    let commit1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit3 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let commit4 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit3 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let commit4 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can get branches when there are none
     assert_eq!(
@@ -1367,15 +1422,18 @@ fn test_evaluate_expression_description(use_git: bool) {
 
     let commit1 = testutils::create_random_commit(&settings, repo)
         .set_description("commit 1".to_string())
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit2 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
         .set_description("commit 2".to_string())
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit3 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit2.id().clone()])
         .set_description("commit 3".to_string())
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can find multiple matches
     assert_eq!(
@@ -1418,7 +1476,8 @@ fn test_evaluate_expression_author(use_git: bool) {
             email: "email1".to_string(),
             timestamp: timestamp.clone(),
         })
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit2 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
         .set_author(Signature {
@@ -1426,7 +1485,8 @@ fn test_evaluate_expression_author(use_git: bool) {
             email: "email2".to_string(),
             timestamp: timestamp.clone(),
         })
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit3 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit2.id().clone()])
         .set_author(Signature {
@@ -1434,7 +1494,8 @@ fn test_evaluate_expression_author(use_git: bool) {
             email: "email3".to_string(),
             timestamp,
         })
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can find multiple matches
     assert_eq!(
@@ -1481,7 +1542,8 @@ fn test_evaluate_expression_committer(use_git: bool) {
             email: "email1".to_string(),
             timestamp: timestamp.clone(),
         })
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit2 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit1.id().clone()])
         .set_committer(Signature {
@@ -1489,7 +1551,8 @@ fn test_evaluate_expression_committer(use_git: bool) {
             email: "email2".to_string(),
             timestamp: timestamp.clone(),
         })
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
     let commit3 = testutils::create_random_commit(&settings, repo)
         .set_parents(vec![commit2.id().clone()])
         .set_committer(Signature {
@@ -1497,7 +1560,8 @@ fn test_evaluate_expression_committer(use_git: bool) {
             email: "email3".to_string(),
             timestamp,
         })
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)
+        .unwrap();
 
     // Can find multiple matches
     assert_eq!(
@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use jujutsu_lib::commit::Commit;
 use jujutsu_lib::commit_builder::CommitBuilder;
 use jujutsu_lib::op_store::{RefTarget, WorkspaceId};
 use jujutsu_lib::repo_path::RepoPath;
-use jujutsu_lib::rewrite::DescendantRebaser;
+use jujutsu_lib::rewrite::{conflict_label_for_commit, DescendantRebaser, EmptyBehavior};
 use jujutsu_lib::testutils;
 use jujutsu_lib::testutils::{assert_rebased, create_random_commit, CommitGraphBuilder, TestRepo};
+use jujutsu_lib::tree::Tree;
 use maplit::{hashmap, hashset};
 use test_case::test_case;
 
@@ -795,7 +797,8 @@ fn test_rebase_descendants_repeated(use_git: bool) {
 
     let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
         .set_description("b2".to_string())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
     let commit_c2 = assert_rebased(rebaser.rebase_next().unwrap(), &commit_c, &[&commit_b2]);
     assert!(rebaser.rebase_next().unwrap().is_none());
@@ -816,7 +819,8 @@ fn test_rebase_descendants_repeated(use_git: bool) {
     // Now mark B3 as rewritten from B2 and rebase descendants again.
     let commit_b3 = CommitBuilder::for_rewrite_from(&settings, &commit_b2)
         .set_description("b3".to_string())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
     let commit_c3 = assert_rebased(rebaser.rebase_next().unwrap(), &commit_c2, &[&commit_b3]);
     assert!(rebaser.rebase_next().unwrap().is_none());
@@ -849,23 +853,27 @@ fn test_rebase_descendants_contents(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let path1 = RepoPath::from_internal_string("file1");
     let tree1 = testutils::create_tree(repo, &[(&path1, "content")]);
-    let commit_a =
-        CommitBuilder::for_new_commit(&settings, tree1.id().clone()).write_to_repo(tx.mut_repo());
+    let commit_a = CommitBuilder::for_new_commit(&settings, tree1.id().clone())
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let path2 = RepoPath::from_internal_string("file2");
     let tree2 = testutils::create_tree(repo, &[(&path2, "content")]);
     let commit_b = CommitBuilder::for_new_commit(&settings, tree2.id().clone())
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let path3 = RepoPath::from_internal_string("file3");
     let tree3 = testutils::create_tree(repo, &[(&path3, "content")]);
     let commit_c = CommitBuilder::for_new_commit(&settings, tree3.id().clone())
         .set_parents(vec![commit_b.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let path4 = RepoPath::from_internal_string("file4");
     let tree4 = testutils::create_tree(repo, &[(&path4, "content")]);
     let commit_d = CommitBuilder::for_new_commit(&settings, tree4.id().clone())
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
 
     let mut rebaser = DescendantRebaser::new(
         &settings,
@@ -918,8 +926,9 @@ fn test_rebase_descendants_basic_branch_update() {
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
-    let commit_b2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_b).write_to_repo(tx.mut_repo());
+    let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     assert_eq!(
         tx.mut_repo().get_local_branch("main"),
@@ -958,10 +967,12 @@ fn test_rebase_descendants_branch_move_two_steps() {
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
-    let commit_b2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_b).write_to_repo(tx.mut_repo());
-    let commit_c2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_c).write_to_repo(tx.mut_repo());
+    let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    let commit_c2 = CommitBuilder::for_rewrite_from(&settings, &commit_c)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     let heads = tx.mut_repo().view().heads();
     assert_eq!(heads.len(), 1);
@@ -1005,8 +1016,9 @@ fn test_rebase_descendants_basic_branch_update_with_non_local_branch() {
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
-    let commit_b2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_b).write_to_repo(tx.mut_repo());
+    let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     assert_eq!(
         tx.mut_repo().get_local_branch("main"),
@@ -1087,16 +1099,19 @@ fn test_rebase_descendants_update_branches_after_divergent_rewrite() {
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
-    let commit_b2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_b).write_to_repo(tx.mut_repo());
+    let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     // Different description so they're not the same commit
     let commit_b3 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
         .set_description("different".to_string())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     // Different description so they're not the same commit
     let commit_b4 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
         .set_description("more different".to_string())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     assert_eq!(
         tx.mut_repo().get_local_branch("main"),
@@ -1144,18 +1159,22 @@ fn test_rebase_descendants_rewrite_updates_branch_conflict() {
     let repo = tx.commit();
 
     let mut tx = repo.start_transaction("test");
-    let commit_a2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_a).write_to_repo(tx.mut_repo());
+    let commit_a2 = CommitBuilder::for_rewrite_from(&settings, &commit_a)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     // Different description so they're not the same commit
     let commit_a3 = CommitBuilder::for_rewrite_from(&settings, &commit_a)
         .set_description("different".to_string())
-        .write_to_repo(tx.mut_repo());
-    let commit_b2 =
-        CommitBuilder::for_rewrite_from(&settings, &commit_b).write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     // Different description so they're not the same commit
     let commit_b3 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
         .set_description("different".to_string())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     assert_eq!(
         tx.mut_repo().get_local_branch("main"),
@@ -1211,7 +1230,8 @@ fn test_rebase_descendants_rewrite_resolves_branch_conflict() {
     let mut tx = repo.start_transaction("test");
     let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
         .set_parents(vec![commit_c.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     assert_eq!(
         tx.mut_repo().get_local_branch("main"),
@@ -1268,11 +1288,14 @@ fn test_rebase_descendants_update_checkout(use_git: bool) {
     // |/
     // A
     let mut tx = repo.start_transaction("test");
-    let commit_a = create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let commit_a = create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_b = create_random_commit(&settings, repo)
         .set_parents(vec![commit_a.id().clone()])
         .set_open(true)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let ws1_id = WorkspaceId::new("ws1".to_string());
     let ws2_id = WorkspaceId::new("ws2".to_string());
     let ws3_id = WorkspaceId::new("ws3".to_string());
@@ -1287,7 +1310,8 @@ fn test_rebase_descendants_update_checkout(use_git: bool) {
     let mut tx = repo.start_transaction("test");
     let commit_c = CommitBuilder::for_rewrite_from(&settings, &commit_b)
         .set_description("C".to_string())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     tx.mut_repo().rebase_descendants(&settings).unwrap();
     let repo = tx.commit();
 
@@ -1312,11 +1336,14 @@ fn test_rebase_descendants_update_checkout_abandoned(use_git: bool) {
     // |
     // A
     let mut tx = repo.start_transaction("test");
-    let commit_a = create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let commit_a = create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_b = create_random_commit(&settings, repo)
         .set_parents(vec![commit_a.id().clone()])
         .set_open(true)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let ws1_id = WorkspaceId::new("ws1".to_string());
     let ws2_id = WorkspaceId::new("ws2".to_string());
     let ws3_id = WorkspaceId::new("ws3".to_string());
@@ -1364,17 +1391,22 @@ fn test_rebase_descendants_update_checkout_abandoned_merge(use_git: bool) {
     // |/
     // A
     let mut tx = repo.start_transaction("test");
-    let commit_a = create_random_commit(&settings, repo).write_to_repo(tx.mut_repo());
+    let commit_a = create_random_commit(&settings, repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_b = create_random_commit(&settings, repo)
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_c = create_random_commit(&settings, repo)
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_d = create_random_commit(&settings, repo)
         .set_parents(vec![commit_b.id().clone(), commit_c.id().clone()])
         .set_open(true)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let workspace_id = WorkspaceId::default();
     tx.mut_repo()
         .set_checkout(workspace_id.clone(), commit_d.id().clone());
@@ -1390,3 +1422,162 @@ fn test_rebase_descendants_update_checkout_abandoned_merge(use_git: bool) {
     assert!(checkout.is_open());
     assert_eq!(checkout.parent_ids(), vec![commit_b.id().clone()]);
 }
+
+#[test_case(EmptyBehavior::Keep, false ; "keep, local backend")]
+#[test_case(EmptyBehavior::Keep, true ; "keep, git backend")]
+#[test_case(EmptyBehavior::AbandonNewlyEmpty, false ; "abandon newly empty, local backend")]
+#[test_case(EmptyBehavior::AbandonNewlyEmpty, true ; "abandon newly empty, git backend")]
+#[test_case(EmptyBehavior::AbandonAllEmpty, false ; "abandon all empty, local backend")]
+#[test_case(EmptyBehavior::AbandonAllEmpty, true ; "abandon all empty, git backend")]
+fn test_rebase_descendants_empty_behavior(empty: EmptyBehavior, use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    // Commit B was replaced by commit B2, which also makes the change from commit
+    // C, so C becomes empty when it's rebased. Commit D was already empty.
+    //
+    // E
+    // D
+    // C
+    // B B2
+    // |/
+    // A
+    let path_a = RepoPath::from_internal_string("a");
+    let path_b = RepoPath::from_internal_string("b");
+    let path_c = RepoPath::from_internal_string("c");
+    let path_e = RepoPath::from_internal_string("e");
+    let tree_a = testutils::create_tree(repo, &[(&path_a, "a")]);
+    let tree_b = testutils::create_tree(repo, &[(&path_a, "a"), (&path_b, "b")]);
+    let tree_c = testutils::create_tree(repo, &[(&path_a, "a"), (&path_b, "b"), (&path_c, "c")]);
+    let tree_e = testutils::create_tree(
+        repo,
+        &[
+            (&path_a, "a"),
+            (&path_b, "b"),
+            (&path_c, "c"),
+            (&path_e, "e"),
+        ],
+    );
+    let mut tx = repo.start_transaction("test");
+    let mut_repo = tx.mut_repo();
+    let mut write_commit = |tree: &Tree, parent: &Commit, description: &str| {
+        CommitBuilder::for_new_commit(&settings, tree.id().clone())
+            .set_parents(vec![parent.id().clone()])
+            .set_description(description.to_string())
+            .write_to_repo(mut_repo)
+            .unwrap()
+    };
+    let root_commit = repo.store().root_commit();
+    let commit_a = write_commit(&tree_a, &root_commit, "A");
+    let commit_b = write_commit(&tree_b, &commit_a, "B");
+    let commit_c = write_commit(&tree_c, &commit_b, "C");
+    let commit_d = write_commit(&tree_c, &commit_c, "D");
+    let commit_e = write_commit(&tree_e, &commit_d, "E");
+    mut_repo.set_local_branch("c".to_string(), RefTarget::Normal(commit_c.id().clone()));
+    let commit_b2 = CommitBuilder::for_rewrite_from(&settings, &commit_b)
+        .set_tree(tree_c.id().clone())
+        .write_to_repo(mut_repo)
+        .unwrap();
+
+    mut_repo.set_empty_behavior(empty);
+    let mut rebaser = mut_repo.create_descendant_rebaser(&settings);
+    let new_commit_e = match empty {
+        EmptyBehavior::Keep => {
+            let new_commit_c =
+                assert_rebased(rebaser.rebase_next().unwrap(), &commit_c, &[&commit_b2]);
+            let new_commit_d =
+                assert_rebased(rebaser.rebase_next().unwrap(), &commit_d, &[&new_commit_c]);
+            assert_rebased(rebaser.rebase_next().unwrap(), &commit_e, &[&new_commit_d])
+        }
+        EmptyBehavior::AbandonNewlyEmpty => {
+            let new_commit_d =
+                assert_rebased(rebaser.rebase_next().unwrap(), &commit_d, &[&commit_b2]);
+            assert_rebased(rebaser.rebase_next().unwrap(), &commit_e, &[&new_commit_d])
+        }
+        EmptyBehavior::AbandonAllEmpty => {
+            assert_rebased(rebaser.rebase_next().unwrap(), &commit_e, &[&commit_b2])
+        }
+    };
+    assert!(rebaser.rebase_next().unwrap().is_none());
+    assert_eq!(new_commit_e.tree_id(), tree_e.id());
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_e.id().clone()}
+    );
+    // The branch on the abandoned commit was moved to its new parent
+    if empty != EmptyBehavior::Keep {
+        assert_eq!(
+            tx.mut_repo().get_local_branch("c"),
+            Some(RefTarget::Normal(commit_b2.id().clone()))
+        );
+    }
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_rebase_descendants_abandoned_with_parents(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    // Commit B was rebased onto commit D and became empty, so it was abandoned.
+    // Commit C should be rebased onto commit D.
+    //
+    // C
+    // B D
+    // |/
+    // A
+    let mut tx = repo.start_transaction("test");
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_a]);
+
+    tx.mut_repo()
+        .record_abandoned_commit_with_parents(commit_b.id().clone(), vec![commit_d.id().clone()]);
+    let mut rebaser = tx.mut_repo().create_descendant_rebaser(&settings);
+    let new_commit_c = assert_rebased(rebaser.rebase_next().unwrap(), &commit_c, &[&commit_d]);
+    assert!(rebaser.rebase_next().unwrap().is_none());
+    assert_eq!(
+        *tx.mut_repo().view().heads(),
+        hashset! {new_commit_c.id().clone()}
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_conflict_label_for_commit(use_git: bool) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction("test");
+    let commit = create_random_commit(&settings, repo)
+        .set_description("first line\nsecond line\n".to_string())
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    assert_eq!(
+        conflict_label_for_commit(&commit),
+        format!(
+            "{} (commit {}) \"first line\"",
+            &commit.change_id().hex()[..12],
+            &commit.id().hex()[..12]
+        )
+    );
+
+    // An empty description is left out
+    let commit = create_random_commit(&settings, repo)
+        .set_description(String::new())
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
+    assert_eq!(
+        conflict_label_for_commit(&commit),
+        format!(
+            "{} (commit {})",
+            &commit.change_id().hex()[..12],
+            &commit.id().hex()[..12]
+        )
+    );
+}
@@ -0,0 +1,194 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use jujutsu_lib::backend::{self, Backend, BackendError, ChangeId};
+use jujutsu_lib::git_backend::GitBackend;
+use jujutsu_lib::local_backend::LocalBackend;
+use jujutsu_lib::signing::{SigStatus, SignResult, Signer, SigningBackend, Verification};
+use jujutsu_lib::store::{MergeSettings, Store};
+use jujutsu_lib::testutils;
+use tempfile::TempDir;
+use test_case::test_case;
+
+/// Signs by appending the reversed data to a marker and the key
+#[derive(Debug)]
+struct TestSigningBackend;
+
+impl SigningBackend for TestSigningBackend {
+    fn name(&self) -> &str {
+        "test"
+    }
+
+    fn can_read(&self, sig: &[u8]) -> bool {
+        sig.starts_with(b"test:")
+    }
+
+    fn sign(&self, data: &[u8], key: Option<&str>) -> SignResult<Vec<u8>> {
+        let mut sig = format!("test:{}:", key.unwrap()).into_bytes();
+        sig.extend(data.iter().rev());
+        Ok(sig)
+    }
+
+    fn verify(&self, data: &[u8], sig: &[u8]) -> SignResult<Verification> {
+        let key_end = sig[5..].iter().position(|b| *b == b':').unwrap() + 5;
+        let key = std::str::from_utf8(&sig[5..key_end]).unwrap();
+        let reversed_data = &sig[key_end + 1..];
+        let status = if reversed_data.iter().rev().eq(data.iter()) {
+            SigStatus::Good
+        } else {
+            SigStatus::Bad
+        };
+        Ok(Verification {
+            status,
+            key: Some(key.to_string()),
+            display: None,
+        })
+    }
+}
+
+fn init_store(temp_dir: &TempDir, use_git: bool, sign_all: bool) -> Arc<Store> {
+    let store_path = temp_dir.path().join("store");
+    std::fs::create_dir(&store_path).unwrap();
+    let backend: Box<dyn Backend> = if use_git {
+        Box::new(GitBackend::init_internal(store_path))
+    } else {
+        Box::new(LocalBackend::init(store_path))
+    };
+    let signer = Signer::new(
+        Some("test".to_string()),
+        Some("some-key".to_string()),
+        sign_all,
+        vec![Box::new(TestSigningBackend)],
+    );
+    Store::new_with_settings(backend, MergeSettings::default(), signer)
+}
+
+fn new_commit(store: &Store, description: &str) -> backend::Commit {
+    let signature = testutils::user_settings().signature();
+    backend::Commit {
+        parents: vec![],
+        predecessors: vec![],
+        root_tree: store.empty_tree_id().clone(),
+        change_id: ChangeId::new(vec![1; 16]),
+        description: description.to_string(),
+        author: signature.clone(),
+        committer: signature,
+        is_open: false,
+        secure_sig: None,
+        copied_from: None,
+    }
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_sign_and_verify(use_git: bool) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let store = init_store(&temp_dir, use_git, true);
+
+    let commit = store.write_commit(new_commit(&store, "signed")).unwrap();
+    assert!(commit.is_signed());
+    let expected_verification = Verification {
+        status: SigStatus::Good,
+        key: Some("some-key".to_string()),
+        display: None,
+    };
+    assert_eq!(
+        commit.verification().unwrap().unwrap(),
+        expected_verification
+    );
+
+    // The signature survives a roundtrip through the backend
+    let read_commit = store.get_commit(commit.id()).unwrap();
+    assert_eq!(
+        read_commit.store_commit().secure_sig,
+        commit.store_commit().secure_sig
+    );
+    assert_eq!(
+        read_commit.verification().unwrap().unwrap(),
+        expected_verification
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_verify_bad_signature(use_git: bool) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let store = init_store(&temp_dir, use_git, true);
+
+    let commit = store.write_commit(new_commit(&store, "signed")).unwrap();
+    let mut secure_sig = commit.store_commit().secure_sig.clone().unwrap();
+    secure_sig.data.push(b'x');
+    assert_eq!(
+        store
+            .signer()
+            .verify(&secure_sig.data, &secure_sig.sig)
+            .unwrap()
+            .status,
+        SigStatus::Bad
+    );
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_sign_all_disabled(use_git: bool) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let store = init_store(&temp_dir, use_git, false);
+
+    let commit = store.write_commit(new_commit(&store, "unsigned")).unwrap();
+    assert!(!commit.is_signed());
+    assert!(commit.verification().is_none());
+    let read_commit = store.get_commit(commit.id()).unwrap();
+    assert_eq!(read_commit.store_commit().secure_sig, None);
+}
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_sign_failure(use_git: bool) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let store_path = temp_dir.path().join("store");
+    std::fs::create_dir(&store_path).unwrap();
+    let backend: Box<dyn Backend> = if use_git {
+        Box::new(GitBackend::init_internal(store_path))
+    } else {
+        Box::new(LocalBackend::init(store_path))
+    };
+    let signer = Signer::new(
+        Some("unknown".to_string()),
+        None,
+        true,
+        vec![Box::new(TestSigningBackend)],
+    );
+    let store = Store::new_with_settings(backend, MergeSettings::default(), signer);
+
+    // The commit isn't written if it can't be signed
+    assert_eq!(
+        store
+            .write_commit(new_commit(&store, "unsigned"))
+            .unwrap_err(),
+        BackendError::Other("Failed to sign commit: Unknown signing backend 'unknown'".to_string())
+    );
+}
+
+#[test]
+fn test_verify_unrecognized_signature() {
+    let signer = Signer::new(None, None, false, vec![Box::new(TestSigningBackend)]);
+    assert_eq!(
+        signer.verify(b"data", b"some other signature").unwrap(),
+        Verification::unknown()
+    );
+    // Signing fails without a configured backend
+    assert!(signer.sign(b"data").is_err());
+}
@@ -90,17 +90,26 @@ fn test_merge_views_heads() {
 
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
-    let head_unchanged = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let head_remove_tx1 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let head_remove_tx2 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let public_head_unchanged =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let head_unchanged = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let head_remove_tx1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let head_remove_tx2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let public_head_unchanged = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.add_public_head(&public_head_unchanged);
-    let public_head_remove_tx1 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let public_head_remove_tx1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.add_public_head(&public_head_remove_tx1);
-    let public_head_remove_tx2 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let public_head_remove_tx2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.add_public_head(&public_head_remove_tx2);
     let repo = tx.commit();
 
@@ -108,10 +117,12 @@ fn test_merge_views_heads() {
     tx1.mut_repo().remove_head(head_remove_tx1.id());
     tx1.mut_repo()
         .remove_public_head(public_head_remove_tx1.id());
-    let head_add_tx1 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
-    let public_head_add_tx1 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let head_add_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
+    let public_head_add_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo().add_public_head(&public_head_add_tx1);
     tx1.commit();
 
@@ -119,10 +130,12 @@ fn test_merge_views_heads() {
     tx2.mut_repo().remove_head(head_remove_tx2.id());
     tx2.mut_repo()
         .remove_public_head(public_head_remove_tx2.id());
-    let head_add_tx2 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx2.mut_repo());
-    let public_head_add_tx2 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx2.mut_repo());
+    let head_add_tx2 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
+    let public_head_add_tx2 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     tx2.mut_repo().add_public_head(&public_head_add_tx2);
     tx2.commit();
 
@@ -163,12 +176,15 @@ fn test_merge_views_checkout() {
     // Workspace 6 gets added in tx1.
     // Workspace 7 gets added in tx2.
     let mut initial_tx = repo.start_transaction("test");
-    let commit1 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(initial_tx.mut_repo());
-    let commit2 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(initial_tx.mut_repo());
-    let commit3 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(initial_tx.mut_repo());
+    let commit1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(initial_tx.mut_repo())
+        .unwrap();
+    let commit2 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(initial_tx.mut_repo())
+        .unwrap();
+    let commit3 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(initial_tx.mut_repo())
+        .unwrap();
     let ws1_id = WorkspaceId::new("ws1".to_string());
     let ws2_id = WorkspaceId::new("ws2".to_string());
     let ws3_id = WorkspaceId::new("ws3".to_string());
@@ -243,14 +259,18 @@ fn test_merge_views_branches() {
 
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
-    let main_branch_local_tx0 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let main_branch_origin_tx0 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let main_branch_origin_tx1 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
-    let main_branch_alternate_tx0 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let main_branch_local_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let main_branch_origin_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let main_branch_origin_tx1 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
+    let main_branch_alternate_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_local_branch(
         "main".to_string(),
         RefTarget::Normal(main_branch_local_tx0.id().clone()),
@@ -265,8 +285,9 @@ fn test_merge_views_branches() {
         "alternate".to_string(),
         RefTarget::Normal(main_branch_alternate_tx0.id().clone()),
     );
-    let feature_branch_local_tx0 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let feature_branch_local_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_git_ref(
         "feature".to_string(),
         RefTarget::Normal(feature_branch_local_tx0.id().clone()),
@@ -274,8 +295,9 @@ fn test_merge_views_branches() {
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("test");
-    let main_branch_local_tx1 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let main_branch_local_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo().set_local_branch(
         "main".to_string(),
         RefTarget::Normal(main_branch_local_tx1.id().clone()),
@@ -285,8 +307,9 @@ fn test_merge_views_branches() {
         "origin".to_string(),
         RefTarget::Normal(main_branch_origin_tx1.id().clone()),
     );
-    let feature_branch_tx1 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let feature_branch_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo().set_local_branch(
         "feature".to_string(),
         RefTarget::Normal(feature_branch_tx1.id().clone()),
@@ -294,8 +317,9 @@ fn test_merge_views_branches() {
     tx1.commit();
 
     let mut tx2 = repo.start_transaction("test");
-    let main_branch_local_tx2 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx2.mut_repo());
+    let main_branch_local_tx2 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     tx2.mut_repo().set_local_branch(
         "main".to_string(),
         RefTarget::Normal(main_branch_local_tx2.id().clone()),
@@ -344,23 +368,33 @@ fn test_merge_views_tags() {
 
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
-    let v1_tx0 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let v1_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_tag("v1.0".to_string(), RefTarget::Normal(v1_tx0.id().clone()));
-    let v2_tx0 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let v2_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_tag("v2.0".to_string(), RefTarget::Normal(v2_tx0.id().clone()));
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("test");
-    let v1_tx1 = testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let v1_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo()
         .set_tag("v1.0".to_string(), RefTarget::Normal(v1_tx1.id().clone()));
-    let v2_tx1 = testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let v2_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo()
         .set_tag("v2.0".to_string(), RefTarget::Normal(v2_tx1.id().clone()));
     tx1.commit();
 
     let mut tx2 = repo.start_transaction("test");
-    let v1_tx2 = testutils::create_random_commit(&settings, &repo).write_to_repo(tx2.mut_repo());
+    let v1_tx2 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     tx2.mut_repo()
         .set_tag("v1.0".to_string(), RefTarget::Normal(v1_tx2.id().clone()));
     tx2.commit();
@@ -390,13 +424,16 @@ fn test_merge_views_git_refs() {
 
     let mut tx = repo.start_transaction("test");
     let mut_repo = tx.mut_repo();
-    let main_branch_tx0 = testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let main_branch_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_git_ref(
         "refs/heads/main".to_string(),
         RefTarget::Normal(main_branch_tx0.id().clone()),
     );
-    let feature_branch_tx0 =
-        testutils::create_random_commit(&settings, repo).write_to_repo(mut_repo);
+    let feature_branch_tx0 = testutils::create_random_commit(&settings, repo)
+        .write_to_repo(mut_repo)
+        .unwrap();
     mut_repo.set_git_ref(
         "refs/heads/feature".to_string(),
         RefTarget::Normal(feature_branch_tx0.id().clone()),
@@ -404,14 +441,16 @@ fn test_merge_views_git_refs() {
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("test");
-    let main_branch_tx1 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let main_branch_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo().set_git_ref(
         "refs/heads/main".to_string(),
         RefTarget::Normal(main_branch_tx1.id().clone()),
     );
-    let feature_branch_tx1 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx1.mut_repo());
+    let feature_branch_tx1 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
     tx1.mut_repo().set_git_ref(
         "refs/heads/feature".to_string(),
         RefTarget::Normal(feature_branch_tx1.id().clone()),
@@ -419,8 +458,9 @@ fn test_merge_views_git_refs() {
     tx1.commit();
 
     let mut tx2 = repo.start_transaction("test");
-    let main_branch_tx2 =
-        testutils::create_random_commit(&settings, &repo).write_to_repo(tx2.mut_repo());
+    let main_branch_tx2 = testutils::create_random_commit(&settings, &repo)
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     tx2.mut_repo().set_git_ref(
         "refs/heads/main".to_string(),
         RefTarget::Normal(main_branch_tx2.id().clone()),
@@ -468,19 +508,22 @@ fn test_merge_views_child_on_rewritten(child_first: bool) {
     let test_repo = TestRepo::init(false);
 
     let mut tx = test_repo.repo.start_transaction("test");
-    let commit_a =
-        testutils::create_random_commit(&settings, &test_repo.repo).write_to_repo(tx.mut_repo());
+    let commit_a = testutils::create_random_commit(&settings, &test_repo.repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("test");
     let commit_b = testutils::create_random_commit(&settings, &repo)
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx1.mut_repo());
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
 
     let mut tx2 = repo.start_transaction("test");
     let commit_a2 = CommitBuilder::for_rewrite_from(&settings, &commit_a)
         .set_description("A2".to_string())
-        .write_to_repo(tx2.mut_repo());
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     tx2.mut_repo().rebase_descendants(&settings).unwrap();
 
     let repo = if child_first {
@@ -511,23 +554,27 @@ fn test_merge_views_child_on_rewritten_divergent(on_rewritten: bool, child_first
     let test_repo = TestRepo::init(false);
 
     let mut tx = test_repo.repo.start_transaction("test");
-    let commit_a2 =
-        testutils::create_random_commit(&settings, &test_repo.repo).write_to_repo(tx.mut_repo());
+    let commit_a2 = testutils::create_random_commit(&settings, &test_repo.repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_a3 = testutils::create_random_commit(&settings, &test_repo.repo)
         .set_change_id(commit_a2.change_id().clone())
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("test");
     let parent = if on_rewritten { &commit_a2 } else { &commit_a3 };
     let commit_b = testutils::create_random_commit(&settings, &repo)
         .set_parents(vec![parent.id().clone()])
-        .write_to_repo(tx1.mut_repo());
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
 
     let mut tx2 = repo.start_transaction("test");
     let commit_a4 = CommitBuilder::for_rewrite_from(&settings, &commit_a2)
         .set_description("A4".to_string())
-        .write_to_repo(tx2.mut_repo());
+        .write_to_repo(tx2.mut_repo())
+        .unwrap();
     tx2.mut_repo().rebase_descendants(&settings).unwrap();
 
     let repo = if child_first {
@@ -563,17 +610,20 @@ fn test_merge_views_child_on_abandoned(child_first: bool) {
     let test_repo = TestRepo::init(false);
 
     let mut tx = test_repo.repo.start_transaction("test");
-    let commit_a =
-        testutils::create_random_commit(&settings, &test_repo.repo).write_to_repo(tx.mut_repo());
+    let commit_a = testutils::create_random_commit(&settings, &test_repo.repo)
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let commit_b = testutils::create_random_commit(&settings, &test_repo.repo)
         .set_parents(vec![commit_a.id().clone()])
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())
+        .unwrap();
     let repo = tx.commit();
 
     let mut tx1 = repo.start_transaction("test");
     let commit_c = testutils::create_random_commit(&settings, &repo)
         .set_parents(vec![commit_b.id().clone()])
-        .write_to_repo(tx1.mut_repo());
+        .write_to_repo(tx1.mut_repo())
+        .unwrap();
 
     let mut tx2 = repo.start_transaction("test");
     tx2.mut_repo()
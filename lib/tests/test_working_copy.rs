@@ -124,6 +124,7 @@ fn test_checkout_file_transitions(use_git: bool) {
                             id: base_file_id,
                             executable: false,
                         },
+                        label: None,
                     }],
                     adds: vec![
                         ConflictPart {
@@ -131,12 +132,14 @@ fn test_checkout_file_transitions(use_git: bool) {
                                 id: left_file_id,
                                 executable: false,
                             },
+                            label: None,
                         },
                         ConflictPart {
                             value: TreeValue::Normal {
                                 id: right_file_id,
                                 executable: false,
                             },
+                            label: None,
                         },
                     ],
                 };
@@ -164,6 +167,7 @@ fn test_checkout_file_transitions(use_git: bool) {
                 let mut tx = repo.start_transaction("test");
                 let id = testutils::create_random_commit(settings, repo)
                     .write_to_repo(tx.mut_repo())
+                    .unwrap()
                     .id()
                     .clone();
                 tx.commit();
@@ -17,14 +17,16 @@ extern crate clap;
 extern crate clap_mangen;
 extern crate config;
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::OsString;
 use std::fmt::Debug;
 use std::fs::OpenOptions;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use std::{fs, io};
 
@@ -33,42 +35,57 @@ use config::Value;
 use criterion::Criterion;
 use git2::{Oid, Repository};
 use itertools::Itertools;
-use jujutsu_lib::backend::{BackendError, CommitId, Timestamp, TreeId, TreeValue};
+use jujutsu_lib::backend::{
+    BackendError, CommitId, ConflictId, FileId, Timestamp, TreeId, TreeValue,
+};
+use jujutsu_lib::bisect::{BisectState, BisectionStep};
 use jujutsu_lib::commit::Commit;
 use jujutsu_lib::commit_builder::CommitBuilder;
+use jujutsu_lib::conflicts::ConflictMarkerStyle;
 use jujutsu_lib::dag_walk::topo_order_reverse;
 use jujutsu_lib::diff::{Diff, DiffHunk};
 use jujutsu_lib::files::DiffLine;
 use jujutsu_lib::git::{GitExportError, GitFetchError, GitImportError, GitRefUpdate};
+use jujutsu_lib::gitattributes::GitAttributesFile;
 use jujutsu_lib::gitignore::GitIgnoreFile;
 use jujutsu_lib::index::{HexPrefix, IndexEntry};
 use jujutsu_lib::matchers::{EverythingMatcher, Matcher, PrefixMatcher, Visit};
 use jujutsu_lib::op_heads_store::{OpHeadResolutionError, OpHeads, OpHeadsStore};
 use jujutsu_lib::op_store::{OpStore, OpStoreError, OperationId, RefTarget, WorkspaceId};
 use jujutsu_lib::operation::Operation;
+use jujutsu_lib::patch::Patch;
 use jujutsu_lib::refs::{classify_branch_push_action, BranchPushAction, BranchPushUpdate};
 use jujutsu_lib::repo::{MutableRepo, ReadonlyRepo, RepoRef};
 use jujutsu_lib::repo_path::RepoPath;
 use jujutsu_lib::revset::{RevsetError, RevsetExpression, RevsetParseError};
 use jujutsu_lib::revset_graph_iterator::{RevsetGraphEdge, RevsetGraphEdgeType};
-use jujutsu_lib::rewrite::{back_out_commit, merge_commit_trees, rebase_commit, DescendantRebaser};
+use jujutsu_lib::rewrite::{
+    back_out_commit, conflict_label_for_commit, conflict_label_for_commits,
+    conflict_labels_for_rebase, merge_commit_trees, rebase_commit,
+    rebase_commit_with_empty_behavior, DescendantRebaser, EmptyBehavior,
+};
 use jujutsu_lib::settings::UserSettings;
 use jujutsu_lib::store::Store;
 use jujutsu_lib::transaction::Transaction;
-use jujutsu_lib::tree::{merge_trees, Tree, TreeDiffIterator, TreeMergeError};
+use jujutsu_lib::tree::{
+    merge_trees_reporting_resolutions, merge_trees_with_labels, ConflictLabels, Tree,
+    TreeDiffIterator, TreeMergeError,
+};
 use jujutsu_lib::view::View;
 use jujutsu_lib::working_copy::{
-    CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, WorkingCopy,
+    CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, TreeState, WorkingCopy,
 };
 use jujutsu_lib::workspace::{Workspace, WorkspaceInitError, WorkspaceLoadError};
-use jujutsu_lib::{conflicts, dag_walk, diff, files, git, revset, tree};
+use jujutsu_lib::{
+    absorb, conflicts, dag_walk, diff, files, git, line_origins, rerere, revset, tree,
+};
 use maplit::{hashmap, hashset};
 use pest::Parser;
 
-use self::chrono::{FixedOffset, TimeZone, Utc};
+use self::chrono::{DateTime, FixedOffset, TimeZone, Utc};
 use crate::commands::CommandError::UserError;
-use crate::diff_edit::DiffEditError;
-use crate::formatter::Formatter;
+use crate::diff_edit::{ConflictResolveError, DiffEditError, DiffGenerateError, MergeTool};
+use crate::formatter::{Formatter, PlainTextFormatter};
 use crate::graphlog::{AsciiGraphDrawer, Edge};
 use crate::template_parser::TemplateParser;
 use crate::templater::Template;
@@ -107,8 +124,13 @@ impl From<BackendError> for CommandError {
 }
 
 impl From<WorkspaceInitError> for CommandError {
-    fn from(_: WorkspaceInitError) -> Self {
-        CommandError::UserError("The target repo already exists".to_string())
+    fn from(err: WorkspaceInitError) -> Self {
+        match err {
+            WorkspaceInitError::DestinationExists(_) => {
+                CommandError::UserError("The target repo already exists".to_string())
+            }
+            WorkspaceInitError::Backend(err) => CommandError::from(err),
+        }
     }
 }
 
@@ -146,6 +168,18 @@ impl From<DiffEditError> for CommandError {
     }
 }
 
+impl From<ConflictResolveError> for CommandError {
+    fn from(err: ConflictResolveError) -> Self {
+        CommandError::UserError(format!("Failed to use external tool to resolve: {err}"))
+    }
+}
+
+impl From<DiffGenerateError> for CommandError {
+    fn from(err: DiffGenerateError) -> Self {
+        CommandError::UserError(format!("Failed to generate diff: {err}"))
+    }
+}
+
 impl From<git2::Error> for CommandError {
     fn from(err: git2::Error) -> Self {
         CommandError::UserError(format!("Git operation failed: {err}"))
@@ -386,7 +420,7 @@ impl WorkspaceCommandHelper {
                     .store()
                     .get_commit(new_git_head.as_ref().unwrap())?;
                 tx.mut_repo()
-                    .check_out(workspace_id, &self.settings, &new_checkout);
+                    .check_out(workspace_id, &self.settings, &new_checkout)?;
                 // The working copy was presumably updated by the git command that updated HEAD,
                 // so we just need to reset our working copy state to it without updating
                 // working copy files.
@@ -661,7 +695,8 @@ impl WorkspaceCommandHelper {
             let mut_repo = tx.mut_repo();
             let commit = CommitBuilder::for_rewrite_from(&self.settings, &checkout_commit)
                 .set_tree(new_tree_id)
-                .write_to_repo(mut_repo);
+                .write_to_repo(mut_repo)?;
+            rerere::record_resolutions_in_rewrite(&checkout_commit, &commit);
             mut_repo.set_checkout(workspace_id, commit.id().clone());
 
             // Rebase descendants
@@ -778,6 +813,13 @@ impl WorkspaceCommandHelper {
         if num_rebased > 0 {
             writeln!(ui, "Rebased {} descendant commits", num_rebased)?;
         }
+        let reused_resolutions = mut_repo.reused_resolutions();
+        if !reused_resolutions.is_empty() {
+            writeln!(ui, "Resolved conflicts using recorded resolutions in:")?;
+            for path in reused_resolutions.iter().sorted().dedup() {
+                writeln!(ui, "    {}", self.format_file_path(path))?;
+            }
+        }
         if self.working_copy_shared_with_git {
             self.export_head_to_git(mut_repo)?;
         }
@@ -1096,7 +1138,9 @@ enum Commands {
     Untrack(UntrackArgs),
     Files(FilesArgs),
     Print(PrintArgs),
+    Annotate(AnnotateArgs),
     Diff(DiffArgs),
+    Interdiff(InterdiffArgs),
     Show(ShowArgs),
     Status(StatusArgs),
     Log(LogArgs),
@@ -1108,15 +1152,27 @@ enum Commands {
     Abandon(AbandonArgs),
     Edit(EditArgs),
     New(NewArgs),
+    Next(NextArgs),
+    Prev(PrevArgs),
     Move(MoveArgs),
     Squash(SquashArgs),
     Unsquash(UnsquashArgs),
+    Absorb(AbsorbArgs),
     Restore(RestoreArgs),
+    Resolve(ResolveArgs),
+    Fix(FixArgs),
+    Run(RunArgs),
+    #[clap(subcommand)]
+    Bisect(BisectCommands),
     Touchup(TouchupArgs),
     Split(SplitArgs),
     Merge(MergeArgs),
     Rebase(RebaseArgs),
+    Parallelize(ParallelizeArgs),
+    Histedit(HisteditArgs),
     Backout(BackoutArgs),
+    ExportPatch(ExportPatchArgs),
+    Apply(ApplyArgs),
     #[clap(subcommand)]
     Branch(BranchSubcommand),
     /// Undo an operation (shortcut for `jj op undo`)
@@ -1198,8 +1254,26 @@ struct PrintArgs {
     path: String,
 }
 
+/// Show the revision that introduced each line of a file
+///
+/// The history is walked back from the given revision, through all parents of
+/// merges, to find the revision that added each line. Renames are not
+/// followed.
+#[derive(clap::Args, Clone, Debug)]
+struct AnnotateArgs {
+    /// The revision to annotate the file in
+    #[clap(long, short, default_value = "@")]
+    revision: String,
+    /// Render the revision of each line using the given template (the syntax
+    /// is not yet documented and is likely to change)
+    #[clap(long, short = 'T')]
+    template: Option<String>,
+    /// The file to annotate
+    path: String,
+}
+
 #[derive(clap::Args, Clone, Debug)]
-#[clap(group(ArgGroup::new("format").args(&["summary", "git", "color-words"])))]
+#[clap(group(ArgGroup::new("format").args(&["summary", "git", "color-words", "side-by-side", "tool"])))]
 struct DiffFormatArgs {
     /// For each path, show only whether it was modified, added, or removed
     #[clap(long, short)]
@@ -1210,6 +1284,14 @@ struct DiffFormatArgs {
     /// Show a word-level diff with changes indicated only by color
     #[clap(long)]
     color_words: bool,
+    /// Show the old and new versions of the lines next to each other
+    ///
+    /// Falls back to a Git-format diff if the terminal is too narrow.
+    #[clap(long)]
+    side_by_side: bool,
+    /// Generate diff by external command
+    #[clap(long)]
+    tool: Option<String>,
 }
 
 /// Show changes in a revision
@@ -1240,6 +1322,26 @@ struct DiffArgs {
     format: DiffFormatArgs,
 }
 
+/// Compare the changes of two commits
+///
+/// This excludes changes from other commits by temporarily rebasing `--from`
+/// onto `--to`'s parents. If you wish to compare the same change across
+/// versions, consider `jj obslog -p` instead.
+#[derive(clap::Args, Clone, Debug)]
+#[clap(group(ArgGroup::new("to_diff").args(&["from", "to"]).multiple(true).required(true)))]
+struct InterdiffArgs {
+    /// Show changes from this revision
+    #[clap(long)]
+    from: Option<String>,
+    /// Show changes to this revision
+    #[clap(long)]
+    to: Option<String>,
+    /// Restrict the diff to these paths
+    paths: Vec<String>,
+    #[clap(flatten)]
+    format: DiffFormatArgs,
+}
+
 /// Show commit description and changes in a revision
 #[derive(clap::Args, Clone, Debug)]
 struct ShowArgs {
@@ -1360,15 +1462,27 @@ struct OpenArgs {
     revision: String,
 }
 
-/// Create a new change with the same content as an existing one
+/// Create new changes with the same content as existing ones
+///
+/// The copies keep the structure of the duplicated revisions: if a revision's
+/// parent is also duplicated, the copy of the revision goes on top of the copy
+/// of the parent. Other parents are kept, so the copies have the same content
+/// as the originals, unless `--destination` is given. Then the copies are
+/// rebased onto the destinations instead, like cherry-picking them.
+///
+/// The copies get new change ids and record which revision they were copied
+/// from, so they are not considered rewrites of the originals.
 ///
 /// For information about open/closed revisions, see
 /// https://github.com/martinvonz/jj/blob/main/docs/working-copy.md.
 #[derive(clap::Args, Clone, Debug)]
 struct DuplicateArgs {
-    /// The revision to duplicate
+    /// The revision(s) to duplicate
     #[clap(default_value = "@")]
-    revision: String,
+    revisions: Vec<String>,
+    /// The revision(s) to put the copies on top of
+    #[clap(long, short)]
+    destination: Vec<String>,
 }
 
 /// Abandon a revision
@@ -1407,6 +1521,44 @@ struct NewArgs {
     message: String,
 }
 
+/// Move the working copy to a child revision
+///
+/// By default, a new working-copy commit is created on top of the child of
+/// the working copy's parent, so `jj next` walks up a stack of revisions one
+/// revision at a time. With `--edit`, the child of the working-copy commit is
+/// edited directly instead.
+///
+/// If there are several children to choose from, you will be asked which one
+/// to go to.
+#[derive(clap::Args, Clone, Debug)]
+struct NextArgs {
+    /// How many revisions to move forward
+    #[clap(default_value = "1")]
+    amount: u64,
+    /// Edit the child revision instead of creating a new commit on top of it
+    #[clap(long, short)]
+    edit: bool,
+}
+
+/// Move the working copy to a parent revision
+///
+/// By default, a new working-copy commit is created on top of the parent of
+/// the working copy's parent, so `jj prev` walks down a stack of revisions one
+/// revision at a time. With `--edit`, the parent of the working-copy commit is
+/// edited directly instead.
+///
+/// If there are several parents to choose from, you will be asked which one
+/// to go to.
+#[derive(clap::Args, Clone, Debug)]
+struct PrevArgs {
+    /// How many revisions to move backward
+    #[clap(default_value = "1")]
+    amount: u64,
+    /// Edit the parent revision instead of creating a new commit on top of it
+    #[clap(long, short)]
+    edit: bool,
+}
+
 /// Move changes from one revision into another
 ///
 /// Use `--interactive` to move only part of the source revision into the
@@ -1470,6 +1622,29 @@ struct UnsquashArgs {
     interactive: bool,
 }
 
+/// Move changes from a revision into the ancestors that last modified the same
+/// lines
+///
+/// Each hunk in the source revision is moved into the closest ancestor among
+/// the `--into` revisions that last modified the lines around it. Descendants
+/// of the rewritten revisions are rebased, so the source revision keeps its
+/// content. Hunks that can't be attributed to a single such ancestor stay in
+/// the source revision. Only changes to files that exist in both the source
+/// revision and its parent are considered.
+#[derive(clap::Args, Clone, Debug)]
+struct AbsorbArgs {
+    /// The revision to move changes from
+    #[clap(long, short, default_value = "@")]
+    from: String,
+    /// The revisions that changes may be moved into
+    ///
+    /// Only ancestors of the source revision are considered.
+    #[clap(long, short = 't', default_value = "all() ~ :public_heads()")]
+    into: String,
+    /// Move only changes to these paths (instead of all paths)
+    paths: Vec<String>,
+}
+
 /// Restore paths from another revision
 ///
 /// That means that the paths get the same content in the destination (`--to`)
@@ -1495,14 +1670,165 @@ struct RestoreArgs {
     paths: Vec<String>,
 }
 
+/// Resolve conflicted files with an external merge tool
+///
+/// Runs the merge tool configured by `ui.merge-editor` (`meld` by default) on
+/// each conflict between two sides of a regular file. The tool is given the
+/// base, the two sides, and an output file to write the resolution to. The
+/// result is written to the revision as the new content of the file.
+/// Conflicts the tool can't handle are skipped. If the tool fails, the
+/// conflicts resolved until then are kept.
+///
+/// Conflicts in binary files are not written to the working copy with conflict
+/// markers. Instead, the file in the working copy describes the conflict and
+/// numbers its sides. Use `--side` to pick one of those sides as the
+/// resolution. This works for other conflicts too.
+#[derive(clap::Args, Clone, Debug)]
+struct ResolveArgs {
+    /// The revision to resolve conflicts in
+    #[clap(long, short, default_value = "@")]
+    revision: String,
+    /// Instead of resolving conflicts, list all the conflicts
+    #[clap(long, short, conflicts_with = "side")]
+    list: bool,
+    /// Pick this side as the resolution instead of running a merge tool
+    /// (numbered from 1)
+    #[clap(long)]
+    side: Option<usize>,
+    /// Only resolve conflicts in these paths (instead of all paths)
+    paths: Vec<String>,
+}
+
+/// Run configured formatters on the files changed in revisions
+///
+/// Tools are configured in `[fix.tools.<name>]` with a `program`, its `args`,
+/// and the `patterns` (in `.gitignore` syntax) of the files it applies to. In
+/// `args`, `$path` is replaced by the path of the file in the repo. Each file
+/// that was added or modified in a revision is passed to the matching tools on
+/// stdin, and replaced by their output. Tools run in order of their names. If
+/// a tool fails, the file is left as it was.
+///
+/// The revisions are rewritten with the new file contents, and their
+/// descendants are rebased. The working copy is only updated to the rewritten
+/// working-copy revision.
+#[derive(clap::Args, Clone, Debug)]
+struct FixArgs {
+    /// The revisions to fix
+    #[clap(long, short, default_value = ":@ ~ :public_heads()")]
+    revisions: String,
+}
+
+/// Run a command on each of a set of revisions
+///
+/// Each revision is checked out into its own temporary directory, where the
+/// command is run. The working copy is not touched. The command's output is
+/// printed after it has run on all the revisions, followed by a table of which
+/// revisions it passed and failed on. `jj run` fails if the command failed on
+/// any of the revisions.
+///
+/// With `--rewrite`, the revisions the command passed on are rewritten with
+/// any changes it made to the files, and their descendants are rebased.
+#[derive(clap::Args, Clone, Debug)]
+struct RunArgs {
+    /// The revisions to run the command on
+    #[clap(long, short, default_value = "@")]
+    revisions: String,
+    /// How many revisions to run the command on in parallel
+    #[clap(long, short, default_value = "1")]
+    jobs: usize,
+    /// Rewrite the revisions with the changes the command made to their files
+    #[clap(long)]
+    rewrite: bool,
+    /// The command to run, and its arguments
+    #[clap(required = true, last = true)]
+    command: Vec<String>,
+}
+
+/// Find the revision that introduced a bug by binary search
+///
+/// Start a bisection with `jj bisect start`, then mark a revision that has the
+/// bug with `jj bisect bad` and one that doesn't with `jj bisect good`. A
+/// revision between them is then checked out for you to test and mark as good
+/// or bad, until the first bad revision is found. Revisions that can't be
+/// tested can be marked with `jj bisect skip`. `jj bisect run` tests the
+/// revisions by running a command on them instead.
+#[derive(Subcommand, Clone, Debug)]
+enum BisectCommands {
+    Start(BisectStartArgs),
+    Good(BisectGoodArgs),
+    Bad(BisectBadArgs),
+    Skip(BisectSkipArgs),
+    Run(BisectRunArgs),
+    Reset(BisectResetArgs),
+}
+
+/// Start a new bisection, forgetting any bisection in progress
+#[derive(clap::Args, Clone, Debug)]
+struct BisectStartArgs {
+    /// A revision that has the bug
+    #[clap(long)]
+    bad: Option<String>,
+    /// Revisions that don't have the bug
+    #[clap(long)]
+    good: Vec<String>,
+}
+
+/// Mark revisions as not having the bug
+///
+/// Their ancestors are then assumed not to have it either.
+#[derive(clap::Args, Clone, Debug)]
+struct BisectGoodArgs {
+    /// The revisions to mark (default: the revision being tested, or `@` if
+    /// none)
+    revisions: Vec<String>,
+}
+
+/// Mark a revision as having the bug
+#[derive(clap::Args, Clone, Debug)]
+struct BisectBadArgs {
+    /// The revision to mark (default: the revision being tested, or `@` if
+    /// none)
+    revision: Option<String>,
+}
+
+/// Mark revisions as impossible to test
+///
+/// Other revisions are tested instead. If the first bad revision can't be
+/// found without testing them, all the revisions it could be are listed.
+#[derive(clap::Args, Clone, Debug)]
+struct BisectSkipArgs {
+    /// The revisions to mark (default: the revision being tested, or `@` if
+    /// none)
+    revisions: Vec<String>,
+}
+
+/// Test the revisions in a bisection by running a command on them
+///
+/// Each revision to test is checked out into a temporary directory, where the
+/// command is run, like in `jj run`. The working copy is not touched. The
+/// revision is marked good if the command exits with status 0, skipped if it
+/// exits with status 125, and bad if it exits with any other status below 128.
+/// Other statuses abort the bisection run.
+#[derive(clap::Args, Clone, Debug)]
+struct BisectRunArgs {
+    /// The command to run, and its arguments
+    #[clap(required = true, last = true)]
+    command: Vec<String>,
+}
+
+/// Forget the bisection in progress
+#[derive(clap::Args, Clone, Debug)]
+struct BisectResetArgs {}
+
 /// Touch up the content changes in a revision
 ///
-/// Starts a diff editor (`meld` by default) on the changes in the revision.
-/// Edit the right side of the diff until it looks the way you want. Once you
-/// close the editor, the revision will be updated. Descendants will be rebased
-/// on top as usual, which may result in conflicts. See `jj squash -i` or `jj
-/// unsquash -i` if you instead want to move changes into or out of the parent
-/// revision.
+/// Starts a diff editor (a built-in one that runs in the terminal by default)
+/// on the changes in the revision. Select the changes to keep, or in an
+/// external diff editor, edit the right side of the diff until it looks the
+/// way you want. Once you're done, the revision will be updated. Descendants
+/// will be rebased on top as usual, which may result in conflicts. See `jj
+/// squash -i` or `jj unsquash -i` if you instead want to move changes into or
+/// out of the parent revision.
 #[derive(clap::Args, Clone, Debug)]
 struct TouchupArgs {
     /// The revision to touch up
@@ -1512,11 +1838,13 @@ struct TouchupArgs {
 
 /// Split a revision in two
 ///
-/// Starts a diff editor (`meld` by default) on the changes in the revision.
-/// Edit the right side of the diff until it has the content you want in the
-/// first revision. Once you close the editor, your edited content will replace
-/// the previous revision. The remaining changes will be put in a new revision
-/// on top. You will be asked to enter a change description for each.
+/// Starts a diff editor (a built-in one that runs in the terminal by default)
+/// on the changes in the revision. Select the changes you want in the first
+/// revision, or in an external diff editor, edit the right side of the diff
+/// until it has the content you want in the first revision. Once you're done,
+/// your edited content will replace the previous revision. The remaining
+/// changes will be put in a new revision on top. You will be asked to enter a
+/// change description for each.
 #[derive(clap::Args, Clone, Debug)]
 struct SplitArgs {
     /// The revision to split
@@ -1587,9 +1915,23 @@ struct MergeArgs {
 /// | B        | C'
 /// |/         |/
 /// A          A
+///
+/// With `--insert-after` or `--insert-before` instead of `-d`, the revision
+/// (with `-r`) or the revisions (with `-s`) are inserted between a commit and
+/// its children. For example, `jj rebase -r C --insert-before B` would
+/// transform your history like this:
+///
+/// D          D'
+/// |          |
+/// C          B'
+/// |   =>     |
+/// B          C'
+/// |          |
+/// A          A
 #[derive(clap::Args, Clone, Debug)]
 #[clap(verbatim_doc_comment)]
 #[clap(group(ArgGroup::new("to_rebase").args(&["branch", "source", "revision"])))]
+#[clap(group(ArgGroup::new("target").args(&["destination", "insert-after", "insert-before"]).required(true)))]
 struct RebaseArgs {
     /// Rebase the whole branch (relative to destination's ancestors)
     #[clap(long, short)]
@@ -1602,8 +1944,69 @@ struct RebaseArgs {
     #[clap(long, short)]
     revision: Option<String>,
     /// The revision to rebase onto
-    #[clap(long, short, required = true)]
+    #[clap(long, short)]
     destination: Vec<String>,
+    /// Insert the rebased revisions after this revision, rebasing its children
+    /// onto them
+    #[clap(long, short = 'A', conflicts_with = "branch")]
+    insert_after: Option<String>,
+    /// Insert the rebased revisions before this revision, rebasing it onto them
+    #[clap(long, short = 'B', conflicts_with = "branch")]
+    insert_before: Option<String>,
+    /// What to do with commits that are empty after the rebase (keep,
+    /// abandon-newly-empty, abandon-all-empty)
+    ///
+    /// With `abandon-newly-empty`, commits whose changes are already in the
+    /// destination are abandoned. With `abandon-all-empty`, commits that were
+    /// empty before the rebase are abandoned too. Commits with more than one
+    /// parent and working-copy commits are always kept. Descendants of
+    /// abandoned commits are rebased onto their parent.
+    #[clap(long, value_name = "BEHAVIOR", default_value = "keep")]
+    empty: EmptyBehavior,
+}
+
+/// Turn a linear chain of revisions into siblings
+///
+/// The revisions must form a linear chain. Each of them is rebased onto the
+/// parents of the first revision in the chain, so they all become siblings.
+/// Children of the revisions become merges of all the siblings, so the
+/// combined content of the descendants doesn't change (unless the siblings
+/// conflict with each other).
+#[derive(clap::Args, Clone, Debug)]
+struct ParallelizeArgs {
+    /// The revisions to parallelize
+    #[clap(required = true)]
+    revisions: Vec<String>,
+}
+
+/// Reorder, squash, drop, and reword revisions by editing a plan
+///
+/// Opens an editor with a plan that lists the revisions, oldest first. Each
+/// line starts with an action that says what to do with the revision:
+///
+///  * `pick`: keep the revision
+///
+///  * `reorder`: the same as `pick`, as a reminder that the line was moved
+///
+///  * `edit-message`: keep the revision and edit its description
+///
+///  * `squash`: combine the revision into the revision above it
+///
+///  * `drop`: remove the revision and its changes
+///
+/// The revisions are rewritten in the order of the lines, so moving a line
+/// reorders the revisions. All revisions must be listed. If the plan has
+/// errors, the editor is opened again with the errors at the top. Removing all
+/// lines leaves the revisions unchanged.
+///
+/// Children of the last revision are rebased onto the last revision in the
+/// new order. Other descendants are rebased onto the rewritten revisions they
+/// were on.
+#[derive(clap::Args, Clone, Debug)]
+struct HisteditArgs {
+    /// The revisions to edit, which must form a linear chain
+    #[clap(default_value = ":@ ~ :public_heads()")]
+    revisions: String,
 }
 
 /// Apply the reverse of a revision on top of another revision
@@ -1619,6 +2022,45 @@ struct BackoutArgs {
     destination: Vec<String>,
 }
 
+/// Export revisions as patch files
+///
+/// Writes one file per revision in the mbox format used by `git format-patch`.
+/// The files can be sent by email (e.g. with `git send-email`) or applied with
+/// `jj apply` or `git am`.
+#[derive(clap::Args, Clone, Debug)]
+struct ExportPatchArgs {
+    /// The revisions to export
+    #[clap(long, short, default_value = "@")]
+    revisions: String,
+    /// The directory to write the patch files to
+    #[clap(long, short, conflicts_with = "stdout")]
+    output_directory: Option<String>,
+    /// Print all patches to stdout instead of writing them to files
+    #[clap(long)]
+    stdout: bool,
+}
+
+/// Apply patch files as new commits
+///
+/// Reads patches in the format written by `jj export-patch` or
+/// `git format-patch` and creates a commit for each of them, in order, on top
+/// of the destination. If a patch doesn't apply and the commit it was created
+/// from exists in the repo, the patch is applied using a 3-way merge instead,
+/// which may result in conflicts.
+#[derive(clap::Args, Clone, Debug)]
+struct ApplyArgs {
+    /// The patch files to apply (a file may contain several patches)
+    #[clap(required = true)]
+    patches: Vec<String>,
+    /// The revision to apply the patches on top of
+    #[clap(long, short, default_value = "@")]
+    destination: String,
+    /// The number of context lines at each end of a hunk that may be ignored
+    /// when looking for where to apply it
+    #[clap(long, default_value = "2")]
+    fuzz: usize,
+}
+
 /// Manage branches.
 ///
 /// For information about branches, see
@@ -2076,7 +2518,7 @@ fn cmd_init(ui: &mut Ui, command: &CommandHelper, args: &InitArgs) -> Result<(),
                     workspace_command.workspace_id(),
                     ui.settings(),
                     &git_head_commit,
-                );
+                )?;
             }
             if tx.mut_repo().has_changes() {
                 workspace_command.finish_transaction(ui, tx)?;
@@ -2116,7 +2558,7 @@ fn cmd_checkout(
                     target.tree_id().clone(),
                 )
                 .set_description(args.message.clone());
-                let new_commit = commit_builder.write_to_repo(tx.mut_repo());
+                let new_commit = commit_builder.write_to_repo(tx.mut_repo())?;
                 tx.mut_repo().edit(workspace_id, &new_commit);
             }
             workspace_command.finish_transaction(ui, tx)?;
@@ -2130,7 +2572,7 @@ fn cmd_checkout(
             target.tree_id().clone(),
         )
         .set_description(args.message.clone());
-        let new_commit = commit_builder.write_to_repo(tx.mut_repo());
+        let new_commit = commit_builder.write_to_repo(tx.mut_repo())?;
         tx.mut_repo().edit(workspace_id, &new_commit);
         workspace_command.finish_transaction(ui, tx)?;
     }
@@ -2191,7 +2633,7 @@ fn cmd_untrack(
     }
     CommitBuilder::for_rewrite_from(ui.settings(), &current_checkout)
         .set_tree(new_tree_id)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())?;
     let num_rebased = tx.mut_repo().rebase_descendants(ui.settings())?;
     if num_rebased > 0 {
         writeln!(ui, "Rebased {} descendant commits", num_rebased)?;
@@ -2227,7 +2669,15 @@ fn cmd_print(ui: &mut Ui, command: &CommandHelper, args: &PrintArgs) -> Result<(
         Some(TreeValue::Conflict(id)) => {
             let conflict = repo.store().read_conflict(&path, &id)?;
             let mut contents = vec![];
-            conflicts::materialize_conflict(repo.store(), &path, &conflict, &mut contents).unwrap();
+            conflicts::materialize_conflict(
+                repo.store(),
+                &path,
+                &conflict,
+                Some(&commit.tree()),
+                ui.settings().conflict_marker_style(),
+                &mut contents,
+            )
+            .unwrap();
             ui.stdout_formatter().write_all(&contents)?;
         }
         _ => {
@@ -2239,10 +2689,89 @@ fn cmd_print(ui: &mut Ui, command: &CommandHelper, args: &PrintArgs) -> Result<(
     Ok(())
 }
 
+fn annotate_template(settings: &UserSettings) -> String {
+    let default_template =
+        r#"change_id.short() " " author.email() " " label("timestamp", author.timestamp())"#;
+    settings
+        .config()
+        .get_string("template.annotate")
+        .unwrap_or_else(|_| String::from(default_template))
+}
+
+fn cmd_annotate(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &AnnotateArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let commit = workspace_command.resolve_single_rev(&args.revision)?;
+    let path = ui.parse_file_path(workspace_command.workspace_root(), &args.path)?;
+    let repo = workspace_command.repo();
+    let content = match commit.tree().path_value(&path) {
+        None => {
+            return Err(CommandError::UserError("No such path".to_string()));
+        }
+        Some(TreeValue::Normal { .. }) => line_origins::read_file_content(&commit, &path)?.unwrap(),
+        _ => {
+            return Err(CommandError::UserError(
+                "Path exists but is not a regular file".to_string(),
+            ));
+        }
+    };
+    let origins = line_origins::get_line_origins(repo.as_repo_ref(), &commit, &path, |_| true)?;
+
+    let template_string = match &args.template {
+        Some(value) => value.to_string(),
+        None => annotate_template(ui.settings()),
+    };
+    let workspace_id = workspace_command.workspace_id();
+    let template = crate::template_parser::parse_commit_template(
+        repo.as_repo_ref(),
+        &workspace_id,
+        &template_string,
+    );
+    // Render each commit once since consecutive lines usually share one
+    let mut rendered_commits: HashMap<CommitId, Vec<u8>> = HashMap::new();
+    let mut formatter = ui.stdout_formatter();
+    let formatter = formatter.as_mut();
+    let lines = content.split_inclusive(|b| *b == b'\n');
+    for (line_number, (line, origin)) in lines.zip(origins).enumerate() {
+        // Every line should have an origin since the whole history is searched,
+        // but don't fail the command if one is missing.
+        if let Some(origin) = origin {
+            let rendered = match rendered_commits.entry(origin.commit_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let origin_commit = repo.store().get_commit(entry.key())?;
+                    let mut buffer = vec![];
+                    template.format(
+                        &origin_commit,
+                        ui.new_formatter(Box::new(&mut buffer)).as_mut(),
+                    )?;
+                    entry.insert(buffer)
+                }
+            };
+            formatter.write_all(rendered)?;
+        }
+        write!(formatter, " {:>4}: ", line_number + 1)?;
+        formatter.write_all(line)?;
+        if !line.ends_with(b"\n") {
+            formatter.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+/// Function that prints a line of a line-based diff, such as
+/// `show_color_words_diff_line()`. Returns whether the output ended with a
+/// newline.
+type DiffLinePrinter<'a> = &'a dyn Fn(&mut dyn Formatter, &DiffLine) -> io::Result<bool>;
+
 fn show_color_words_diff_hunks(
     left: &[u8],
     right: &[u8],
     formatter: &mut dyn Formatter,
+    show_line: DiffLinePrinter,
 ) -> io::Result<()> {
     let num_context_lines = 3;
     let mut context = VecDeque::new();
@@ -2250,6 +2779,8 @@ fn show_color_words_diff_hunks(
     let mut skipped_context = false;
     // Are the lines in `context` to be printed before the next modified line?
     let mut context_before = true;
+    // Did the output so far end with a newline?
+    let mut ended_with_newline = true;
     for diff_line in files::diff(left, right) {
         if diff_line.is_unmodified() {
             context.push_back(diff_line.clone());
@@ -2261,7 +2792,7 @@ fn show_color_words_diff_hunks(
                 }
                 if !context_before {
                     for line in &context {
-                        show_color_words_diff_line(formatter, line)?;
+                        show_line(formatter, line)?;
                     }
                     context.clear();
                     context_before = true;
@@ -2269,28 +2800,27 @@ fn show_color_words_diff_hunks(
                 if !skipped_context {
                     formatter.write_bytes(b"    ...\n")?;
                     skipped_context = true;
+                    ended_with_newline = true;
                 }
             }
         } else {
             for line in &context {
-                show_color_words_diff_line(formatter, line)?;
+                show_line(formatter, line)?;
             }
             context.clear();
-            show_color_words_diff_line(formatter, &diff_line)?;
+            ended_with_newline = show_line(formatter, &diff_line)?;
             context_before = false;
             skipped_context = false;
         }
     }
     if !context_before {
         for line in &context {
-            show_color_words_diff_line(formatter, line)?;
+            ended_with_newline = show_line(formatter, line)?;
         }
     }
 
     // If the last diff line doesn't end with newline, add it.
-    let no_hunk = left.is_empty() && right.is_empty();
-    let any_last_newline = left.ends_with(b"\n") || right.ends_with(b"\n");
-    if !skipped_context && !no_hunk && !any_last_newline {
+    if !ended_with_newline {
         formatter.write_bytes(b"\n")?;
     }
 
@@ -2300,7 +2830,7 @@ fn show_color_words_diff_hunks(
 fn show_color_words_diff_line(
     formatter: &mut dyn Formatter,
     diff_line: &DiffLine,
-) -> io::Result<()> {
+) -> io::Result<bool> {
     if diff_line.has_left_content {
         formatter.add_label(String::from("removed"))?;
         formatter.write_bytes(format!("{:>4}", diff_line.left_line_number).as_bytes())?;
@@ -2317,10 +2847,12 @@ fn show_color_words_diff_line(
     } else {
         formatter.write_bytes(b"    : ")?;
     }
+    let mut ended_with_newline = false;
     for hunk in &diff_line.hunks {
         match hunk {
             DiffHunk::Matching(data) => {
                 formatter.write_bytes(data)?;
+                ended_with_newline = data.ends_with(b"\n");
             }
             DiffHunk::Different(data) => {
                 let before = data[0];
@@ -2329,17 +2861,134 @@ fn show_color_words_diff_line(
                     formatter.add_label(String::from("removed"))?;
                     formatter.write_bytes(before)?;
                     formatter.remove_label()?;
+                    ended_with_newline = before.ends_with(b"\n");
                 }
                 if !after.is_empty() {
                     formatter.add_label(String::from("added"))?;
                     formatter.write_bytes(after)?;
                     formatter.remove_label()?;
+                    ended_with_newline = after.ends_with(b"\n");
                 }
             }
         }
     }
 
-    Ok(())
+    Ok(ended_with_newline)
+}
+
+/// A piece of one side of a side-by-side diff line, with the label to format it
+/// with.
+type SideBySideSegment = (Option<&'static str>, String);
+
+/// Splits the segments into rows of at most `width` columns each.
+fn wrap_side_by_side_segments(
+    segments: Vec<SideBySideSegment>,
+    width: usize,
+) -> Vec<Vec<SideBySideSegment>> {
+    let mut rows = vec![vec![]];
+    let mut row_width = 0;
+    for (label, text) in segments {
+        let mut current = String::new();
+        for c in text.chars() {
+            let char_width = textwrap::core::display_width(c.encode_utf8(&mut [0; 4]));
+            if row_width + char_width > width {
+                if !current.is_empty() {
+                    rows.last_mut().unwrap().push((label, current));
+                    current = String::new();
+                }
+                rows.push(vec![]);
+                row_width = 0;
+            }
+            current.push(c);
+            row_width += char_width;
+        }
+        if !current.is_empty() {
+            rows.last_mut().unwrap().push((label, current));
+        }
+    }
+    rows
+}
+
+fn side_by_side_segments(diff_line: &DiffLine, right_side: bool) -> Vec<SideBySideSegment> {
+    let (label, index) = if right_side {
+        ("added", 1)
+    } else {
+        ("removed", 0)
+    };
+    let mut segments = vec![];
+    for hunk in &diff_line.hunks {
+        let (label, data) = match hunk {
+            DiffHunk::Matching(data) => (None, *data),
+            DiffHunk::Different(data) => (Some(label), data[index]),
+        };
+        let text = String::from_utf8_lossy(data)
+            .trim_end_matches(['\n', '\r'])
+            .replace('\t', "    ");
+        if !text.is_empty() {
+            segments.push((label, text));
+        }
+    }
+    segments
+}
+
+fn show_side_by_side_diff_line(
+    formatter: &mut dyn Formatter,
+    diff_line: &DiffLine,
+    width: usize,
+) -> io::Result<bool> {
+    // Each side has a 4-digit line number and a space before the text, and the
+    // sides are separated by " | "
+    let text_width = (width - 3) / 2 - 5;
+    let left_rows = wrap_side_by_side_segments(side_by_side_segments(diff_line, false), text_width);
+    let right_rows = wrap_side_by_side_segments(side_by_side_segments(diff_line, true), text_width);
+    let num_rows = left_rows.len().max(right_rows.len());
+    for i in 0..num_rows {
+        if i == 0 && diff_line.has_left_content {
+            formatter.add_label(String::from("removed"))?;
+            formatter.write_bytes(format!("{:>4}", diff_line.left_line_number).as_bytes())?;
+            formatter.remove_label()?;
+            formatter.write_bytes(b" ")?;
+        } else {
+            formatter.write_bytes(b"     ")?;
+        }
+        let mut left_width = 0;
+        for (label, text) in left_rows.get(i).into_iter().flatten() {
+            if let Some(label) = label {
+                formatter.add_label(label.to_string())?;
+            }
+            formatter.write_str(text)?;
+            if label.is_some() {
+                formatter.remove_label()?;
+            }
+            left_width += textwrap::core::display_width(text);
+        }
+        formatter.write_str(&" ".repeat(text_width - left_width))?;
+        let right_row = right_rows.get(i).filter(|row| !row.is_empty());
+        if i == 0 && diff_line.has_right_content {
+            formatter.write_bytes(b" | ")?;
+            formatter.add_label(String::from("added"))?;
+            formatter.write_bytes(format!("{:>4}", diff_line.right_line_number).as_bytes())?;
+            formatter.remove_label()?;
+            if right_row.is_some() {
+                formatter.write_bytes(b" ")?;
+            }
+        } else if right_row.is_some() {
+            formatter.write_bytes(b" |      ")?;
+        } else {
+            formatter.write_bytes(b" |")?;
+        }
+        for (label, text) in right_row.into_iter().flatten() {
+            if let Some(label) = label {
+                formatter.add_label(label.to_string())?;
+            }
+            formatter.write_str(text)?;
+            if label.is_some() {
+                formatter.remove_label()?;
+            }
+        }
+        formatter.write_bytes(b"\n")?;
+    }
+    Ok(true)
 }
 
 fn cmd_diff(ui: &mut Ui, command: &CommandHelper, args: &DiffArgs) -> Result<(), CommandError> {
@@ -2360,25 +3009,75 @@ fn cmd_diff(ui: &mut Ui, command: &CommandHelper, args: &DiffArgs) -> Result<(),
     }
     let workspace_root = workspace_command.workspace_root();
     let matcher = matcher_from_values(ui, workspace_root, &args.paths)?;
-    let diff_iterator = from_tree.diff(&to_tree, matcher.as_ref());
     show_diff(
         ui.stdout_formatter().as_mut(),
         &workspace_command,
-        diff_iterator,
-        diff_format_for(ui, &args.format),
+        &from_tree,
+        &to_tree,
+        matcher.as_ref(),
+        &diff_format_for(ui, &args.format)?,
     )?;
     Ok(())
 }
 
-fn cmd_show(ui: &mut Ui, command: &CommandHelper, args: &ShowArgs) -> Result<(), CommandError> {
+fn cmd_interdiff(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &InterdiffArgs,
+) -> Result<(), CommandError> {
     let workspace_command = command.workspace_helper(ui)?;
-    let commit = workspace_command.resolve_single_rev(&args.revision)?;
-    let parents = commit.parents();
-    let from_tree = merge_commit_trees(workspace_command.repo().as_repo_ref(), &parents);
-    let to_tree = commit.tree();
-    let diff_iterator = from_tree.diff(&to_tree, &EverythingMatcher);
-    // TODO: Add branches, tags, etc
-    // TODO: Indent the description like Git does
+    let from = workspace_command.resolve_single_rev(args.from.as_deref().unwrap_or("@"))?;
+    let to = workspace_command.resolve_single_rev(args.to.as_deref().unwrap_or("@"))?;
+
+    let from_tree = rebase_to_dest_parent(&workspace_command, &from, &to)?;
+    let workspace_root = workspace_command.workspace_root();
+    let matcher = matcher_from_values(ui, workspace_root, &args.paths)?;
+    show_diff(
+        ui.stdout_formatter().as_mut(),
+        &workspace_command,
+        &from_tree,
+        &to.tree(),
+        matcher.as_ref(),
+        &diff_format_for(ui, &args.format)?,
+    )?;
+    Ok(())
+}
+
+/// Returns the tree of `source` rebased onto the parents of `destination`, so
+/// that diffing it against `destination`'s tree only shows how the changes
+/// made in the two commits differ.
+fn rebase_to_dest_parent(
+    workspace_command: &WorkspaceCommandHelper,
+    source: &Commit,
+    destination: &Commit,
+) -> Result<Tree, CommandError> {
+    if source.parent_ids() == destination.parent_ids() {
+        return Ok(source.tree());
+    }
+    let repo = workspace_command.repo();
+    let destination_parent_tree = merge_commit_trees(repo.as_repo_ref(), &destination.parents());
+    let source_parent_tree = merge_commit_trees(repo.as_repo_ref(), &source.parents());
+    // Use the same labels as rebasing would, so conflicts created by rebasing
+    // `source` compare equal to the ones in the rebased commit.
+    let labels = conflict_labels_for_rebase(source, &destination.parents());
+    let rebased_tree_id = merge_trees_with_labels(
+        &destination_parent_tree,
+        &source_parent_tree,
+        &source.tree(),
+        &labels,
+    )?;
+    let tree = repo.store().get_tree(&RepoPath::root(), &rebased_tree_id)?;
+    Ok(tree)
+}
+
+fn cmd_show(ui: &mut Ui, command: &CommandHelper, args: &ShowArgs) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let commit = workspace_command.resolve_single_rev(&args.revision)?;
+    let parents = commit.parents();
+    let from_tree = merge_commit_trees(workspace_command.repo().as_repo_ref(), &parents);
+    let to_tree = commit.tree();
+    // TODO: Add branches, tags, etc
+    // TODO: Indent the description like Git does
     let template_string = r#"
             "Commit ID: " commit_id "\n"
             "Change ID: " change_id "\n"
@@ -2406,51 +3105,101 @@ fn cmd_show(ui: &mut Ui, command: &CommandHelper, args: &ShowArgs) -> Result<(),
     show_diff(
         formatter,
         &workspace_command,
-        diff_iterator,
-        diff_format_for(ui, &args.format),
+        &from_tree,
+        &to_tree,
+        &EverythingMatcher,
+        &diff_format_for(ui, &args.format)?,
     )?;
     Ok(())
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug)]
 enum DiffFormat {
     Summary,
     Git,
     ColorWords,
+    SideBySide { width: usize },
+    Tool(Box<MergeTool>),
+}
+
+/// The narrowest terminal the side-by-side diff format is used for.
+const MIN_SIDE_BY_SIDE_WIDTH: usize = 60;
+
+fn side_by_side_format(ui: &Ui) -> DiffFormat {
+    let width = ui.term_width().unwrap_or(80);
+    if width < MIN_SIDE_BY_SIDE_WIDTH {
+        DiffFormat::Git
+    } else {
+        DiffFormat::SideBySide { width }
+    }
 }
 
-fn diff_format_for(ui: &Ui, args: &DiffFormatArgs) -> DiffFormat {
-    if args.summary {
+fn diff_format_for(ui: &Ui, args: &DiffFormatArgs) -> Result<DiffFormat, CommandError> {
+    let format = if args.summary {
         DiffFormat::Summary
     } else if args.git {
         DiffFormat::Git
     } else if args.color_words {
         DiffFormat::ColorWords
+    } else if args.side_by_side {
+        side_by_side_format(ui)
+    } else if let Some(name) = &args.tool {
+        let tool =
+            crate::diff_edit::get_tool(ui.settings(), name).map_err(DiffGenerateError::from)?;
+        DiffFormat::Tool(Box::new(tool))
+    } else if let Ok(name) = ui.settings().config().get_string("ui.diff.tool") {
+        let tool =
+            crate::diff_edit::get_tool(ui.settings(), &name).map_err(DiffGenerateError::from)?;
+        DiffFormat::Tool(Box::new(tool))
     } else {
         match ui.settings().config().get_string("diff.format") {
             Ok(value) if &value == "summary" => DiffFormat::Summary,
             Ok(value) if &value == "git" => DiffFormat::Git,
             Ok(value) if &value == "color-words" => DiffFormat::ColorWords,
+            Ok(value) if &value == "side-by-side" => side_by_side_format(ui),
             _ => DiffFormat::ColorWords,
         }
-    }
+    };
+    Ok(format)
 }
 
 fn show_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
-    tree_diff: TreeDiffIterator,
-    format: DiffFormat,
+    from_tree: &Tree,
+    to_tree: &Tree,
+    matcher: &dyn Matcher,
+    format: &DiffFormat,
 ) -> Result<(), CommandError> {
+    let tree_diff = from_tree.diff(to_tree, matcher);
     match format {
         DiffFormat::Summary => {
             show_diff_summary(formatter, workspace_command, tree_diff)?;
         }
         DiffFormat::Git => {
-            show_git_diff(formatter, workspace_command, tree_diff)?;
+            show_git_diff(formatter, workspace_command, to_tree, tree_diff)?;
         }
         DiffFormat::ColorWords => {
-            show_color_words_diff(formatter, workspace_command, tree_diff)?;
+            show_color_words_diff(
+                formatter,
+                workspace_command,
+                to_tree,
+                tree_diff,
+                &show_color_words_diff_line,
+            )?;
+        }
+        DiffFormat::SideBySide { width } => {
+            show_color_words_diff(
+                formatter,
+                workspace_command,
+                to_tree,
+                tree_diff,
+                &|formatter, diff_line| show_side_by_side_diff_line(formatter, diff_line, *width),
+            )?;
+        }
+        DiffFormat::Tool(tool) => {
+            let content = crate::diff_edit::generate_diff(from_tree, to_tree, matcher, tool)?;
+            formatter.write_all(&content)?;
         }
     }
     Ok(())
@@ -2484,7 +3233,15 @@ fn diff_content(
         TreeValue::Conflict(id) => {
             let conflict = repo.store().read_conflict(path, id).unwrap();
             let mut content = vec![];
-            conflicts::materialize_conflict(repo.store(), path, &conflict, &mut content).unwrap();
+            conflicts::materialize_conflict(
+                repo.store(),
+                path,
+                &conflict,
+                None,
+                ConflictMarkerStyle::Diff,
+                &mut content,
+            )
+            .unwrap();
             Ok(content)
         }
     }
@@ -2506,10 +3263,21 @@ fn basic_diff_file_type(value: &TreeValue) -> String {
     }
 }
 
+/// Determines whether a file should be shown as binary in a diff. The
+/// `.gitattributes` files in `tree` take precedence over looking at the
+/// content.
+fn is_binary_diff(tree: &Tree, path: &RepoPath, left: &[u8], right: &[u8]) -> bool {
+    GitAttributesFile::for_path_in_tree(tree, path)
+        .is_binary(&path.to_internal_file_string())
+        .unwrap_or_else(|| files::is_binary(left) || files::is_binary(right))
+}
+
 fn show_color_words_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
+    tree: &Tree,
     tree_diff: TreeDiffIterator,
+    show_line: DiffLinePrinter,
 ) -> Result<(), CommandError> {
     let repo = workspace_command.repo();
     formatter.add_label(String::from("diff"))?;
@@ -2522,7 +3290,11 @@ fn show_color_words_diff(
                 formatter.add_label(String::from("header"))?;
                 formatter.write_str(&format!("Added {} {}:\n", description, ui_path))?;
                 formatter.remove_label()?;
-                show_color_words_diff_hunks(&[], &right_content, formatter)?;
+                if is_binary_diff(tree, &path, &[], &right_content) {
+                    formatter.write_str("    Binary file differs\n")?;
+                } else {
+                    show_color_words_diff_hunks(&[], &right_content, formatter, show_line)?;
+                }
             }
             tree::Diff::Modified(left_value, right_value) => {
                 let left_content = diff_content(repo, &path, &left_value)?;
@@ -2571,7 +3343,18 @@ fn show_color_words_diff(
                 formatter.add_label(String::from("header"))?;
                 formatter.write_str(&format!("{} {}:\n", description, ui_path))?;
                 formatter.remove_label()?;
-                show_color_words_diff_hunks(&left_content, &right_content, formatter)?;
+                if is_binary_diff(tree, &path, &left_content, &right_content) {
+                    if left_content != right_content {
+                        formatter.write_str("    Binary file differs\n")?;
+                    }
+                } else {
+                    show_color_words_diff_hunks(
+                        &left_content,
+                        &right_content,
+                        formatter,
+                        show_line,
+                    )?;
+                }
             }
             tree::Diff::Removed(left_value) => {
                 let left_content = diff_content(repo, &path, &left_value)?;
@@ -2579,7 +3362,11 @@ fn show_color_words_diff(
                 formatter.add_label(String::from("header"))?;
                 formatter.write_str(&format!("Removed {} {}:\n", description, ui_path))?;
                 formatter.remove_label()?;
-                show_color_words_diff_hunks(&left_content, &[], formatter)?;
+                if is_binary_diff(tree, &path, &left_content, &[]) {
+                    formatter.write_str("    Binary file differs\n")?;
+                } else {
+                    show_color_words_diff_hunks(&left_content, &[], formatter, show_line)?;
+                }
             }
         }
     }
@@ -2633,7 +3420,15 @@ fn git_diff_part(
             mode = "100644".to_string();
             hash = id.hex();
             let conflict = repo.store().read_conflict(path, id).unwrap();
-            conflicts::materialize_conflict(repo.store(), path, &conflict, &mut content).unwrap();
+            conflicts::materialize_conflict(
+                repo.store(),
+                path,
+                &conflict,
+                None,
+                ConflictMarkerStyle::Diff,
+                &mut content,
+            )
+            .unwrap();
         }
     }
     let hash = hash[0..10].to_string();
@@ -2785,6 +3580,7 @@ fn show_unified_diff_hunks(
 fn show_git_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
+    tree: &Tree,
     tree_diff: TreeDiffIterator,
 ) -> Result<(), CommandError> {
     let repo = workspace_command.repo();
@@ -2798,6 +3594,15 @@ fn show_git_diff(
                 let right_part = git_diff_part(repo, &path, &right_value)?;
                 writeln!(formatter, "new file mode {}", &right_part.mode)?;
                 writeln!(formatter, "index 0000000000..{}", &right_part.hash)?;
+                if is_binary_diff(tree, &path, &[], &right_part.content) {
+                    writeln!(
+                        formatter,
+                        "Binary files /dev/null and b/{} differ",
+                        path_string
+                    )?;
+                    formatter.remove_label()?;
+                    continue;
+                }
                 writeln!(formatter, "--- /dev/null")?;
                 writeln!(formatter, "+++ b/{}", path_string)?;
                 formatter.remove_label()?;
@@ -2819,6 +3624,17 @@ fn show_git_diff(
                         &left_part.hash, right_part.hash, left_part.mode
                     )?;
                 }
+                if is_binary_diff(tree, &path, &left_part.content, &right_part.content) {
+                    if left_part.content != right_part.content {
+                        writeln!(
+                            formatter,
+                            "Binary files a/{} and b/{} differ",
+                            path_string, path_string
+                        )?;
+                    }
+                    formatter.remove_label()?;
+                    continue;
+                }
                 if left_part.content != right_part.content {
                     writeln!(formatter, "--- a/{}", path_string)?;
                     writeln!(formatter, "+++ b/{}", path_string)?;
@@ -2830,6 +3646,15 @@ fn show_git_diff(
                 let left_part = git_diff_part(repo, &path, &left_value)?;
                 writeln!(formatter, "deleted file mode {}", &left_part.mode)?;
                 writeln!(formatter, "index {}..0000000000", &left_part.hash)?;
+                if is_binary_diff(tree, &path, &left_part.content, &[]) {
+                    writeln!(
+                        formatter,
+                        "Binary files a/{} and /dev/null differ",
+                        path_string
+                    )?;
+                    formatter.remove_label()?;
+                    continue;
+                }
                 writeln!(formatter, "--- a/{}", path_string)?;
                 writeln!(formatter, "+++ /dev/null")?;
                 formatter.remove_label()?;
@@ -3025,8 +3850,15 @@ fn cmd_log(ui: &mut Ui, command: &CommandHelper, args: &LogArgs) -> Result<(), C
     let checkout_id = repo.view().get_checkout(&workspace_id);
     let revset = revset_expression.evaluate(repo.as_repo_ref(), Some(&workspace_id))?;
     let store = repo.store();
-    let diff_format = (args.patch || args.diff_format.git || args.diff_format.summary)
-        .then(|| diff_format_for(ui, &args.diff_format));
+    let diff_format = if args.patch
+        || args.diff_format.git
+        || args.diff_format.summary
+        || args.diff_format.tool.is_some()
+    {
+        Some(diff_format_for(ui, &args.diff_format)?)
+    } else {
+        None
+    };
 
     let template_string = match &args.template {
         Some(value) => value.to_string(),
@@ -3091,7 +3923,7 @@ fn cmd_log(ui: &mut Ui, command: &CommandHelper, args: &LogArgs) -> Result<(), C
             if !buffer.ends_with(b"\n") {
                 buffer.push(b'\n');
             }
-            if let Some(diff_format) = diff_format {
+            if let Some(diff_format) = &diff_format {
                 let writer = Box::new(&mut buffer);
                 let mut formatter = ui.new_formatter(writer);
                 show_patch(formatter.as_mut(), &workspace_command, &commit, diff_format)?;
@@ -3113,7 +3945,7 @@ fn cmd_log(ui: &mut Ui, command: &CommandHelper, args: &LogArgs) -> Result<(), C
         for index_entry in iter {
             let commit = store.get_commit(&index_entry.commit_id())?;
             template.format(&commit, formatter)?;
-            if let Some(diff_format) = diff_format {
+            if let Some(diff_format) = &diff_format {
                 show_patch(formatter, &workspace_command, &commit, diff_format)?;
             }
         }
@@ -3126,13 +3958,19 @@ fn show_patch(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
     commit: &Commit,
-    format: DiffFormat,
+    format: &DiffFormat,
 ) -> Result<(), CommandError> {
     let parents = commit.parents();
     let from_tree = merge_commit_trees(workspace_command.repo().as_repo_ref(), &parents);
     let to_tree = commit.tree();
-    let diff_iterator = from_tree.diff(&to_tree, &EverythingMatcher);
-    show_diff(formatter, workspace_command, diff_iterator, format)
+    show_diff(
+        formatter,
+        workspace_command,
+        &from_tree,
+        &to_tree,
+        &EverythingMatcher,
+        format,
+    )
 }
 
 fn cmd_obslog(ui: &mut Ui, command: &CommandHelper, args: &ObslogArgs) -> Result<(), CommandError> {
@@ -3142,8 +3980,15 @@ fn cmd_obslog(ui: &mut Ui, command: &CommandHelper, args: &ObslogArgs) -> Result
     let workspace_id = workspace_command.workspace_id();
     let checkout_id = workspace_command.repo().view().get_checkout(&workspace_id);
 
-    let diff_format = (args.patch || args.diff_format.git || args.diff_format.summary)
-        .then(|| diff_format_for(ui, &args.diff_format));
+    let diff_format = if args.patch
+        || args.diff_format.git
+        || args.diff_format.summary
+        || args.diff_format.tool.is_some()
+    {
+        Some(diff_format_for(ui, &args.diff_format)?)
+    } else {
+        None
+    };
 
     let template_string = match &args.template {
         Some(value) => value.to_string(),
@@ -3180,7 +4025,7 @@ fn cmd_obslog(ui: &mut Ui, command: &CommandHelper, args: &ObslogArgs) -> Result
             if !buffer.ends_with(b"\n") {
                 buffer.push(b'\n');
             }
-            if let Some(diff_format) = diff_format {
+            if let Some(diff_format) = &diff_format {
                 let writer = Box::new(&mut buffer);
                 let mut formatter = ui.new_formatter(writer);
                 show_predecessor_patch(
@@ -3200,7 +4045,7 @@ fn cmd_obslog(ui: &mut Ui, command: &CommandHelper, args: &ObslogArgs) -> Result
     } else {
         for commit in commits {
             template.format(&commit, formatter)?;
-            if let Some(diff_format) = diff_format {
+            if let Some(diff_format) = &diff_format {
                 show_predecessor_patch(formatter, &workspace_command, &commit, diff_format)?;
             }
         }
@@ -3213,29 +4058,43 @@ fn show_predecessor_patch(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
     commit: &Commit,
-    diff_format: DiffFormat,
+    diff_format: &DiffFormat,
 ) -> Result<(), CommandError> {
     if let Some(predecessor) = commit.predecessors().first() {
-        let predecessor_tree = if predecessor.parent_ids() == commit.parent_ids() {
-            predecessor.tree()
-        } else {
-            // Rebase the predecessor to have the current commit's parent(s) and use that
-            // tree as base
-            let new_parent_tree =
-                merge_commit_trees(workspace_command.repo().as_repo_ref(), &commit.parents());
-            let old_parent_tree = merge_commit_trees(
-                workspace_command.repo().as_repo_ref(),
-                &predecessor.parents(),
-            );
-            let rebased_tree_id =
-                merge_trees(&new_parent_tree, &old_parent_tree, &predecessor.tree())?;
-            workspace_command
-                .repo()
-                .store()
-                .get_tree(&RepoPath::root(), &rebased_tree_id)?
-        };
-        let diff_iterator = predecessor_tree.diff(&commit.tree(), &EverythingMatcher);
-        show_diff(formatter, workspace_command, diff_iterator, diff_format)?;
+        // Rebase the predecessor to have the current commit's parent(s) and use that
+        // tree as base, like `jj interdiff` does
+        let predecessor_tree = rebase_to_dest_parent(workspace_command, predecessor, commit)?;
+        show_diff(
+            formatter,
+            workspace_command,
+            &predecessor_tree,
+            &commit.tree(),
+            &EverythingMatcher,
+            diff_format,
+        )?;
+    }
+    Ok(())
+}
+
+/// Opens the file at `path` in the user's editor and waits for it to exit.
+fn run_editor(ui: &Ui, path: &Path) -> Result<(), CommandError> {
+    let editor = ui
+        .settings()
+        .config()
+        .get_string("ui.editor")
+        .unwrap_or_else(|_| "pico".to_string());
+    // Handle things like `EDITOR=emacs -nw`
+    let args = editor.split(' ').collect_vec();
+    let editor_args = if args.len() > 1 { &args[1..] } else { &[] };
+    let exit_status = std::process::Command::new(args[0])
+        .args(editor_args)
+        .arg(path)
+        .status()
+        .map_err(|_| CommandError::UserError(format!("Failed to run editor '{editor}'")))?;
+    if !exit_status.success() {
+        return Err(CommandError::UserError(format!(
+            "Editor '{editor}' exited with an error"
+        )));
     }
     Ok(())
 }
@@ -3260,24 +4119,7 @@ fn edit_description(
             .unwrap();
     }
 
-    let editor = ui
-        .settings()
-        .config()
-        .get_string("ui.editor")
-        .unwrap_or_else(|_| "pico".to_string());
-    // Handle things like `EDITOR=emacs -nw`
-    let args = editor.split(' ').collect_vec();
-    let editor_args = if args.len() > 1 { &args[1..] } else { &[] };
-    let exit_status = std::process::Command::new(args[0])
-        .args(editor_args)
-        .arg(&description_file_path)
-        .status()
-        .map_err(|_| CommandError::UserError(format!("Failed to run editor '{editor}'")))?;
-    if !exit_status.success() {
-        return Err(CommandError::UserError(format!(
-            "Editor '{editor}' exited with an error"
-        )));
-    }
+    run_editor(ui, &description_file_path)?;
 
     let mut description_file = OpenOptions::new()
         .read(true)
@@ -3325,7 +4167,7 @@ fn cmd_describe(
             workspace_command.start_transaction(&format!("describe commit {}", commit.id().hex()));
         CommitBuilder::for_rewrite_from(ui.settings(), &commit)
             .set_description(description)
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())?;
         workspace_command.finish_transaction(ui, tx)?;
     }
     Ok(())
@@ -3338,7 +4180,7 @@ fn cmd_open(ui: &mut Ui, command: &CommandHelper, args: &OpenArgs) -> Result<(),
     let mut tx = workspace_command.start_transaction(&format!("open commit {}", commit.id().hex()));
     CommitBuilder::for_rewrite_from(ui.settings(), &commit)
         .set_open(true)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())?;
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
@@ -3365,7 +4207,7 @@ fn cmd_close(ui: &mut Ui, command: &CommandHelper, args: &CloseArgs) -> Result<(
     commit_builder = commit_builder.set_description(description);
     let mut tx =
         workspace_command.start_transaction(&format!("close commit {}", commit.id().hex()));
-    let new_commit = commit_builder.write_to_repo(tx.mut_repo());
+    let new_commit = commit_builder.write_to_repo(tx.mut_repo())?;
     let workspace_ids = tx.mut_repo().view().workspaces_for_checkout(commit.id());
     if !workspace_ids.is_empty() {
         let new_checkout = CommitBuilder::for_open_commit(
@@ -3373,7 +4215,7 @@ fn cmd_close(ui: &mut Ui, command: &CommandHelper, args: &CloseArgs) -> Result<(
             new_commit.id().clone(),
             new_commit.tree_id().clone(),
         )
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())?;
         for workspace_id in workspace_ids {
             tx.mut_repo().edit(workspace_id, &new_checkout);
         }
@@ -3388,20 +4230,103 @@ fn cmd_duplicate(
     args: &DuplicateArgs,
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let predecessor = workspace_command.resolve_single_rev(&args.revision)?;
-    let mut tx = workspace_command
-        .start_transaction(&format!("duplicate commit {}", predecessor.id().hex()));
+    let mut originals: Vec<Commit> = vec![];
+    for revset in &args.revisions {
+        let revisions = workspace_command.resolve_revset(revset)?;
+        workspace_command.check_non_empty(&revisions)?;
+        for commit in revisions {
+            if commit.id() == workspace_command.repo().store().root_commit_id() {
+                return Err(CommandError::UserError(
+                    "Cannot duplicate the root commit".to_string(),
+                ));
+            }
+            if !originals.contains(&commit) {
+                originals.push(commit);
+            }
+        }
+    }
+    let mut destinations = vec![];
+    for revision_str in &args.destination {
+        let destination = workspace_command.resolve_single_rev(revision_str)?;
+        if !destinations.contains(&destination) {
+            destinations.push(destination);
+        }
+    }
+    // Duplicate parents before their children
+    let original_ids: HashSet<CommitId> =
+        originals.iter().map(|commit| commit.id().clone()).collect();
+    let originals = workspace_command
+        .repo()
+        .index()
+        .topo_order(original_ids.iter())
+        .into_iter()
+        .map(|entry| {
+            workspace_command
+                .repo()
+                .store()
+                .get_commit(&entry.commit_id())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tx = workspace_command.start_transaction(&format!(
+        "duplicate {} commits starting at {}",
+        originals.len(),
+        originals[0].id().hex()
+    ));
     let mut_repo = tx.mut_repo();
-    let new_commit = CommitBuilder::for_rewrite_from(ui.settings(), &predecessor)
-        .generate_new_change_id()
-        .write_to_repo(mut_repo);
-    ui.write("Created: ")?;
-    ui.write_commit_summary(
-        mut_repo.as_repo_ref(),
-        &workspace_command.workspace_id(),
-        &new_commit,
-    )?;
-    ui.write("\n")?;
+    let mut copies: HashMap<CommitId, Commit> = HashMap::new();
+    for original in &originals {
+        let mut new_parents = vec![];
+        for parent in original.parents() {
+            let new_parent = if let Some(copy) = copies.get(parent.id()) {
+                vec![copy.clone()]
+            } else if destinations.is_empty() {
+                vec![parent]
+            } else {
+                destinations.clone()
+            };
+            for new_parent in new_parent {
+                if !new_parents.contains(&new_parent) {
+                    new_parents.push(new_parent);
+                }
+            }
+        }
+        let new_tree_id = if new_parents == original.parents() {
+            original.tree_id().clone()
+        } else {
+            let (tree_id, reused_resolutions) = merge_trees_reporting_resolutions(
+                &merge_commit_trees(mut_repo.as_repo_ref(), &new_parents),
+                &merge_commit_trees(mut_repo.as_repo_ref(), &original.parents()),
+                &original.tree(),
+                &conflict_labels_for_rebase(original, &new_parents),
+            )?;
+            mut_repo.record_reused_resolutions(reused_resolutions);
+            tree_id
+        };
+        let copy = CommitBuilder::for_rewrite_from(ui.settings(), original)
+            .generate_new_change_id()
+            .set_parents(
+                new_parents
+                    .iter()
+                    .map(|parent| parent.id().clone())
+                    .collect(),
+            )
+            .set_tree(new_tree_id)
+            .set_predecessors(vec![])
+            .set_copied_from(Some(original.id().clone()))
+            .write_to_repo(mut_repo)?;
+        ui.write(&format!(
+            "Duplicated {} as ",
+            short_commit_hash(original.id())
+        ))?;
+        ui.write_commit_summary(
+            mut_repo.as_repo_ref(),
+            &workspace_command.workspace_id(),
+            &copy,
+        )?;
+        ui.write("\n")?;
+        copies.insert(original.id().clone(), copy);
+    }
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
@@ -3475,13 +4400,144 @@ fn cmd_new(ui: &mut Ui, command: &CommandHelper, args: &NewArgs) -> Result<(), C
     .set_description(args.message.clone());
     let mut tx = workspace_command.start_transaction("new empty commit");
     let mut_repo = tx.mut_repo();
-    let new_commit = commit_builder.write_to_repo(mut_repo);
+    let new_commit = commit_builder.write_to_repo(mut_repo)?;
     let workspace_id = workspace_command.workspace_id();
     mut_repo.edit(workspace_id, &new_commit);
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
 
+fn choose_commit(
+    ui: &mut Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    input: &mut dyn io::BufRead,
+    candidates: Vec<Commit>,
+) -> Result<Commit, CommandError> {
+    if candidates.len() == 1 {
+        return Ok(candidates.into_iter().next().unwrap());
+    }
+    let workspace_id = workspace_command.workspace_id();
+    ui.write("There are several revisions to choose from:\n")?;
+    for (i, commit) in candidates.iter().enumerate() {
+        ui.write(&format!("{}: ", i + 1))?;
+        ui.write_commit_summary(
+            workspace_command.repo().as_repo_ref(),
+            &workspace_id,
+            commit,
+        )?;
+        ui.write("\n")?;
+    }
+    loop {
+        ui.write("Enter the number of the revision to go to: ")?;
+        ui.stdout_formatter().flush()?;
+        let mut response = String::new();
+        if input.read_line(&mut response)? == 0 {
+            ui.write("\n")?;
+            return Err(CommandError::UserError(
+                "No revision was chosen".to_string(),
+            ));
+        }
+        match response.trim().parse::<usize>() {
+            Ok(choice) if choice >= 1 && choice <= candidates.len() => {
+                return Ok(candidates.into_iter().nth(choice - 1).unwrap());
+            }
+            _ => {
+                ui.write(&format!(
+                    "Please enter a number between 1 and {}\n",
+                    candidates.len()
+                ))?;
+            }
+        }
+    }
+}
+
+/// Moves the working copy `amount` revisions forward (to children) or backward
+/// (to parents).
+fn move_to_relative(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    amount: u64,
+    edit: bool,
+    forward: bool,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let workspace_id = workspace_command.workspace_id();
+    let checkout = workspace_command.resolve_single_rev("@")?;
+    // Unless we're editing revisions directly, the working-copy commit sits on
+    // top of the current position in the stack
+    let mut current =
+        if edit {
+            checkout.clone()
+        } else {
+            match checkout.parents().as_slice() {
+                [parent] => parent.clone(),
+                _ => return Err(CommandError::UserError(
+                    "The working-copy commit must have a single parent (use --edit to move from \
+                     a merge commit)"
+                        .to_string(),
+                )),
+            }
+        };
+    let store = workspace_command.repo().store().clone();
+    let root_commit_id = store.root_commit_id().clone();
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    for _ in 0..amount {
+        let candidates = if forward {
+            RevsetExpression::commit(current.id().clone())
+                .children()
+                .minus(&RevsetExpression::commit(checkout.id().clone()))
+                .evaluate(workspace_command.repo().as_repo_ref(), Some(&workspace_id))?
+                .iter()
+                .commits(&store)
+                .try_collect()?
+        } else {
+            current
+                .parents()
+                .into_iter()
+                .filter(|parent| parent.id() != &root_commit_id)
+                .collect_vec()
+        };
+        if candidates.is_empty() {
+            return Err(CommandError::UserError(format!(
+                "No {} found {} {} {}",
+                if forward { "descendant" } else { "ancestor" },
+                amount,
+                if amount == 1 { "revision" } else { "revisions" },
+                if forward { "forward" } else { "back" },
+            )));
+        }
+        current = choose_commit(ui, &workspace_command, &mut input, candidates)?;
+    }
+    let mut tx = workspace_command.start_transaction(&format!(
+        "{} commit {}",
+        if forward { "next" } else { "prev" },
+        current.id().hex()
+    ));
+    if edit {
+        workspace_command.check_rewriteable(&current)?;
+        tx.mut_repo().edit(workspace_id, &current);
+    } else {
+        let new_commit = CommitBuilder::for_open_commit(
+            ui.settings(),
+            current.id().clone(),
+            current.tree_id().clone(),
+        )
+        .write_to_repo(tx.mut_repo())?;
+        tx.mut_repo().edit(workspace_id, &new_commit);
+    }
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+fn cmd_next(ui: &mut Ui, command: &CommandHelper, args: &NextArgs) -> Result<(), CommandError> {
+    move_to_relative(ui, command, args.amount, args.edit, true)
+}
+
+fn cmd_prev(ui: &mut Ui, command: &CommandHelper, args: &PrevArgs) -> Result<(), CommandError> {
+    move_to_relative(ui, command, args.amount, args.edit, false)
+}
+
 fn cmd_move(ui: &mut Ui, command: &CommandHelper, args: &MoveArgs) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
     let source = workspace_command.resolve_single_rev(args.from.as_deref().unwrap_or("@"))?;
@@ -3535,13 +4591,22 @@ from the source will be moved into the destination.
         .store()
         .get_tree(&RepoPath::root(), &new_parent_tree_id)?;
     // Apply the reverse of the selected changes onto the source
-    let new_source_tree_id = merge_trees(&source_tree, &new_parent_tree, &parent_tree)?;
+    let source_labels = ConflictLabels {
+        side1: Some(conflict_label_for_commit(&source)),
+        base: Some(format!(
+            "changes moved from {}",
+            conflict_label_for_commit(&source)
+        )),
+        side2: conflict_label_for_commits(&source.parents()),
+    };
+    let new_source_tree_id =
+        merge_trees_with_labels(&source_tree, &new_parent_tree, &parent_tree, &source_labels)?;
     if new_source_tree_id == *parent_tree.id() && !repo.view().is_checkout(source.id()) {
         mut_repo.record_abandoned_commit(source.id().clone());
     } else {
         CommitBuilder::for_rewrite_from(ui.settings(), &source)
             .set_tree(new_source_tree_id)
-            .write_to_repo(mut_repo);
+            .write_to_repo(mut_repo)?;
     }
     if repo.index().is_ancestor(source.id(), destination.id()) {
         // If we're moving changes to a descendant, first rebase descendants onto the
@@ -3554,10 +4619,24 @@ from the source will be moved into the destination.
         destination = mut_repo.store().get_commit(&rebased_destination_id)?;
     }
     // Apply the selected changes onto the destination
-    let new_destination_tree_id = merge_trees(&destination.tree(), &parent_tree, &new_parent_tree)?;
-    CommitBuilder::for_rewrite_from(ui.settings(), &destination)
+    let destination_labels = ConflictLabels {
+        side1: Some(conflict_label_for_commit(&destination)),
+        base: conflict_label_for_commits(&source.parents()),
+        side2: Some(format!(
+            "changes moved from {}",
+            conflict_label_for_commit(&source)
+        )),
+    };
+    let new_destination_tree_id = merge_trees_with_labels(
+        &destination.tree(),
+        &parent_tree,
+        &new_parent_tree,
+        &destination_labels,
+    )?;
+    let new_destination = CommitBuilder::for_rewrite_from(ui.settings(), &destination)
         .set_tree(new_destination_tree_id)
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)?;
+    rerere::record_resolutions_in_rewrite(&destination, &new_destination);
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
@@ -3612,14 +4691,15 @@ from the source will be moved into the parent.
     let new_parent = CommitBuilder::for_rewrite_from(ui.settings(), parent)
         .set_tree(new_parent_tree_id)
         .set_predecessors(vec![parent.id().clone(), commit.id().clone()])
-        .write_to_repo(mut_repo);
+        .write_to_repo(mut_repo)?;
+    rerere::record_resolutions_in_rewrite(parent, &new_parent);
     if abandon_child {
         mut_repo.record_abandoned_commit(commit.id().clone());
     } else {
         // Commit the remainder on top of the new parent commit.
         CommitBuilder::for_rewrite_from(ui.settings(), &commit)
             .set_parents(vec![new_parent.id().clone()])
-            .write_to_repo(mut_repo);
+            .write_to_repo(mut_repo)?;
     }
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
@@ -3679,52 +4759,116 @@ aborted.
         // Commit the new child on top of the parent's parents.
         CommitBuilder::for_rewrite_from(ui.settings(), &commit)
             .set_parents(parent.parent_ids())
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())?;
     } else {
         let new_parent = CommitBuilder::for_rewrite_from(ui.settings(), parent)
             .set_tree(new_parent_tree_id)
             .set_predecessors(vec![parent.id().clone(), commit.id().clone()])
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())?;
+        rerere::record_resolutions_in_rewrite(parent, &new_parent);
         // Commit the new child on top of the new parent.
         CommitBuilder::for_rewrite_from(ui.settings(), &commit)
             .set_parents(vec![new_parent.id().clone()])
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())?;
     }
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
 
-fn cmd_restore(
-    ui: &mut Ui,
-    command: &CommandHelper,
-    args: &RestoreArgs,
-) -> Result<(), CommandError> {
+fn cmd_absorb(ui: &mut Ui, command: &CommandHelper, args: &AbsorbArgs) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let (from_str, to_str) = match (args.from.as_deref(), args.to.as_deref()) {
-        (None, None) => ("@-", "@"),
-        (Some(from), None) => (from, "@"),
-        (None, Some(to)) => ("@", to),
-        (Some(from), Some(to)) => (from, to),
-    };
-    let from_commit = workspace_command.resolve_single_rev(from_str)?;
-    let to_commit = workspace_command.resolve_single_rev(to_str)?;
-    workspace_command.check_rewriteable(&to_commit)?;
-    let tree_id;
-    if args.interactive {
-        let instructions = format!(
-            "\
-You are restoring state from: {}
-into: {}
-
-The left side of the diff shows the contents of the commit you're
-restoring from. The right side initially shows the contents of the
-commit you're restoring into.
-
-Adjust the right side until it has the changes you wanted from the left
-side. If you don't make any changes, then the operation will be aborted.
-",
-            short_commit_description(&from_commit),
-            short_commit_description(&to_commit)
+    let source = workspace_command.resolve_single_rev(&args.from)?;
+    if source.parent_ids().len() != 1 {
+        return Err(CommandError::UserError(String::from(
+            "Cannot absorb changes from merge commits",
+        )));
+    }
+    let destinations_expression = revset::parse(&args.into)?
+        .intersection(&RevsetExpression::commits(source.parent_ids()).ancestors());
+    let root_commit_id = workspace_command.repo().store().root_commit_id().clone();
+    let destinations: HashSet<CommitId> = destinations_expression
+        .evaluate(
+            workspace_command.repo().as_repo_ref(),
+            Some(&workspace_command.workspace_id()),
+        )?
+        .iter()
+        .commit_ids()
+        .filter(|id| *id != root_commit_id)
+        .collect();
+    let matcher = matcher_from_values(ui, workspace_command.workspace_root(), &args.paths)?;
+    let selected_trees = absorb::split_hunks_to_trees(
+        workspace_command.repo().as_repo_ref(),
+        &source,
+        &destinations,
+        matcher.as_ref(),
+    )?;
+    if selected_trees.is_empty() {
+        ui.write("Nothing changed.\n")?;
+        return Ok(());
+    }
+    let mut tx =
+        workspace_command.start_transaction(&format!("absorb commit {}", source.id().hex()));
+    let rewritten_commits =
+        absorb::absorb_hunks(ui.settings(), tx.mut_repo(), &source, selected_trees)?;
+    let mut rebaser = tx.mut_repo().create_descendant_rebaser(ui.settings());
+    rebaser.rebase_all()?;
+    let rebased = rebaser.rebased().clone();
+    ui.write("Absorbed changes into these revisions:\n")?;
+    for commit in &rewritten_commits {
+        let commit = match rebased.get(commit.id()) {
+            Some(new_commit_id) => tx.repo().store().get_commit(new_commit_id)?,
+            None => commit.clone(),
+        };
+        ui.write("  ")?;
+        ui.write_commit_summary(
+            tx.repo().as_repo_ref(),
+            &workspace_command.workspace_id(),
+            &commit,
+        )?;
+        ui.write("\n")?;
+    }
+    let num_rebased = rebased
+        .keys()
+        .filter(|id| !rewritten_commits.iter().any(|commit| commit.id() == *id))
+        .count();
+    if num_rebased > 0 {
+        writeln!(ui, "Rebased {} descendant commits", num_rebased)?;
+    }
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+fn cmd_restore(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &RestoreArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let (from_str, to_str) = match (args.from.as_deref(), args.to.as_deref()) {
+        (None, None) => ("@-", "@"),
+        (Some(from), None) => (from, "@"),
+        (None, Some(to)) => ("@", to),
+        (Some(from), Some(to)) => (from, to),
+    };
+    let from_commit = workspace_command.resolve_single_rev(from_str)?;
+    let to_commit = workspace_command.resolve_single_rev(to_str)?;
+    workspace_command.check_rewriteable(&to_commit)?;
+    let tree_id;
+    if args.interactive {
+        let instructions = format!(
+            "\
+You are restoring state from: {}
+into: {}
+
+The left side of the diff shows the contents of the commit you're
+restoring from. The right side initially shows the contents of the
+commit you're restoring into.
+
+Adjust the right side until it has the changes you wanted from the left
+side. If you don't make any changes, then the operation will be aborted.
+",
+            short_commit_description(&from_commit),
+            short_commit_description(&to_commit)
         );
         tree_id = workspace_command.edit_diff(
             ui,
@@ -3760,7 +4904,8 @@ side. If you don't make any changes, then the operation will be aborted.
         let mut_repo = tx.mut_repo();
         let new_commit = CommitBuilder::for_rewrite_from(ui.settings(), &to_commit)
             .set_tree(tree_id)
-            .write_to_repo(mut_repo);
+            .write_to_repo(mut_repo)?;
+        rerere::record_resolutions_in_rewrite(&to_commit, &new_commit);
         ui.write("Created ")?;
         ui.write_commit_summary(
             mut_repo.as_repo_ref(),
@@ -3773,6 +4918,807 @@ side. If you don't make any changes, then the operation will be aborted.
     Ok(())
 }
 
+fn cmd_resolve(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ResolveArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let commit = workspace_command.resolve_single_rev(&args.revision)?;
+    let matcher = matcher_from_values(ui, workspace_command.workspace_root(), &args.paths)?;
+    let conflicts = commit
+        .tree()
+        .conflicts()
+        .into_iter()
+        .filter(|(path, _)| matcher.matches(path))
+        .collect_vec();
+    if conflicts.is_empty() {
+        return Err(CommandError::UserError(
+            "No conflicts found at this revision".to_string(),
+        ));
+    }
+    if args.list {
+        return print_conflicted_paths(ui, &workspace_command, &conflicts);
+    }
+    workspace_command.check_rewriteable(&commit)?;
+    let store = workspace_command.repo().store().clone();
+    let tree_id = match args.side {
+        Some(side) => {
+            if side == 0 {
+                return Err(CommandError::UserError(
+                    "Sides are numbered from 1".to_string(),
+                ));
+            }
+            let mut tree_builder = store.tree_builder(commit.tree_id().clone());
+            let mut num_resolved = 0;
+            for (path, conflict_id) in &conflicts {
+                let conflict = store.read_conflict(path, conflict_id)?;
+                match conflict.adds.get(side - 1) {
+                    Some(part) => {
+                        tree_builder.set(path.clone(), part.value.clone());
+                        num_resolved += 1;
+                    }
+                    None => ui.write_warn(format!(
+                        "Skipping {}: the conflict has only {} sides\n",
+                        workspace_command.format_file_path(path),
+                        conflict.adds.len()
+                    ))?,
+                }
+            }
+            if num_resolved == 0 {
+                return Err(CommandError::UserError(format!(
+                    "None of the conflicts has a side #{side}"
+                )));
+            }
+            tree_builder.write_tree()
+        }
+        None => {
+            let settings = ui.settings().clone();
+            let mut tree = commit.tree();
+            let mut skipped = vec![];
+            let mut stopped = None;
+            for (path, _) in &conflicts {
+                match crate::diff_edit::run_mergetool(ui, &settings, &tree, path) {
+                    Ok(tree_id) => {
+                        tree = store.get_tree(&RepoPath::root(), &tree_id)?;
+                    }
+                    Err(
+                        err @ (ConflictResolveError::NotNormalFiles(..)
+                        | ConflictResolveError::ConflictTooComplicated { .. }),
+                    ) => {
+                        // Try the other conflicts
+                        skipped.push((path, err));
+                    }
+                    Err(err) => {
+                        // Keep the conflicts resolved so far
+                        stopped = Some((path, err));
+                        break;
+                    }
+                }
+            }
+            if tree.id() == commit.tree_id() && skipped.len() + stopped.iter().len() == 1 {
+                let (_, err) = stopped.or_else(|| skipped.pop()).unwrap();
+                return Err(err.into());
+            }
+            for (path, err) in &skipped {
+                ui.write_warn(format!(
+                    "Skipping {}: {}\n",
+                    workspace_command.format_file_path(path),
+                    err
+                ))?;
+            }
+            if let Some((path, err)) = &stopped {
+                ui.write_warn(format!(
+                    "Stopped resolving conflicts at {}: {}\n",
+                    workspace_command.format_file_path(path),
+                    err
+                ))?;
+            }
+            if tree.id() == commit.tree_id() {
+                return Err(CommandError::UserError(
+                    "None of the conflicts could be resolved".to_string(),
+                ));
+            }
+            tree.id().clone()
+        }
+    };
+    let mut tx = workspace_command.start_transaction(&format!(
+        "resolve conflicts in commit {}",
+        commit.id().hex()
+    ));
+    let mut_repo = tx.mut_repo();
+    let new_commit = CommitBuilder::for_rewrite_from(ui.settings(), &commit)
+        .set_tree(tree_id)
+        .write_to_repo(mut_repo)?;
+    rerere::record_resolutions_in_rewrite(&commit, &new_commit);
+    ui.write("Created ")?;
+    ui.write_commit_summary(
+        mut_repo.as_repo_ref(),
+        &workspace_command.workspace_id(),
+        &new_commit,
+    )?;
+    ui.write("\n")?;
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+fn print_conflicted_paths(
+    ui: &mut Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    conflicts: &[(RepoPath, ConflictId)],
+) -> Result<(), CommandError> {
+    let store = workspace_command.repo().store();
+    let formatted_paths = conflicts
+        .iter()
+        .map(|(path, _)| workspace_command.format_file_path(path))
+        .collect_vec();
+    let max_path_len = formatted_paths.iter().map(|p| p.len()).max().unwrap_or(0);
+    for ((path, conflict_id), formatted_path) in conflicts.iter().zip(formatted_paths) {
+        let conflict = store.read_conflict(path, conflict_id)?;
+        // Sides that delete the path are not included in the conflict's adds
+        let num_sides = conflict.removes.len() + 1;
+        let mut description = format!("{num_sides}-sided conflict");
+        let num_deletions = num_sides.saturating_sub(conflict.adds.len());
+        if num_deletions > 0 {
+            description.push_str(&format!(" including {num_deletions} deletion"));
+            if num_deletions > 1 {
+                description.push('s');
+            }
+        }
+        let has_non_files = conflict
+            .removes
+            .iter()
+            .chain(conflict.adds.iter())
+            .any(|part| !matches!(part.value, TreeValue::Normal { .. }));
+        if has_non_files {
+            description.push_str(" including non-file values");
+        }
+        ui.write(&format!(
+            "{formatted_path:max_path_len$}    {description}\n"
+        ))?;
+    }
+    Ok(())
+}
+
+/// A formatter configured in `[fix.tools.<name>]`.
+struct FixTool {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    patterns: Arc<GitIgnoreFile>,
+}
+
+impl FixTool {
+    /// Runs the tool on the content of the file at `path`. Returns `None` if
+    /// the tool couldn't be run or exited with a non-zero status.
+    fn run(&self, path: &str, content: &[u8]) -> Option<Vec<u8>> {
+        let args = self.args.iter().map(|arg| arg.replace("$path", path));
+        let mut child = std::process::Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let content = content.to_vec();
+        // Write from another thread so a tool that writes before reading all its
+        // input can't block us
+        let writer = std::thread::spawn(move || stdin.write_all(&content));
+        let output = child.wait_with_output().ok()?;
+        // If the tool exits without reading all of its input, the write fails,
+        // but the exit status tells us whether the tool succeeded
+        let _ = writer.join().unwrap();
+        output.status.success().then(|| output.stdout)
+    }
+}
+
+fn get_fix_tools(settings: &UserSettings) -> Vec<FixTool> {
+    let config = settings.config();
+    let names = config
+        .get_table("fix.tools")
+        .map(|table| table.into_keys().sorted().collect_vec())
+        .unwrap_or_default();
+    let mut tools = vec![];
+    for name in names {
+        let program = match config.get_string(&format!("fix.tools.{name}.program")) {
+            Ok(program) => program,
+            Err(_) => continue,
+        };
+        let get_strings = |key: &str| -> Vec<String> {
+            config
+                .get_array(&format!("fix.tools.{name}.{key}"))
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|value| value.into_string().ok())
+                .collect()
+        };
+        let args = get_strings("args");
+        let patterns =
+            GitIgnoreFile::empty().chain("", get_strings("patterns").join("\n").as_bytes());
+        tools.push(FixTool {
+            name,
+            program,
+            args,
+            patterns,
+        });
+    }
+    tools
+}
+
+/// Rewrites `commits`, which must be ordered parents first, so each of them
+/// gets its tree from `new_trees` (if it's there) and the rewritten versions of
+/// its parents.
+///
+/// The commits in `new_trees` are rewritten one at a time instead of letting
+/// them be rebased, so each of them gets exactly its new tree. The other ones
+/// are rebased onto their rewritten parents so they keep the parents' changes.
+fn rewrite_commits_with_trees(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    commits: &[Commit],
+    new_trees: &HashMap<CommitId, TreeId>,
+) -> Result<(), CommandError> {
+    let mut rewritten: HashMap<CommitId, CommitId> = HashMap::new();
+    for commit in commits {
+        let new_parent_ids = commit
+            .parent_ids()
+            .iter()
+            .map(|id| rewritten.get(id).unwrap_or(id).clone())
+            .collect_vec();
+        let new_commit = match new_trees.get(commit.id()) {
+            Some(new_tree_id) => CommitBuilder::for_rewrite_from(settings, commit)
+                .set_parents(new_parent_ids)
+                .set_tree(new_tree_id.clone())
+                .write_to_repo(mut_repo)?,
+            None if new_parent_ids != commit.parent_ids() => {
+                let new_parents = new_parent_ids
+                    .iter()
+                    .map(|id| mut_repo.store().get_commit(id))
+                    .try_collect::<_, Vec<_>, _>()?;
+                rebase_commit(settings, mut_repo, commit, &new_parents)?
+            }
+            None => continue,
+        };
+        rewritten.insert(commit.id().clone(), new_commit.id().clone());
+    }
+    Ok(())
+}
+
+fn cmd_fix(ui: &mut Ui, command: &CommandHelper, args: &FixArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let tools = get_fix_tools(ui.settings());
+    if tools.is_empty() {
+        return Err(CommandError::UserError(
+            "No tools are configured in `fix.tools`".to_string(),
+        ));
+    }
+    let mut commits = workspace_command.resolve_revset(&args.revisions)?;
+    for commit in &commits {
+        workspace_command.check_rewriteable(commit)?;
+    }
+    // Fix parents before their children
+    commits.reverse();
+    let store = workspace_command.repo().store().clone();
+    // The fixed version of each file content we've seen, so each tool runs once
+    // per content
+    let mut fixed_file_ids: HashMap<(RepoPath, FileId), FileId> = HashMap::new();
+    let mut fixed_trees = HashMap::new();
+    // The paths fixed in each commit or its ancestors. Each commit gets exactly
+    // its fixed tree, so these are fixed again in the descendants even if they
+    // don't modify them, or the descendants would undo the fixes.
+    let mut fixed_paths: HashMap<CommitId, HashSet<RepoPath>> = HashMap::new();
+    for commit in &commits {
+        let parent_tree =
+            merge_commit_trees(workspace_command.repo().as_repo_ref(), &commit.parents());
+        let tree = commit.tree();
+        let mut commit_fixed_paths: HashSet<RepoPath> = commit
+            .parent_ids()
+            .iter()
+            .filter_map(|parent_id| fixed_paths.get(parent_id))
+            .flatten()
+            .cloned()
+            .collect();
+        let paths = commit_fixed_paths
+            .iter()
+            .cloned()
+            .chain(
+                parent_tree
+                    .diff(&tree, &EverythingMatcher)
+                    .map(|(path, _)| path),
+            )
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let mut tree_builder = store.tree_builder(commit.tree_id().clone());
+        let mut changed = false;
+        for path in paths {
+            let (id, executable) = match tree.path_value(&path) {
+                Some(TreeValue::Normal { id, executable }) => (id, executable),
+                _ => continue,
+            };
+            let key = (path.clone(), id.clone());
+            let new_id = match fixed_file_ids.get(&key) {
+                Some(new_id) => new_id.clone(),
+                None => {
+                    let path_str = path.to_internal_file_string();
+                    let mut content = vec![];
+                    store.read_file(&path, &id)?.read_to_end(&mut content)?;
+                    for tool in &tools {
+                        if !tool.patterns.matches_file(&path_str) {
+                            continue;
+                        }
+                        match tool.run(&path_str, &content) {
+                            Some(new_content) => content = new_content,
+                            None => ui.write_warn(format!(
+                                "Failed to run {} on {}\n",
+                                tool.name,
+                                workspace_command.format_file_path(&path)
+                            ))?,
+                        }
+                    }
+                    let new_id = store.write_file(&path, &mut content.as_slice())?;
+                    fixed_file_ids.insert(key, new_id.clone());
+                    new_id
+                }
+            };
+            if new_id != id {
+                commit_fixed_paths.insert(path.clone());
+                tree_builder.set(
+                    path,
+                    TreeValue::Normal {
+                        id: new_id,
+                        executable,
+                    },
+                );
+                changed = true;
+            }
+        }
+        if changed {
+            fixed_trees.insert(commit.id().clone(), tree_builder.write_tree());
+        }
+        fixed_paths.insert(commit.id().clone(), commit_fixed_paths);
+    }
+    if fixed_trees.is_empty() {
+        ui.write("Nothing changed.\n")?;
+        return Ok(());
+    }
+
+    let mut tx = workspace_command.start_transaction(&format!(
+        "fix {} commits with {}",
+        fixed_trees.len(),
+        tools.iter().map(|tool| &tool.name).join(", ")
+    ));
+    rewrite_commits_with_trees(ui.settings(), tx.mut_repo(), &commits, &fixed_trees)?;
+    writeln!(ui, "Fixed {} commits", fixed_trees.len())?;
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+/// The result of running the command of `jj run` on one commit.
+struct RunOutput {
+    output: std::process::Output,
+    /// The tree the command left behind, if it was asked for and the command
+    /// succeeded
+    new_tree_id: Option<TreeId>,
+}
+
+/// Checks out `commit` into a temporary directory and runs `command` there.
+fn run_on_commit(
+    commit: &Commit,
+    command: &[String],
+    rewrite: bool,
+    conflict_marker_style: ConflictMarkerStyle,
+) -> Result<RunOutput, CommandError> {
+    let temp_dir = tempfile::tempdir()?;
+    let working_copy_path = temp_dir.path().join("working_copy");
+    let state_path = temp_dir.path().join("state");
+    fs::create_dir(&working_copy_path)?;
+    fs::create_dir(&state_path)?;
+    let mut tree_state = TreeState::init(
+        commit.store().clone(),
+        working_copy_path.clone(),
+        state_path,
+    );
+    tree_state.set_conflict_marker_style(conflict_marker_style);
+    tree_state.check_out(&commit.tree()).map_err(|err| {
+        CommandError::InternalError(format!(
+            "Failed to check out commit {}: {}",
+            commit.id().hex(),
+            err
+        ))
+    })?;
+    let output = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(&working_copy_path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| CommandError::UserError(format!("Failed to run {}: {}", command[0], err)))?;
+    let new_tree_id = if rewrite && output.status.success() {
+        Some(tree_state.snapshot(GitIgnoreFile::empty())?)
+    } else {
+        None
+    };
+    Ok(RunOutput {
+        output,
+        new_tree_id,
+    })
+}
+
+fn cmd_run(ui: &mut Ui, command: &CommandHelper, args: &RunArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let commits = workspace_command.resolve_revset(&args.revisions)?;
+    if args.rewrite {
+        for commit in &commits {
+            workspace_command.check_rewriteable(commit)?;
+        }
+    }
+    if args.jobs == 0 {
+        return Err(CommandError::UserError(
+            "The number of jobs must be at least 1".to_string(),
+        ));
+    }
+
+    // Run the oldest commits first, and each worker thread takes the next commit
+    // from the queue until it's empty
+    let queue: Arc<Mutex<VecDeque<(usize, Commit)>>> = Arc::new(Mutex::new(
+        commits.iter().cloned().enumerate().rev().collect(),
+    ));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let command_args = Arc::new(args.command.clone());
+    let conflict_marker_style = ui.settings().conflict_marker_style();
+    let workers = (0..args.jobs.min(commits.len()))
+        .map(|_| {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            let command_args = command_args.clone();
+            let rewrite = args.rewrite;
+            std::thread::spawn(move || loop {
+                let next = queue.lock().unwrap().pop_front();
+                let (index, commit) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let result = run_on_commit(&commit, &command_args, rewrite, conflict_marker_style);
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect_vec();
+    drop(sender);
+    let mut results: Vec<Option<RunOutput>> = commits.iter().map(|_| None).collect();
+    // Let the workers finish before reporting an error so no command is left
+    // running in a temporary directory
+    let mut first_error = None;
+    for (index, result) in receiver {
+        match result {
+            Ok(result) => results[index] = Some(result),
+            Err(err) => {
+                queue.lock().unwrap().clear();
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    if let Some(err) = first_error {
+        return Err(err);
+    }
+    let results = results.into_iter().map(Option::unwrap).collect_vec();
+
+    for result in &results {
+        ui.stdout_formatter().write_all(&result.output.stdout)?;
+        ui.stderr_formatter().write_all(&result.output.stderr)?;
+    }
+    let workspace_id = workspace_command.workspace_id();
+    let mut num_failed = 0;
+    for (commit, result) in commits.iter().zip(&results) {
+        if result.output.status.success() {
+            ui.write("passed ")?;
+        } else {
+            num_failed += 1;
+            ui.write("failed ")?;
+        }
+        ui.write_commit_summary(
+            workspace_command.repo().as_repo_ref(),
+            &workspace_id,
+            commit,
+        )?;
+        if !result.output.status.success() {
+            ui.write(&format!(" ({})", result.output.status))?;
+        }
+        ui.write("\n")?;
+    }
+
+    if args.rewrite {
+        let new_trees: HashMap<CommitId, TreeId> = commits
+            .iter()
+            .zip(&results)
+            .filter_map(|(commit, result)| {
+                let new_tree_id = result.new_tree_id.as_ref()?;
+                (new_tree_id != commit.tree_id())
+                    .then(|| (commit.id().clone(), new_tree_id.clone()))
+            })
+            .collect();
+        if !new_trees.is_empty() {
+            let mut tx = workspace_command.start_transaction(&format!(
+                "run {} on {} commits",
+                args.command.join(" "),
+                new_trees.len()
+            ));
+            let mut commits_parents_first = commits.clone();
+            commits_parents_first.reverse();
+            rewrite_commits_with_trees(
+                ui.settings(),
+                tx.mut_repo(),
+                &commits_parents_first,
+                &new_trees,
+            )?;
+            writeln!(ui, "Rewrote {} commits", new_trees.len())?;
+            workspace_command.finish_transaction(ui, tx)?;
+        }
+    }
+
+    if num_failed > 0 {
+        return Err(CommandError::UserError(format!(
+            "The command failed on {} of {} revisions",
+            num_failed,
+            commits.len()
+        )));
+    }
+    Ok(())
+}
+
+fn cmd_bisect(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    subcommand: &BisectCommands,
+) -> Result<(), CommandError> {
+    match subcommand {
+        BisectCommands::Start(command_matches) => cmd_bisect_start(ui, command, command_matches),
+        BisectCommands::Good(command_matches) => cmd_bisect_good(ui, command, command_matches),
+        BisectCommands::Bad(command_matches) => cmd_bisect_bad(ui, command, command_matches),
+        BisectCommands::Skip(command_matches) => cmd_bisect_skip(ui, command, command_matches),
+        BisectCommands::Run(command_matches) => cmd_bisect_run(ui, command, command_matches),
+        BisectCommands::Reset(command_matches) => cmd_bisect_reset(ui, command, command_matches),
+    }
+}
+
+fn load_bisect_state(
+    workspace_command: &WorkspaceCommandHelper,
+) -> Result<BisectState, CommandError> {
+    BisectState::load(workspace_command.repo().repo_path())?.ok_or_else(|| {
+        CommandError::UserError(
+            "No bisection in progress. Start one with `jj bisect start`.".to_string(),
+        )
+    })
+}
+
+/// Resolves the revisions to mark in a bisection, defaulting to the revision
+/// being tested.
+fn resolve_bisect_revisions(
+    workspace_command: &WorkspaceCommandHelper,
+    state: &BisectState,
+    revisions: &[String],
+) -> Result<Vec<CommitId>, CommandError> {
+    if revisions.is_empty() {
+        return match &state.current {
+            Some(current) => Ok(vec![current.clone()]),
+            None => Ok(vec![workspace_command
+                .resolve_single_rev("@")?
+                .id()
+                .clone()]),
+        };
+    }
+    let mut ids = vec![];
+    for revision in revisions {
+        for commit in workspace_command.resolve_revset(revision)? {
+            ids.push(commit.id().clone());
+        }
+    }
+    Ok(ids)
+}
+
+/// Saves the state of the bisection and takes the next step: checks out the
+/// next revision to test, or reports the result.
+fn bisect_next_step(
+    ui: &mut Ui,
+    workspace_command: &mut WorkspaceCommandHelper,
+    mut state: BisectState,
+) -> Result<(), CommandError> {
+    let repo = workspace_command.repo().clone();
+    let workspace_id = workspace_command.workspace_id();
+    let step = state.next_step(repo.index().as_index_ref());
+    state.current = None;
+    match step {
+        BisectionStep::NeedsBad => {
+            ui.write("Mark a revision that has the bug with `jj bisect bad`\n")?;
+        }
+        BisectionStep::NeedsGood => {
+            ui.write("Mark a revision that doesn't have the bug with `jj bisect good`\n")?;
+        }
+        BisectionStep::BadIsAncestorOfGood => {
+            state.save(repo.repo_path())?;
+            return Err(CommandError::UserError(
+                "The bad revision is an ancestor of a good revision".to_string(),
+            ));
+        }
+        BisectionStep::UnknownRevision(commit_id) => {
+            return Err(CommandError::UserError(format!(
+                "The bisection refers to revision {} that is not in the repo. Run `jj bisect \
+                 reset` to start over.",
+                short_commit_hash(&commit_id)
+            )));
+        }
+        BisectionStep::Test {
+            commit_id,
+            num_untested,
+        } => {
+            let commit = repo.store().get_commit(&commit_id)?;
+            state.current = Some(commit_id);
+            state.save(repo.repo_path())?;
+            writeln!(ui, "Bisecting: {} untested revisions left", num_untested)?;
+            ui.write("Now testing: ")?;
+            ui.write_commit_summary(repo.as_repo_ref(), &workspace_id, &commit)?;
+            ui.write("\n")?;
+            let mut tx = workspace_command
+                .start_transaction(&format!("check out commit {}", commit.id().hex()));
+            let new_commit = CommitBuilder::for_open_commit(
+                ui.settings(),
+                commit.id().clone(),
+                commit.tree_id().clone(),
+            )
+            .write_to_repo(tx.mut_repo())?;
+            tx.mut_repo().edit(workspace_id, &new_commit);
+            workspace_command.finish_transaction(ui, tx)?;
+            return Ok(());
+        }
+        BisectionStep::Found(commit_id) => {
+            let commit = repo.store().get_commit(&commit_id)?;
+            ui.write("The first bad revision is: ")?;
+            ui.write_commit_summary(repo.as_repo_ref(), &workspace_id, &commit)?;
+            ui.write("\n")?;
+        }
+        BisectionStep::Indeterminate(commit_ids) => {
+            ui.write("The first bad revision could be any of these:\n")?;
+            for commit_id in commit_ids {
+                let commit = repo.store().get_commit(&commit_id)?;
+                ui.write("  ")?;
+                ui.write_commit_summary(repo.as_repo_ref(), &workspace_id, &commit)?;
+                ui.write("\n")?;
+            }
+        }
+    }
+    state.save(repo.repo_path())?;
+    Ok(())
+}
+
+fn cmd_bisect_start(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectStartArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut state = BisectState::default();
+    if let Some(revision) = &args.bad {
+        state.mark_bad(workspace_command.resolve_single_rev(revision)?.id().clone());
+    }
+    for revision in &args.good {
+        for commit in workspace_command.resolve_revset(revision)? {
+            state.mark_good(commit.id().clone());
+        }
+    }
+    bisect_next_step(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_good(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectGoodArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut state = load_bisect_state(&workspace_command)?;
+    for id in resolve_bisect_revisions(&workspace_command, &state, &args.revisions)? {
+        state.mark_good(id);
+    }
+    bisect_next_step(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_bad(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectBadArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut state = load_bisect_state(&workspace_command)?;
+    let id = match &args.revision {
+        Some(revision) => workspace_command.resolve_single_rev(revision)?.id().clone(),
+        None => resolve_bisect_revisions(&workspace_command, &state, &[])?.remove(0),
+    };
+    state.mark_bad(id);
+    bisect_next_step(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_skip(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectSkipArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut state = load_bisect_state(&workspace_command)?;
+    for id in resolve_bisect_revisions(&workspace_command, &state, &args.revisions)? {
+        state.mark_skipped(id);
+    }
+    bisect_next_step(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_run(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectRunArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut state = load_bisect_state(&workspace_command)?;
+    let repo = workspace_command.repo().clone();
+    let workspace_id = workspace_command.workspace_id();
+    loop {
+        let commit_id = match state.next_step(repo.index().as_index_ref()) {
+            BisectionStep::NeedsBad | BisectionStep::NeedsGood => {
+                return Err(CommandError::UserError(
+                    "Mark a good and a bad revision before running a bisection".to_string(),
+                ));
+            }
+            BisectionStep::Test { commit_id, .. } => commit_id,
+            _ => break,
+        };
+        let commit = repo.store().get_commit(&commit_id)?;
+        let result = run_on_commit(
+            &commit,
+            &args.command,
+            false,
+            ui.settings().conflict_marker_style(),
+        )?;
+        ui.stdout_formatter().write_all(&result.output.stdout)?;
+        ui.stderr_formatter().write_all(&result.output.stderr)?;
+        match result.output.status.code() {
+            Some(0) => {
+                ui.write("good ")?;
+                state.mark_good(commit_id);
+            }
+            Some(125) => {
+                ui.write("skip ")?;
+                state.mark_skipped(commit_id);
+            }
+            Some(code) if code < 128 => {
+                ui.write("bad ")?;
+                state.mark_bad(commit_id);
+            }
+            _ => {
+                return Err(CommandError::UserError(format!(
+                    "Aborting the bisection because the command failed with {}",
+                    result.output.status
+                )));
+            }
+        }
+        ui.write_commit_summary(repo.as_repo_ref(), &workspace_id, &commit)?;
+        ui.write("\n")?;
+        state.save(repo.repo_path())?;
+    }
+    bisect_next_step(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_reset(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &BisectResetArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    BisectState::remove(workspace_command.repo().repo_path())?;
+    Ok(())
+}
+
 fn cmd_touchup(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -3801,7 +5747,8 @@ don't make any changes, then the operation will be aborted.",
         let mut_repo = tx.mut_repo();
         let new_commit = CommitBuilder::for_rewrite_from(ui.settings(), &commit)
             .set_tree(tree_id)
-            .write_to_repo(mut_repo);
+            .write_to_repo(mut_repo)?;
+        rerere::record_resolutions_in_rewrite(&commit, &new_commit);
         ui.write("Created ")?;
         ui.write_commit_summary(
             mut_repo.as_repo_ref(),
@@ -3854,7 +5801,7 @@ any changes, then the operation will be aborted.
         let first_commit = CommitBuilder::for_rewrite_from(ui.settings(), &commit)
             .set_tree(tree_id)
             .set_description(first_description)
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())?;
         let second_description = edit_description(
             ui,
             tx.base_repo(),
@@ -3866,7 +5813,7 @@ any changes, then the operation will be aborted.
             .set_tree(commit.tree_id().clone())
             .generate_new_change_id()
             .set_description(second_description)
-            .write_to_repo(tx.mut_repo());
+            .write_to_repo(tx.mut_repo())?;
         let mut rebaser = DescendantRebaser::new(
             ui.settings(),
             tx.mut_repo(),
@@ -3929,7 +5876,7 @@ fn cmd_merge(ui: &mut Ui, command: &CommandHelper, args: &MergeArgs) -> Result<(
         .set_parents(parent_ids)
         .set_description(description)
         .set_open(false)
-        .write_to_repo(tx.mut_repo());
+        .write_to_repo(tx.mut_repo())?;
     workspace_command.finish_transaction(ui, tx)?;
 
     Ok(())
@@ -3937,31 +5884,185 @@ fn cmd_merge(ui: &mut Ui, command: &CommandHelper, args: &MergeArgs) -> Result<(
 
 fn cmd_rebase(ui: &mut Ui, command: &CommandHelper, args: &RebaseArgs) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let mut new_parents = vec![];
-    for revision_str in &args.destination {
-        let destination = workspace_command.resolve_single_rev(revision_str)?;
-        new_parents.push(destination);
+    let (new_parents, insertion) = if let Some(after_str) = &args.insert_after {
+        let after = workspace_command.resolve_single_rev(after_str)?;
+        let children = RevsetExpression::commit(after.id().clone())
+            .children()
+            .evaluate(
+                workspace_command.repo().as_repo_ref(),
+                Some(&workspace_command.workspace_id()),
+            )?
+            .iter()
+            .commits(workspace_command.repo().store())
+            .try_collect()?;
+        let insertion = RebaseInsertion {
+            children,
+            replaced_parent_ids: vec![after.id().clone()],
+        };
+        (vec![after], Some(insertion))
+    } else if let Some(before_str) = &args.insert_before {
+        let before = workspace_command.resolve_single_rev(before_str)?;
+        if before.id() == workspace_command.repo().store().root_commit_id() {
+            return Err(CommandError::UserError(
+                "Cannot insert revisions before the root commit".to_string(),
+            ));
+        }
+        let insertion = RebaseInsertion {
+            replaced_parent_ids: before.parent_ids(),
+            children: vec![before.clone()],
+        };
+        (before.parents(), Some(insertion))
+    } else {
+        let mut new_parents = vec![];
+        for revision_str in &args.destination {
+            let destination = workspace_command.resolve_single_rev(revision_str)?;
+            new_parents.push(destination);
+        }
+        (new_parents, None)
+    };
+    if let Some(insertion) = &insertion {
+        for child in &insertion.children {
+            workspace_command.check_rewriteable(child)?;
+        }
     }
     if let Some(rev_str) = &args.revision {
-        rebase_revision(ui, &mut workspace_command, &new_parents, rev_str)?;
+        rebase_revision(
+            ui,
+            &mut workspace_command,
+            &new_parents,
+            insertion.as_ref(),
+            rev_str,
+            args.empty,
+        )?;
     } else if let Some(source_str) = &args.source {
-        rebase_descendants(ui, &mut workspace_command, &new_parents, source_str)?;
+        rebase_descendants(
+            ui,
+            &mut workspace_command,
+            &new_parents,
+            insertion.as_ref(),
+            source_str,
+            args.empty,
+        )?;
+    } else if insertion.is_some() {
+        return Err(CommandError::UserError(
+            "--insert-after and --insert-before require -r or -s".to_string(),
+        ));
     } else {
         let branch_str = args.branch.as_deref().unwrap_or("@");
-        rebase_branch(ui, &mut workspace_command, &new_parents, branch_str)?;
+        rebase_branch(
+            ui,
+            &mut workspace_command,
+            &new_parents,
+            branch_str,
+            args.empty,
+        )?;
     }
     Ok(())
 }
 
+/// The commits that `jj rebase --insert-after/--insert-before` rebases onto the
+/// inserted commits.
+struct RebaseInsertion {
+    /// The commits to rebase onto the inserted commits.
+    children: Vec<Commit>,
+    /// The parents of `children` that the inserted commits replace.
+    replaced_parent_ids: Vec<CommitId>,
+}
+
+impl RebaseInsertion {
+    /// Rebases the children onto the inserted commits, whose heads are
+    /// `new_head_ids`. Children in `moved` are the inserted commits themselves,
+    /// so they're left alone. Returns the rebased children.
+    fn rebase_children(
+        &self,
+        ui: &Ui,
+        mut_repo: &mut MutableRepo,
+        moved: &HashSet<CommitId>,
+        new_head_ids: &[CommitId],
+        empty: EmptyBehavior,
+    ) -> Result<Vec<Commit>, CommandError> {
+        let mut rebased_children = vec![];
+        for child in &self.children {
+            if moved.contains(child.id()) {
+                continue;
+            }
+            let mut new_parent_ids = vec![];
+            for parent_id in child.parent_ids() {
+                if !self.replaced_parent_ids.contains(&parent_id) {
+                    new_parent_ids.push(parent_id);
+                } else if !new_parent_ids.contains(&new_head_ids[0]) {
+                    new_parent_ids.extend(new_head_ids.iter().cloned());
+                }
+            }
+            let new_parents: Vec<_> = new_parent_ids
+                .iter()
+                .map(|id| mut_repo.store().get_commit(id))
+                .try_collect()?;
+            rebased_children.extend(rebase_or_abandon_commit(
+                ui,
+                mut_repo,
+                child,
+                &new_parents,
+                empty,
+            )?);
+        }
+        Ok(rebased_children)
+    }
+
+    /// Returns the insertion with the children and replaced parents updated
+    /// to the commits they were rewritten to according to `rewritten`.
+    fn rewritten(
+        &self,
+        store: &Arc<Store>,
+        rewritten: &HashMap<CommitId, CommitId>,
+    ) -> Result<RebaseInsertion, CommandError> {
+        let current_id = |id: &CommitId| rewritten.get(id).unwrap_or(id).clone();
+        let children = self
+            .children
+            .iter()
+            .map(|child| store.get_commit(&current_id(child.id())))
+            .try_collect()?;
+        let replaced_parent_ids = self.replaced_parent_ids.iter().map(current_id).collect();
+        Ok(RebaseInsertion {
+            children,
+            replaced_parent_ids,
+        })
+    }
+}
+
+/// Rebases `commit` onto `new_parents`, or records it as abandoned if it
+/// became empty and `empty` says to abandon it. Returns the rebased commit, or
+/// `None` if it was abandoned.
+fn rebase_or_abandon_commit(
+    ui: &Ui,
+    mut_repo: &mut MutableRepo,
+    commit: &Commit,
+    new_parents: &[Commit],
+    empty: EmptyBehavior,
+) -> Result<Option<Commit>, CommandError> {
+    let rebased_commit =
+        rebase_commit_with_empty_behavior(ui.settings(), mut_repo, commit, new_parents, empty)?;
+    if rebased_commit.is_none() {
+        let new_parent_ids = new_parents
+            .iter()
+            .map(|commit| commit.id().clone())
+            .collect();
+        mut_repo.record_abandoned_commit_with_parents(commit.id().clone(), new_parent_ids);
+    }
+    Ok(rebased_commit)
+}
+
 fn rebase_branch(
     ui: &mut Ui,
     workspace_command: &mut WorkspaceCommandHelper,
     new_parents: &[Commit],
     branch_str: &str,
+    empty: EmptyBehavior,
 ) -> Result<(), CommandError> {
     let branch_commit = workspace_command.resolve_single_rev(branch_str)?;
     let mut tx = workspace_command
         .start_transaction(&format!("rebase branch at {}", branch_commit.id().hex()));
+    tx.set_empty_behavior(empty);
     check_rebase_destinations(workspace_command, new_parents, &branch_commit)?;
 
     let parent_ids = new_parents
@@ -3984,8 +6085,10 @@ fn rebase_branch(
     {
         let root_commit = root_result?;
         workspace_command.check_rewriteable(&root_commit)?;
-        rebase_commit(ui.settings(), tx.mut_repo(), &root_commit, new_parents);
-        num_rebased += 1;
+        if rebase_or_abandon_commit(ui, tx.mut_repo(), &root_commit, new_parents, empty)?.is_some()
+        {
+            num_rebased += 1;
+        }
     }
     num_rebased += tx.mut_repo().rebase_descendants(ui.settings())?;
     writeln!(ui, "Rebased {} commits", num_rebased)?;
@@ -3997,17 +6100,71 @@ fn rebase_descendants(
     ui: &mut Ui,
     workspace_command: &mut WorkspaceCommandHelper,
     new_parents: &[Commit],
+    insertion: Option<&RebaseInsertion>,
     source_str: &str,
+    empty: EmptyBehavior,
 ) -> Result<(), CommandError> {
     let old_commit = workspace_command.resolve_single_rev(source_str)?;
     workspace_command.check_rewriteable(&old_commit)?;
+    let moved: HashSet<_> = RevsetExpression::commit(old_commit.id().clone())
+        .descendants()
+        .evaluate(
+            workspace_command.repo().as_repo_ref(),
+            Some(&workspace_command.workspace_id()),
+        )?
+        .iter()
+        .commit_ids()
+        .collect();
+    if let Some(insertion) = insertion {
+        if insertion
+            .children
+            .iter()
+            .any(|child| moved.contains(child.id()))
+        {
+            return Err(CommandError::UserError(format!(
+                "Cannot insert {} and its descendants before one of them",
+                short_commit_hash(old_commit.id())
+            )));
+        }
+    }
     check_rebase_destinations(workspace_command, new_parents, &old_commit)?;
     let mut tx = workspace_command.start_transaction(&format!(
         "rebase commit {} and descendants",
         old_commit.id().hex()
     ));
-    rebase_commit(ui.settings(), tx.mut_repo(), &old_commit, new_parents);
-    let num_rebased = tx.mut_repo().rebase_descendants(ui.settings())? + 1;
+    tx.set_empty_behavior(empty);
+    let mut num_rebased = 0;
+    let new_commit = rebase_or_abandon_commit(ui, tx.mut_repo(), &old_commit, new_parents, empty)?;
+    if new_commit.is_some() {
+        num_rebased += 1;
+    }
+    if let Some(insertion) = insertion {
+        // Rebase the descendants first so we know the heads of the inserted
+        // commits.
+        let mut rebaser = tx.mut_repo().create_descendant_rebaser(ui.settings());
+        rebaser.rebase_all()?;
+        let mut new_ids = rebaser
+            .rebased()
+            .iter()
+            .filter(|(old_id, _)| moved.contains(old_id))
+            .map(|(_, new_id)| new_id.clone())
+            .collect_vec();
+        num_rebased += rebaser.rebased().len();
+        new_ids.extend(new_commit.iter().map(|commit| commit.id().clone()));
+        let new_head_ids = if new_ids.is_empty() {
+            // All the inserted commits were abandoned
+            new_parents
+                .iter()
+                .map(|commit| commit.id().clone())
+                .collect_vec()
+        } else {
+            tx.repo().index().heads(&new_ids)
+        };
+        num_rebased += insertion
+            .rebase_children(ui, tx.mut_repo(), &moved, &new_head_ids, empty)?
+            .len();
+    }
+    num_rebased += tx.mut_repo().rebase_descendants(ui.settings())?;
     writeln!(ui, "Rebased {} commits", num_rebased)?;
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
@@ -4017,45 +6174,142 @@ fn rebase_revision(
     ui: &mut Ui,
     workspace_command: &mut WorkspaceCommandHelper,
     new_parents: &[Commit],
+    insertion: Option<&RebaseInsertion>,
     rev_str: &str,
+    empty: EmptyBehavior,
 ) -> Result<(), CommandError> {
     let old_commit = workspace_command.resolve_single_rev(rev_str)?;
     workspace_command.check_rewriteable(&old_commit)?;
+    if let Some(insertion) = insertion {
+        return insert_revision(
+            ui,
+            workspace_command,
+            new_parents,
+            insertion,
+            &old_commit,
+            empty,
+        );
+    }
     check_rebase_destinations(workspace_command, new_parents, &old_commit)?;
     let mut tx =
         workspace_command.start_transaction(&format!("rebase commit {}", old_commit.id().hex()));
-    rebase_commit(ui.settings(), tx.mut_repo(), &old_commit, new_parents);
-    // Manually rebase children because we don't want to rebase them onto the
-    // rewritten commit. (But we still want to record the commit as rewritten so
-    // branches and the working copy get updated to the rewritten commit.)
+    tx.set_empty_behavior(empty);
+    rebase_or_abandon_commit(ui, tx.mut_repo(), &old_commit, new_parents, empty)?;
+    // Manually rebase children because we don't want to rebase them onto the
+    // rewritten commit. (But we still want to record the commit as rewritten so
+    // branches and the working copy get updated to the rewritten commit.)
+    let children_expression = RevsetExpression::commit(old_commit.id().clone()).children();
+    let mut num_rebased_descendants = 0;
+    let store = workspace_command.repo.store();
+    for child_commit in children_expression
+        .evaluate(
+            workspace_command.repo().as_repo_ref(),
+            Some(&workspace_command.workspace_id()),
+        )
+        .unwrap()
+        .iter()
+        .commits(store)
+    {
+        if rebase_or_abandon_commit(
+            ui,
+            tx.mut_repo(),
+            &child_commit?,
+            &old_commit.parents(),
+            empty,
+        )?
+        .is_some()
+        {
+            num_rebased_descendants += 1;
+        }
+    }
+    num_rebased_descendants += tx.mut_repo().rebase_descendants(ui.settings())?;
+    if num_rebased_descendants > 0 {
+        writeln!(
+            ui,
+            "Also rebased {} descendant commits onto parent of rebased commit",
+            num_rebased_descendants
+        )?;
+    }
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+/// Implements `jj rebase -r` with `--insert-after` or `--insert-before`.
+fn insert_revision(
+    ui: &mut Ui,
+    workspace_command: &mut WorkspaceCommandHelper,
+    new_parents: &[Commit],
+    insertion: &RebaseInsertion,
+    old_commit: &Commit,
+    empty: EmptyBehavior,
+) -> Result<(), CommandError> {
+    if new_parents.contains(old_commit) || insertion.children.contains(old_commit) {
+        return Err(CommandError::UserError(format!(
+            "Cannot insert {} next to itself",
+            short_commit_hash(old_commit.id())
+        )));
+    }
+    let mut tx =
+        workspace_command.start_transaction(&format!("rebase commit {}", old_commit.id().hex()));
+    tx.set_empty_behavior(empty);
+    // The commit may be inserted among its own descendants, so first rebase
+    // them onto its parents to get them out of the way. We keep track of the
+    // rewritten commits to find where the descendants ended up.
+    let mut rebased_ids = HashMap::new();
     let children_expression = RevsetExpression::commit(old_commit.id().clone()).children();
-    let mut num_rebased_descendants = 0;
-    let store = workspace_command.repo.store();
     for child_commit in children_expression
         .evaluate(
             workspace_command.repo().as_repo_ref(),
             Some(&workspace_command.workspace_id()),
-        )
-        .unwrap()
+        )?
         .iter()
-        .commits(store)
+        .commits(workspace_command.repo().store())
     {
-        rebase_commit(
-            ui.settings(),
+        let child_commit = child_commit?;
+        if let Some(new_child_commit) = rebase_or_abandon_commit(
+            ui,
             tx.mut_repo(),
-            &child_commit?,
+            &child_commit,
             &old_commit.parents(),
-        );
-        num_rebased_descendants += 1;
-    }
-    num_rebased_descendants += tx.mut_repo().rebase_descendants(ui.settings())?;
-    if num_rebased_descendants > 0 {
-        writeln!(
-            ui,
-            "Also rebased {} descendant commits onto parent of rebased commit",
-            num_rebased_descendants
-        )?;
+            empty,
+        )? {
+            rebased_ids.insert(child_commit.id().clone(), new_child_commit.id().clone());
+        }
     }
+    let mut rebaser = tx.mut_repo().create_descendant_rebaser(ui.settings());
+    rebaser.rebase_all()?;
+    rebased_ids.extend(rebaser.rebased().clone());
+    let mut_repo = tx.mut_repo();
+    let insertion = insertion.rewritten(mut_repo.store(), &rebased_ids)?;
+    let new_parents: Vec<_> = new_parents
+        .iter()
+        .map(|parent| {
+            let parent_id = rebased_ids.get(parent.id()).unwrap_or_else(|| parent.id());
+            mut_repo.store().get_commit(parent_id)
+        })
+        .try_collect()?;
+
+    let new_commit = rebase_or_abandon_commit(ui, mut_repo, old_commit, &new_parents, empty)?;
+    let new_head_ids = match &new_commit {
+        Some(new_commit) => vec![new_commit.id().clone()],
+        None => new_parents
+            .iter()
+            .map(|commit| commit.id().clone())
+            .collect_vec(),
+    };
+    let moved = hashset! {old_commit.id().clone()};
+    insertion.rebase_children(ui, mut_repo, &moved, &new_head_ids, empty)?;
+    mut_repo.rebase_descendants(ui.settings())?;
+    // Some commits were rebased twice, so count the original commits that got
+    // rewritten instead.
+    let old_head_ids = tx.base_repo().view().heads().iter().cloned().collect_vec();
+    let new_head_ids = tx.repo().view().heads().iter().cloned().collect_vec();
+    let num_rebased = tx
+        .repo()
+        .index()
+        .walk_revs(&old_head_ids, &new_head_ids)
+        .count();
+    writeln!(ui, "Rebased {} commits", num_rebased)?;
     workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
@@ -4081,6 +6335,491 @@ fn check_rebase_destinations(
     Ok(())
 }
 
+fn cmd_parallelize(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ParallelizeArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut targets: Vec<Commit> = vec![];
+    for revset in &args.revisions {
+        let revisions = workspace_command.resolve_revset(revset)?;
+        workspace_command.check_non_empty(&revisions)?;
+        for commit in revisions {
+            workspace_command.check_rewriteable(&commit)?;
+            if !targets.contains(&commit) {
+                targets.push(commit);
+            }
+        }
+    }
+    let chain = linear_chain(&targets).ok_or_else(|| {
+        CommandError::UserError(
+            "Cannot parallelize revisions that don't form a linear chain".to_string(),
+        )
+    })?;
+    if chain.len() < 2 {
+        ui.write("Nothing changed.\n")?;
+        return Ok(());
+    }
+    let chain_ids: HashSet<CommitId> = chain.iter().map(|commit| commit.id().clone()).collect();
+    let children: Vec<Commit> = RevsetExpression::commits(chain_ids.iter().cloned().collect())
+        .children()
+        .evaluate(
+            workspace_command.repo().as_repo_ref(),
+            Some(&workspace_command.workspace_id()),
+        )?
+        .iter()
+        .commits(workspace_command.repo().store())
+        .filter_ok(|commit| !chain_ids.contains(commit.id()))
+        .try_collect()?;
+    for child in &children {
+        workspace_command.check_rewriteable(child)?;
+    }
+
+    let mut tx = workspace_command.start_transaction(&format!(
+        "parallelize {} commits starting at {}",
+        chain.len(),
+        chain[0].id().hex()
+    ));
+    let mut_repo = tx.mut_repo();
+    let new_parents = chain[0].parents();
+    let siblings = chain
+        .iter()
+        .map(|commit| rebase_commit(ui.settings(), mut_repo, commit, &new_parents))
+        .try_collect::<_, Vec<_>, _>()?;
+    // Replace the parents in the chain by all the siblings, keeping the other
+    // parents in place
+    for child in &children {
+        let mut new_child_parents = vec![];
+        for parent in child.parents() {
+            if !chain_ids.contains(parent.id()) {
+                new_child_parents.push(parent);
+            } else if !new_child_parents.contains(&siblings[0]) {
+                new_child_parents.extend(siblings.iter().cloned());
+            }
+        }
+        rebase_commit(ui.settings(), mut_repo, child, &new_child_parents)?;
+    }
+    let num_rebased = children.len() + mut_repo.rebase_descendants(ui.settings())?;
+    writeln!(ui, "Parallelized {} commits", chain.len())?;
+    if num_rebased > 0 {
+        writeln!(ui, "Rebased {} descendant commits", num_rebased)?;
+    }
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HisteditAction {
+    Pick,
+    Reorder,
+    EditMessage,
+    Squash,
+    Drop,
+}
+
+impl HisteditAction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pick" => Some(HisteditAction::Pick),
+            "reorder" => Some(HisteditAction::Reorder),
+            "edit-message" => Some(HisteditAction::EditMessage),
+            "squash" => Some(HisteditAction::Squash),
+            "drop" => Some(HisteditAction::Drop),
+            _ => None,
+        }
+    }
+}
+
+/// A kept revision with the revisions squashed into it
+struct HisteditGroup {
+    commit: Commit,
+    edit_message: bool,
+    squashed: Vec<Commit>,
+}
+
+/// The revisions to keep in their new order, and the revisions to drop
+struct HisteditPlan {
+    groups: Vec<HisteditGroup>,
+    dropped: Vec<Commit>,
+}
+
+const HISTEDIT_ERROR_PREFIX: &str = "# Error: ";
+
+fn histedit_plan_text(chain: &[Commit]) -> String {
+    let mut text = String::new();
+    for commit in chain {
+        let first_line = commit.description().lines().next().unwrap_or("");
+        text.push_str(&format!(
+            "pick {} {}\n",
+            short_commit_hash(commit.id()),
+            first_line
+        ));
+    }
+    text.push_str(
+        r##"
+# The revisions above are rewritten from top to bottom. Actions:
+#   pick = keep the revision
+#   reorder = keep the revision (same as pick, for lines that were moved)
+#   edit-message = keep the revision and edit its description
+#   squash = combine the revision into the revision above it
+#   drop = remove the revision and its changes
+# Move lines to reorder the revisions. All revisions must be listed.
+# Lines starting with "#" are ignored. Remove all lines to leave the
+# revisions unchanged.
+"##,
+    );
+    text
+}
+
+/// Parses an edited plan. Returns all errors found in the plan.
+fn parse_histedit_plan(text: &str, chain: &[Commit]) -> Result<HisteditPlan, Vec<String>> {
+    let mut errors = vec![];
+    let mut groups: Vec<HisteditGroup> = vec![];
+    let mut dropped = vec![];
+    let mut seen: HashSet<CommitId> = HashSet::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let action_name = words.next().unwrap();
+        let action = match HisteditAction::from_name(action_name) {
+            Some(action) => action,
+            None => {
+                errors.push(format!(
+                    r#"Unknown action "{action_name}" in line "{line}""#
+                ));
+                continue;
+            }
+        };
+        let prefix = match words.next() {
+            Some(prefix) => prefix,
+            None => {
+                errors.push(format!(r#"Missing revision in line "{line}""#));
+                continue;
+            }
+        };
+        let matches = chain
+            .iter()
+            .filter(|commit| commit.id().hex().starts_with(prefix))
+            .collect_vec();
+        let commit = match matches.as_slice() {
+            [commit] => (*commit).clone(),
+            [] => {
+                errors.push(format!(
+                    r#"Revision "{prefix}" is not one of the revisions being edited"#
+                ));
+                continue;
+            }
+            _ => {
+                errors.push(format!(r#"Revision "{prefix}" is ambiguous"#));
+                continue;
+            }
+        };
+        if !seen.insert(commit.id().clone()) {
+            errors.push(format!(
+                "Revision {} is listed more than once",
+                short_commit_hash(commit.id())
+            ));
+            continue;
+        }
+        match action {
+            HisteditAction::Pick | HisteditAction::Reorder | HisteditAction::EditMessage => {
+                groups.push(HisteditGroup {
+                    commit,
+                    edit_message: action == HisteditAction::EditMessage,
+                    squashed: vec![],
+                });
+            }
+            HisteditAction::Squash => match groups.last_mut() {
+                Some(group) => group.squashed.push(commit),
+                None => errors.push(format!(
+                    "Cannot squash revision {} because there is no revision above it to squash \
+                     into",
+                    short_commit_hash(commit.id())
+                )),
+            },
+            HisteditAction::Drop => dropped.push(commit),
+        }
+    }
+    for commit in chain {
+        if !seen.contains(commit.id()) {
+            errors.push(format!(
+                r#"Revision {} is missing from the plan (use "drop" to remove it)"#,
+                short_commit_hash(commit.id())
+            ));
+        }
+    }
+    if errors.is_empty() {
+        Ok(HisteditPlan { groups, dropped })
+    } else {
+        Err(errors)
+    }
+}
+
+/// Lets the user edit the plan until it has no errors. Returns `None` if they
+/// removed all lines.
+fn edit_histedit_plan(
+    ui: &Ui,
+    repo: &ReadonlyRepo,
+    chain: &[Commit],
+) -> Result<Option<HisteditPlan>, CommandError> {
+    let random: u32 = rand::random();
+    let plan_file_path = repo.repo_path().join(format!("histedit-{}.txt", random));
+    let mut plan_text = histedit_plan_text(chain);
+    let mut previous_plan: Option<String> = None;
+    loop {
+        std::fs::write(&plan_file_path, &plan_text)?;
+        run_editor(ui, &plan_file_path)?;
+        let edited = std::fs::read_to_string(&plan_file_path)?;
+        // Error messages from the previous attempt are not part of the plan
+        let plan = edited
+            .lines()
+            .filter(|line| !line.starts_with(HISTEDIT_ERROR_PREFIX))
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        if plan
+            .lines()
+            .all(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+        {
+            std::fs::remove_file(&plan_file_path).ok();
+            return Ok(None);
+        }
+        match parse_histedit_plan(&plan, chain) {
+            Ok(result) => {
+                std::fs::remove_file(&plan_file_path).ok();
+                return Ok(Some(result));
+            }
+            Err(errors) => {
+                // Give up if the user didn't change anything, so they can get
+                // out of the loop by just closing the editor
+                if previous_plan.as_ref() == Some(&plan) {
+                    std::fs::remove_file(&plan_file_path).ok();
+                    return Err(CommandError::UserError(format!(
+                        "The plan has errors:\n{}",
+                        errors.join("\n")
+                    )));
+                }
+                plan_text = errors
+                    .iter()
+                    .map(|error| format!("{HISTEDIT_ERROR_PREFIX}{error}\n"))
+                    .chain(std::iter::once(plan.clone()))
+                    .collect();
+                previous_plan = Some(plan);
+            }
+        }
+    }
+}
+
+fn cmd_histedit(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &HisteditArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let targets = workspace_command.resolve_revset(&args.revisions)?;
+    workspace_command.check_non_empty(&targets)?;
+    for commit in &targets {
+        workspace_command.check_rewriteable(commit)?;
+    }
+    let chain = linear_chain(&targets).ok_or_else(|| {
+        CommandError::UserError("Cannot edit revisions that don't form a linear chain".to_string())
+    })?;
+    let old_tip = chain.last().unwrap().clone();
+    let children: Vec<Commit> = RevsetExpression::commit(old_tip.id().clone())
+        .children()
+        .evaluate(
+            workspace_command.repo().as_repo_ref(),
+            Some(&workspace_command.workspace_id()),
+        )?
+        .iter()
+        .commits(workspace_command.repo().store())
+        .try_collect()?;
+    for child in &children {
+        workspace_command.check_rewriteable(child)?;
+    }
+
+    let HisteditPlan { groups, dropped } =
+        match edit_histedit_plan(ui, workspace_command.repo(), &chain)? {
+            Some(plan) => plan,
+            None => {
+                ui.write("Nothing changed.\n")?;
+                return Ok(());
+            }
+        };
+    let mut descriptions = vec![];
+    for group in &groups {
+        let mut description = group.commit.description().to_string();
+        for squashed in &group.squashed {
+            if description.is_empty() {
+                description = squashed.description().to_string();
+            } else if !squashed.description().is_empty() {
+                if !description.ends_with('\n') {
+                    description.push('\n');
+                }
+                description.push('\n');
+                description.push_str(squashed.description());
+            }
+        }
+        if group.edit_message {
+            description = edit_description(ui, workspace_command.repo(), &description)?;
+        }
+        descriptions.push(description);
+    }
+    let unchanged =
+        dropped.is_empty()
+            && groups.len() == chain.len()
+            && groups.iter().zip(&descriptions).zip(&chain).all(
+                |((group, description), commit)| {
+                    group.commit == *commit
+                        && group.squashed.is_empty()
+                        && description == commit.description()
+                },
+            );
+    if unchanged {
+        ui.write("Nothing changed.\n")?;
+        return Ok(());
+    }
+
+    let mut tx = workspace_command.start_transaction(&format!(
+        "histedit {} commits starting at {}",
+        chain.len(),
+        chain[0].id().hex()
+    ));
+    let mut_repo = tx.mut_repo();
+    let mut parents = chain[0].parents();
+    let mut num_rewritten = 0;
+    for (group, description) in groups.iter().zip(descriptions) {
+        let commit = &group.commit;
+        let parent_ids = parents
+            .iter()
+            .map(|parent| parent.id().clone())
+            .collect_vec();
+        let new_commit = if group.squashed.is_empty() && description == commit.description() {
+            if commit.parent_ids() == parent_ids {
+                commit.clone()
+            } else {
+                num_rewritten += 1;
+                rebase_commit(ui.settings(), mut_repo, commit, &parents)?
+            }
+        } else {
+            let mut tree_id = if commit.parent_ids() == parent_ids {
+                commit.tree_id().clone()
+            } else {
+                let (tree_id, reused_resolutions) = merge_trees_reporting_resolutions(
+                    &merge_commit_trees(mut_repo.as_repo_ref(), &parents),
+                    &merge_commit_trees(mut_repo.as_repo_ref(), &commit.parents()),
+                    &commit.tree(),
+                    &conflict_labels_for_rebase(commit, &parents),
+                )?;
+                mut_repo.record_reused_resolutions(reused_resolutions);
+                tree_id
+            };
+            for squashed in &group.squashed {
+                let labels = ConflictLabels {
+                    side1: Some(conflict_label_for_commit(commit)),
+                    base: conflict_label_for_commits(&squashed.parents()),
+                    side2: Some(format!(
+                        "changes squashed from {}",
+                        conflict_label_for_commit(squashed)
+                    )),
+                };
+                let tree = mut_repo.store().get_tree(&RepoPath::root(), &tree_id)?;
+                tree_id = merge_trees_with_labels(
+                    &tree,
+                    &merge_commit_trees(mut_repo.as_repo_ref(), &squashed.parents()),
+                    &squashed.tree(),
+                    &labels,
+                )?;
+            }
+            let predecessors = std::iter::once(commit)
+                .chain(&group.squashed)
+                .map(|commit| commit.id().clone())
+                .collect();
+            num_rewritten += 1;
+            let new_commit = CommitBuilder::for_rewrite_from(ui.settings(), commit)
+                .set_parents(parent_ids)
+                .set_tree(tree_id)
+                .set_description(description)
+                .set_predecessors(predecessors)
+                .write_to_repo(mut_repo)?;
+            for squashed in &group.squashed {
+                mut_repo.record_rewritten_commit(squashed.id().clone(), new_commit.id().clone());
+            }
+            new_commit
+        };
+        parents = vec![new_commit];
+    }
+    for commit in &dropped {
+        mut_repo.record_abandoned_commit(commit.id().clone());
+    }
+    // The children of the old last revision go on top of the new last revision
+    let mut num_rebased = 0;
+    if parents.iter().map(|parent| parent.id()).collect_vec() != vec![old_tip.id()] {
+        for child in &children {
+            let mut new_child_parents = vec![];
+            for parent in child.parents() {
+                if parent.id() != old_tip.id() {
+                    new_child_parents.push(parent);
+                } else {
+                    new_child_parents.extend(parents.iter().cloned());
+                }
+            }
+            rebase_commit(ui.settings(), mut_repo, child, &new_child_parents)?;
+            num_rebased += 1;
+        }
+    }
+    num_rebased += mut_repo.rebase_descendants(ui.settings())?;
+    if num_rewritten > 0 {
+        writeln!(ui, "Rewrote {} commits", num_rewritten)?;
+    }
+    if !dropped.is_empty() {
+        writeln!(ui, "Dropped {} commits", dropped.len())?;
+    }
+    if num_rebased > 0 {
+        writeln!(ui, "Rebased {} descendant commits", num_rebased)?;
+    }
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
+/// Returns the commits in order from the root of the chain if they form a
+/// linear chain, i.e. if each commit except the first has the previous one as
+/// its only parent.
+fn linear_chain(commits: &[Commit]) -> Option<Vec<Commit>> {
+    let ids: HashSet<&CommitId> = commits.iter().map(|commit| commit.id()).collect();
+    let mut roots = vec![];
+    let mut child_by_parent_id: HashMap<&CommitId, &Commit> = HashMap::new();
+    for commit in commits {
+        let parent_ids_in_chain = commit
+            .parent_ids()
+            .iter()
+            .filter(|id| ids.contains(id))
+            .count();
+        if parent_ids_in_chain == 0 {
+            roots.push(commit);
+            continue;
+        }
+        if commit.parent_ids().len() > 1 {
+            return None;
+        }
+        let parent_id = ids.get(&commit.parent_ids()[0]).unwrap();
+        if child_by_parent_id.insert(parent_id, commit).is_some() {
+            return None;
+        }
+    }
+    if roots.len() != 1 {
+        return None;
+    }
+    let mut chain = vec![roots[0].clone()];
+    while let Some(child) = child_by_parent_id.get(chain.last().unwrap().id()) {
+        chain.push((*child).clone());
+    }
+    Some(chain)
+}
+
 fn cmd_backout(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -4097,12 +6836,212 @@ fn cmd_backout(
         "back out commit {}",
         commit_to_back_out.id().hex()
     ));
-    back_out_commit(ui.settings(), tx.mut_repo(), &commit_to_back_out, &parents);
+    back_out_commit(ui.settings(), tx.mut_repo(), &commit_to_back_out, &parents)?;
     workspace_command.finish_transaction(ui, tx)?;
 
     Ok(())
 }
 
+/// Returns the file name `git format-patch` would use for a patch with the
+/// given subject.
+fn patch_file_name(number: usize, subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.chars().take(52).collect::<String>();
+    let slug = slug.trim_end_matches(['-', '.']);
+    if slug.is_empty() {
+        format!("{number:04}.patch")
+    } else {
+        format!("{number:04}-{slug}.patch")
+    }
+}
+
+fn write_patch(
+    formatter: &mut dyn Formatter,
+    workspace_command: &WorkspaceCommandHelper,
+    commit: &Commit,
+    number: usize,
+    total: usize,
+) -> Result<(), CommandError> {
+    let (subject, body) = commit
+        .description()
+        .split_once('\n')
+        .unwrap_or((commit.description(), ""));
+    let author = commit.author();
+    writeln!(
+        formatter,
+        "From {} Mon Sep 17 00:00:00 2001",
+        commit.id().hex()
+    )?;
+    writeln!(formatter, "From: {} <{}>", author.name, author.email)?;
+    writeln!(
+        formatter,
+        "Date: {}",
+        datetime_from_timestamp(&author.timestamp).to_rfc2822()
+    )?;
+    if total == 1 {
+        writeln!(formatter, "Subject: [PATCH] {subject}")?;
+    } else {
+        writeln!(formatter, "Subject: [PATCH {number}/{total}] {subject}")?;
+    }
+    writeln!(formatter)?;
+    let body = body.trim();
+    if !body.is_empty() {
+        writeln!(formatter, "{body}")?;
+    }
+    writeln!(formatter, "---")?;
+    let parents = commit.parents();
+    let from_tree = merge_commit_trees(workspace_command.repo().as_repo_ref(), &parents);
+    let to_tree = commit.tree();
+    let tree_diff = from_tree.diff(&to_tree, &EverythingMatcher);
+    show_git_diff(formatter, workspace_command, &to_tree, tree_diff)?;
+    writeln!(formatter)?;
+    Ok(())
+}
+
+fn cmd_export_patch(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ExportPatchArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    let mut commits = workspace_command.resolve_revset(&args.revisions)?;
+    workspace_command.check_non_empty(&commits)?;
+    // Write the patches in the order they should be applied
+    commits.reverse();
+    for (i, commit) in commits.iter().enumerate() {
+        let mut content = vec![];
+        {
+            let mut formatter = PlainTextFormatter::new(Box::new(&mut content));
+            write_patch(
+                &mut formatter,
+                &workspace_command,
+                commit,
+                i + 1,
+                commits.len(),
+            )?;
+        }
+        if args.stdout {
+            ui.stdout_formatter().write_all(&content)?;
+        } else {
+            let subject = commit.description().lines().next().unwrap_or("");
+            let file_name = patch_file_name(i + 1, subject);
+            let path = match &args.output_directory {
+                Some(dir) => Path::new(dir).join(&file_name),
+                None => PathBuf::from(&file_name),
+            };
+            let full_path = ui.cwd().join(&path);
+            if let Some(dir) = full_path.parent() {
+                fs::create_dir_all(dir).map_err(|err| {
+                    UserError(format!(
+                        "Failed to create directory {}: {err}",
+                        dir.display()
+                    ))
+                })?;
+            }
+            fs::write(&full_path, &content)
+                .map_err(|err| UserError(format!("Failed to write {}: {err}", path.display())))?;
+            writeln!(ui, "{}", path.display())?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies the patch to the tree. If that fails, tries to apply it to the
+/// commit it was created from and merges the result into the tree instead.
+fn apply_patch_to_tree(
+    ui: &mut Ui,
+    repo: &Arc<ReadonlyRepo>,
+    tree: &Tree,
+    patch: &Patch,
+    max_fuzz: usize,
+) -> Result<TreeId, CommandError> {
+    let subject = patch.description.lines().next().unwrap_or("");
+    let err = match jujutsu_lib::patch::apply_patch(tree, patch, max_fuzz) {
+        Ok(tree_id) => return Ok(tree_id),
+        Err(err) => err,
+    };
+    let failed = || UserError(format!("Failed to apply patch \"{subject}\": {err}"));
+    let source_commit = patch
+        .source_commit_hex
+        .as_ref()
+        .and_then(|hex| hex::decode(hex).ok())
+        .and_then(|id| repo.store().get_commit(&CommitId::new(id)).ok());
+    let source_commit = match source_commit {
+        Some(commit) => commit,
+        None => return Err(failed()),
+    };
+    let base_tree = merge_commit_trees(repo.as_repo_ref(), &source_commit.parents());
+    let patched_tree_id = match jujutsu_lib::patch::apply_patch(&base_tree, patch, 0) {
+        Ok(tree_id) => tree_id,
+        Err(_) => return Err(failed()),
+    };
+    let patched_tree = repo.store().get_tree(&RepoPath::root(), &patched_tree_id)?;
+    ui.write_warn(format!(
+        "Patch \"{subject}\" did not apply cleanly, falling back to 3-way merge\n"
+    ))?;
+    let labels = ConflictLabels {
+        side1: None,
+        base: conflict_label_for_commits(&source_commit.parents()),
+        side2: Some(format!("patch \"{subject}\"")),
+    };
+    Ok(merge_trees_with_labels(
+        tree,
+        &base_tree,
+        &patched_tree,
+        &labels,
+    )?)
+}
+
+fn cmd_apply(ui: &mut Ui, command: &CommandHelper, args: &ApplyArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let destination = workspace_command.resolve_single_rev(&args.destination)?;
+    let mut patches = vec![];
+    for path in &args.patches {
+        let content = fs::read(ui.cwd().join(path))
+            .map_err(|err| UserError(format!("Failed to read {path}: {err}")))?;
+        let file_patches = jujutsu_lib::patch::parse_mbox(&content)
+            .map_err(|err| UserError(format!("Failed to parse {path}: {err}")))?;
+        patches.extend(file_patches);
+    }
+    if patches.is_empty() {
+        return Err(UserError("No patches found".to_string()));
+    }
+    let repo = workspace_command.repo().clone();
+    let mut tx = workspace_command.start_transaction(&format!(
+        "apply {} patches on top of commit {}",
+        patches.len(),
+        destination.id().hex()
+    ));
+    let mut parent = destination;
+    for patch in &patches {
+        let tree_id = apply_patch_to_tree(ui, &repo, &parent.tree(), patch, args.fuzz)?;
+        let mut commit_builder = CommitBuilder::for_new_commit(ui.settings(), tree_id)
+            .set_parents(vec![parent.id().clone()])
+            .set_description(patch.description.clone());
+        if let Some(author) = &patch.author {
+            commit_builder = commit_builder.set_author(author.clone());
+        }
+        let commit = commit_builder.write_to_repo(tx.mut_repo())?;
+        ui.write("Created ")?;
+        ui.write_commit_summary(
+            tx.repo().as_repo_ref(),
+            &workspace_command.workspace_id(),
+            &commit,
+        )?;
+        ui.write("\n")?;
+        parent = commit;
+    }
+    workspace_command.finish_transaction(ui, tx)?;
+    Ok(())
+}
+
 fn is_fast_forward(repo: RepoRef, branch_name: &str, new_target_id: &CommitId) -> bool {
     if let Some(current_target) = repo.view().get_local_branch(branch_name) {
         current_target
@@ -4516,14 +7455,18 @@ fn cmd_bench(
     Ok(())
 }
 
+fn datetime_from_timestamp(timestamp: &Timestamp) -> DateTime<FixedOffset> {
+    Utc.timestamp(
+        timestamp.timestamp.0 as i64 / 1000,
+        (timestamp.timestamp.0 % 1000) as u32 * 1000000,
+    )
+    .with_timezone(&FixedOffset::east(timestamp.tz_offset * 60))
+}
+
 fn format_timestamp(timestamp: &Timestamp) -> String {
-    let utc = Utc
-        .timestamp(
-            timestamp.timestamp.0 as i64 / 1000,
-            (timestamp.timestamp.0 % 1000) as u32 * 1000000,
-        )
-        .with_timezone(&FixedOffset::east(timestamp.tz_offset * 60));
-    utc.format("%Y-%m-%d %H:%M:%S.%3f %:z").to_string()
+    datetime_from_timestamp(timestamp)
+        .format("%Y-%m-%d %H:%M:%S.%3f %:z")
+        .to_string()
 }
 
 fn cmd_op_log(
@@ -4752,7 +7695,7 @@ fn cmd_workspace_add(
         new_workspace_command.workspace_id(),
         ui.settings(),
         &new_checkout_commit,
-    );
+    )?;
     new_workspace_command.finish_transaction(ui, tx)?;
     Ok(())
 }
@@ -5010,7 +7953,7 @@ fn cmd_git_clone(
                     workspace_command.workspace_id(),
                     ui.settings(),
                     &commit,
-                );
+                )?;
             }
             workspace_command.finish_transaction(ui, checkout_tx)?;
         }
@@ -5443,7 +8386,9 @@ where
         Commands::Untrack(sub_args) => cmd_untrack(ui, &command_helper, sub_args),
         Commands::Files(sub_args) => cmd_files(ui, &command_helper, sub_args),
         Commands::Print(sub_args) => cmd_print(ui, &command_helper, sub_args),
+        Commands::Annotate(sub_args) => cmd_annotate(ui, &command_helper, sub_args),
         Commands::Diff(sub_args) => cmd_diff(ui, &command_helper, sub_args),
+        Commands::Interdiff(sub_args) => cmd_interdiff(ui, &command_helper, sub_args),
         Commands::Show(sub_args) => cmd_show(ui, &command_helper, sub_args),
         Commands::Status(sub_args) => cmd_status(ui, &command_helper, sub_args),
         Commands::Log(sub_args) => cmd_log(ui, &command_helper, sub_args),
@@ -5455,15 +8400,26 @@ where
         Commands::Abandon(sub_args) => cmd_abandon(ui, &command_helper, sub_args),
         Commands::Edit(sub_args) => cmd_edit(ui, &command_helper, sub_args),
         Commands::New(sub_args) => cmd_new(ui, &command_helper, sub_args),
+        Commands::Next(sub_args) => cmd_next(ui, &command_helper, sub_args),
+        Commands::Prev(sub_args) => cmd_prev(ui, &command_helper, sub_args),
         Commands::Move(sub_args) => cmd_move(ui, &command_helper, sub_args),
         Commands::Squash(sub_args) => cmd_squash(ui, &command_helper, sub_args),
         Commands::Unsquash(sub_args) => cmd_unsquash(ui, &command_helper, sub_args),
+        Commands::Absorb(sub_args) => cmd_absorb(ui, &command_helper, sub_args),
         Commands::Restore(sub_args) => cmd_restore(ui, &command_helper, sub_args),
+        Commands::Resolve(sub_args) => cmd_resolve(ui, &command_helper, sub_args),
+        Commands::Fix(sub_args) => cmd_fix(ui, &command_helper, sub_args),
+        Commands::Run(sub_args) => cmd_run(ui, &command_helper, sub_args),
+        Commands::Bisect(sub_args) => cmd_bisect(ui, &command_helper, sub_args),
         Commands::Touchup(sub_args) => cmd_touchup(ui, &command_helper, sub_args),
         Commands::Split(sub_args) => cmd_split(ui, &command_helper, sub_args),
         Commands::Merge(sub_args) => cmd_merge(ui, &command_helper, sub_args),
         Commands::Rebase(sub_args) => cmd_rebase(ui, &command_helper, sub_args),
+        Commands::Parallelize(sub_args) => cmd_parallelize(ui, &command_helper, sub_args),
+        Commands::Histedit(sub_args) => cmd_histedit(ui, &command_helper, sub_args),
         Commands::Backout(sub_args) => cmd_backout(ui, &command_helper, sub_args),
+        Commands::ExportPatch(sub_args) => cmd_export_patch(ui, &command_helper, sub_args),
+        Commands::Apply(sub_args) => cmd_apply(ui, &command_helper, sub_args),
         Commands::Branch(sub_args) => cmd_branch(ui, &command_helper, sub_args),
         Commands::Undo(sub_args) => cmd_op_undo(ui, &command_helper, sub_args),
         Commands::Operation(sub_args) => cmd_operation(ui, &command_helper, sub_args),
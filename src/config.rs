@@ -78,6 +78,11 @@ fn env_overrides() -> config::Config {
     if let Ok(value) = env::var("JJ_TIMESTAMP") {
         builder = builder.set_override("user.timestamp", value).unwrap();
     }
+    if let Ok(value) = env::var("JJ_RANDOMNESS_SEED") {
+        builder = builder
+            .set_override("debug.randomness-seed", value)
+            .unwrap();
+    }
     if let Ok(value) = env::var("JJ_EDITOR") {
         builder = builder.set_override("ui.editor", value).unwrap();
     }
@@ -13,16 +13,19 @@
 // limitations under the License.
 
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::Arc;
 
 use config::ConfigError;
 use itertools::Itertools;
-use jujutsu_lib::backend::TreeId;
+use jujutsu_lib::backend::{BackendError, TreeId, TreeValue};
+use jujutsu_lib::conflicts::{
+    describe_conflict, materialize_conflict, update_conflict_from_content,
+};
 use jujutsu_lib::gitignore::GitIgnoreFile;
-use jujutsu_lib::matchers::EverythingMatcher;
+use jujutsu_lib::matchers::{EverythingMatcher, Matcher};
 use jujutsu_lib::repo_path::RepoPath;
 use jujutsu_lib::settings::UserSettings;
 use jujutsu_lib::store::Store;
@@ -31,6 +34,7 @@ use jujutsu_lib::working_copy::{CheckoutError, SnapshotError, TreeState};
 use tempfile::tempdir;
 use thiserror::Error;
 
+use crate::diff_select::{select_changes, BUILTIN_EDITOR_NAME};
 use crate::ui::Ui;
 
 #[derive(Debug, Error)]
@@ -53,6 +57,8 @@ pub enum DiffEditError {
     IoError(#[source] std::io::Error),
     #[error("Failed to snapshot changes: {0:?}")]
     SnapshotError(SnapshotError),
+    #[error(transparent)]
+    BackendError(#[from] BackendError),
 }
 
 impl From<CheckoutError> for DiffEditError {
@@ -67,15 +73,84 @@ impl From<SnapshotError> for DiffEditError {
     }
 }
 
+#[derive(Debug, Error)]
+pub enum DiffGenerateError {
+    #[error("Invalid config: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Failed to write directories to diff: {0:?}")]
+    CheckoutError(CheckoutError),
+    #[error("Error setting up temporary directory: {0:?}")]
+    SetUpDirError(#[source] std::io::Error),
+    #[error("Error executing diff tool '{tool_binary}': {source}")]
+    ExecuteToolError {
+        tool_binary: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum ConflictResolveError {
+    #[error("Invalid config: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("Couldn't find the path {} in this revision", .0.to_internal_file_string())]
+    PathNotFound(RepoPath),
+    #[error("Couldn't find any conflicts at {} in this revision", .0.to_internal_file_string())]
+    NotAConflict(RepoPath),
+    #[error(
+        "Only conflicts between two regular files are supported (not deletions, symlinks, \
+         executable files, etc.). Conflict summary for {}:\n{}",
+        .0.to_internal_file_string(),
+        .1
+    )]
+    NotNormalFiles(RepoPath, String),
+    #[error(
+        "The conflict at {} has {sides} sides. At most 2 sides are supported.",
+        path.to_internal_file_string()
+    )]
+    ConflictTooComplicated { path: RepoPath, sides: usize },
+    #[error(
+        "The merge tool '{tool_name}' is not configured for merging; set \
+         merge-tools.{tool_name}.merge-args"
+    )]
+    NotAMergeTool { tool_name: String },
+    #[error("Error setting up temporary directory: {0:?}")]
+    SetUpDirError(#[source] std::io::Error),
+    #[error("Error executing merge tool '{tool_binary}': {source}")]
+    ExecuteToolError {
+        tool_binary: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("The merge tool exited with a non-zero code")]
+    ToolAborted,
+    #[error("The merge tool didn't change the output file; the conflict was not resolved")]
+    OutputUnchanged,
+    #[error("I/O error: {0:?}")]
+    IoError(#[source] std::io::Error),
+    #[error("{0}")]
+    BackendError(#[from] BackendError),
+}
+
+impl From<CheckoutError> for DiffGenerateError {
+    fn from(err: CheckoutError) -> Self {
+        DiffGenerateError::CheckoutError(err)
+    }
+}
+
 fn check_out(
     store: Arc<Store>,
     wc_dir: PathBuf,
     state_dir: PathBuf,
     tree: &Tree,
     sparse_patterns: Vec<RepoPath>,
-) -> Result<TreeState, DiffEditError> {
-    std::fs::create_dir(&wc_dir).map_err(DiffEditError::SetUpDirError)?;
-    std::fs::create_dir(&state_dir).map_err(DiffEditError::SetUpDirError)?;
+) -> Result<TreeState, CheckoutError> {
+    for dir in [&wc_dir, &state_dir] {
+        std::fs::create_dir(dir).map_err(|err| CheckoutError::IoError {
+            message: format!("Failed to create directory {}", dir.display()),
+            err,
+        })?;
+    }
     let mut tree_state = TreeState::init(store, wc_dir, state_dir);
     tree_state.set_sparse_patterns(sparse_patterns)?;
     tree_state.check_out(tree)?;
@@ -101,6 +176,19 @@ pub fn edit_diff(
     instructions: &str,
     base_ignores: Arc<GitIgnoreFile>,
 ) -> Result<TreeId, DiffEditError> {
+    // Without a configured editor, use the built-in one since it works everywhere
+    let editor_name = settings
+        .config()
+        .get_string("ui.diff-editor")
+        .unwrap_or_else(|_| BUILTIN_EDITOR_NAME.to_string());
+    if editor_name == BUILTIN_EDITOR_NAME {
+        // Tell the user what they're selecting the changes for
+        if !instructions.is_empty() {
+            writeln!(ui, "{}\n", instructions.trim_end()).map_err(DiffEditError::IoError)?;
+        }
+        return select_changes(ui, left_tree, right_tree, &mut std::io::stdin().lock());
+    }
+
     let store = left_tree.store();
     let changed_files = left_tree
         .diff(right_tree, &EverythingMatcher)
@@ -139,20 +227,6 @@ pub fn edit_diff(
             .map_err(DiffEditError::SetUpDirError)?;
     }
 
-    // TODO: Make this configuration have a table of possible editors and detect the
-    // best one here.
-    let editor_name = match settings.config().get_string("ui.diff-editor") {
-        Ok(editor_binary) => editor_binary,
-        Err(_) => {
-            let default_editor = "meld".to_string();
-            ui.write_hint(format!(
-                "Using default editor '{}'; you can change this by setting ui.diff-editor\n",
-                default_editor
-            ))
-            .map_err(DiffEditError::IoError)?;
-            default_editor
-        }
-    };
     let editor = get_tool(settings, &editor_name)?;
     // Start a diff editor on the two directories.
     let exit_status = Command::new(&editor.program)
@@ -174,15 +248,290 @@ pub fn edit_diff(
     Ok(right_tree_state.snapshot(base_ignores)?)
 }
 
+/// Runs the configured merge tool (`ui.merge-editor`) on the 2-sided file
+/// conflict at `repo_path` in `tree`. Returns the id of a new tree where the
+/// path has the merge tool's output. If the output still has conflict markers,
+/// the path stays conflicted with the remaining conflicts.
+pub fn run_mergetool(
+    ui: &mut Ui,
+    settings: &UserSettings,
+    tree: &Tree,
+    repo_path: &RepoPath,
+) -> Result<TreeId, ConflictResolveError> {
+    let conflict_id = match tree.path_value(repo_path) {
+        Some(TreeValue::Conflict(id)) => id,
+        Some(_) => return Err(ConflictResolveError::NotAConflict(repo_path.clone())),
+        None => return Err(ConflictResolveError::PathNotFound(repo_path.clone())),
+    };
+    let store = tree.store();
+    let conflict = store.read_conflict(repo_path, &conflict_id)?;
+    let sides = conflict.removes.len() + 1;
+    if sides > 2 {
+        return Err(ConflictResolveError::ConflictTooComplicated {
+            path: repo_path.clone(),
+            sides,
+        });
+    }
+    let file_ids = conflict
+        .removes
+        .iter()
+        .chain(conflict.adds.iter())
+        .map(|part| match &part.value {
+            TreeValue::Normal {
+                id,
+                executable: false,
+            } => Some(id.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    let file_ids = match file_ids {
+        // If a side is missing, that side deleted the file
+        Some(file_ids) if file_ids.len() == 3 => file_ids,
+        _ => {
+            let mut summary = vec![];
+            describe_conflict(&conflict, &mut summary).map_err(ConflictResolveError::IoError)?;
+            return Err(ConflictResolveError::NotNormalFiles(
+                repo_path.clone(),
+                String::from_utf8_lossy(&summary).trim_end().to_string(),
+            ));
+        }
+    };
+
+    let editor_name = match settings.config().get_string("ui.merge-editor") {
+        Ok(editor_binary) => editor_binary,
+        Err(_) => {
+            let default_editor = "meld".to_string();
+            ui.write_hint(format!(
+                "Using default editor '{}'; you can change this by setting ui.merge-editor\n",
+                default_editor
+            ))
+            .map_err(ConflictResolveError::IoError)?;
+            default_editor
+        }
+    };
+    let editor = get_tool(settings, &editor_name)?;
+    if editor.merge_args.is_empty() {
+        return Err(ConflictResolveError::NotAMergeTool {
+            tool_name: editor_name,
+        });
+    }
+
+    // Write the base and the two sides to temporary files. The output file
+    // initially contains the conflict with markers, which some tools let the
+    // user edit directly.
+    // The files are named after the conflicted file so the tool can show which
+    // file is being resolved.
+    let temp_dir = tempdir().map_err(ConflictResolveError::SetUpDirError)?;
+    let file_name = repo_path
+        .components()
+        .last()
+        .map_or("file", |component| component.as_str());
+    let mut paths = vec![];
+    for (suffix, file_id) in ["base", "left", "right"].iter().zip(&file_ids) {
+        let path = temp_dir.path().join(format!("{file_name}.{suffix}"));
+        let mut content = vec![];
+        store
+            .read_file(repo_path, file_id)?
+            .read_to_end(&mut content)
+            .map_err(ConflictResolveError::IoError)?;
+        std::fs::write(&path, content).map_err(ConflictResolveError::SetUpDirError)?;
+        paths.push(path);
+    }
+    let output_path = temp_dir.path().join(file_name);
+    let mut initial_output = vec![];
+    materialize_conflict(
+        store,
+        repo_path,
+        &conflict,
+        Some(tree),
+        settings.conflict_marker_style(),
+        &mut initial_output,
+    )
+    .map_err(ConflictResolveError::IoError)?;
+    std::fs::write(&output_path, &initial_output).map_err(ConflictResolveError::SetUpDirError)?;
+
+    let args = editor
+        .merge_args
+        .iter()
+        .map(|arg| {
+            arg.replace("$base", &paths[0].to_string_lossy())
+                .replace("$left", &paths[1].to_string_lossy())
+                .replace("$right", &paths[2].to_string_lossy())
+                .replace("$output", &output_path.to_string_lossy())
+        })
+        .collect_vec();
+    let exit_status = Command::new(&editor.program)
+        .args(args)
+        .status()
+        .map_err(|e| ConflictResolveError::ExecuteToolError {
+            tool_binary: editor.program.clone(),
+            source: e,
+        })?;
+    if !exit_status.success() {
+        return Err(ConflictResolveError::ToolAborted);
+    }
+    let output = std::fs::read(&output_path).map_err(ConflictResolveError::IoError)?;
+    if output == initial_output {
+        return Err(ConflictResolveError::OutputUnchanged);
+    }
+
+    let new_value = match update_conflict_from_content(
+        store,
+        repo_path,
+        &conflict_id,
+        Some(tree),
+        &output,
+        settings.conflict_marker_style(),
+    )? {
+        Some(new_conflict_id) => TreeValue::Conflict(new_conflict_id),
+        None => {
+            let id = store.write_file(repo_path, &mut output.as_slice())?;
+            TreeValue::Normal {
+                id,
+                executable: false,
+            }
+        }
+    };
+    let mut tree_builder = store.tree_builder(tree.id().clone());
+    tree_builder.set(repo_path.clone(), new_value);
+    Ok(tree_builder.write_tree())
+}
+
+/// Runs an external diff tool on the changes between `left_tree` and
+/// `right_tree` and returns what it printed to stdout.
+pub fn generate_diff(
+    left_tree: &Tree,
+    right_tree: &Tree,
+    matcher: &dyn Matcher,
+    tool: &MergeTool,
+) -> Result<Vec<u8>, DiffGenerateError> {
+    let store = left_tree.store();
+    let changed_files = left_tree
+        .diff(right_tree, matcher)
+        .map(|(path, _value)| path)
+        .collect_vec();
+    if changed_files.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Check out the two trees in temporary directories. Only include changed files
+    // in the sparse checkout patterns.
+    let temp_dir = tempdir().map_err(DiffGenerateError::SetUpDirError)?;
+    let left_wc_dir = temp_dir.path().join("left");
+    let left_state_dir = temp_dir.path().join("left_state");
+    let right_wc_dir = temp_dir.path().join("right");
+    let right_state_dir = temp_dir.path().join("right_state");
+    check_out(
+        store.clone(),
+        left_wc_dir.clone(),
+        left_state_dir,
+        left_tree,
+        changed_files.clone(),
+    )?;
+    check_out(
+        store.clone(),
+        right_wc_dir.clone(),
+        right_state_dir,
+        right_tree,
+        changed_files.clone(),
+    )?;
+
+    let mut output = vec![];
+    match tool.diff_invocation_mode {
+        DiffToolMode::Dir => {
+            output.extend(run_diff_tool(tool, &left_wc_dir, &right_wc_dir)?);
+        }
+        DiffToolMode::FileByFile => {
+            // Files that are missing on one side are compared to an empty file.
+            let empty_file = temp_dir.path().join("empty");
+            File::create(&empty_file).map_err(DiffGenerateError::SetUpDirError)?;
+            let existing_or_empty = |path: PathBuf| {
+                if path.is_file() {
+                    path
+                } else {
+                    empty_file.clone()
+                }
+            };
+            for path in &changed_files {
+                let left_path = existing_or_empty(path.to_fs_path(&left_wc_dir));
+                let right_path = existing_or_empty(path.to_fs_path(&right_wc_dir));
+                output.extend(run_diff_tool(tool, &left_path, &right_path)?);
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn run_diff_tool(
+    tool: &MergeTool,
+    left: &Path,
+    right: &Path,
+) -> Result<Vec<u8>, DiffGenerateError> {
+    let args = tool
+        .diff_args
+        .iter()
+        .map(|arg| {
+            arg.replace("$left", &left.to_string_lossy())
+                .replace("$right", &right.to_string_lossy())
+        })
+        .collect_vec();
+    // Diff tools commonly exit with a non-zero code when the inputs differ, so we
+    // don't treat that as an error.
+    let output = Command::new(&tool.program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| DiffGenerateError::ExecuteToolError {
+            tool_binary: tool.program.clone(),
+            source: e,
+        })?;
+    Ok(output.stdout)
+}
+
+/// How a diff tool is invoked.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffToolMode {
+    /// Invoke the tool once, with the left and right directories.
+    Dir,
+    /// Invoke the tool once for each changed file, with the left and right
+    /// files.
+    FileByFile,
+}
+
+impl Default for DiffToolMode {
+    fn default() -> Self {
+        DiffToolMode::Dir
+    }
+}
+
 /// Merge/diff tool loaded from the settings.
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
-struct MergeTool {
+pub struct MergeTool {
     /// Program to execute.
     pub program: String,
     /// Arguments to pass to the program when editing diffs.
     #[serde(default)]
     pub edit_args: Vec<String>,
+    /// Arguments to pass to the program when generating diffs. `$left` and
+    /// `$right` are replaced by the paths to the left and right sides.
+    #[serde(default = "default_diff_args")]
+    pub diff_args: Vec<String>,
+    /// Whether to pass directories or individual files to the program when
+    /// generating diffs.
+    #[serde(default)]
+    pub diff_invocation_mode: DiffToolMode,
+    /// Arguments to pass to the program when resolving 3-way conflicts.
+    /// `$base`, `$left`, `$right`, and `$output` are replaced by the paths to
+    /// the corresponding files. Empty if the program can't be used for merging.
+    #[serde(default)]
+    pub merge_args: Vec<String>,
+}
+
+fn default_diff_args() -> Vec<String> {
+    vec!["$left".to_string(), "$right".to_string()]
 }
 
 impl MergeTool {
@@ -190,17 +539,60 @@ impl MergeTool {
         MergeTool {
             program: program.to_owned(),
             edit_args: vec![],
+            diff_args: default_diff_args(),
+            diff_invocation_mode: DiffToolMode::default(),
+            merge_args: vec![],
         }
     }
+
+    pub fn with_merge_args(mut self, args: &[&str]) -> Self {
+        self.merge_args = args.iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+}
+
+/// Returns the built-in configuration for well-known tools.
+fn builtin_tool(name: &str) -> Option<MergeTool> {
+    let tool = match name {
+        "kdiff3" => MergeTool::with_program(name)
+            .with_merge_args(&["$base", "$left", "$right", "-o", "$output", "--auto"]),
+        "meld" => MergeTool::with_program(name).with_merge_args(&[
+            "$left",
+            "$base",
+            "$right",
+            "-o",
+            "$output",
+            "--auto-merge",
+        ]),
+        "vimdiff" => MergeTool::with_program(name).with_merge_args(&[
+            "-f",
+            "-d",
+            "$output",
+            "-M",
+            "$left",
+            "$base",
+            "$right",
+            "-c",
+            "wincmd J",
+            "-c",
+            "set modifiable",
+            "-c",
+            "set write",
+        ]),
+        _ => return None,
+    };
+    Some(tool)
 }
 
-/// Loads merge tool options from `[merge-tools.<name>]`. The given name is used
-/// as an executable name if no configuration found for that name.
-fn get_tool(settings: &UserSettings, name: &str) -> Result<MergeTool, ConfigError> {
+/// Loads merge tool options from `[merge-tools.<name>]`. If there's no
+/// configuration for that name, the built-in configuration for well-known
+/// tools is used, or else the name is used as an executable name.
+pub fn get_tool(settings: &UserSettings, name: &str) -> Result<MergeTool, ConfigError> {
     const TABLE_KEY: &str = "merge-tools";
+    let default_tool = || builtin_tool(name).unwrap_or_else(|| MergeTool::with_program(name));
     let tools_table = match settings.config().get_table(TABLE_KEY) {
         Ok(table) => table,
-        Err(ConfigError::NotFound(_)) => return Ok(MergeTool::with_program(name)),
+        Err(ConfigError::NotFound(_)) => return Ok(default_tool()),
         Err(err) => return Err(err),
     };
     if let Some(v) = tools_table.get(name) {
@@ -209,6 +601,6 @@ fn get_tool(settings: &UserSettings, name: &str) -> Result<MergeTool, ConfigErro
             // add config key, deserialize error is otherwise unclear
             .map_err(|e| ConfigError::Message(format!("{TABLE_KEY}.{name}: {e}")))
     } else {
-        Ok(MergeTool::with_program(name))
+        Ok(default_tool())
     }
 }
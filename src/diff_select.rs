@@ -0,0 +1,430 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The built-in diff editor, which runs in the terminal. It goes through the
+//! changes between two trees and asks which files, hunks, and lines to select,
+//! and then builds the tree with only the selected changes.
+
+use std::io::{BufRead, Read, Write};
+
+use itertools::Itertools;
+use jujutsu_lib::backend::{TreeId, TreeValue};
+use jujutsu_lib::diff::{find_line_ranges, Diff, DiffHunk};
+use jujutsu_lib::files;
+use jujutsu_lib::gitattributes::GitAttributesFile;
+use jujutsu_lib::matchers::EverythingMatcher;
+use jujutsu_lib::repo_path::RepoPath;
+use jujutsu_lib::tree::{self, Tree};
+
+use crate::diff_edit::DiffEditError;
+use crate::ui::Ui;
+
+/// The name to set `ui.diff-editor` to for using the built-in diff editor.
+pub const BUILTIN_EDITOR_NAME: &str = ":builtin";
+
+/// A part of the difference between two versions of a text file.
+enum Section {
+    Unchanged(Vec<u8>),
+    Changed {
+        removed: Vec<Vec<u8>>,
+        added: Vec<Vec<u8>>,
+        /// Which of the removed lines to remove
+        selected_removed: Vec<bool>,
+        /// Which of the added lines to add
+        selected_added: Vec<bool>,
+    },
+}
+
+impl Section {
+    fn select_all(&mut self, selected: bool) {
+        if let Section::Changed {
+            selected_removed,
+            selected_added,
+            ..
+        } = self
+        {
+            selected_removed.fill(selected);
+            selected_added.fill(selected);
+        }
+    }
+}
+
+fn split_lines(content: &[u8]) -> Vec<Vec<u8>> {
+    content
+        .split_inclusive(|b| *b == b'\n')
+        .map(|line| line.to_vec())
+        .collect()
+}
+
+fn diff_sections(left: &[u8], right: &[u8]) -> Vec<Section> {
+    let diff = Diff::for_tokenizer(&[left, right], &find_line_ranges);
+    diff.hunks()
+        .map(|hunk| match hunk {
+            DiffHunk::Matching(content) => Section::Unchanged(content.to_vec()),
+            DiffHunk::Different(contents) => {
+                let removed = split_lines(contents[0]);
+                let added = split_lines(contents[1]);
+                Section::Changed {
+                    selected_removed: vec![false; removed.len()],
+                    selected_added: vec![false; added.len()],
+                    removed,
+                    added,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Builds the file content with only the selected changes. Removed lines that
+/// aren't selected are kept in place, and the selected added lines come after
+/// them.
+fn selected_content(sections: &[Section]) -> Vec<u8> {
+    let mut content = vec![];
+    for section in sections {
+        match section {
+            Section::Unchanged(text) => content.extend_from_slice(text),
+            Section::Changed {
+                removed,
+                added,
+                selected_removed,
+                selected_added,
+            } => {
+                for (line, selected) in removed.iter().zip(selected_removed) {
+                    if !selected {
+                        content.extend_from_slice(line);
+                    }
+                }
+                for (line, selected) in added.iter().zip(selected_added) {
+                    if *selected {
+                        content.extend_from_slice(line);
+                    }
+                }
+            }
+        }
+    }
+    content
+}
+
+fn read_file(tree: &Tree, path: &RepoPath, value: &TreeValue) -> Result<Vec<u8>, DiffEditError> {
+    let mut content = vec![];
+    if let TreeValue::Normal { id, .. } = value {
+        tree.store()
+            .read_file(path, id)?
+            .read_to_end(&mut content)
+            .map_err(DiffEditError::IoError)?;
+    }
+    Ok(content)
+}
+
+fn describe_value(value: &TreeValue) -> &'static str {
+    match value {
+        TreeValue::Normal {
+            executable: false, ..
+        } => "regular file",
+        TreeValue::Normal {
+            executable: true, ..
+        } => "executable file",
+        TreeValue::Symlink(_) => "symlink",
+        TreeValue::Tree(_) => "tree",
+        TreeValue::GitSubmodule(_) => "Git submodule",
+        TreeValue::Conflict(_) => "conflict",
+    }
+}
+
+/// What the user answered when asked about a change.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    Yes,
+    No,
+    Lines,
+    All,
+    Done,
+    Quit,
+}
+
+const ANSWERS: [(char, Answer, &str); 6] = [
+    ('y', Answer::Yes, "select this change"),
+    ('n', Answer::No, "don't select this change"),
+    ('l', Answer::Lines, "select individual lines of this change"),
+    (
+        'a',
+        Answer::All,
+        "select this change and the rest of the changes in the file",
+    ),
+    (
+        'd',
+        Answer::Done,
+        "don't select this change or the rest of the changes in the file",
+    ),
+    (
+        'q',
+        Answer::Quit,
+        "quit; don't select this change or any of the remaining ones",
+    ),
+];
+
+struct Selector<'a, 'ui> {
+    ui: &'a mut Ui<'ui>,
+    input: &'a mut dyn BufRead,
+}
+
+impl Selector<'_, '_> {
+    fn write_labeled(&mut self, label: &str, text: &str) -> Result<(), DiffEditError> {
+        let mut formatter = self.ui.stdout_formatter();
+        let mut write = || -> std::io::Result<()> {
+            formatter.add_label("diff".to_string())?;
+            formatter.add_label(label.to_string())?;
+            formatter.write_str(text)?;
+            formatter.remove_label()?;
+            formatter.remove_label()
+        };
+        write().map_err(DiffEditError::IoError)
+    }
+
+    fn write_line(&mut self, label: &str, prefix: &str, line: &[u8]) -> Result<(), DiffEditError> {
+        let mut text = format!("{prefix}{}", String::from_utf8_lossy(line));
+        if !text.ends_with('\n') {
+            text.push_str("\n\\ No newline at end of file\n");
+        }
+        self.write_labeled(label, &text)
+    }
+
+    /// Asks `question` until one of `choices` is answered. The end of the input
+    /// counts as quitting.
+    fn ask(&mut self, question: &str, choices: &[Answer]) -> Result<Answer, DiffEditError> {
+        let answers = ANSWERS
+            .iter()
+            .filter(|(_, answer, _)| choices.contains(answer))
+            .collect::<Vec<_>>();
+        let keys = answers.iter().map(|(key, _, _)| format!("{key},")).join("");
+        loop {
+            self.ui
+                .write(&format!("{question} [{keys}?] "))
+                .map_err(DiffEditError::IoError)?;
+            self.ui
+                .stdout_formatter()
+                .flush()
+                .map_err(DiffEditError::IoError)?;
+            let mut response = String::new();
+            let num_read = self
+                .input
+                .read_line(&mut response)
+                .map_err(DiffEditError::IoError)?;
+            if num_read == 0 {
+                self.ui.write("\n").map_err(DiffEditError::IoError)?;
+                return Ok(Answer::Quit);
+            }
+            let key = response.trim().chars().next();
+            if let Some((_, answer, _)) = answers.iter().find(|(k, _, _)| Some(*k) == key) {
+                return Ok(*answer);
+            }
+            for (key, _, help) in &answers {
+                self.ui
+                    .write(&format!("{key} - {help}\n"))
+                    .map_err(DiffEditError::IoError)?;
+            }
+            self.ui
+                .write("? - print help\n")
+                .map_err(DiffEditError::IoError)?;
+        }
+    }
+
+    /// Asks about each of the changed lines in `section`. Returns false if the
+    /// user quit.
+    fn select_lines(&mut self, section: &mut Section) -> Result<bool, DiffEditError> {
+        if let Section::Changed {
+            removed,
+            added,
+            selected_removed,
+            selected_added,
+        } = section
+        {
+            let lines = removed
+                .iter()
+                .zip(selected_removed.iter_mut())
+                .map(|(line, selected)| ("removed", "-", line, selected))
+                .chain(
+                    added
+                        .iter()
+                        .zip(selected_added.iter_mut())
+                        .map(|(line, selected)| ("added", "+", line, selected)),
+                );
+            for (label, prefix, line, selected) in lines {
+                self.write_line(label, prefix, line)?;
+                match self.ask(
+                    "Select this line?",
+                    &[Answer::Yes, Answer::No, Answer::Quit],
+                )? {
+                    Answer::Yes => *selected = true,
+                    Answer::Quit => return Ok(false),
+                    _ => {}
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Asks about each of the hunks in a text file. Returns false if the user
+    /// quit.
+    fn select_hunks(&mut self, sections: &mut [Section]) -> Result<bool, DiffEditError> {
+        let mut left_line = 1;
+        let mut right_line = 1;
+        let mut rest = None;
+        for section in sections.iter_mut() {
+            let (num_removed, num_added) = match section {
+                Section::Unchanged(text) => {
+                    let num_lines = split_lines(text).len();
+                    left_line += num_lines;
+                    right_line += num_lines;
+                    continue;
+                }
+                Section::Changed { removed, added, .. } => (removed.len(), added.len()),
+            };
+            if let Some(selected) = rest {
+                section.select_all(selected);
+            } else {
+                self.write_labeled(
+                    "hunk_header",
+                    &format!("@@ -{left_line},{num_removed} +{right_line},{num_added} @@\n"),
+                )?;
+                if let Section::Changed { removed, added, .. } = &*section {
+                    for line in removed.clone() {
+                        self.write_line("removed", "-", &line)?;
+                    }
+                    for line in added.clone() {
+                        self.write_line("added", "+", &line)?;
+                    }
+                }
+                match self.ask(
+                    "Select this hunk?",
+                    &[
+                        Answer::Yes,
+                        Answer::No,
+                        Answer::Lines,
+                        Answer::All,
+                        Answer::Done,
+                        Answer::Quit,
+                    ],
+                )? {
+                    Answer::Yes => section.select_all(true),
+                    Answer::No => {}
+                    Answer::Lines => {
+                        if !self.select_lines(section)? {
+                            return Ok(false);
+                        }
+                    }
+                    Answer::All => {
+                        section.select_all(true);
+                        rest = Some(true);
+                    }
+                    Answer::Done => rest = Some(false),
+                    Answer::Quit => return Ok(false),
+                }
+            }
+            left_line += num_removed;
+            right_line += num_added;
+        }
+        Ok(true)
+    }
+}
+
+/// Asks which of the changes between `left_tree` and `right_tree` to select,
+/// reading the answers from `input`. Returns the id of `left_tree` with the
+/// selected changes applied.
+///
+/// Changes to text files can be selected by hunk or by line. Other changes,
+/// such as added or removed files, can only be selected as a whole.
+pub fn select_changes(
+    ui: &mut Ui,
+    left_tree: &Tree,
+    right_tree: &Tree,
+    input: &mut dyn BufRead,
+) -> Result<TreeId, DiffEditError> {
+    let store = left_tree.store();
+    let mut tree_builder = store.tree_builder(left_tree.id().clone());
+    let mut selector = Selector { ui, input };
+    for (path, diff) in left_tree.diff(right_tree, &EverythingMatcher) {
+        let ui_path = path.to_internal_file_string();
+        if let tree::Diff::Modified(
+            left_value @ TreeValue::Normal {
+                executable: left_executable,
+                ..
+            },
+            right_value @ TreeValue::Normal {
+                executable: right_executable,
+                ..
+            },
+        ) = &diff
+        {
+            let left_content = read_file(left_tree, &path, left_value)?;
+            let right_content = read_file(right_tree, &path, right_value)?;
+            let is_binary = GitAttributesFile::for_path_in_tree(right_tree, &path)
+                .is_binary(&ui_path)
+                .unwrap_or_else(|| {
+                    files::is_binary(&left_content) || files::is_binary(&right_content)
+                });
+            if left_executable == right_executable && !is_binary {
+                selector.write_labeled(
+                    "header",
+                    &format!("Modified {} {}:\n", describe_value(left_value), ui_path),
+                )?;
+                let mut sections = diff_sections(&left_content, &right_content);
+                let finished = selector.select_hunks(&mut sections)?;
+                let content = selected_content(&sections);
+                if content != left_content {
+                    let id = store.write_file(&path, &mut content.as_slice())?;
+                    tree_builder.set(
+                        path,
+                        TreeValue::Normal {
+                            id,
+                            executable: *left_executable,
+                        },
+                    );
+                }
+                if !finished {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        let header = match &diff {
+            tree::Diff::Added(right_value) => {
+                format!("Added {} {}:\n", describe_value(right_value), ui_path)
+            }
+            tree::Diff::Removed(left_value) => {
+                format!("Removed {} {}:\n", describe_value(left_value), ui_path)
+            }
+            tree::Diff::Modified(left_value, right_value) => format!(
+                "Changed {} from {} to {}:\n",
+                ui_path,
+                describe_value(left_value),
+                describe_value(right_value)
+            ),
+        };
+        selector.write_labeled("header", &header)?;
+        match selector.ask(
+            "Select this change?",
+            &[Answer::Yes, Answer::No, Answer::Quit],
+        )? {
+            Answer::Yes => match diff.into_options().1 {
+                Some(value) => tree_builder.set(path, value),
+                None => tree_builder.remove(path),
+            },
+            Answer::Quit => break,
+            _ => {}
+        }
+    }
+    Ok(tree_builder.write_tree())
+}
@@ -20,6 +20,7 @@ extern crate pest_derive;
 pub mod commands;
 pub mod config;
 pub mod diff_edit;
+pub mod diff_select;
 pub mod formatter;
 pub mod graphlog;
 pub mod template_parser;
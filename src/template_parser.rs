@@ -28,7 +28,8 @@ use crate::templater::{
     CommitterProperty, ConditionalTemplate, ConflictProperty, ConstantTemplateProperty,
     CurrentCheckoutProperty, DescriptionProperty, DivergentProperty, DynamicLabelTemplate,
     GitRefsProperty, IsGitHeadProperty, LabelTemplate, ListTemplate, LiteralTemplate, OpenProperty,
-    StringPropertyTemplate, TagProperty, Template, TemplateFunction, TemplateProperty,
+    SignatureProperty, StringPropertyTemplate, TagProperty, Template, TemplateFunction,
+    TemplateProperty,
 };
 
 #[derive(Parser)]
@@ -254,6 +255,7 @@ fn parse_commit_keyword<'a>(
         "is_git_head" => Property::Boolean(Box::new(IsGitHeadProperty::new(repo))),
         "divergent" => Property::Boolean(Box::new(DivergentProperty::new(repo))),
         "conflict" => Property::Boolean(Box::new(ConflictProperty)),
+        "signature" => Property::String(Box::new(SignatureProperty)),
         name => panic!("unexpected identifier: {}", name),
     };
     (property, pair.as_str().to_string())
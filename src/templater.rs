@@ -23,6 +23,7 @@ use jujutsu_lib::commit::Commit;
 use jujutsu_lib::op_store::WorkspaceId;
 use jujutsu_lib::repo::RepoRef;
 use jujutsu_lib::revset::RevsetExpression;
+use jujutsu_lib::signing::SigStatus;
 
 use crate::formatter::Formatter;
 
@@ -367,6 +368,30 @@ impl TemplateProperty<Commit, bool> for ConflictProperty {
     }
 }
 
+/// The verification status of the commit's signature, such as "good by Some
+/// One", or the empty string if the commit is not signed.
+pub struct SignatureProperty;
+
+impl TemplateProperty<Commit, String> for SignatureProperty {
+    fn extract(&self, context: &Commit) -> String {
+        match context.verification() {
+            None => String::new(),
+            Some(Err(err)) => format!("unknown ({err})"),
+            Some(Ok(verification)) => {
+                let status = match verification.status {
+                    SigStatus::Good => "good",
+                    SigStatus::Bad => "bad",
+                    SigStatus::Unknown => "unknown",
+                };
+                match verification.display.or(verification.key) {
+                    Some(signer) => format!("{status} by {signer}"),
+                    None => status.to_string(),
+                }
+            }
+        }
+    }
+}
+
 pub struct ConditionalTemplate<'a, C> {
     pub condition: Box<dyn TemplateProperty<C, bool> + 'a>,
     pub true_template: Box<dyn Template<C> + 'a>,
@@ -143,6 +143,19 @@ impl<'stdout> Ui<'stdout> {
         &self.settings
     }
 
+    /// Returns the width of the terminal, if known. The `$COLUMNS` environment
+    /// variable takes precedence over the size of the terminal attached to
+    /// stdout.
+    pub fn term_width(&self) -> Option<usize> {
+        if let Some(columns) = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+        {
+            return Some(columns);
+        }
+        stdout_terminal_width()
+    }
+
     pub fn new_formatter<'output>(
         &self,
         output: Box<dyn Write + 'output>,
@@ -269,6 +282,11 @@ pub fn relative_path(mut from: &Path, to: &Path) -> PathBuf {
     result
 }
 
+fn stdout_terminal_width() -> Option<usize> {
+    let (terminal_size::Width(width), _) = terminal_size::terminal_size()?;
+    (width > 0).then(|| usize::from(width))
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -50,12 +50,18 @@ fn main() {
     let args: Args = Args::parse();
     let edit_script_path = PathBuf::from(std::env::var_os("DIFF_EDIT_SCRIPT").unwrap());
     let edit_script = String::from_utf8(std::fs::read(&edit_script_path).unwrap()).unwrap();
-    for instruction in edit_script.split('\0') {
+    let mut instructions = edit_script.split('\0');
+    while let Some(instruction) = instructions.next() {
         let (command, payload) = instruction.split_once('\n').unwrap_or((instruction, ""));
         let parts = command.split(' ').collect_vec();
         match parts.as_slice() {
             [""] => {}
             ["fail"] => exit(1),
+            ["next_invocation"] => {
+                // The rest of the script is for the next time the tool is run
+                std::fs::write(&edit_script_path, instructions.join("\0")).unwrap();
+                break;
+            }
             ["files-before", ..] => {
                 let expected = parts[1..].iter().copied().map(str::to_string).collect();
                 let actual = files_recursively(&args.before);
@@ -78,6 +84,24 @@ fn main() {
                     exit(1)
                 }
             }
+            ["print", message] => {
+                println!("{message}");
+            }
+            ["print-files-before"] => {
+                for file in files_recursively(&args.before).iter().sorted() {
+                    println!("{file}");
+                }
+            }
+            ["print-files-after"] => {
+                for file in files_recursively(&args.after).iter().sorted() {
+                    println!("{file}");
+                }
+            }
+            ["print-contents"] => {
+                let before = std::fs::read_to_string(args.before.as_path()).unwrap();
+                let after = std::fs::read_to_string(args.after.as_path()).unwrap();
+                print!("--- before\n{before}+++ after\n{after}");
+            }
             ["rm", file] => {
                 std::fs::remove_file(args.after.join(file)).unwrap();
             }
@@ -88,6 +112,10 @@ fn main() {
                     std::fs::remove_file(args.after.join(file)).unwrap();
                 }
             }
+            ["write"] => {
+                // When used as a merge tool, "after" is the output file
+                std::fs::write(&args.after, payload).unwrap();
+            }
             ["write", file] => {
                 std::fs::write(args.after.join(file), payload).unwrap();
             }
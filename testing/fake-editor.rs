@@ -31,7 +31,8 @@ fn main() {
     let args: Args = Args::parse();
     let edit_script_path = PathBuf::from(std::env::var_os("EDIT_SCRIPT").unwrap());
     let edit_script = String::from_utf8(std::fs::read(&edit_script_path).unwrap()).unwrap();
-    for instruction in edit_script.split('\0') {
+    let mut instructions = edit_script.split('\0');
+    while let Some(instruction) = instructions.next() {
         let (command, payload) = instruction.split_once('\n').unwrap_or((instruction, ""));
         let parts = command.split(' ').collect_vec();
         match parts.as_slice() {
@@ -40,6 +41,14 @@ fn main() {
             ["write"] => {
                 std::fs::write(&args.file, payload).unwrap();
             }
+            ["dump", dest_path] => {
+                std::fs::copy(&args.file, dest_path).unwrap();
+            }
+            ["next_invocation"] => {
+                // The rest of the script is for the next time the editor is run
+                std::fs::write(&edit_script_path, instructions.join("\0")).unwrap();
+                break;
+            }
             _ => {
                 eprintln!("unexpected command: {}", command);
                 exit(1)
@@ -0,0 +1,62 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::exit;
+
+use clap::Parser;
+
+/// A fake signing program, useful for testing
+///
+/// Implements the protocol of the "command" signing backend. The "signature"
+/// is just the key and a checksum of the signed data.
+#[derive(Parser, Debug)]
+#[clap()]
+enum Args {
+    /// Sign the data on stdin and write the signature to stdout
+    Sign { key: Option<String> },
+    /// Verify the signature in the given file against the data on stdin
+    Verify { signature: PathBuf },
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn main() {
+    let args: Args = Args::parse();
+    let mut input = vec![];
+    std::io::stdin().read_to_end(&mut input).unwrap();
+    match args {
+        Args::Sign { key } => {
+            let key = key.unwrap_or_else(|| "default-key".to_string());
+            print!("fake-sig {} {:016x}", key, checksum(&input));
+        }
+        Args::Verify { signature } => {
+            let signature = std::fs::read_to_string(signature).unwrap();
+            let mut parts = signature.split(' ');
+            if parts.next() != Some("fake-sig") {
+                exit(2);
+            }
+            let key = parts.next().unwrap();
+            println!("{key}");
+            if parts.next() != Some(&format!("{:016x}", checksum(&input))) {
+                exit(1);
+            }
+        }
+    }
+}
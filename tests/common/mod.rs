@@ -66,6 +66,7 @@ impl TestEnvironment {
         cmd.env("JJ_CONFIG", self.config_dir.to_str().unwrap());
         let timestamp = timestamp + chrono::Duration::seconds(*command_number);
         cmd.env("JJ_TIMESTAMP", timestamp.to_rfc3339());
+        cmd.env("JJ_RANDOMNESS_SEED", command_number.to_string());
         cmd.env("JJ_USER", "Test User");
         cmd.env("JJ_EMAIL", "test.user@example.com");
         cmd
@@ -151,6 +152,58 @@ impl TestEnvironment {
         self.add_env_var("DIFF_EDIT_SCRIPT", edit_script.to_str().unwrap());
         edit_script
     }
+
+    /// Sets up the fake diff-editor as a merge tool that gets the base and output
+    /// files, and reads an edit script from the returned path
+    pub fn set_up_fake_merge_tool(&mut self) -> PathBuf {
+        let diff_editor_path = assert_cmd::cargo::cargo_bin("fake-diff-editor");
+        assert!(diff_editor_path.is_file());
+        // Simplified TOML escaping, hoping that there are no '"' or control characters
+        // in it
+        let escaped_diff_editor_path = diff_editor_path.to_str().unwrap().replace('\\', r"\\");
+        self.add_config(
+            format!(
+                r###"
+        [ui]
+        merge-editor = "fake-merge-tool"
+
+        [merge-tools.fake-merge-tool]
+        program = "{}"
+        merge-args = ["$base", "$output"]
+        "###,
+                escaped_diff_editor_path
+            )
+            .as_bytes(),
+        );
+        let edit_script = self.env_root().join("merge_edit_script");
+        self.add_env_var("DIFF_EDIT_SCRIPT", edit_script.to_str().unwrap());
+        edit_script
+    }
+
+    /// Sets up the fake diff-editor as a merge driver called `name` that gets
+    /// the base and output files, and reads an edit script from the returned
+    /// path
+    pub fn set_up_fake_merge_driver(&mut self, name: &str) -> PathBuf {
+        let diff_editor_path = assert_cmd::cargo::cargo_bin("fake-diff-editor");
+        assert!(diff_editor_path.is_file());
+        // Simplified TOML escaping, hoping that there are no '"' or control characters
+        // in it
+        let escaped_diff_editor_path = diff_editor_path.to_str().unwrap().replace('\\', r"\\");
+        self.add_config(
+            format!(
+                r###"
+        [merge-drivers.{}]
+        program = "{}"
+        args = ["$base", "$output"]
+        "###,
+                name, escaped_diff_editor_path
+            )
+            .as_bytes(),
+        );
+        let edit_script = self.env_root().join("merge_driver_script");
+        self.add_env_var("DIFF_EDIT_SCRIPT", edit_script.to_str().unwrap());
+        edit_script
+    }
 }
 
 pub fn get_stdout_string(assert: &assert_cmd::assert::Assert) -> String {
@@ -160,3 +213,23 @@ pub fn get_stdout_string(assert: &assert_cmd::assert::Assert) -> String {
 pub fn get_stderr_string(assert: &assert_cmd::assert::Assert) -> String {
     String::from_utf8(assert.get_output().stderr.clone()).unwrap()
 }
+
+/// Creates a closed commit on top of `parents` (on top of the root commit if
+/// there are none), adding a file called `name` with a branch of the same name
+/// pointing to the commit.
+pub fn create_commit(test_env: &TestEnvironment, repo_path: &Path, name: &str, parents: &[&str]) {
+    if parents.is_empty() {
+        test_env.jj_cmd_success(repo_path, &["co", "root"]);
+    } else if parents.len() == 1 {
+        test_env.jj_cmd_success(repo_path, &["co", parents[0]]);
+    } else {
+        let mut args = vec!["merge", "-m", name];
+        args.extend(parents);
+        test_env.jj_cmd_success(repo_path, &args);
+        test_env.jj_cmd_success(repo_path, &["co", &format!(r#"description("{name}")"#)]);
+        test_env.jj_cmd_success(repo_path, &["edit", "@-"]);
+    }
+    std::fs::write(repo_path.join(name), format!("{name}\n")).unwrap();
+    test_env.jj_cmd_success(repo_path, &["branch", "create", name]);
+    test_env.jj_cmd_success(repo_path, &["close", "-m", name]);
+}
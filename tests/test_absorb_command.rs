@@ -0,0 +1,119 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+#[test]
+fn test_absorb() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "a"]);
+    std::fs::write(repo_path.join("file1"), "1a\n2a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    std::fs::write(repo_path.join("file1"), "1a\n2a\n3b\n4b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ bcd001fa1ee2 
+    o 62285f0e83fb b
+    o 1dd174d72170 a
+    o 000000000000 
+    "###);
+
+    // Nothing to absorb
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+
+    // Each hunk goes into the commit that added the lines around it
+    std::fs::write(repo_path.join("file1"), "1A\n2a\n3b\n4B\n5\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Absorbed changes into these revisions:
+      0549c2a20a10 (no description set)
+      489fa3ad6197 (no description set)
+    Rebased 1 descendant commits
+    Working copy now at: a98c51f95566 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ a98c51f95566 
+    o 0549c2a20a10 b
+    o 489fa3ad6197 a
+    o 000000000000 
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1", "-r", "a"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1A
+    2a
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1A
+    2a
+    3b
+    4B
+    5
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    // A hunk that modifies lines from several commits stays in place
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    std::fs::write(repo_path.join("file1"), "1a\n2A\n3B\n4B\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+       1    1: 1a
+       2    2: 2a2A
+       3    3: 3b3B
+       4    4: 4b4B
+    "###);
+
+    // Commits outside of --into are not rewritten
+    std::fs::write(repo_path.join("file1"), "1A\n2a\n3b\n4B\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb", "--into", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Absorbed changes into these revisions:
+      60b4bd7c306d (no description set)
+    Rebased 1 descendant commits
+    Working copy now at: ef352e1f5141 (no description set)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+       1    1: 1a1A
+       2    2: 2a
+       3    3: 3b
+       4    4: 4B
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &["log", "-T", r#"commit_id.short() " " branches"#],
+    )
+}
@@ -0,0 +1,122 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+#[test]
+fn test_annotate() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file"), "1\n2\n3\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "initial"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "-m", "left"]);
+    std::fs::write(repo_path.join("file"), "0\n1\n2\n3\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "@-", "-m", "right"]);
+    std::fs::write(repo_path.join("file"), "1\n2\n3 right\n").unwrap();
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "merge",
+            "-m",
+            "merge",
+            "description(left)",
+            "description(right)",
+        ],
+    );
+    test_env.jj_cmd_success(&repo_path, &["edit", "description(merge)"]);
+    std::fs::write(repo_path.join("file"), "0\n1\n2\n3 right\nno newline").unwrap();
+
+    // Change ids are random, so use a template without them
+    let template = r#"commit_id.short() " " description.first_line()"#;
+    let stdout = test_env.jj_cmd_success(&repo_path, &["annotate", "file", "-T", template]);
+    insta::assert_snapshot!(stdout, @r###"
+    3ab1d8d144c3 left    1: 0
+    4b6535aec57b initial    2: 1
+    4b6535aec57b initial    3: 2
+    79aa7f29641d right    4: 3 right
+    6b36e340ebb6 merge    5: no newline
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "annotate",
+            "file",
+            "-r",
+            "description(right)",
+            "-T",
+            "description.first_line()",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    initial    1: 1
+    initial    2: 2
+    right    3: 3 right
+    "###);
+
+    // Errors
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["annotate", "nonexistent"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No such path
+    "###);
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("file"), "").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["annotate", "dir"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Path exists but is not a regular file
+    "###);
+}
+
+#[test]
+fn test_annotate_same_line_through_both_parents() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file"), "x\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "initial"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "-m", "left"]);
+    std::fs::write(repo_path.join("file"), "x\np\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "@-", "-m", "right"]);
+    std::fs::write(repo_path.join("file"), "q\nq2\nx\n").unwrap();
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "merge",
+            "-m",
+            "merge",
+            "description(left)",
+            "description(right)",
+        ],
+    );
+    test_env.jj_cmd_success(&repo_path, &["edit", "description(merge)"]);
+    std::fs::write(repo_path.join("file"), "x\np\nq\nq2\nx\n").unwrap();
+
+    // Both copies of "x" are attributed to the initial commit
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["annotate", "file", "-T", "description.first_line()"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    initial    1: x
+    left    2: p
+    right    3: q
+    right    4: q2
+    initial    5: x
+    "###);
+}
@@ -0,0 +1,210 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+/// Creates a linear history of commits 1 to 6, where each commit writes its
+/// number to a file
+fn init_history(test_env: &TestEnvironment) -> PathBuf {
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    for i in 1..=6 {
+        std::fs::write(repo_path.join("file"), format!("{i}\n")).unwrap();
+        test_env.jj_cmd_success(&repo_path, &["close", "-m", &i.to_string()]);
+    }
+    repo_path
+}
+
+#[test]
+fn test_bisect() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_history(&test_env);
+
+    // A bisection must be started first
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No bisection in progress. Start one with `jj bisect start`.
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "start"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Mark a revision that has the bug with `jj bisect bad`
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "bad", "description(6)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Mark a revision that doesn't have the bug with `jj bisect good`
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "good", "description(1)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 4 untested revisions left
+    Now testing: efa28cb98aee 4
+    Working copy now at: 2a6dbf068b1c (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ (no description set)
+    | o 6
+    | o 5
+    |/  
+    o 4
+    o 3
+    o 2
+    o 1
+    o (no description set)
+    "###);
+    // Without arguments, the revision being tested is marked
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "bad"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 2 untested revisions left
+    Now testing: 9cd2fd28a287 3
+    Working copy now at: f8a0714da217 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "skip"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 1 untested revisions left
+    Now testing: 8c586e3d7c3f 2
+    Working copy now at: c6bdb6abb168 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The first bad revision could be any of these:
+      efa28cb98aee 4
+      9cd2fd28a287 3
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ (no description set)
+    | o 6
+    | o 5
+    | o 4
+    | o 3
+    |/  
+    o 2
+    o 1
+    o (no description set)
+    "###);
+
+    // Marking a good revision after the bad one is an error
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "good", "description(5)"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The bad revision is an ancestor of a good revision
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "reset"]);
+    insta::assert_snapshot!(stdout, @"");
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No bisection in progress. Start one with `jj bisect start`.
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bisect_run() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_history(&test_env);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "start"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Mark a revision that has the bug with `jj bisect bad`
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "run", "--", "true"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Mark a good and a bad revision before running a bisection
+    "###);
+
+    // The bug appears in revision 4, and revision 3 can't be tested
+    let command = r#"n=$(cat file); [ $n = 3 ] && exit 125; [ $n -lt 4 ]"#;
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--bad",
+            "description(6)",
+            "--good",
+            "description(1)",
+        ],
+    );
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "run", "--", "sh", "-c", command]);
+    insta::assert_snapshot!(stdout, @r###"
+    bad efa28cb98aee 4
+    skip 9cd2fd28a287 3
+    good 8c586e3d7c3f 2
+    The first bad revision could be any of these:
+      efa28cb98aee 4
+      9cd2fd28a287 3
+    "###);
+    // The working copy is still where `jj bisect start` checked it out
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ (no description set)
+    | o 6
+    | o 5
+    |/  
+    o 4
+    o 3
+    o 2
+    o 1
+    o (no description set)
+    "###);
+
+    // A command that's killed aborts the run
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--bad",
+            "description(6)",
+            "--good",
+            "description(1)",
+        ],
+    );
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["bisect", "run", "--", "sh", "-c", "kill $$"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Aborting the bisection because the command failed with signal: 15 (SIGTERM)
+    "###);
+}
+
+#[test]
+fn test_bisect_unknown_revision() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_history(&test_env);
+
+    test_env.jj_cmd_success(&repo_path, &["bisect", "start"]);
+    test_env.jj_cmd_success(&repo_path, &["bisect", "bad", "description(6)"]);
+    // Mark a well-formed id of a commit that doesn't exist
+    let state_path = repo_path.join(".jj").join("repo").join("bisect");
+    let mut state = std::fs::read_to_string(&state_path).unwrap();
+    state.push_str(&format!("good {}\n", "ab".repeat(20)));
+    std::fs::write(&state_path, state).unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "skip", "description(3)"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The bisection refers to revision abababababab that is not in the repo. Run `jj bisect reset` to start over.
+    "###);
+
+    // The bisection can be started over
+    test_env.jj_cmd_success(&repo_path, &["bisect", "reset"]);
+    test_env.jj_cmd_success(&repo_path, &["bisect", "start"]);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(repo_path, &["log", "-T", "description"])
+}
@@ -0,0 +1,196 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
+
+pub mod common;
+
+fn jj_cmd_with_input(
+    test_env: &TestEnvironment,
+    repo_path: &Path,
+    args: &[&str],
+    input: &str,
+) -> String {
+    let assert = test_env
+        .jj_cmd(repo_path, args)
+        .write_stdin(input)
+        .assert()
+        .success();
+    get_stdout_string(&assert)
+}
+
+#[test]
+fn test_builtin_diff_editor_split() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE1\nE2\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "new\n").unwrap();
+    let edit_script = test_env.set_up_fake_editor();
+    std::fs::write(edit_script, "").unwrap();
+
+    // The first hunk is selected, and only one line of the second one. The
+    // added file isn't selected.
+    let stdout = jj_cmd_with_input(&test_env, &repo_path, &["split"], "y\nl\nn\ny\nn\nn\n");
+    insta::assert_snapshot!(stdout, @r###"
+    You are splitting a commit in two: 9a00686e97e5 ()
+
+    The diff initially shows the changes in the commit you're splitting.
+
+    Adjust the right side until it shows the contents you want for the first
+    commit. The remainder will be in the second commit. If you don't make
+    any changes, then the operation will be aborted.
+
+    Modified regular file file1:
+    @@ -1,1 +1,1 @@
+    -a
+    +A
+    Select this hunk? [y,n,l,a,d,q,?] @@ -5,1 +5,2 @@
+    -e
+    +E1
+    +E2
+    Select this hunk? [y,n,l,a,d,q,?] -e
+    Select this line? [y,n,q,?] +E1
+    Select this line? [y,n,q,?] +E2
+    Select this line? [y,n,q,?] Added regular file file2:
+    Select this change? [y,n,q,?] First part: 327327e07f41 (no description set)
+    Second part: 531bc98fa827 (no description set)
+    Working copy now at: 531bc98fa827 (no description set)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+       1    1: aA
+       2    2: b
+       3    3: c
+       4    4: d
+        ...
+            6: E1
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+        ...
+       2    2: b
+       3    3: c
+       4    4: d
+       5     : e
+       6    5: E1
+            6: E2
+    Added regular file file2:
+            1: new
+    "###);
+}
+
+#[test]
+fn test_builtin_diff_editor_squash() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "b\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "b\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "b\n").unwrap();
+
+    // Invalid answers print the help. Added files are selected as a whole.
+    let stdout = jj_cmd_with_input(&test_env, &repo_path, &["squash", "-i"], "x\nn\ny\nq\n");
+    insta::assert_snapshot!(stdout, @r###"
+    You are moving changes from: 52d75f7bcf14 ()
+    into its parent: 414e1614246a ()
+
+    The left side of the diff shows the contents of the parent commit. The
+    right side initially shows the contents of the commit you're moving
+    changes from.
+
+    Adjust the right side until the diff shows the changes you want to move
+    to the destination. If you don't make any changes, then all the changes
+    from the source will be moved into the parent.
+
+    Modified regular file file1:
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    Select this hunk? [y,n,l,a,d,q,?] y - select this change
+    n - don't select this change
+    l - select individual lines of this change
+    a - select this change and the rest of the changes in the file
+    d - don't select this change or the rest of the changes in the file
+    q - quit; don't select this change or any of the remaining ones
+    ? - print help
+    Select this hunk? [y,n,l,a,d,q,?] Added regular file file2:
+    Select this change? [y,n,q,?] Added regular file file3:
+    Select this change? [y,n,q,?] Working copy now at: 12a49c2d5f13 (no description set)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file1
+    A file2
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    A file3
+    "###);
+
+    // The end of the input counts as quitting, so nothing is selected
+    let stderr = test_env
+        .jj_cmd(&repo_path, &["squash", "-i"])
+        .write_stdin("")
+        .assert()
+        .failure();
+    insta::assert_snapshot!(get_stderr_string(&stderr), @r###"
+    Error: No changes selected
+    "###);
+}
+
+#[test]
+fn test_builtin_diff_editor_touchup() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "a\nc\nd").unwrap();
+
+    // Keep the removal but not the addition
+    let stdout = jj_cmd_with_input(&test_env, &repo_path, &["touchup"], "y\nn\n");
+    insta::assert_snapshot!(stdout, @r###"
+    You are editing changes in: 2afd0c15ab81 ()
+
+    The diff initially shows the commit's changes.
+
+    Adjust the right side until it shows the contents you want. If you
+    don't make any changes, then the operation will be aborted.
+
+    Modified regular file file1:
+    @@ -2,1 +2,0 @@
+    -b
+    Select this hunk? [y,n,l,a,d,q,?] @@ -4,0 +3,1 @@
+    +d
+    \ No newline at end of file
+    Select this hunk? [y,n,l,a,d,q,?] Created a3537a41c680 (no description set)
+    Working copy now at: a3537a41c680 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    c
+    "###);
+}
@@ -313,3 +313,186 @@ fn test_color_words_diff_missing_newline() {
        9     : I
     "###);
 }
+
+#[test]
+fn test_diff_binary() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), b"a\0b\n").unwrap();
+    std::fs::write(repo_path.join("file2"), b"a\0b\n").unwrap();
+    std::fs::write(repo_path.join("file3.dat"), "foo\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), b"a\0c\n").unwrap();
+    std::fs::write(repo_path.join("file3.dat"), "bar\n").unwrap();
+    std::fs::write(repo_path.join("file4"), b"\0").unwrap();
+    std::fs::write(repo_path.join(".gitattributes"), "*.dat binary\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file .gitattributes:
+            1: *.dat binary
+    Removed regular file file1:
+        Binary file differs
+    Modified regular file file2:
+        Binary file differs
+    Modified regular file file3.dat:
+        Binary file differs
+    Added regular file file4:
+        Binary file differs
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "file1", "file2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    deleted file mode 100644
+    index 1a23e4be73..0000000000
+    Binary files a/file1 and /dev/null differ
+    diff --git a/file2 b/file2
+    index 1a23e4be73...659b72404b 100644
+    Binary files a/file2 and b/file2 differ
+    "###);
+}
+
+#[test]
+fn test_diff_external_tool() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\nbar\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "foo\n").unwrap();
+
+    let edit_script = test_env.set_up_fake_diff_editor();
+    let tool_path = assert_cmd::cargo::cargo_bin("fake-diff-editor");
+    let escaped_tool_path = tool_path.to_str().unwrap().replace('\\', r"\\");
+    test_env.add_config(
+        format!(
+            r###"
+        [merge-tools.fake-dir-tool]
+        program = "{escaped_tool_path}"
+
+        [merge-tools.fake-file-tool]
+        program = "{escaped_tool_path}"
+        diff-invocation-mode = "file-by-file"
+        "###
+        )
+        .as_bytes(),
+    );
+
+    // A directory diff tool is invoked once, with only the changed files checked
+    // out
+    std::fs::write(
+        &edit_script,
+        "print-files-before\0print ---\0print-files-after",
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-dir-tool"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1
+    file2
+    ---
+    file2
+    file3
+    "###);
+
+    // A file-by-file tool is invoked for each changed file, with missing files
+    // replaced by empty ones
+    std::fs::write(&edit_script, "print-contents").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-file-tool"]);
+    insta::assert_snapshot!(stdout, @r###"
+    --- before
+    foo
+    +++ after
+    --- before
+    foo
+    +++ after
+    foo
+    bar
+    --- before
+    +++ after
+    foo
+    "###);
+
+    // The tool can be set in the config and is then used by `jj show` and
+    // `jj log -p` too
+    test_env.add_config(b"ui.diff.tool = \"fake-dir-tool\"\n");
+    std::fs::write(&edit_script, "print-files-after").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["show", "@-"]);
+    insta::assert_snapshot!(stdout.lines().last().unwrap(), @"file2");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "-p", "-r", "@", "--no-graph", "-T", "\"\""],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    file2
+    file3
+    "###);
+
+    // A command-line format flag overrides the configured tool
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    R file1
+    M file2
+    A file3
+    "###);
+}
+
+#[test]
+fn test_diff_side_by_side() {
+    let mut test_env = TestEnvironment::default();
+    test_env.add_env_var("COLUMNS", "60");
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "1\n2\n3\n4\n5\n6\n7\n8\n9\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(
+        repo_path.join("file2"),
+        "1\n2\n3\nfour\n5\n6\n7\n8\n9 is a number with a long line that needs wrapping",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("file3"), "foo\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--side-by-side"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Removed regular file file1:
+       1 foo                     |
+    Modified regular file file2:
+       1 1                       |    1 1
+       2 2                       |    2 2
+       3 3                       |    3 3
+       4 4                       |    4 four
+       5 5                       |    5 5
+       6 6                       |    6 6
+       7 7                       |    7 7
+        ...
+       9 9                       |    9 9
+                                 |    9  is a number with a lon
+                                 |      g line that needs wrapp
+                                 |      ing
+    Added regular file file3:
+                                 |    1 foo
+    "###);
+
+    // Narrow terminals get a Git-format diff instead
+    test_env.add_env_var("COLUMNS", "40");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--side-by-side", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    deleted file mode 100644
+    index 257cc5642c..0000000000
+    --- a/file1
+    +++ /dev/null
+    @@ -1,1 +1,0 @@
+    -foo
+    "###);
+}
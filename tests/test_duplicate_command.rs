@@ -0,0 +1,123 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::{create_commit, TestEnvironment};
+
+pub mod common;
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &[
+            "log",
+            "-r",
+            "all() ~ @",
+            "-T",
+            r#"description.first_line() " " branches if(divergent, " divergent")"#,
+        ],
+    )
+}
+
+#[test]
+fn test_duplicate() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "release", &[]);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["duplicate", "root"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot duplicate the root commit
+    "###);
+
+    // Without a destination, the copy stays on the original's parents
+    let stdout = test_env.jj_cmd_success(&repo_path, &["duplicate", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Duplicated 18db23c14b3c as d1a1608a3289 b
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    o b 
+    | o release release
+    | | o c c
+    | | o b b
+    | |/  
+    |/|   
+    o | a a
+    |/  
+    o (no description set) 
+    "###);
+
+    // The copies are not divergent from the originals
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["duplicate", "b:c", "-d", "release"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Duplicated 18db23c14b3c as b2de52ea85dc b
+    Duplicated 8949660d7fc7 as d4ffa011a7fc c
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    o c 
+    o b 
+    | o b 
+    o | release release
+    | | o c c
+    | | o b b
+    | |/  
+    | o a a
+    |/  
+    o (no description set) 
+    "###);
+    // Only the changes from the duplicated revisions are copied
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", "heads((release:) ~ @)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    b
+    c
+    release
+    "###);
+}
+
+#[test]
+fn test_duplicate_multiple_destinations() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &[]);
+    create_commit(&test_env, &repo_path, "d", &[]);
+
+    // The roots of the copies go on top of all the destinations
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["duplicate", "a", "b", "-d", "c", "-d", "d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Duplicated 247da0ddee3d as 334387b97faf a
+    Duplicated 18db23c14b3c as f5029ceac11d b
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    o b 
+    o   a 
+    |\  
+    o | d d
+    | o c c
+    |/  
+    | o b b
+    | o a a
+    |/  
+    o (no description set) 
+    "###);
+}
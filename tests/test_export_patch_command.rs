@@ -0,0 +1,306 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use common::TestEnvironment;
+
+pub mod common;
+
+fn create_commit(
+    test_env: &TestEnvironment,
+    repo_path: &Path,
+    name: &str,
+    parent: &str,
+    description: &str,
+    files: &[(&str, &str)],
+) {
+    test_env.jj_cmd_success(repo_path, &["co", parent]);
+    for (name, content) in files {
+        std::fs::write(repo_path.join(name), content).unwrap();
+    }
+    test_env.jj_cmd_success(repo_path, &["branch", "create", name]);
+    test_env.jj_cmd_success(repo_path, &["close", "-m", description]);
+}
+
+#[test]
+fn test_export_patch() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "first",
+        "root",
+        "first",
+        &[("file", "a\nb\nc\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "second",
+        "first",
+        "second: update file\n\nMore details.\n",
+        &[("file", "a\nB\nc\n"), ("file2", "x\n")],
+    );
+
+    // A single patch
+    let stdout = test_env.jj_cmd_success(&repo_path, &["export-patch", "-r", "second", "--stdout"]);
+    insta::assert_snapshot!(stdout, @r###"
+    From 49b0ff70c6764e173e27ab30c0ef20d0fd0e7cb2 Mon Sep 17 00:00:00 2001
+    From: Test User <test.user@example.com>
+    Date: Sat, 03 Feb 2001 04:05:11 +0700
+    Subject: [PATCH] second: update file
+
+    More details.
+    ---
+    diff --git a/file b/file
+    index de980441c3...7be73ce3c1 100644
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,3 @@
+     a
+    -b
+    +B
+     c
+    diff --git a/file2 b/file2
+    new file mode 100644
+    index 0000000000..587be6b4c3
+    --- /dev/null
+    +++ b/file2
+    @@ -1,0 +1,1 @@
+    +x
+    "###);
+
+    // Several patches are written to files in order
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["export-patch", "-r", "root..second", "-o", "../patches"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    ../patches/0001-first.patch
+    ../patches/0002-second-update-file.patch
+    "###);
+    let content =
+        std::fs::read_to_string(test_env.env_root().join("patches/0001-first.patch")).unwrap();
+    insta::assert_snapshot!(content, @r###"
+    From fa6b88b41d1461fb476bb17e97259b50f58c0b03 Mon Sep 17 00:00:00 2001
+    From: Test User <test.user@example.com>
+    Date: Sat, 03 Feb 2001 04:05:08 +0700
+    Subject: [PATCH 1/2] first
+
+    ---
+    diff --git a/file b/file
+    new file mode 100644
+    index 0000000000..de980441c3
+    --- /dev/null
+    +++ b/file
+    @@ -1,0 +1,3 @@
+    +a
+    +b
+    +c
+    "###);
+}
+
+#[test]
+fn test_apply() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        "root",
+        "base",
+        &[("file", "a\nb\nc\nd\ne\nf\ng\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "first",
+        "base",
+        "first",
+        &[("file", "a\nb\nc\nD\ne\nf\ng\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "second",
+        "first",
+        "second\n\nMore details.\n",
+        &[("file", "a\nb\nc\nD\ne\nf\nG\n"), ("file2", "x\n")],
+    );
+    test_env.jj_cmd_success(
+        &repo_path,
+        &["export-patch", "-r", "base..second", "-o", "../patches"],
+    );
+
+    // The patches apply with an offset on top of a commit that has added lines
+    create_commit(
+        &test_env,
+        &repo_path,
+        "other",
+        "base",
+        "other",
+        &[("file", "new\nlines\na\nb\nc\nd\ne\nf\ng\n")],
+    );
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "apply",
+            "-d",
+            "other",
+            "../patches/0001-first.patch",
+            "../patches/0002-second.patch",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Created 84f741d14030 first
+    Created 9ca15c69b03e second
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-r",
+            "other:",
+            "-T",
+            r#"description.first_line() " " author.email()"#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    o second test.user@example.com
+    o first test.user@example.com
+    | @ (no description set) test.user@example.com
+    |/  
+    o other test.user@example.com
+    ~ 
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["print", "file", "-r", r#"other: & description("second")"#],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    new
+    lines
+    a
+    b
+    c
+    D
+    e
+    f
+    G
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["print", "file2", "-r", r#"other: & description("second")"#],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    x
+    "###);
+}
+
+#[test]
+fn test_apply_three_way_fallback() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        "root",
+        "base",
+        &[("file", "a\nb\nc\n"), ("other", "foo\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "change",
+        "base",
+        "change",
+        &[("file", "a\nB\nc\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "conflicting",
+        "base",
+        "conflicting",
+        &[("file", "a\nb2\nc\n"), ("other", "bar\n")],
+    );
+    test_env.jj_cmd_success(&repo_path, &["export-patch", "-r", "change", "-o", ".."]);
+
+    // The patch doesn't apply, so it's merged in from the commit it was created
+    // from, resulting in a conflict
+    let assert = test_env
+        .jj_cmd(
+            &repo_path,
+            &["apply", "-d", "conflicting", "../0001-change.patch"],
+        )
+        .assert()
+        .success();
+    insta::assert_snapshot!(common::get_stdout_string(&assert), @r###"
+    Created 68fdfe5045b2 change
+    "###);
+    insta::assert_snapshot!(common::get_stderr_string(&assert), @r###"
+    Patch "change" did not apply cleanly, falling back to 3-way merge
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["print", "file", "-r", "conflicting+ & description(change)"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    <<<<<<<
+    ------- 1fbec814b18b (commit 41c14654deca) "base"
+    +++++++ patch "change"
+    -b
+    +B
+    +++++++
+    b2
+    >>>>>>>
+    c
+    "###);
+
+    // Without the source commit, the patch can't be applied
+    let patch = std::fs::read_to_string(test_env.env_root().join("0001-change.patch")).unwrap();
+    let patch = patch.split_once('\n').unwrap().1;
+    std::fs::write(test_env.env_root().join("modified.patch"), patch).unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["apply", "-d", "conflicting", "../modified.patch"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to apply patch "change": Hunk #1 does not apply to file
+    "###);
+    // A "From " line without a commit id is not used to find the source commit
+    std::fs::write(
+        test_env.env_root().join("modified.patch"),
+        format!("From John Mon Sep 17 00:00:00 2001\n{patch}"),
+    )
+    .unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["apply", "-d", "conflicting", "../modified.patch"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to apply patch "change": Hunk #1 does not apply to file
+    "###);
+}
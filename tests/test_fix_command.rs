@@ -0,0 +1,225 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
+
+pub mod common;
+
+/// Configures the fake formatter as the fix tool `name` for files matching
+/// `pattern`, passing it `args`
+fn add_fake_formatter(test_env: &TestEnvironment, name: &str, pattern: &str, args: &[&str]) {
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    // Simplified TOML escaping, hoping that there are no '"' or control characters
+    // in it
+    let escaped_formatter_path = formatter_path.to_str().unwrap().replace('\\', r"\\");
+    let args = args
+        .iter()
+        .map(|arg| format!("{arg:?}"))
+        .collect::<Vec<_>>();
+    test_env.add_config(
+        format!(
+            r###"
+        [fix.tools.{name}]
+        program = "{escaped_formatter_path}"
+        args = [{}]
+        patterns = ["{pattern}"]
+        "###,
+            args.join(", ")
+        )
+        .as_bytes(),
+    );
+}
+
+#[test]
+fn test_fix_no_tools() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No tools are configured in `fix.tools`
+    "###);
+}
+
+#[test]
+fn test_fix() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_fake_formatter(&test_env, "upper", "*.txt", &["--uppercase"]);
+
+    std::fs::write(repo_path.join("file.txt"), "a\n").unwrap();
+    std::fs::write(repo_path.join("other"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "a"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "a"]);
+    std::fs::write(repo_path.join("file.txt"), "a\nb\n").unwrap();
+    std::fs::write(repo_path.join("other"), "a\nb\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "b"]);
+    std::fs::write(repo_path.join("file.txt"), "a\nb\nc\n").unwrap();
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 46743ac6edc1 
+    o 3147d0df3aa8 b
+    o f7cfbc46b0f9 a
+    o 000000000000 
+    "###);
+
+    // Every commit in the stack is fixed, and only matching files are changed
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 3 commits
+    Working copy now at: de4f2e2030b0 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ de4f2e2030b0 
+    o 2da8693b96dc b
+    o 8f15e5b0ecb8 a
+    o 000000000000 
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "a", "file.txt"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "b", "file.txt"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    B
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "b", "other"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    b
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file.txt:
+       1    1: A
+       2    2: B
+            3: C
+    "###);
+    // The working copy was updated to the fixed commit
+    let content = std::fs::read_to_string(repo_path.join("file.txt")).unwrap();
+    insta::assert_snapshot!(content, @r###"
+    A
+    B
+    C
+    "###);
+
+    // Fixing again changes nothing
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_fix_child_without_fixed_file() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_fake_formatter(&test_env, "upper", "*.txt", &["--uppercase"]);
+
+    std::fs::write(repo_path.join("file.txt"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "a"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "a"]);
+    std::fs::write(repo_path.join("other"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "b"]);
+
+    // The child keeps the fix even though it doesn't modify the file
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 3 commits
+    Working copy now at: 5ad7029f5d2a (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "b", "file.txt"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file other:
+            1: b
+    "###);
+}
+
+#[test]
+fn test_fix_tools_in_order_and_failures() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_fake_formatter(&test_env, "1-append", "*", &["--append", "x\n"]);
+    add_fake_formatter(&test_env, "2-upper", "*", &["--uppercase"]);
+    add_fake_formatter(&test_env, "3-fail", "file2", &["--fail"]);
+
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "b\n").unwrap();
+    let assert = test_env
+        .jj_cmd(&repo_path, &["fix", "-r", "@"])
+        .assert()
+        .success();
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    Fixed 1 commits
+    Working copy now at: 644a5db0e0dd (no description set)
+    Added 0 files, modified 2 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    Failed to run 3-fail on file2
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    X
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    B
+    X
+    "###);
+}
+
+#[test]
+fn test_fix_tool_not_reading_input() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    add_fake_formatter(&test_env, "replace", "*", &["--replace", "fixed\n"]);
+
+    // Larger than a pipe's buffer, so writing it fails once the tool has exited
+    std::fs::write(repo_path.join("file"), "a\n".repeat(100_000)).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 1 commits
+    Working copy now at: 8a45a6c895e0 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    fixed
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &["log", "-T", r#"commit_id.short() " " branches"#],
+    )
+}
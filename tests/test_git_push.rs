@@ -192,7 +192,7 @@ fn test_git_push_conflict() {
     test_env.jj_cmd_success(&workspace_root, &["close", "-m", "third"]);
     let stderr = test_env.jj_cmd_failure(&workspace_root, &["git", "push", "--all"]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: Won't push commit 50ccff1aeab0 since it has conflicts
+    Error: Won't push commit 51e9ff9f379d since it has conflicts
     "###);
 }
 
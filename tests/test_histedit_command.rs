@@ -0,0 +1,270 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::{create_commit, TestEnvironment};
+
+pub mod common;
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &["log", "-T", r#"branches " " description.first_line()"#],
+    )
+}
+
+fn short_id(test_env: &TestEnvironment, repo_path: &Path, revision: &str) -> String {
+    test_env
+        .jj_cmd_success(
+            repo_path,
+            &[
+                "log",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                "commit_id.short()",
+            ],
+        )
+        .trim()
+        .to_string()
+}
+
+/// Creates a chain a-b-c-d with the working copy on top
+fn set_up_chain(test_env: &TestEnvironment, repo_path: &Path) {
+    create_commit(test_env, repo_path, "a", &[]);
+    create_commit(test_env, repo_path, "b", &["a"]);
+    create_commit(test_env, repo_path, "c", &["b"]);
+    create_commit(test_env, repo_path, "d", &["c"]);
+}
+
+#[test]
+fn test_histedit_plan() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+    set_up_chain(&test_env, &repo_path);
+
+    // Leaving the plan as is changes nothing
+    let plan_path = test_env.env_root().join("plan");
+    std::fs::write(&edit_script, format!("dump {}", plan_path.display())).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "a:d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+    insta::assert_snapshot!(std::fs::read_to_string(&plan_path).unwrap(), @r###"
+    pick 247da0ddee3d a
+    pick 18db23c14b3c b
+    pick 8949660d7fc7 c
+    pick 2a4970a3803e d
+
+    # The revisions above are rewritten from top to bottom. Actions:
+    #   pick = keep the revision
+    #   reorder = keep the revision (same as pick, for lines that were moved)
+    #   edit-message = keep the revision and edit its description
+    #   squash = combine the revision into the revision above it
+    #   drop = remove the revision and its changes
+    # Move lines to reorder the revisions. All revisions must be listed.
+    # Lines starting with "#" are ignored. Remove all lines to leave the
+    # revisions unchanged.
+    "###);
+
+    // Removing all lines changes nothing
+    std::fs::write(&edit_script, "write\n# just a comment\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "a:d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+
+    // The revisions must form a linear chain
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["histedit", "a | c"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot edit revisions that don't form a linear chain
+    "###);
+}
+
+#[test]
+fn test_histedit_reorder() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+    set_up_chain(&test_env, &repo_path);
+    let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| short_id(&test_env, &repo_path, name));
+
+    std::fs::write(
+        &edit_script,
+        format!("write\npick {a}\nreorder {c}\npick {b} b\npick {d}\n"),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "a:d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rewrote 3 commits
+    Rebased 1 descendant commits
+    Working copy now at: 52e85b6c1d83 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    o d d
+    o b b
+    o c c
+    o a a
+    o  (no description set)
+    "###);
+    // The files stay with their revisions
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "c", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A c
+    "###);
+
+    // The whole edit is a single operation
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    o d d
+    o c c
+    o b b
+    o a a
+    o  (no description set)
+    "###);
+}
+
+#[test]
+fn test_histedit_squash_drop_edit_message() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+    set_up_chain(&test_env, &repo_path);
+    let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| short_id(&test_env, &repo_path, name));
+
+    // The description editor is opened after the plan
+    let description_path = test_env.env_root().join("description");
+    std::fs::write(
+        &edit_script,
+        format!(
+            "write\npick {a}\nsquash {b}\ndrop {c}\nedit-message {d}\n\0next_invocation\0dump {}\0write\nnew d\n",
+            description_path.display()
+        ),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "a:d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rewrote 2 commits
+    Dropped 1 commits
+    Rebased 1 descendant commits
+    Working copy now at: bbcdacdada9f (no description set)
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(std::fs::read_to_string(&description_path).unwrap(), @r###"
+    d
+    JJ: Lines starting with "JJ: " (like this one) will be removed.
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    o d new d
+    o a b c a
+    o  (no description set)
+    "###);
+    // The descriptions of squashed revisions are combined
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-r", "a", "-T", "description"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    a
+
+    b
+    "###);
+    // The changes from the dropped revision are gone
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    b
+    d
+    "###);
+}
+
+#[test]
+fn test_histedit_errors() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+    set_up_chain(&test_env, &repo_path);
+    let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| short_id(&test_env, &repo_path, name));
+
+    // Errors are shown in the plan, and jj gives up if the plan is not changed
+    let plan_path = test_env.env_root().join("plan");
+    let bad_plan = format!("squash {a}\npik {b}\npick\npick 0123\npick {b}\npick {b}\n");
+    std::fs::write(
+        &edit_script,
+        format!(
+            "write\n{bad_plan}\0next_invocation\0dump {}\0write\n{bad_plan}",
+            plan_path.display()
+        ),
+    )
+    .unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["histedit", "a:d"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The plan has errors:
+    Cannot squash revision 247da0ddee3d because there is no revision above it to squash into
+    Unknown action "pik" in line "pik 18db23c14b3c"
+    Missing revision in line "pick"
+    Revision "0123" is not one of the revisions being edited
+    Revision 18db23c14b3c is listed more than once
+    Revision 8949660d7fc7 is missing from the plan (use "drop" to remove it)
+    Revision 2a4970a3803e is missing from the plan (use "drop" to remove it)
+    "###);
+    insta::assert_snapshot!(std::fs::read_to_string(&plan_path).unwrap(), @r###"
+    # Error: Cannot squash revision 247da0ddee3d because there is no revision above it to squash into
+    # Error: Unknown action "pik" in line "pik 18db23c14b3c"
+    # Error: Missing revision in line "pick"
+    # Error: Revision "0123" is not one of the revisions being edited
+    # Error: Revision 18db23c14b3c is listed more than once
+    # Error: Revision 8949660d7fc7 is missing from the plan (use "drop" to remove it)
+    # Error: Revision 2a4970a3803e is missing from the plan (use "drop" to remove it)
+    squash 247da0ddee3d
+    pik 18db23c14b3c
+    pick
+    pick 0123
+    pick 18db23c14b3c
+    pick 18db23c14b3c
+    "###);
+
+    // The plan can be fixed after an error
+    std::fs::write(
+        &edit_script,
+        format!(
+            "write\ndrop {d}\0next_invocation\0write\npick {a}\npick {b}\npick {c}\ndrop {d}\n"
+        ),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "a:d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Dropped 1 commits
+    Rebased 1 descendant commits
+    Working copy now at: dbc9db9f09b2 (no description set)
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    o c d c
+    o b b
+    o a a
+    o  (no description set)
+    "###);
+}
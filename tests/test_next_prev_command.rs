@@ -0,0 +1,177 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::{create_commit, get_stdout_string, TestEnvironment};
+
+pub mod common;
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &["log", "-T", r#"branches " " description.first_line()"#],
+    )
+}
+
+fn jj_cmd_with_input(
+    test_env: &TestEnvironment,
+    repo_path: &Path,
+    args: &[&str],
+    input: &str,
+) -> String {
+    let assert = test_env
+        .jj_cmd(repo_path, args)
+        .write_stdin(input)
+        .assert()
+        .success();
+    get_stdout_string(&assert)
+}
+
+#[test]
+fn test_next_prev() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    test_env.jj_cmd_success(&repo_path, &["co", "root"]);
+
+    // A new working-copy commit is created on top of the child
+    let stdout = test_env.jj_cmd_success(&repo_path, &["next"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: 7725052b4a5e (no description set)
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    | o c c
+    | o b b
+    |/  
+    o a a
+    o  (no description set)
+    "###);
+
+    // Can move several revisions at once
+    let stdout = test_env.jj_cmd_success(&repo_path, &["next", "2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: 1dc5c616b9f4 (no description set)
+    Added 2 files, modified 0 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["prev", "2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: c34be688665f (no description set)
+    Added 0 files, modified 0 files, removed 2 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    | o c c
+    | o b b
+    |/  
+    o a a
+    o  (no description set)
+    "###);
+
+    // Cannot move past the root commit or the top of the stack
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["prev"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No ancestor found 1 revision back
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["next", "4"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No descendant found 4 revisions forward
+    "###);
+}
+
+#[test]
+fn test_next_prev_edit() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    test_env.jj_cmd_success(&repo_path, &["edit", "c"]);
+
+    // The parent is edited directly
+    let stdout = test_env.jj_cmd_success(&repo_path, &["prev", "--edit"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: 18db23c14b3c b
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    o c c
+    @ b b
+    o a a
+    o  (no description set)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["next", "--edit"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: 8949660d7fc7 c
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ c c
+    o b b
+    o a a
+    o  (no description set)
+    "###);
+}
+
+#[test]
+fn test_next_choose_child() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["a"]);
+    test_env.jj_cmd_success(&repo_path, &["co", "a"]);
+
+    // The user is asked which child to go to, and invalid answers are rejected
+    let stdout = jj_cmd_with_input(&test_env, &repo_path, &["next"], "3\nfoo\n2\n");
+    insta::assert_snapshot!(stdout, @r###"
+    There are several revisions to choose from:
+    1: 73862f5c6376 c
+    2: 18db23c14b3c b
+    Enter the number of the revision to go to: Please enter a number between 1 and 2
+    Enter the number of the revision to go to: Please enter a number between 1 and 2
+    Enter the number of the revision to go to: Working copy now at: aee43673990d (no description set)
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  (no description set)
+    | o c c
+    o | b b
+    |/  
+    o a a
+    o  (no description set)
+    "###);
+
+    // Nothing happens if no child is chosen
+    test_env.jj_cmd_success(&repo_path, &["co", "a"]);
+    let assert = test_env
+        .jj_cmd(&repo_path, &["next"])
+        .write_stdin("")
+        .assert()
+        .failure();
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    There are several revisions to choose from:
+    1: 73862f5c6376 c
+    2: 18db23c14b3c b
+    Enter the number of the revision to go to: 
+    "###);
+}
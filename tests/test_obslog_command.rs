@@ -36,7 +36,7 @@ fn test_obslog_with_or_without_diff() {
     insta::assert_snapshot!(stdout, @r###"
     @ 1daafc17fefb test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     | my description
-    o 813918f7b4e6 test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
+    o 23ea5f230aaf test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
     | my description
     o 8f02f5470c55 test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     | my description
@@ -52,11 +52,11 @@ fn test_obslog_with_or_without_diff() {
     | my description
     | Resolved conflict in file1:
     |    1    1: <<<<<<<resolved
-    |    2     : -------
-    |    3     : +++++++
+    |    2     : ------- 611830d3641a (commit a75cc5f7570a)
+    |    3     : +++++++ 1fbec814b18b (commit 8f02f5470c55) "my description"
     |    4     : +bar
     |    5     : >>>>>>>
-    o 813918f7b4e6 test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
+    o 23ea5f230aaf test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
     | my description
     o 8f02f5470c55 test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     | my description
@@ -74,7 +74,7 @@ fn test_obslog_with_or_without_diff() {
     insta::assert_snapshot!(stdout, @r###"
     1daafc17fefb test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     my description
-    813918f7b4e6 test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
+    23ea5f230aaf test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
     my description
     8f02f5470c55 test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     my description
@@ -88,17 +88,17 @@ fn test_obslog_with_or_without_diff() {
     1daafc17fefb test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     my description
     diff --git a/file1 b/file1
-    index e155302a24...2ab19ae607 100644
+    index a4211a4d53...2ab19ae607 100644
     --- a/file1
     +++ b/file1
     @@ -1,5 +1,1 @@
     -<<<<<<<
-    --------
-    -+++++++
+    -------- 611830d3641a (commit a75cc5f7570a)
+    -+++++++ 1fbec814b18b (commit 8f02f5470c55) "my description"
     -+bar
     ->>>>>>>
     +resolved
-    813918f7b4e6 test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
+    23ea5f230aaf test.user@example.com 2001-02-03 04:05:08.000 +07:00    conflict
     my description
     8f02f5470c55 test.user@example.com 2001-02-03 04:05:08.000 +07:00   
     my description
@@ -0,0 +1,97 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::{create_commit, TestEnvironment};
+
+pub mod common;
+
+#[test]
+fn test_parallelize() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    create_commit(&test_env, &repo_path, "e", &["b"]);
+    test_env.jj_cmd_success(&repo_path, &["co", "d"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    | o e
+    o | d
+    o | c
+    |/  
+    o b
+    o a
+    o 
+    "###);
+
+    // The revisions must form a linear chain
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["parallelize", "a", "c"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot parallelize revisions that don't form a linear chain
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["parallelize", "b", "c", "e"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot parallelize revisions that don't form a linear chain
+    "###);
+
+    // A single revision is already parallel
+    let stdout = test_env.jj_cmd_success(&repo_path, &["parallelize", "c"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+
+    // Descendants become merges of all the siblings
+    let stdout = test_env.jj_cmd_success(&repo_path, &["parallelize", "a:c"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Parallelized 3 commits
+    Rebased 3 descendant commits
+    Working copy now at: 3d621135c1c4 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    o-.   d
+    |\ \  
+    | | | o-.   e
+    | | | |\ \  
+    | |_|/ / /  
+    |/| | | |   
+    | | |/ /    
+    | |/| |     
+    | | |/      
+    o | | c
+    | o | b
+    |/ /  
+    | o a
+    |/  
+    o 
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files", "-r", "d"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    b
+    c
+    d
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(repo_path, &["log", "-T", "branches"])
+}
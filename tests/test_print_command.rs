@@ -61,11 +61,11 @@ fn test_print() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1"]);
     insta::assert_snapshot!(stdout, @r###"
     <<<<<<<
-    -------
-    +++++++
+    ------- 1fbec814b18b (commit 0ac173acd922)
+    +++++++ 611830d3641a (commit 414e1614246a)
     -b
     +a
-    +++++++
+    +++++++ b260b8a10343 (commit 94e1bae2e305)
     c
     >>>>>>>
     "###);
@@ -300,6 +300,286 @@ fn test_rebase_with_descendants() {
     "###);
 }
 
+#[test]
+fn test_rebase_empty_behavior() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    // An empty commit on top of c
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "empty"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "empty"]);
+    // The upstream commit makes the same change as b
+    test_env.jj_cmd_success(&repo_path, &["co", "a"]);
+    std::fs::write(repo_path.join("b"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "upstream"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "upstream"]);
+    test_env.jj_cmd_success(&repo_path, &["co", "empty"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    | o upstream
+    o | empty
+    o | c
+    o | b
+    |/  
+    o a
+    o 
+    "###);
+
+    // Invalid value
+    test_env.jj_cmd_cli_error(
+        &repo_path,
+        &["rebase", "-s", "b", "-d", "upstream", "--empty", "drop"],
+    );
+
+    // Commits that became empty are abandoned, but commits that were already
+    // empty are kept
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "rebase",
+            "-s",
+            "b",
+            "-d",
+            "upstream",
+            "--empty",
+            "abandon-newly-empty",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 3 commits
+    Working copy now at: 003bbae10689 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    o empty
+    o c
+    o b upstream
+    o a
+    o 
+    "###);
+
+    // All empty commits are abandoned, except for the working-copy commit
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "rebase",
+            "-s",
+            "b",
+            "-d",
+            "upstream",
+            "--empty",
+            "abandon-all-empty",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 2 commits
+    Working copy now at: 63688e318007 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    o c empty
+    o b upstream
+    o a
+    o 
+    "###);
+}
+
+#[test]
+fn test_rebase_insert() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "a", &[]);
+    create_commit(&test_env, &repo_path, "b", &["a"]);
+    create_commit(&test_env, &repo_path, "c", &["b"]);
+    create_commit(&test_env, &repo_path, "d", &["c"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    o d
+    o c
+    o b
+    o a
+    o 
+    "###);
+
+    // Both a destination and an insertion point
+    test_env.jj_cmd_cli_error(&repo_path, &["rebase", "-r", "d", "-d", "a", "-A", "a"]);
+    // Insertion without -r or -s
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-A", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: --insert-after and --insert-before require -r or -s
+    "###);
+    // Insertion next to itself
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-r", "b", "-A", "b"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot insert 18db23c14b3c next to itself
+    "###);
+    // Insertion of descendants before one of them
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["rebase", "-s", "b", "-B", "c"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot insert 18db23c14b3c and its descendants before one of them
+    "###);
+
+    // Move a commit earlier in the stack
+    let stdout = test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "d", "-B", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 4 commits
+    Working copy now at: e9b30cf48200 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    o c
+    o b
+    o d
+    o a
+    o 
+    "###);
+
+    // Move a commit later in the stack
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "b", "-A", "c"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 4 commits
+    Working copy now at: aea9f713b9c3 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ 
+    o d
+    o b
+    o c
+    o a
+    o 
+    "###);
+
+    // Insert a commit and its descendants from another branch
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    create_commit(&test_env, &repo_path, "e", &["a"]);
+    create_commit(&test_env, &repo_path, "f", &["e"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["rebase", "-s", "e", "-A", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 5 commits
+    Working copy now at: 8f583b2bf8bd (no description set)
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    o d
+    o c
+    @ 
+    o f
+    o e
+    o b
+    o a
+    o 
+    "###);
+}
+
 fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
     test_env.jj_cmd_success(repo_path, &["log", "-T", "branches"])
 }
+
+#[test]
+fn test_rebase_reuses_recorded_resolution() {
+    let test_env = TestEnvironment::default();
+    test_env.add_config(b"rerere.enabled = true");
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
+
+    std::fs::write(&file_path, "line1\nline2\nline3\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "base"]);
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "base", "-r", "@-"]);
+    // left1 and left2 make the same change
+    for (name, line) in [("left1", "left"), ("left2", "left"), ("right", "right")] {
+        test_env.jj_cmd_success(&repo_path, &["co", "base"]);
+        std::fs::write(&file_path, format!("line1\n{line}\nline3\n")).unwrap();
+        test_env.jj_cmd_success(&repo_path, &["close", "-m", name]);
+        test_env.jj_cmd_success(&repo_path, &["branch", "create", name, "-r", "@-"]);
+    }
+
+    // Rebasing left1 creates a conflict, which we resolve
+    test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "left1", "-d", "right"]);
+    test_env.jj_cmd_success(&repo_path, &["co", "left1"]);
+    std::fs::write(&file_path, "line1\nleft and right\nline3\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["squash"]);
+
+    // The same conflict is resolved the same way when rebasing left2
+    let stdout = test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "left2", "-d", "right"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Resolved conflicts using recorded resolutions in:
+        file
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "left2", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    line1
+    left and right
+    line3
+    "###);
+}
+
+#[test]
+fn test_rebase_with_merge_attributes() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let driver_script = test_env.set_up_fake_merge_driver("fake");
+
+    std::fs::write(
+        repo_path.join(".gitattributes"),
+        "CHANGELOG.md merge=union\n*.json merge=fake\n",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("CHANGELOG.md"), "# Changelog\n").unwrap();
+    std::fs::write(repo_path.join("config.json"), "{}\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "base"]);
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "base", "-r", "@-"]);
+    for name in ["left", "right"] {
+        test_env.jj_cmd_success(&repo_path, &["co", "base"]);
+        std::fs::write(
+            repo_path.join("CHANGELOG.md"),
+            format!("# Changelog\n* {name} change\n"),
+        )
+        .unwrap();
+        std::fs::write(repo_path.join("config.json"), format!("{{{name}}}\n")).unwrap();
+        test_env.jj_cmd_success(&repo_path, &["close", "-m", name]);
+        test_env.jj_cmd_success(&repo_path, &["branch", "create", name, "-r", "@-"]);
+    }
+
+    // The changelog entries from both sides are kept, and the driver merges the
+    // JSON file
+    std::fs::write(&driver_script, "write\n{merged}\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "left", "-d", "right"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "left", "CHANGELOG.md"]);
+    insta::assert_snapshot!(stdout, @r###"
+    # Changelog
+    * right change
+    * left change
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "left", "config.json"]);
+    insta::assert_snapshot!(stdout, @r###"
+    {merged}
+    "###);
+
+    // If the driver fails, the file is left conflicted
+    std::fs::write(&driver_script, "fail").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "left", "-d", "right"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "left", "config.json"]);
+    insta::assert_snapshot!(stdout, @r###"
+    <<<<<<<
+    ------- 611830d3641a (commit fa1c53ef343b) "base"
+    +++++++ e463bcb1a6e5 (commit 1a5b75fcb332) "left"
+    -{}
+    +{left}
+    +++++++ befba86ae9e0 (commit 028dc7b642aa) "right"
+    {right}
+    >>>>>>>
+    "###);
+}
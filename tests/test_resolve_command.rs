@@ -0,0 +1,496 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use common::TestEnvironment;
+
+pub mod common;
+
+fn create_commit(
+    test_env: &TestEnvironment,
+    repo_path: &Path,
+    name: &str,
+    parents: &[&str],
+    files: &[(&str, &[u8])],
+) {
+    if parents.is_empty() {
+        test_env.jj_cmd_success(repo_path, &["co", "root"]);
+    } else if parents.len() == 1 {
+        test_env.jj_cmd_success(repo_path, &["co", parents[0]]);
+    } else {
+        let mut args = vec!["merge", "-m", name];
+        args.extend(parents);
+        test_env.jj_cmd_success(repo_path, &args);
+        test_env.jj_cmd_success(repo_path, &["co", &format!(r#"description("{name}")"#)]);
+        test_env.jj_cmd_success(repo_path, &["edit", "@-"]);
+    }
+    for (name, content) in files {
+        std::fs::write(repo_path.join(name), content).unwrap();
+    }
+    test_env.jj_cmd_success(repo_path, &["branch", "create", name]);
+    test_env.jj_cmd_success(repo_path, &["close", "-m", name]);
+}
+
+#[test]
+fn test_binary_conflict_in_working_copy() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        &[],
+        &[
+            ("file", b"base\0"),
+            ("text.dat", b"base\n"),
+            (".gitattributes", b"*.dat binary\n"),
+        ],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "a",
+        &["base"],
+        &[("file", b"a\0"), ("text.dat", b"a\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "b",
+        &["base"],
+        &[("file", b"b\0"), ("text.dat", b"b\n")],
+    );
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+
+    // The conflict is described instead of being materialized with markers
+    let content = std::fs::read_to_string(repo_path.join("file")).unwrap();
+    assert!(content.starts_with("Conflict in binary file:\n"));
+    assert!(content.contains("(side #2)\n"));
+    // Same for text files that have the binary attribute
+    let content = std::fs::read_to_string(repo_path.join("text.dat")).unwrap();
+    assert!(content.starts_with("Conflict in binary file:\n"));
+
+    // The conflicts are left alone when the working copy is snapshotted
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    assert!(stdout.contains("The working copy is clean\n"));
+
+    // The conflict can be resolved by writing the content
+    std::fs::write(repo_path.join("text.dat"), "resolved\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "text.dat"]);
+    assert_eq!(stdout, "resolved\n");
+}
+
+#[test]
+fn test_resolve_by_side() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        &[],
+        &[("file", b"base\0"), ("other", b"base\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "a",
+        &["base"],
+        &[("file", b"a\0"), ("other", b"a\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "b",
+        &["base"],
+        &[("file", b"b\0"), ("other", b"b\n")],
+    );
+    create_commit(&test_env, &repo_path, "c", &["base"], &[("file", b"c\0")]);
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b", "c"], &[]);
+
+    // Sides are numbered from 1
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "conflict", "--side=0"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Sides are numbered from 1
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "conflict", "--side=4"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Skipping file: the conflict has only 3 sides
+    Skipping other: the conflict has only 2 sides
+    Error: None of the conflicts has a side #4
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "a", "--side=1"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No conflicts found at this revision
+    "###);
+
+    // Conflicts with fewer sides are skipped
+    let assert = test_env
+        .jj_cmd(&repo_path, &["resolve", "-r", "conflict", "--side=3"])
+        .assert()
+        .success();
+    insta::assert_snapshot!(common::get_stderr_string(&assert), @r###"
+    Skipping other: the conflict has only 2 sides
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "conflict", "file"]);
+    assert_eq!(stdout, "c\0");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "-r", "conflict", "--list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    other    2-sided conflict
+    "###);
+
+    // The sides are numbered in the order they're stored in the conflict, which
+    // doesn't depend on the order of the parents
+    test_env.jj_cmd_success(&repo_path, &["resolve", "-r", "conflict", "--side=2"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "conflict", "other"]);
+    assert_eq!(stdout, "a\n");
+    // The working copy was rebased onto the resolved commit
+    assert_eq!(std::fs::read(repo_path.join("file")).unwrap(), b"c\0");
+    assert_eq!(std::fs::read(repo_path.join("other")).unwrap(), b"a\n");
+}
+
+#[test]
+fn test_resolve_in_working_copy_with_git_style_markers() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(br#"ui.conflict-marker-style = "git""#);
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        &[],
+        &[("file", b"1\n2\n3\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "a",
+        &["base"],
+        &[("file", b"a1\n2\na3\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "b",
+        &["base"],
+        &[("file", b"b1\n2\nb3\n")],
+    );
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+
+    let content = std::fs::read_to_string(repo_path.join("file")).unwrap();
+    insta::assert_snapshot!(content, @r###"
+    <<<<<<< b260b8a10343 (commit f18e76274408) "b"
+    b1
+    ||||||| 1fbec814b18b (commit 4ded1b39bf7b) "base"
+    1
+    =======
+    a1
+    >>>>>>> 7a19fb2d099a (commit 448b624cdbed) "a"
+    2
+    <<<<<<< b260b8a10343 (commit f18e76274408) "b"
+    b3
+    ||||||| 1fbec814b18b (commit 4ded1b39bf7b) "base"
+    3
+    =======
+    a3
+    >>>>>>> 7a19fb2d099a (commit 448b624cdbed) "a"
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "conflict", "file"]);
+    assert_eq!(stdout, content);
+
+    // Resolving one of the hunks leaves the other one conflicted
+    let (_, rest) = content.split_once("2\n").unwrap();
+    std::fs::write(repo_path.join("file"), format!("ab1\n2\n{rest}")).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Parent commit: 41ac7b07bd25 conflict
+    Working copy : f59d54eaf019 (no description set)
+    Working copy changes:
+    M file
+    There are unresolved conflicts at these paths:
+    file
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    ab1
+    2
+    <<<<<<< b260b8a10343 (commit f18e76274408) "b"
+    b3
+    ||||||| 1fbec814b18b (commit 4ded1b39bf7b) "base"
+    3
+    =======
+    a3
+    >>>>>>> 7a19fb2d099a (commit 448b624cdbed) "a"
+    "###);
+
+    // The Git-style markers in the file are still recognized after changing the
+    // setting
+    test_env.add_config(br#"ui.conflict-marker-style = "diff""#);
+    let content = std::fs::read_to_string(repo_path.join("file")).unwrap();
+    std::fs::write(repo_path.join("file"), content.replace("ab1", "x1")).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    x1
+    2
+    <<<<<<<
+    ------- 1fbec814b18b (commit 4ded1b39bf7b) "base"
+    +++++++ b260b8a10343 (commit f18e76274408) "b"
+    -3
+    +b3
+    +++++++ 7a19fb2d099a (commit 448b624cdbed) "a"
+    a3
+    >>>>>>>
+    "###);
+}
+
+#[test]
+fn test_resolve_list() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        &[],
+        &[("file1", b"base\n"), ("file2", b"base\n")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "a",
+        &["base"],
+        &[("file1", b"a\n"), ("file2", b"a\n")],
+    );
+    // Create "b" by hand since `create_commit()` can't delete files
+    test_env.jj_cmd_success(&repo_path, &["co", "base"]);
+    std::fs::write(repo_path.join("file1"), "b\n").unwrap();
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "b"]);
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1    2-sided conflict
+    file2    2-sided conflict including 1 deletion
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "-l", "file2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file2    2-sided conflict including 1 deletion
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "--list", "-r", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No conflicts found at this revision
+    "###);
+}
+
+#[test]
+fn test_resolve_with_merge_tool() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_merge_tool();
+
+    create_commit(&test_env, &repo_path, "base", &[], &[("file", b"base\n")]);
+    create_commit(&test_env, &repo_path, "a", &["base"], &[("file", b"a\n")]);
+    create_commit(&test_env, &repo_path, "b", &["base"], &[("file", b"b\n")]);
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+
+    // The tool gets the base and the output file, which initially contains the
+    // conflict markers
+    std::fs::write(&edit_script, "print-contents\0write\nresolved\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "-r", "conflict"]);
+    insta::assert_snapshot!(stdout, @r###"
+    --- before
+    base
+    +++ after
+    <<<<<<<
+    ------- 1fbec814b18b (commit 974104c267e1) "base"
+    +++++++ b260b8a10343 (commit 1f9abbd547da) "b"
+    -base
+    +b
+    +++++++ 7a19fb2d099a (commit e520d6a40616) "a"
+    a
+    >>>>>>>
+    Created 61888f245094 conflict
+    Rebased 1 descendant commits
+    Working copy now at: 9a97b8e054af (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "conflict", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    resolved
+    "###);
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+
+    // If the output still has conflict markers, the file stays conflicted
+    std::fs::write(
+        &edit_script,
+        "write\n<<<<<<<\n-------\n+++++++\n-base\n+b\n+++++++\na2\n>>>>>>>\n",
+    )
+    .unwrap();
+    test_env.jj_cmd_success(&repo_path, &["resolve", "-r", "conflict"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "-r", "conflict", "--list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file    2-sided conflict
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "conflict", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    <<<<<<<
+    ------- 1fbec814b18b (commit 974104c267e1) "base"
+    +++++++ b260b8a10343 (commit 1f9abbd547da) "b"
+    -base
+    +b
+    +++++++ 7a19fb2d099a (commit e520d6a40616) "a"
+    a2
+    >>>>>>>
+    "###);
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+
+    // Nothing is resolved if the tool fails or doesn't change the output
+    std::fs::write(&edit_script, "fail").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "conflict"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to use external tool to resolve: The merge tool exited with a non-zero code
+    "###);
+    std::fs::write(&edit_script, "").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "conflict"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to use external tool to resolve: The merge tool didn't change the output file; the conflict was not resolved
+    "###);
+
+    // Tools without merge arguments can't be used
+    test_env.add_config(br#"ui.merge-editor = "not-a-merge-tool""#);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "conflict"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to use external tool to resolve: The merge tool 'not-a-merge-tool' is not configured for merging; set merge-tools.not-a-merge-tool.merge-args
+    "###);
+}
+
+#[test]
+fn test_resolve_with_merge_tool_unsupported_conflicts() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.set_up_fake_merge_tool();
+
+    create_commit(&test_env, &repo_path, "base", &[], &[("file", b"base\n")]);
+    create_commit(&test_env, &repo_path, "a", &["base"], &[("file", b"a\n")]);
+    // Create "b" by hand since `create_commit()` can't delete files
+    test_env.jj_cmd_success(&repo_path, &["co", "base"]);
+    std::fs::remove_file(repo_path.join("file")).unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "b"]);
+    create_commit(&test_env, &repo_path, "c", &["base"], &[("file", b"c\n")]);
+    create_commit(&test_env, &repo_path, "delete", &["a", "b"], &[]);
+    create_commit(&test_env, &repo_path, "three", &["a", "c", "delete"], &[]);
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "delete"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to use external tool to resolve: Only conflicts between two regular files are supported (not deletions, symlinks, executable files, etc.). Conflict summary for file:
+    Conflict:
+      Removing file with id df967b96a579e45a18b8251732d16804b2e56a55 (1fbec814b18b (commit 974104c267e1) "base")
+      Adding file with id 78981922613b2afb6025042ff6bd878ac1994e85 (7a19fb2d099a (commit e520d6a40616) "a")
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "three"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to use external tool to resolve: The conflict at file has 3 sides. At most 2 sides are supported.
+    "###);
+}
+
+#[test]
+fn test_resolve_with_merge_tool_keeps_partial_progress() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_merge_tool();
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        &[],
+        &[
+            ("file1", b"base\n"),
+            ("file2", b"base\n"),
+            ("file3", b"base\n"),
+        ],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "a",
+        &["base"],
+        &[("file1", b"a\n"), ("file2", b"a\n"), ("file3", b"a\n")],
+    );
+    // Create "b" by hand since `create_commit()` can't delete files
+    test_env.jj_cmd_success(&repo_path, &["co", "base"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "b\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    test_env.jj_cmd_success(&repo_path, &["close", "-m", "b"]);
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+
+    // Unsupported conflicts are skipped, and the conflicts resolved before the
+    // tool fails are kept
+    std::fs::write(&edit_script, "write\nresolved\n\0next_invocation\0fail").unwrap();
+    let assert = test_env
+        .jj_cmd(&repo_path, &["resolve", "-r", "conflict"])
+        .assert()
+        .success();
+    insta::assert_snapshot!(common::get_stdout_string(&assert), @r###"
+    Created de97c16f1010 conflict
+    Rebased 1 descendant commits
+    Working copy now at: b3abf0b1868e (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(common::get_stderr_string(&assert), @r###"
+    Skipping file1: Only conflicts between two regular files are supported (not deletions, symlinks, executable files, etc.). Conflict summary for file1:
+    Conflict:
+      Removing file with id df967b96a579e45a18b8251732d16804b2e56a55 (1fbec814b18b (commit 70eb3b912184) "base")
+      Adding file with id 78981922613b2afb6025042ff6bd878ac1994e85 (7a19fb2d099a (commit 742cfd7a183a) "a")
+    Stopped resolving conflicts at file3: The merge tool exited with a non-zero code
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "conflict", "file2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    resolved
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "-r", "conflict", "--list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1    2-sided conflict including 1 deletion
+    file3    2-sided conflict
+    "###);
+
+    // If nothing can be resolved, the failures are reported
+    std::fs::write(&edit_script, "fail").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "-r", "conflict"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Skipping file1: Only conflicts between two regular files are supported (not deletions, symlinks, executable files, etc.). Conflict summary for file1:
+    Conflict:
+      Removing file with id df967b96a579e45a18b8251732d16804b2e56a55 (1fbec814b18b (commit 70eb3b912184) "base")
+      Adding file with id 78981922613b2afb6025042ff6bd878ac1994e85 (7a19fb2d099a (commit 742cfd7a183a) "a")
+    Stopped resolving conflicts at file3: The merge tool exited with a non-zero code
+    Error: None of the conflicts could be resolved
+    "###);
+}
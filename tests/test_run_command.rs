@@ -0,0 +1,164 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
+
+pub mod common;
+
+fn init_stack(test_env: &TestEnvironment) -> PathBuf {
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    for name in ["a", "b", "c"] {
+        std::fs::write(repo_path.join("file"), format!("{name}\n")).unwrap();
+        test_env.jj_cmd_success(&repo_path, &["branch", "create", name]);
+        test_env.jj_cmd_success(&repo_path, &["close", "-m", name]);
+    }
+    repo_path
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ dcc36c1e950a 
+    o 2a7d0efb97bc c
+    o e2f18b912a73 b
+    o 214d2e392056 a
+    o 000000000000 
+    "###);
+
+    // The command runs on each revision, and fails on one of them
+    let command = "cat file; touch new-file; ! grep -q b file";
+    let assert = test_env
+        .jj_cmd(&repo_path, &["run", "-r", "a:c", "--", "sh", "-c", command])
+        .assert()
+        .failure();
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    c
+    b
+    a
+    passed 2a7d0efb97bc c
+    failed e2f18b912a73 b (exit status: 1)
+    passed 214d2e392056 a
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    Error: The command failed on 1 of 3 revisions
+    "###);
+    // The working copy wasn't touched
+    assert!(!repo_path.join("new-file").exists());
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Parent commit: 2a7d0efb97bc c
+    Working copy : dcc36c1e950a (no description set)
+    The working copy is clean
+    "###);
+
+    // Running in parallel gives the same results
+    let assert = test_env
+        .jj_cmd(
+            &repo_path,
+            &["run", "-r", "a:c", "-j", "3", "--", "sh", "-c", command],
+        )
+        .assert()
+        .failure();
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    c
+    b
+    a
+    passed 2a7d0efb97bc c
+    failed e2f18b912a73 b (exit status: 1)
+    passed 214d2e392056 a
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    Error: The command failed on 1 of 3 revisions
+    "###);
+
+    // The revset can be empty
+    let stdout = test_env.jj_cmd_success(&repo_path, &["run", "-r", "none()", "--", "false"]);
+    insta::assert_snapshot!(stdout, @"");
+
+    // A command that can't be run is an error
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["run", "--", "nonexistent-command"]);
+    insta::assert_snapshot!(stderr.lines().next().unwrap(), @"Error: Failed to run nonexistent-command: No such file or directory (os error 2)");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_rewrite() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+
+    // Revisions the command passed on are rewritten with its changes
+    let command = "echo x >> other; ! grep -q b file";
+    let assert = test_env
+        .jj_cmd(
+            &repo_path,
+            &["run", "-r", "a:c", "--rewrite", "--", "sh", "-c", command],
+        )
+        .assert()
+        .failure();
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    passed 2a7d0efb97bc c
+    failed e2f18b912a73 b (exit status: 1)
+    passed 214d2e392056 a
+    Rewrote 2 commits
+    Rebased 1 descendant commits
+    Working copy now at: e2e03a92466d (no description set)
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    Error: The command failed on 1 of 3 revisions
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ e2e03a92466d 
+    o c7dd48ab15fd c
+    o a6a36e743e7e b
+    o 790e9bd6fffd a
+    o 000000000000 
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "a", "other"]);
+    insta::assert_snapshot!(stdout, @r###"
+    x
+    "###);
+    // The revision the command failed on is rebased and keeps its parent's
+    // changes
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "-r", "b", "other"]);
+    insta::assert_snapshot!(stdout, @r###"
+    x
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file:
+       1    1: ab
+    "###);
+
+    // A command that doesn't change anything doesn't rewrite anything
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["run", "-r", "a", "--rewrite", "--", "true"]);
+    insta::assert_snapshot!(stdout, @r###"
+    passed 790e9bd6fffd a
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &["log", "-T", r#"commit_id.short() " " branches"#],
+    )
+}
@@ -0,0 +1,204 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+/// Configures the fake signer as the "command" signing backend
+fn set_up_fake_signer(test_env: &TestEnvironment, sign_all: bool) {
+    let signer_path = assert_cmd::cargo::cargo_bin("fake-signer");
+    assert!(signer_path.is_file());
+    // Simplified TOML escaping, hoping that there are no '"' or control characters
+    // in it
+    let escaped_signer_path = signer_path.to_str().unwrap().replace('\\', r"\\");
+    test_env.add_config(
+        format!(
+            r###"
+        [signing]
+        backend = "command"
+        key = "test-key"
+        sign-all = {sign_all}
+
+        [signing.backends.command]
+        program = "{escaped_signer_path}"
+        "###
+        )
+        .as_bytes(),
+    );
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &[
+            "log",
+            "-T",
+            r#"description.first_line() " [" signature "]""#,
+        ],
+    )
+}
+
+#[test]
+fn test_sign_all_git_backend() {
+    let test_env = TestEnvironment::default();
+    set_up_fake_signer(&test_env, true);
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "first"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ first [good by test-key]
+    o (no description set) []
+    "###);
+
+    // The signature is stored in the Git commit
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-r", "@", "-T", "commit_id"]);
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+    let oid = git2::Oid::from_str(stdout.lines().next().unwrap().trim_start_matches("@ ")).unwrap();
+    let (sig, _) = git_repo.extract_signature(&oid, None).unwrap();
+    assert!(sig.as_str().unwrap().starts_with("fake-sig test-key "));
+}
+
+#[test]
+fn test_sign_all_local_backend() {
+    let test_env = TestEnvironment::default();
+    set_up_fake_signer(&test_env, true);
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "first"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ first [good by test-key]
+    o (no description set) []
+    "###);
+}
+
+#[test]
+fn test_signing_per_repo_config() {
+    let test_env = TestEnvironment::default();
+    set_up_fake_signer(&test_env, true);
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "first"]);
+
+    // Signing can be turned off for a single repo. Rewriting a signed commit
+    // then drops the signature.
+    std::fs::write(
+        repo_path.join(".jj/repo/config"),
+        "[signing]\nsign-all = false\n",
+    )
+    .unwrap();
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "second"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ second []
+    o (no description set) []
+    "###);
+}
+
+#[test]
+fn test_sign_all_failure() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(
+        br#"
+        [signing]
+        backend = "command"
+        key = "test-key"
+        sign-all = true
+
+        [signing.backends.command]
+        program = "nonexistent-signer"
+        "#,
+    );
+
+    // A failure to sign is reported as an error and nothing is written
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["describe", "-m", "first"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Unexpected error from store: Error: Failed to sign commit: Failed to run nonexistent-signer: No such file or directory (os error 2)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @ (no description set) []
+    o (no description set) []
+    "###);
+}
+
+#[test]
+fn test_signature_verification() {
+    let test_env = TestEnvironment::default();
+    set_up_fake_signer(&test_env, false);
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+
+    // Create commits with good and bad signatures directly in Git
+    let signature = git2::Signature::now("Some One", "some.one@example.com").unwrap();
+    let tree = git_repo
+        .find_tree(git_repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let fake_sign = |data: &[u8]| {
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin("fake-signer"))
+            .args(["sign", "other-key"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(data).unwrap();
+        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    };
+    let create_signed_commit = |message: &str, sign_message: &str, sig: Option<&str>| {
+        let buffer_to_sign = git_repo
+            .commit_create_buffer(&signature, &signature, sign_message, &tree, &[])
+            .unwrap();
+        let buffer = git_repo
+            .commit_create_buffer(&signature, &signature, message, &tree, &[])
+            .unwrap();
+        let sig = sig.map_or_else(|| fake_sign(&buffer_to_sign), str::to_string);
+        let oid = git_repo
+            .commit_signed(buffer.as_str().unwrap(), &sig, None)
+            .unwrap();
+        git_repo
+            .reference(&format!("refs/heads/{message}"), oid, true, "")
+            .unwrap();
+    };
+    create_signed_commit("good", "good", None);
+    // The signature was made for different content
+    create_signed_commit("bad", "something else", None);
+    create_signed_commit("unknown", "unknown", Some("not a fake signature"));
+    test_env.jj_cmd_success(&repo_path, &["git", "import"]);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-r",
+            "branches()",
+            "-T",
+            r#"description.first_line() " [" signature "]""#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    o unknown [unknown]
+    ~ 
+    o good [good by other-key]
+    ~ 
+    o bad [bad by other-key]
+    ~ 
+    "###);
+}
@@ -128,9 +128,9 @@ fn test_edit_merge() {
     .unwrap();
     let stdout = test_env.jj_cmd_success(&repo_path, &["touchup", "-r", "@-"]);
     insta::assert_snapshot!(stdout, @r###"
-    Created 9fed260d03e9 merge
+    Created 5e49ed0f4392 merge
     Rebased 1 descendant commits
-    Working copy now at: e80e55af699c (no description set)
+    Working copy now at: 54b4ae03a18c (no description set)
     Added 0 files, modified 0 files, removed 1 files
     "###);
     let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r", "@-"]);
@@ -142,12 +142,12 @@ fn test_edit_merge() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file2"]);
     insta::assert_snapshot!(stdout, @r###"
     <<<<<<<
-    -------
-    +++++++
+    ------- 611830d3641a (commit 2375fa164210)
+    +++++++ 1fbec814b18b (commit a06a17414fe8)
     -a
-    +c
-    +++++++
-    b
+    +b
+    +++++++ 7a19fb2d099a (commit 31efa657b426)
+    c
     >>>>>>>
     "###);
 }